@@ -0,0 +1,270 @@
+//! Stand-ins for the board and the USB link, so the state machine and handlers run on the host.
+
+use core::convert::Infallible;
+use core::future::{pending, Future};
+
+use embassy_time::{Duration, Timer};
+use embassy_usb::driver::EndpointError;
+use embedded_hal_async::i2c::{ErrorKind, ErrorType, I2c, NoAcknowledgeSource, Operation};
+use protocol::reset_reason::ResetReason;
+use protocol::spi::{SpiTransfer, DEFAULT_SPI_FREQUENCY_KHZ, DEFAULT_SPI_MODE};
+use protocol::transport::Framing;
+
+use crate::handlers::{BridgeUart, I2cController, Peripherals, SpiController, TemperatureSensor};
+use crate::usb_transport::PacketSink;
+use crate::ENCODED_FRAME_BUFFER_SIZE;
+
+/// Run `future` to completion. Timers follow the host clock, so a test waits out real time.
+pub fn run<F: Future>(future: F) -> F::Output {
+    embassy_futures::block_on(future)
+}
+
+/// Address the fake I2C device answers on.
+pub const DEVICE_ADDRESS: u8 = 0x48;
+
+/// A bus with one device at [`DEVICE_ADDRESS`]. The first byte written sets the register pointer,
+/// later bytes are stored from there, and reads return what is stored.
+pub struct FakeI2c {
+    pub registers: [u8; 256],
+    /// Every transfer takes this long.
+    pub delay: Duration,
+    /// Transfers never finish, like a device holding the bus.
+    pub stuck: bool,
+    /// How often the handlers gave up on a transfer.
+    pub aborts: usize,
+}
+
+impl Default for FakeI2c {
+    fn default() -> Self {
+        Self {
+            registers: [0; 256],
+            delay: Duration::from_ticks(0),
+            stuck: false,
+            aborts: 0,
+        }
+    }
+}
+
+impl ErrorType for FakeI2c {
+    type Error = ErrorKind;
+}
+
+impl I2c for FakeI2c {
+    async fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), ErrorKind> {
+        if self.stuck {
+            pending::<()>().await;
+        }
+        Timer::after(self.delay).await;
+        if address != DEVICE_ADDRESS {
+            return Err(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address));
+        }
+        let mut pointer: Option<u8> = None;
+        for operation in operations {
+            match operation {
+                Operation::Write(bytes) => {
+                    for &byte in bytes.iter() {
+                        pointer = Some(match pointer {
+                            None => byte,
+                            Some(register) => {
+                                self.registers[usize::from(register)] = byte;
+                                register.wrapping_add(1)
+                            }
+                        });
+                    }
+                }
+                Operation::Read(buffer) => {
+                    let mut register = pointer.unwrap_or(0);
+                    for byte in buffer.iter_mut() {
+                        *byte = self.registers[usize::from(register)];
+                        register = register.wrapping_add(1);
+                    }
+                    pointer = Some(register);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl I2cController for FakeI2c {
+    fn abort(&mut self) {
+        self.aborts += 1;
+    }
+}
+
+/// An SPI bus that logs every transaction and shifts back each byte's position in it.
+pub struct FakeSpi {
+    pub mode: u8,
+    pub freq_khz: u16,
+    pub transactions: Vec<(u8, Vec<u8>)>,
+}
+
+impl Default for FakeSpi {
+    fn default() -> Self {
+        Self {
+            mode: DEFAULT_SPI_MODE,
+            freq_khz: DEFAULT_SPI_FREQUENCY_KHZ,
+            transactions: Vec::new(),
+        }
+    }
+}
+
+impl SpiTransfer for FakeSpi {
+    type Error = &'static str;
+
+    async fn transfer(&mut self, cs: u8, words: &mut [u8]) -> Result<(), Self::Error> {
+        self.transactions.push((cs, words.to_vec()));
+        for (position, word) in words.iter_mut().enumerate() {
+            *word = position as u8;
+        }
+        Ok(())
+    }
+}
+
+impl SpiController for FakeSpi {
+    fn mode(&self) -> u8 {
+        self.mode
+    }
+
+    fn freq_khz(&self) -> u16 {
+        self.freq_khz
+    }
+
+    fn configure(&mut self, mode: u8, freq_khz: u16) -> bool {
+        self.mode = mode;
+        self.freq_khz = freq_khz;
+        true
+    }
+}
+
+/// A UART that keeps what is written to it and never receives anything.
+#[derive(Default)]
+pub struct FakeUart {
+    pub baud: u32,
+    pub written: Vec<u8>,
+}
+
+impl embedded_io_async::ErrorType for FakeUart {
+    type Error = Infallible;
+}
+
+impl embedded_io_async::Read for FakeUart {
+    async fn read(&mut self, _buf: &mut [u8]) -> Result<usize, Infallible> {
+        pending().await
+    }
+}
+
+impl embedded_io_async::Write for FakeUart {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Infallible> {
+        self.written.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+}
+
+impl BridgeUart for FakeUart {
+    fn set_baudrate(&mut self, baud: u32) {
+        self.baud = baud;
+    }
+}
+
+/// A temperature sensor that returns `raw`, or fails when it is `None`.
+#[derive(Default)]
+pub struct FakeSensor {
+    pub raw: Option<u16>,
+}
+
+impl TemperatureSensor for FakeSensor {
+    async fn read_raw(&mut self) -> Option<u16> {
+        self.raw
+    }
+}
+
+#[derive(Default)]
+pub struct FakeBoard {
+    pub i2c0: FakeI2c,
+    pub i2c1: FakeI2c,
+    pub spi: FakeSpi,
+    pub uart: FakeUart,
+    pub temperature: FakeSensor,
+}
+
+impl Peripherals for FakeBoard {
+    type I2c0 = FakeI2c;
+    type I2c1 = FakeI2c;
+    type Spi = FakeSpi;
+    type Uart = FakeUart;
+    type Temperature = FakeSensor;
+
+    fn i2c0(&mut self) -> &mut FakeI2c {
+        &mut self.i2c0
+    }
+
+    fn i2c1(&mut self) -> &mut FakeI2c {
+        &mut self.i2c1
+    }
+
+    fn spi(&mut self) -> &mut FakeSpi {
+        &mut self.spi
+    }
+
+    fn uart(&mut self) -> &mut FakeUart {
+        &mut self.uart
+    }
+
+    fn temperature(&mut self) -> &mut FakeSensor {
+        &mut self.temperature
+    }
+
+    fn reset_reason(&self) -> ResetReason {
+        ResetReason::PowerOn
+    }
+}
+
+/// Keeps every packet sent to it, or fails every write with `failure` when one is set.
+#[derive(Default)]
+pub struct Recorder {
+    pub packets: Vec<Vec<u8>>,
+    pub failure: Option<EndpointError>,
+}
+
+impl Recorder {
+    /// Everything sent since the last call, as one stream.
+    pub fn take(&mut self) -> Vec<u8> {
+        self.packets.drain(..).flatten().collect()
+    }
+
+    /// The payloads of the frames sent since the last call.
+    pub fn take_payloads(&mut self, framing: Framing) -> Vec<Vec<u8>> {
+        let stream = self.take();
+        let mut rest = stream.as_slice();
+        let mut payloads = Vec::new();
+        while !rest.is_empty() {
+            let (frame, tail) = framing.take_from_bytes(rest).expect("a whole frame");
+            assert!(frame.is_intact());
+            payloads.push(frame.payload.to_vec());
+            rest = tail;
+        }
+        payloads
+    }
+}
+
+impl PacketSink for Recorder {
+    async fn write_packet(&mut self, data: &[u8]) -> Result<(), EndpointError> {
+        if let Some(err) = self.failure {
+            return Err(err);
+        }
+        self.packets.push(data.to_vec());
+        Ok(())
+    }
+}
+
+/// `payload` framed the way a host would send it.
+pub fn frame(framing: Framing, payload: &[u8]) -> Vec<u8> {
+    let mut buffer = [0u8; ENCODED_FRAME_BUFFER_SIZE];
+    let len = framing.encode_into(payload, &mut buffer).unwrap();
+    buffer[..len].to_vec()
+}
@@ -0,0 +1,66 @@
+use crate::handlers::SpiController;
+use crate::state::Error;
+use crate::Response;
+use embassy_time::Instant;
+use protocol::device_info::DeviceConfig;
+use protocol::heartbeat::HeartbeatSchedule;
+
+/// Every setting a command can change, as they stand.
+pub fn current(spi: &impl SpiController, heartbeat: &HeartbeatSchedule) -> DeviceConfig {
+    DeviceConfig {
+        spi_mode: spi.mode(),
+        spi_freq_khz: spi.freq_khz(),
+        heartbeat_ms: heartbeat.interval_ms(),
+    }
+}
+
+/// Reply with every setting a command can change, encoded as a `DeviceConfig`.
+pub fn execute(
+    response: &mut Response,
+    spi: &impl SpiController,
+    heartbeat: &HeartbeatSchedule,
+) -> Result<(), Error> {
+    response
+        .ok(&current(spi, heartbeat).encode())
+        .map_err(|_| Error::BufferProcessFailed)
+}
+
+/// Put the SPI bus and heartbeats back to how the firmware starts and reply with the settings now
+/// in effect. The session, its framing and anything queued to send are left as they are.
+pub fn reset(
+    response: &mut Response,
+    port: &mut impl SpiController,
+    heartbeat: &mut HeartbeatSchedule,
+) -> Result<(), Error> {
+    let defaults = DeviceConfig::DEFAULT;
+    if !port.configure(defaults.spi_mode, defaults.spi_freq_khz) {
+        return Err(Error::ExecutionFailed);
+    }
+    heartbeat.configure(false, 0, Instant::now().as_millis());
+    execute(response, port, heartbeat)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake::FakeSpi;
+
+    #[test]
+    fn reset_restores_the_defaults_and_replies_with_them() {
+        let mut port = FakeSpi {
+            mode: 3,
+            freq_khz: 4_000,
+            ..FakeSpi::default()
+        };
+        let mut heartbeat = HeartbeatSchedule::new();
+        heartbeat.configure(true, 500, 0);
+        let mut response = Response::new();
+
+        reset(&mut response, &mut port, &mut heartbeat).unwrap();
+        let defaults = DeviceConfig::DEFAULT;
+        assert_eq!(port.mode, defaults.spi_mode);
+        assert_eq!(port.freq_khz, defaults.spi_freq_khz);
+        assert!(!heartbeat.is_enabled());
+        assert_eq!(DeviceConfig::decode(response.as_bytes()), Some(defaults));
+    }
+}
@@ -0,0 +1,12 @@
+use crate::state::Error;
+use crate::{Response, ECHO_PREFIX};
+
+pub fn execute(payload: &[u8], response: &mut Response) -> Result<(), Error> {
+    response
+        .extend(ECHO_PREFIX)
+        .map_err(|_| Error::ExecutionFailed)?;
+    response
+        .extend(payload)
+        .map_err(|_| Error::ExecutionFailed)?;
+    Ok(())
+}
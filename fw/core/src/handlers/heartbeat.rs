@@ -0,0 +1,17 @@
+use crate::state::Error;
+use crate::Response;
+use embassy_time::Instant;
+use protocol::heartbeat::{self, HeartbeatSchedule};
+
+/// Start or stop idle heartbeats and confirm the interval now in effect. The serial loop sends
+/// them from its timer tick (see `StateMachine::send_heartbeat_if_due`).
+pub fn execute(
+    enable: bool,
+    interval_ms: u16,
+    response: &mut Response,
+    schedule: &mut HeartbeatSchedule,
+) -> Result<(), Error> {
+    schedule.configure(enable, interval_ms, Instant::now().as_millis());
+    heartbeat::confirmation(response, schedule.interval_ms())
+        .map_err(|_| Error::BufferProcessFailed)
+}
@@ -0,0 +1,207 @@
+use crate::handlers::{push_error_message, I2cController};
+use crate::state::Error;
+use crate::{Response, MAX_COMMAND_SIZE};
+use core::fmt::Write;
+use embassy_time::{with_timeout, Duration};
+use embedded_hal_async::i2c::Operation;
+use protocol::MAX_I2C_ADDRESS;
+
+/// Longest a single I2C transaction may run before it is abandoned, so a device holding the bus
+/// can't wedge the state machine.
+pub const I2C_TRANSACTION_TIMEOUT: Duration = Duration::from_millis(50);
+
+fn push_i2c_error(response: &mut Response, err: impl core::fmt::Debug) -> Result<(), Error> {
+    response.clear();
+    write!(response, "i2c error: {:?}", err).map_err(|_| Error::BufferProcessFailed)
+}
+
+/// Refuse an address outside the 7-bit range with a message that says so, rather than the driver's
+/// generic error. Decoding already rejects one; this keeps the handlers safe to call directly.
+fn check_address(address: u8, response: &mut Response) -> Result<(), Error> {
+    if address > MAX_I2C_ADDRESS {
+        let _ = push_error_message(response, "i2c error: address must be 7-bit");
+        return Err(Error::InvalidAddress);
+    }
+    Ok(())
+}
+
+/// Map a transaction outcome onto the handler result, recording detail in the response.
+fn finish_transaction<B: I2cController>(
+    outcome: Result<Result<(), B::Error>, embassy_time::TimeoutError>,
+    response: &mut Response,
+    bus: &mut B,
+) -> Result<(), Error> {
+    match outcome {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(err)) => {
+            let _ = push_i2c_error(response, err);
+            Err(Error::ExecutionFailed)
+        }
+        Err(_) => {
+            bus.abort();
+            let _ = push_error_message(response, "i2c timeout");
+            Err(Error::I2cTimeout)
+        }
+    }
+}
+
+pub async fn execute_read<B: I2cController>(
+    address: u8,
+    register: u8,
+    length: u8,
+    response: &mut Response,
+    bus: &mut B,
+) -> Result<(), Error> {
+    check_address(address, response)?;
+    let len = length as usize;
+    let available_capacity = response.remaining();
+    if len == 0 {
+        let _ = push_error_message(response, "i2c error: length must be greater than zero");
+        return Err(Error::ExecutionFailed);
+    }
+    if len > available_capacity {
+        let _ = push_error_message(response, "i2c error: length exceeds buffer");
+        return Err(Error::ExecutionFailed);
+    }
+
+    // Read straight into the response; a failure below replaces it with the error.
+    let read_buf = response.grow(len).map_err(|_| Error::BufferProcessFailed)?;
+
+    // Use a single transaction to write the register address then read the requested bytes.
+    let outcome = with_timeout(
+        I2C_TRANSACTION_TIMEOUT,
+        bus.write_read(address, &[register], read_buf),
+    )
+    .await;
+    finish_transaction(outcome, response, bus)
+}
+
+/// Read one byte from each register in turn, replying with a `[register, value]` pair for each.
+/// The first failing register ends the command with its error.
+pub async fn execute_read_multi<B: I2cController>(
+    address: u8,
+    registers: &[u8],
+    response: &mut Response,
+    bus: &mut B,
+) -> Result<(), Error> {
+    check_address(address, response)?;
+    if registers.is_empty() {
+        let _ = push_error_message(response, "i2c error: no registers to read");
+        return Err(Error::ExecutionFailed);
+    }
+    if registers.len() * 2 > response.remaining() {
+        let _ = push_error_message(response, "i2c error: too many registers");
+        return Err(Error::ExecutionFailed);
+    }
+
+    // Pairs go straight into the response; an error replaces them rather than trailing after them.
+    for &register in registers {
+        let pair = response.grow(2).map_err(|_| Error::BufferProcessFailed)?;
+        pair[0] = register;
+        let outcome = with_timeout(
+            I2C_TRANSACTION_TIMEOUT,
+            bus.write_read(address, &[register], &mut pair[1..]),
+        )
+        .await;
+        finish_transaction(outcome, response, bus)?;
+    }
+    Ok(())
+}
+
+pub async fn execute_write<B: I2cController>(
+    address: u8,
+    register: u8,
+    payload: &[u8],
+    response: &mut Response,
+    bus: &mut B,
+) -> Result<(), Error> {
+    check_address(address, response)?;
+    if payload.is_empty() {
+        let _ = push_error_message(response, "i2c error: payload must not be empty");
+        return Err(Error::ExecutionFailed);
+    }
+
+    let total_len = payload.len() + 1; // include register byte
+    if total_len > MAX_COMMAND_SIZE {
+        let _ = push_error_message(response, "i2c error: payload too large");
+        return Err(Error::ExecutionFailed);
+    }
+
+    // The register byte goes out ahead of the payload in the same write rather than being copied
+    // in front of it.
+    let mut operations = [Operation::Write(&[register]), Operation::Write(payload)];
+    let outcome = with_timeout(
+        I2C_TRANSACTION_TIMEOUT,
+        bus.transaction(address, &mut operations),
+    )
+    .await;
+    finish_transaction(outcome, response, bus)?;
+
+    response.clear();
+    write!(
+        response,
+        "OK [{:#04X}, {:#04X}, {}]",
+        address,
+        register,
+        payload.len()
+    )
+    .map_err(|_| Error::BufferProcessFailed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake::{run, FakeI2c, DEVICE_ADDRESS};
+
+    #[test]
+    fn stuck_bus_times_out_and_aborts_the_transfer() {
+        let mut bus = FakeI2c {
+            stuck: true,
+            ..FakeI2c::default()
+        };
+        let mut response = Response::new();
+
+        let result = run(execute_read(
+            DEVICE_ADDRESS,
+            0x10,
+            2,
+            &mut response,
+            &mut bus,
+        ));
+        assert_eq!(result, Err(Error::I2cTimeout));
+        assert_eq!(response.as_bytes(), b"i2c timeout");
+        assert_eq!(bus.aborts, 1);
+
+        response.clear();
+        let result = run(execute_write(
+            DEVICE_ADDRESS,
+            0x10,
+            &[1],
+            &mut response,
+            &mut bus,
+        ));
+        assert_eq!(result, Err(Error::I2cTimeout));
+        assert_eq!(bus.aborts, 2);
+    }
+
+    #[test]
+    fn slow_device_within_the_timeout_still_answers() {
+        let mut bus = FakeI2c {
+            delay: I2C_TRANSACTION_TIMEOUT / 2,
+            ..FakeI2c::default()
+        };
+        bus.registers[0x10] = 0xAB;
+        let mut response = Response::new();
+
+        run(execute_read(
+            DEVICE_ADDRESS,
+            0x10,
+            1,
+            &mut response,
+            &mut bus,
+        ))
+        .unwrap();
+        assert_eq!(response.as_bytes(), [0xAB]);
+        assert_eq!(bus.aborts, 0);
+    }
+}
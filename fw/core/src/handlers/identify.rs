@@ -0,0 +1,11 @@
+use crate::state::Error;
+use crate::Response;
+use embassy_time::Instant;
+use protocol::identify::{self, IdentifyWindow};
+
+/// Start the identify pattern and confirm it. The state machine shows the pattern over the usual
+/// status while the window is open (see `StateMachine::refresh_status_led`).
+pub fn execute(response: &mut Response, window: &mut IdentifyWindow) -> Result<(), Error> {
+    window.start(Instant::now().as_millis());
+    identify::confirmation(response).map_err(|_| Error::BufferProcessFailed)
+}
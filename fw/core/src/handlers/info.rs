@@ -0,0 +1,34 @@
+use crate::handlers::config;
+use crate::handlers::uart::UART_CAPABILITIES;
+use crate::handlers::SpiController;
+use crate::state::Error;
+use crate::Response;
+use protocol::device_info::{DeviceInfo, DeviceLabel, DEVICE_INFO_MAX_LEN};
+use protocol::heartbeat::HeartbeatSchedule;
+use protocol::MAX_I2C_READ_LEN;
+
+/// What the firmware calls itself in `info`.
+const LABEL: DeviceLabel =
+    match DeviceLabel::new(concat!("SiTerm RP2040 v", env!("CARGO_PKG_VERSION"))) {
+        Some(label) => label,
+        None => panic!("device label too long"),
+    };
+
+/// Reply with what the board can do and the settings it runs with, encoded as a `DeviceInfo`.
+pub fn execute(
+    response: &mut Response,
+    spi: &impl SpiController,
+    heartbeat: &HeartbeatSchedule,
+) -> Result<(), Error> {
+    let info = DeviceInfo {
+        max_i2c_read: MAX_I2C_READ_LEN,
+        config: config::current(spi, heartbeat),
+        uart: Some(UART_CAPABILITIES),
+        label: LABEL,
+    };
+    let mut encoded = [0u8; DEVICE_INFO_MAX_LEN];
+    let len = info.encode(&mut encoded);
+    response
+        .ok(&encoded[..len])
+        .map_err(|_| Error::BufferProcessFailed)
+}
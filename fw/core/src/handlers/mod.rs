@@ -0,0 +1,169 @@
+pub mod config;
+pub mod echo;
+pub mod heartbeat;
+pub mod i2c;
+pub mod identify;
+pub mod info;
+pub mod reset_reason;
+pub mod response_format;
+pub mod self_test;
+pub mod spi;
+pub mod stats;
+pub mod temperature;
+pub mod uart;
+
+use core::fmt::Debug;
+use core::future::Future;
+
+use crate::state::{CommandOwned, Error};
+use crate::Response;
+use protocol::heartbeat::HeartbeatSchedule;
+use protocol::identify::IdentifyWindow;
+use protocol::reset_reason::ResetReason;
+use protocol::response::ResponseFormat;
+use protocol::spi::SpiTransfer;
+use protocol::stats::Stats;
+use protocol::I2cBus;
+
+/// Everything the handlers drive. The board supplies the real peripherals; tests supply fakes.
+pub trait Peripherals {
+    type I2c0: I2cController;
+    type I2c1: I2cController;
+    type Spi: SpiController;
+    type Uart: BridgeUart;
+    type Temperature: TemperatureSensor;
+
+    fn i2c0(&mut self) -> &mut Self::I2c0;
+    fn i2c1(&mut self) -> &mut Self::I2c1;
+    fn spi(&mut self) -> &mut Self::Spi;
+    /// Hardware UART used for bridging.
+    fn uart(&mut self) -> &mut Self::Uart;
+    fn temperature(&mut self) -> &mut Self::Temperature;
+    /// Why the board last reset, read from the reset registers at boot.
+    fn reset_reason(&self) -> ResetReason;
+}
+
+/// An I2C controller that can be made to give up on a transfer a device never finished.
+pub trait I2cController: embedded_hal_async::i2c::I2c {
+    /// Abandon the transfer in flight after a timeout, so the next one starts clean.
+    fn abort(&mut self);
+}
+
+/// The SPI bus with its chip selects, and the settings it runs with.
+pub trait SpiController: SpiTransfer<Error: Debug> {
+    /// SPI mode the bus is running in.
+    fn mode(&self) -> u8;
+    /// Clock rate the bus is running at.
+    fn freq_khz(&self) -> u16;
+    /// Run the bus in SPI `mode` at `freq_khz`, or return false and leave it as it was when the
+    /// board can't. Only called with settings `protocol::spi::config_error` accepts.
+    fn configure(&mut self, mode: u8, freq_khz: u16) -> bool;
+}
+
+/// The UART a bridge pipes to. Line errors are the caller's to drop.
+pub trait BridgeUart: embedded_io_async::Read + embedded_io_async::Write {
+    fn set_baudrate(&mut self, baud: u32);
+}
+
+/// The chip's internal temperature sensor.
+pub trait TemperatureSensor {
+    /// One raw 12-bit conversion (see `protocol::temperature`), or `None` if it failed.
+    fn read_raw(&mut self) -> impl Future<Output = Option<u16>>;
+}
+
+/// Leave `message` in the response as the detail of the error the handler is about to return.
+pub(crate) fn push_error_message(response: &mut Response, message: &str) -> Result<(), Error> {
+    response
+        .ok(message.as_bytes())
+        .map_err(|_| Error::BufferProcessFailed)
+}
+
+pub async fn execute_command<P: Peripherals>(
+    command: CommandOwned,
+    response: &mut Response,
+    peripherals: &mut P,
+    heartbeat: &mut HeartbeatSchedule,
+    identify: &mut IdentifyWindow,
+    link_stats: &Stats,
+    format: &mut ResponseFormat,
+) -> Result<(), Error> {
+    response.tag(command.method());
+    match command {
+        CommandOwned::EchoWrite(payload) => echo::execute(payload.as_slice(), response),
+        CommandOwned::I2cRead {
+            bus,
+            address,
+            register,
+            length,
+        } => match bus {
+            I2cBus::I2c0 => {
+                let bus = peripherals.i2c0();
+                i2c::execute_read(address, register, length, response, bus).await
+            }
+            I2cBus::I2c1 => {
+                let bus = peripherals.i2c1();
+                i2c::execute_read(address, register, length, response, bus).await
+            }
+        },
+        CommandOwned::I2cWrite {
+            bus,
+            address,
+            register,
+            payload,
+        } => match bus {
+            I2cBus::I2c0 => {
+                let bus = peripherals.i2c0();
+                i2c::execute_write(address, register, &payload, response, bus).await
+            }
+            I2cBus::I2c1 => {
+                let bus = peripherals.i2c1();
+                i2c::execute_write(address, register, &payload, response, bus).await
+            }
+        },
+        CommandOwned::I2cReadMulti {
+            bus,
+            address,
+            registers,
+        } => match bus {
+            I2cBus::I2c0 => {
+                let bus = peripherals.i2c0();
+                i2c::execute_read_multi(address, &registers, response, bus).await
+            }
+            I2cBus::I2c1 => {
+                let bus = peripherals.i2c1();
+                i2c::execute_read_multi(address, &registers, response, bus).await
+            }
+        },
+        CommandOwned::SpiRead {
+            cs,
+            register,
+            length,
+        } => spi::execute_read(cs, register, length, response, peripherals.spi()).await,
+        CommandOwned::SpiWrite {
+            cs,
+            register,
+            payload,
+        } => spi::execute_write(cs, register, &payload, response, peripherals.spi()).await,
+        CommandOwned::SpiConfig { mode, freq_khz } => {
+            spi::execute_config(mode, freq_khz, response, peripherals.spi())
+        }
+        CommandOwned::Temperature => {
+            temperature::execute(response, peripherals.temperature()).await
+        }
+        CommandOwned::UartBridge { baud } => uart::open_bridge(baud, response, peripherals.uart()),
+        CommandOwned::Heartbeat {
+            interval_ms,
+            enable,
+        } => heartbeat::execute(enable, interval_ms, response, heartbeat),
+        CommandOwned::GetConfig => config::execute(response, peripherals.spi(), heartbeat),
+        CommandOwned::ResetConfig => config::reset(response, peripherals.spi(), heartbeat),
+        CommandOwned::Identify => identify::execute(response, identify),
+        CommandOwned::Stats => stats::execute(response, link_stats),
+        CommandOwned::SetResponseFormat(requested) => {
+            response_format::execute(requested, response, format)
+        }
+        CommandOwned::SelfTest => self_test::execute(response, peripherals).await,
+        CommandOwned::ResetReason => reset_reason::execute(response, peripherals.reset_reason()),
+        CommandOwned::Info => info::execute(response, peripherals.spi(), heartbeat),
+    }
+}
@@ -0,0 +1,10 @@
+use core::fmt::Write;
+
+use crate::state::Error;
+use crate::Response;
+use protocol::reset_reason::ResetReason;
+
+/// Report why the board last reset, as read at boot.
+pub fn execute(response: &mut Response, reason: ResetReason) -> Result<(), Error> {
+    write!(response, "{reason}").map_err(|_| Error::BufferProcessFailed)
+}
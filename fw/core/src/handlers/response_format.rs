@@ -0,0 +1,14 @@
+use crate::state::Error;
+use crate::Response;
+use protocol::response::{self, ResponseFormat};
+
+/// Record how the host wants responses shown and confirm it. The state machine tags every later
+/// handler response with it until the session ends.
+pub fn execute(
+    requested: ResponseFormat,
+    response: &mut Response,
+    format: &mut ResponseFormat,
+) -> Result<(), Error> {
+    *format = requested;
+    response::confirmation(response, requested).map_err(|_| Error::BufferProcessFailed)
+}
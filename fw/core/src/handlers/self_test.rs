@@ -0,0 +1,69 @@
+use core::fmt::Write;
+
+use crate::handlers::i2c::I2C_TRANSACTION_TIMEOUT;
+use crate::handlers::{I2cController, Peripherals, TemperatureSensor};
+use crate::state::Error;
+use crate::status_led::{self, StatusColours, StatusPattern};
+use crate::Response;
+use embassy_time::{with_timeout, Duration, Timer};
+use embedded_hal_async::i2c::{Error as _, ErrorKind};
+use protocol::self_test::{
+    AdcCheck, BusScan, SelfTestReport, SCAN_FIRST_ADDRESS, SCAN_LAST_ADDRESS,
+};
+use protocol::temperature::DeciCelsius;
+
+/// How long each colour stays up, long enough to name it.
+const COLOUR_HOLD: Duration = Duration::from_millis(300);
+
+/// Run every check and reply with the report. The checks only read, so the handler succeeds
+/// whatever they find; a failing check shows in the report, not as an error response.
+pub async fn execute<P: Peripherals>(
+    response: &mut Response,
+    peripherals: &mut P,
+) -> Result<(), Error> {
+    let led_colours = show_colours().await;
+    let i2c = [
+        scan(peripherals.i2c0()).await,
+        scan(peripherals.i2c1()).await,
+    ];
+    let adc = match peripherals.temperature().read_raw().await {
+        Some(raw) => AdcCheck::Reading(DeciCelsius::from_raw(raw)),
+        None => AdcCheck::ConversionFailed,
+    };
+
+    let report = SelfTestReport {
+        led_colours,
+        i2c,
+        adc,
+    };
+    write!(response, "{report}").map_err(|_| Error::BufferProcessFailed)
+}
+
+/// Step the status LED through every colour. The state machine puts its own pattern back once
+/// the command is answered.
+async fn show_colours() -> u8 {
+    for colour in StatusColours::ALL {
+        status_led::signal(StatusPattern::Solid(colour));
+        Timer::after(COLOUR_HOLD).await;
+    }
+    StatusColours::ALL.len() as u8
+}
+
+/// Probe every unreserved address with a one-byte read, stopping at the first probe that does
+/// anything but answer or NAK.
+async fn scan<B: I2cController>(bus: &mut B) -> BusScan {
+    let mut found = 0u128;
+    for address in SCAN_FIRST_ADDRESS..=SCAN_LAST_ADDRESS {
+        let mut byte = [0u8; 1];
+        match with_timeout(I2C_TRANSACTION_TIMEOUT, bus.read(address, &mut byte)).await {
+            Ok(Ok(())) => found |= 1 << address,
+            Ok(Err(err)) if matches!(err.kind(), ErrorKind::NoAcknowledge(_)) => {}
+            Ok(Err(_)) => return BusScan::Faulted { address },
+            Err(_) => {
+                bus.abort();
+                return BusScan::Stuck { address };
+            }
+        }
+    }
+    BusScan::Finished { found }
+}
@@ -0,0 +1,179 @@
+use crate::handlers::{push_error_message, SpiController};
+use crate::state::Error;
+use crate::{Response, MAX_COMMAND_SIZE};
+use core::fmt::Write;
+use protocol::spi as transaction;
+
+fn push_spi_error(response: &mut Response, err: impl core::fmt::Debug) -> Result<(), Error> {
+    response.clear();
+    write!(response, "spi error: {:?}", err).map_err(|_| Error::BufferProcessFailed)
+}
+
+pub async fn execute_read<S: SpiController>(
+    cs: u8,
+    register: u8,
+    length: u8,
+    response: &mut Response,
+    port: &mut S,
+) -> Result<(), Error> {
+    let len = length as usize;
+    if len == 0 {
+        let _ = push_error_message(response, "spi error: length must be greater than zero");
+        return Err(Error::ExecutionFailed);
+    }
+    // One extra byte for the register clocked out ahead of the data.
+    if len + 1 > response.remaining() {
+        let _ = push_error_message(response, "spi error: length exceeds buffer");
+        return Err(Error::ExecutionFailed);
+    }
+
+    // The transaction runs in the response, then the data is moved over the register byte.
+    let start = response.len();
+    let frame = response
+        .grow(len + 1)
+        .map_err(|_| Error::BufferProcessFailed)?;
+    if let Err(err) = transaction::read_register(port, cs, register, frame).await {
+        let _ = push_spi_error(response, err);
+        return Err(Error::ExecutionFailed);
+    }
+    frame.copy_within(1.., 0);
+    response.truncate(start + len);
+    Ok(())
+}
+
+pub async fn execute_write<S: SpiController>(
+    cs: u8,
+    register: u8,
+    payload: &[u8],
+    response: &mut Response,
+    port: &mut S,
+) -> Result<(), Error> {
+    if payload.is_empty() {
+        let _ = push_error_message(response, "spi error: payload must not be empty");
+        return Err(Error::ExecutionFailed);
+    }
+    if payload.len() + 1 > MAX_COMMAND_SIZE {
+        let _ = push_error_message(response, "spi error: payload too large");
+        return Err(Error::ExecutionFailed);
+    }
+
+    // The transaction is laid out in the response, which the confirmation below replaces.
+    response.clear();
+    let frame = response
+        .grow(payload.len() + 1)
+        .map_err(|_| Error::BufferProcessFailed)?;
+    if let Err(err) = transaction::write_register(port, cs, register, payload, frame).await {
+        let _ = push_spi_error(response, err);
+        return Err(Error::ExecutionFailed);
+    }
+
+    response.clear();
+    write!(
+        response,
+        "OK [CS{}, {:#04X}, {}]",
+        cs,
+        register,
+        payload.len()
+    )
+    .map_err(|_| Error::BufferProcessFailed)
+}
+
+/// Apply `spi config`. Commands run one at a time, so no transfer is in flight and every chip select
+/// is high while the peripheral is disabled, reprogrammed and enabled again; a change in clock idle
+/// level happens with no device listening. The settings are checked first because the driver
+/// panics on a clock it can't divide down to.
+pub fn execute_config<S: SpiController>(
+    mode: u8,
+    freq_khz: u16,
+    response: &mut Response,
+    port: &mut S,
+) -> Result<(), Error> {
+    let error = transaction::config_error(mode, freq_khz);
+    if error.is_some() || !port.configure(mode, freq_khz) {
+        response.clear();
+        let _ = write!(response, "spi error: {}", error.unwrap_or_default());
+        return Err(Error::ExecutionFailed);
+    }
+
+    response.clear();
+    transaction::confirmation(response, port.mode(), port.freq_khz())
+        .map_err(|_| Error::BufferProcessFailed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake::{run, FakeSpi};
+
+    #[test]
+    fn read_clocks_out_the_register_and_replies_with_what_followed() {
+        let mut port = FakeSpi::default();
+        let mut response = Response::new();
+
+        run(execute_read(2, 0x0F, 3, &mut response, &mut port)).unwrap();
+        assert_eq!(
+            port.transactions,
+            [(
+                2,
+                vec![
+                    0x0F,
+                    transaction::FILL_BYTE,
+                    transaction::FILL_BYTE,
+                    transaction::FILL_BYTE
+                ]
+            )]
+        );
+        // The fake shifts back each byte's position, so the data starts at 1.
+        assert_eq!(response.as_bytes(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn read_of_the_longest_length_fits() {
+        let mut port = FakeSpi::default();
+        let mut response = Response::new();
+
+        run(execute_read(0, 0x00, u8::MAX, &mut response, &mut port)).unwrap();
+        assert_eq!(response.len(), usize::from(u8::MAX));
+        assert_eq!(response.as_bytes()[254], 255);
+
+        let result = run(execute_read(0, 0x00, 0, &mut response, &mut port));
+        assert_eq!(result, Err(Error::ExecutionFailed));
+        assert_eq!(
+            response.as_bytes(),
+            b"spi error: length must be greater than zero"
+        );
+    }
+
+    #[test]
+    fn write_sends_register_then_payload_in_one_transaction() {
+        let mut port = FakeSpi::default();
+        let mut response = Response::new();
+
+        run(execute_write(
+            1,
+            0x20,
+            &[0xAA, 0xBB],
+            &mut response,
+            &mut port,
+        ))
+        .unwrap();
+        assert_eq!(port.transactions, [(1, vec![0x20, 0xAA, 0xBB])]);
+        assert_eq!(response.as_bytes(), b"OK [CS1, 0x20, 2]");
+    }
+
+    #[test]
+    fn write_of_the_largest_payload_fits() {
+        let mut port = FakeSpi::default();
+        let mut response = Response::new();
+        let payload = [0x55; MAX_COMMAND_SIZE - 1];
+
+        run(execute_write(0, 0x01, &payload, &mut response, &mut port)).unwrap();
+        assert_eq!(port.transactions[0].1.len(), MAX_COMMAND_SIZE);
+
+        let too_long = [0x55; MAX_COMMAND_SIZE];
+        let result = run(execute_write(0, 0x01, &too_long, &mut response, &mut port));
+        assert_eq!(result, Err(Error::ExecutionFailed));
+        assert_eq!(response.as_bytes(), b"spi error: payload too large");
+        assert_eq!(port.transactions.len(), 1);
+    }
+}
@@ -0,0 +1,10 @@
+use core::fmt::Write;
+
+use crate::state::Error;
+use crate::Response;
+use protocol::stats::Stats;
+
+/// Report the USB error counts as text.
+pub fn execute(response: &mut Response, stats: &Stats) -> Result<(), Error> {
+    write!(response, "{stats}").map_err(|_| Error::BufferProcessFailed)
+}
@@ -0,0 +1,22 @@
+use crate::handlers::TemperatureSensor;
+use crate::state::Error;
+use crate::Response;
+use protocol::temperature::DeciCelsius;
+
+/// Sample the internal temperature sensor and reply with the reading in tenths of a degree.
+/// The conversion is documented in `protocol::temperature`.
+pub async fn execute(
+    response: &mut Response,
+    sensor: &mut impl TemperatureSensor,
+) -> Result<(), Error> {
+    let raw = match sensor.read_raw().await {
+        Some(raw) => raw,
+        None => {
+            let _ = response.ok(b"adc conversion failed");
+            return Err(Error::ExecutionFailed);
+        }
+    };
+    response
+        .ok(&DeciCelsius::from_raw(raw).to_be_bytes())
+        .map_err(|_| Error::BufferProcessFailed)
+}
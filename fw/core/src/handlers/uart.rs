@@ -0,0 +1,28 @@
+use crate::handlers::BridgeUart;
+use crate::state::Error;
+use crate::Response;
+use protocol::bridge;
+use protocol::device_info::{Parity, UartCapabilities};
+
+/// What the RP2040 hardware UART supports. The UART is clocked from the 125 MHz peripheral clock
+/// with 16x oversampling, so 7.8125 Mbaud is the ceiling.
+pub const UART_CAPABILITIES: UartCapabilities = UartCapabilities {
+    max_baud: 7_812_500,
+    parities: Parity::None as u8 | Parity::Even as u8 | Parity::Odd as u8,
+};
+
+/// Retune the UART for a bridge session and confirm it. The state machine switches to bridging
+/// once this response has been sent.
+pub fn open_bridge(
+    baud: u32,
+    response: &mut Response,
+    uart: &mut impl BridgeUart,
+) -> Result<(), Error> {
+    if baud == 0 || !UART_CAPABILITIES.supports_baud(baud) {
+        let _ = response.ok(b"unsupported baud");
+        return Err(Error::ExecutionFailed);
+    }
+    uart.set_baudrate(baud);
+    response.clear();
+    bridge::opened_response(response, baud).map_err(|_| Error::BufferProcessFailed)
+}
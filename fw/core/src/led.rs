@@ -0,0 +1,145 @@
+//! Status LED rhythms for telling states apart without relying on colour.
+//!
+//! By default the firmware shows its state mostly through the LED's colour, which some colour-blind
+//! users can't tell apart. Built with the `led-rhythms` feature it instead lights every state in
+//! one soft colour and gives each its own [`Rhythm`]:
+//!
+//! - [`LedState::Starting`]: slow, even blink, one second on and one off.
+//! - [`LedState::WaitingForHost`]: a short blip every two seconds.
+//! - [`LedState::Ready`]: steady on.
+//! - [`LedState::Working`]: fast flicker.
+//! - [`LedState::Bridging`]: on, dipping off briefly every two seconds.
+//! - [`LedState::Replied`]: one long flash.
+//! - [`LedState::TimedOut`]: two flashes, then a pause.
+//! - [`LedState::Failed`]: three flashes, then a pause.
+//! - [`LedState::UsbFault`]: four quick flashes, then a pause.
+//! - [`LedState::Identify`]: even blink four times a second.
+//!
+//! The states that only follow an event (a reply, a timeout, a failure, a USB fault) are told
+//! apart by how many flashes they make, so they can be read from a single cycle.
+
+/// What the status LED is showing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LedState {
+    /// Powered up or reset, and no host has spoken yet.
+    Starting,
+    /// Waiting for the host's handshake.
+    WaitingForHost,
+    /// Connected and idle between commands.
+    Ready,
+    /// Decoding or running a command.
+    Working,
+    /// The UART bridge is open.
+    Bridging,
+    /// A response was sent.
+    Replied,
+    /// The handshake timed out.
+    TimedOut,
+    /// A command failed.
+    Failed,
+    /// The USB link itself failed.
+    UsbFault,
+    /// Answering `identify`; see `protocol::identify`.
+    Identify,
+}
+
+impl LedState {
+    pub const ALL: [Self; 10] = [
+        Self::Starting,
+        Self::WaitingForHost,
+        Self::Ready,
+        Self::Working,
+        Self::Bridging,
+        Self::Replied,
+        Self::TimedOut,
+        Self::Failed,
+        Self::UsbFault,
+        Self::Identify,
+    ];
+
+    /// How this state blinks when states are shown by rhythm.
+    pub const fn rhythm(self) -> Rhythm {
+        match self {
+            Self::Starting => Rhythm::flashes(1, 1_000, 0, 1_000),
+            Self::WaitingForHost => Rhythm::flashes(1, 100, 0, 1_900),
+            Self::Ready => Rhythm::STEADY,
+            Self::Working => Rhythm::flashes(1, 50, 0, 50),
+            Self::Bridging => Rhythm::flashes(1, 1_800, 0, 200),
+            Self::Replied => Rhythm::flashes(1, 500, 0, 300),
+            Self::TimedOut => Rhythm::flashes(2, 150, 150, 600),
+            Self::Failed => Rhythm::flashes(3, 150, 150, 600),
+            Self::UsbFault => Rhythm::flashes(4, 80, 80, 600),
+            Self::Identify => Rhythm::flashes(1, 125, 0, 125),
+        }
+    }
+}
+
+/// A repeating cycle of `count` flashes, each on for `on_ms` and off for `off_ms` before the next,
+/// then dark for `pause_ms` after the last one. No flashes means steady on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rhythm {
+    pub count: u8,
+    pub on_ms: u16,
+    pub off_ms: u16,
+    pub pause_ms: u16,
+}
+
+impl Rhythm {
+    pub const STEADY: Self = Self::flashes(0, 0, 0, 0);
+
+    pub const fn flashes(count: u8, on_ms: u16, off_ms: u16, pause_ms: u16) -> Self {
+        Self {
+            count,
+            on_ms,
+            off_ms,
+            pause_ms,
+        }
+    }
+
+    pub const fn is_steady(&self) -> bool {
+        self.count == 0
+    }
+
+    /// How long one cycle takes, so an event can be held long enough to be read in full.
+    pub const fn cycle_ms(&self) -> u32 {
+        if self.is_steady() {
+            return 0;
+        }
+        let count = self.count as u32;
+        count * self.on_ms as u32 + (count - 1) * self.off_ms as u32 + self.pause_ms as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_state_has_its_own_rhythm() {
+        for (index, state) in LedState::ALL.iter().enumerate() {
+            for other in &LedState::ALL[index + 1..] {
+                assert_ne!(state.rhythm(), other.rhythm(), "{state:?} and {other:?}");
+            }
+        }
+
+        let events = [
+            LedState::Replied,
+            LedState::TimedOut,
+            LedState::Failed,
+            LedState::UsbFault,
+        ];
+        for (index, event) in events.iter().enumerate() {
+            assert_eq!(event.rhythm().count as usize, index + 1, "{event:?}");
+        }
+    }
+
+    #[test]
+    fn a_cycle_ends_with_the_pause() {
+        assert_eq!(
+            LedState::Failed.rhythm().cycle_ms(),
+            3 * 150 + 2 * 150 + 600
+        );
+        assert_eq!(LedState::Replied.rhythm().cycle_ms(), 800);
+        assert_eq!(Rhythm::STEADY.cycle_ms(), 0);
+    }
+}
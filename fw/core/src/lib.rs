@@ -0,0 +1,58 @@
+//! The firmware's serial state machine and command handlers, independent of the board.
+//!
+//! [`state::StateMachine`] speaks the SiTerm protocol over anything that implements
+//! [`usb_transport::PacketSink`] and runs commands on anything that implements
+//! [`handlers::Peripherals`]. The RP2040 build in `fw/rp2040` wires both to the hardware; the tests
+//! here use stand-ins, so the whole exchange can be checked without a board.
+
+#![cfg_attr(not(test), no_std)]
+
+#[cfg(test)]
+mod fake;
+pub mod handlers;
+pub mod led;
+pub mod state;
+pub mod status_led;
+pub mod usb_transport;
+
+// Shared buffer sizes and protocol limits used by the transport/state machine modules.
+/// Max packet size of the CDC endpoints and the control endpoint. Reads arrive and writes go out at
+/// most this many bytes at a time, so every buffer sized by packets derives from it. The RP2040 is
+/// a full-speed device, which caps it at 64; smaller powers of two down to 8 also work.
+pub const USB_PACKET_SIZE: usize = 64;
+/// One USB packet, the most a single `read_packet` returns.
+pub const READ_BUFFER_SIZE: usize = USB_PACKET_SIZE;
+pub(crate) const HANDSHAKE_BUFFER_SIZE: usize = 64;
+pub(crate) const ECHO_PREFIX: &[u8] = b"";
+pub(crate) const FRAME_BUFFER_SIZE: usize = 576;
+pub(crate) const MAX_COMMAND_SIZE: usize = protocol::MAX_COMMAND_LEN;
+/// Longest command put back together from chunks (see `protocol::chunk`).
+pub(crate) const MAX_CHUNKED_COMMAND_SIZE: usize = protocol::chunk::MAX_CHUNKED_COMMAND_LEN;
+/// Response buffer shared by the handlers and the state machine.
+pub type Response = protocol::response::ResponseBuilder<{ protocol::response::MAX_RESPONSE_LEN }>;
+pub(crate) const ENCODED_FRAME_BUFFER_SIZE: usize = 320;
+/// Room for framed responses waiting to share a USB write; bigger frames are sent on their own.
+pub(crate) const RESPONSE_BATCH_SIZE: usize = 256;
+pub(crate) const WRITE_RETRY_TIMEOUT_MS: u64 = 250;
+
+// Packet sizes full-speed USB allows for bulk and control endpoints.
+const _: () = assert!(
+    USB_PACKET_SIZE.is_power_of_two() && USB_PACKET_SIZE >= 8 && USB_PACKET_SIZE <= 64,
+    "USB_PACKET_SIZE must be 8, 16, 32 or 64 on a full-speed device"
+);
+// The frame reader takes whole packets, so it has to fit a whole number of them.
+const _: () = assert!(
+    FRAME_BUFFER_SIZE >= 2 * USB_PACKET_SIZE && FRAME_BUFFER_SIZE.is_multiple_of(USB_PACKET_SIZE),
+    "FRAME_BUFFER_SIZE must be a multiple of USB_PACKET_SIZE with room for two packets"
+);
+// A resync arriving behind the longest partial frame has to fit, or the frame it cuts short is
+// reported as an error before the resync clears it (see `protocol::resync`).
+const _: () = assert!(
+    FRAME_BUFFER_SIZE >= MAX_COMMAND_SIZE + 5 + protocol::resync::RESYNC_LEN,
+    "FRAME_BUFFER_SIZE must hold a partial frame followed by a resync"
+);
+// A batch smaller than a packet would save no USB transactions.
+const _: () = assert!(
+    RESPONSE_BATCH_SIZE >= USB_PACKET_SIZE,
+    "RESPONSE_BATCH_SIZE must hold at least one USB packet"
+);
@@ -0,0 +1,1258 @@
+use core::pin::pin;
+use core::str;
+
+use embassy_futures::select::{select, Either};
+use embassy_time::{Duration, Instant, Timer};
+use embassy_usb::driver::EndpointError;
+use embedded_io_async::{Read, Write};
+use heapless::Vec;
+use protocol::{
+    bridge::{EscapeDetector, BRIDGE_CLOSED},
+    chunk::{self, ChunkAssembler, CHUNK_ERROR_CODE},
+    decode_command,
+    flow::{BusySignal, BUSY_AFTER_MS},
+    handshake::{self, HandshakeRequest},
+    heartbeat::{HeartbeatSchedule, HEARTBEAT},
+    identify::IdentifyWindow,
+    nak,
+    response::{self, ResponseFormat},
+    stats::{LinkEvent, Stats},
+    transport::{FrameReader, Framing},
+    Command, I2cBus, Method, HANDSHAKE_DELIMITER, HANDSHAKE_INCOMPATIBLE, MAX_ECHO_LEN,
+    MAX_I2C_READ_MULTI,
+};
+
+use crate::handlers::{self, Peripherals};
+use crate::led::LedState;
+use crate::status_led::{
+    self, StatusColours, StatusPattern, COMMUNICATION_PULSE_PERIOD, DEFAULT_BLINK_PERIOD,
+    ERROR_BLINK_PERIOD, ERROR_HOLD_DURATION, HANDSHAKE_BLINK_PERIOD, IDENTIFY_BLINK_PERIOD,
+    SUCCESS_BLINK_PERIOD, SUCCESS_HOLD_DURATION, USB_FAULT_BLINK_PERIOD, USB_FAULT_HOLD_DURATION,
+    WARNING_HOLD_DURATION,
+};
+use crate::usb_transport::{
+    send_batch, send_framed_payload, send_or_batch, send_raw_payload, write_packet_with_retry,
+    PacketSink, ResponseBatch,
+};
+use crate::{
+    Response, FRAME_BUFFER_SIZE, HANDSHAKE_BUFFER_SIZE, MAX_CHUNKED_COMMAND_SIZE, MAX_COMMAND_SIZE,
+    READ_BUFFER_SIZE,
+};
+
+/// High-level states cycled through while talking to the tui host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SystemState {
+    Init,
+    WaitForHandshake,
+    WaitForMessage,
+    ParseCommand,
+    ExecuteAction,
+    SendResponse,
+    /// USB and the hardware UART are piped together without framing until the host sends the
+    /// bridge escape (see `protocol::bridge`).
+    Bridging,
+    Error(Error),
+}
+
+/// Errors surfaced to the host when parsing or executing a command fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    InvalidChecksum,
+    UnknownCommand,
+    Timeout,
+    ExecutionFailed,
+    BufferProcessFailed,
+    InvalidBus,
+    /// An I2C address above the 7-bit range.
+    InvalidAddress,
+    InvalidChipSelect,
+    I2cTimeout,
+    /// A command frame arrived whole but failed its CRC; the host should resend it.
+    Retransmit,
+    /// A chunk was missing, out of order or didn't add up, so the partial command was dropped.
+    InvalidChunk,
+}
+
+impl Error {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Error::InvalidChecksum => "InvalidChecksum",
+            Error::UnknownCommand => "UnknownCommand",
+            Error::Timeout => "Timeout",
+            Error::ExecutionFailed => "ExecutionFailed",
+            Error::BufferProcessFailed => "BufferProcessFailed",
+            Error::InvalidBus => "InvalidBus",
+            Error::InvalidAddress => "InvalidAddress",
+            Error::InvalidChipSelect => "InvalidChipSelect",
+            Error::I2cTimeout => "I2cTimeout",
+            Error::Retransmit => nak::NAK_CODE,
+            Error::InvalidChunk => CHUNK_ERROR_CODE,
+        }
+    }
+}
+
+/// Payload of a flow control frame, untagged on a tagged session since it answers no command.
+fn flow_payload(tagged: bool, frame: &[u8]) -> Vec<u8, 16> {
+    let mut payload: Vec<u8, 16> = Vec::new();
+    if tagged {
+        let _ = payload.push(response::tag_byte(None, ResponseFormat::Auto));
+    }
+    let _ = payload.extend_from_slice(frame);
+    payload
+}
+
+/// Owned variants of protocol commands so handlers can borrow payloads without lifetime issues.
+pub enum CommandOwned {
+    EchoWrite(Vec<u8, MAX_ECHO_LEN>),
+    I2cRead {
+        bus: I2cBus,
+        address: u8,
+        register: u8,
+        length: u8,
+    },
+    I2cWrite {
+        bus: I2cBus,
+        address: u8,
+        register: u8,
+        payload: Vec<u8, MAX_COMMAND_SIZE>,
+    },
+    SpiRead {
+        cs: u8,
+        register: u8,
+        length: u8,
+    },
+    SpiWrite {
+        cs: u8,
+        register: u8,
+        payload: Vec<u8, MAX_COMMAND_SIZE>,
+    },
+    I2cReadMulti {
+        bus: I2cBus,
+        address: u8,
+        registers: Vec<u8, { MAX_I2C_READ_MULTI as usize }>,
+    },
+    SpiConfig {
+        mode: u8,
+        freq_khz: u16,
+    },
+    Temperature,
+    UartBridge {
+        baud: u32,
+    },
+    Heartbeat {
+        interval_ms: u16,
+        enable: bool,
+    },
+    GetConfig,
+    ResetConfig,
+    Identify,
+    Stats,
+    SetResponseFormat(ResponseFormat),
+    SelfTest,
+    ResetReason,
+    Info,
+}
+
+impl CommandOwned {
+    /// Converts borrowed commands into owned versions for convenience.
+    pub fn from_command(command: Command<'_>) -> Result<Self, Error> {
+        match command {
+            Command::EchoWrite { payload } => {
+                let mut buffer: Vec<u8, MAX_ECHO_LEN> = Vec::new();
+                buffer
+                    .extend_from_slice(payload)
+                    .map_err(|_| Error::ExecutionFailed)?;
+                Ok(CommandOwned::EchoWrite(buffer))
+            }
+            Command::I2cRead {
+                bus,
+                address,
+                register,
+                length,
+            } => Ok(CommandOwned::I2cRead {
+                bus,
+                address,
+                register,
+                length,
+            }),
+            Command::I2cWrite {
+                bus,
+                address,
+                register,
+                payload,
+            } => {
+                let mut buffer: Vec<u8, MAX_COMMAND_SIZE> = Vec::new();
+                buffer
+                    .extend_from_slice(payload)
+                    .map_err(|_| Error::ExecutionFailed)?;
+
+                Ok(CommandOwned::I2cWrite {
+                    bus,
+                    address,
+                    register,
+                    payload: buffer,
+                })
+            }
+            Command::SpiRead {
+                cs,
+                register,
+                length,
+            } => Ok(CommandOwned::SpiRead {
+                cs,
+                register,
+                length,
+            }),
+            Command::SpiWrite {
+                cs,
+                register,
+                payload,
+            } => {
+                let mut buffer: Vec<u8, MAX_COMMAND_SIZE> = Vec::new();
+                buffer
+                    .extend_from_slice(payload)
+                    .map_err(|_| Error::ExecutionFailed)?;
+
+                Ok(CommandOwned::SpiWrite {
+                    cs,
+                    register,
+                    payload: buffer,
+                })
+            }
+            Command::I2cReadMulti {
+                bus,
+                address,
+                registers,
+            } => Ok(CommandOwned::I2cReadMulti {
+                bus,
+                address,
+                registers: Vec::from_slice(registers).map_err(|_| Error::ExecutionFailed)?,
+            }),
+            Command::SpiConfig { mode, freq_khz } => Ok(CommandOwned::SpiConfig { mode, freq_khz }),
+            Command::Temperature => Ok(CommandOwned::Temperature),
+            Command::UartBridge { baud } => Ok(CommandOwned::UartBridge { baud }),
+            Command::Heartbeat {
+                interval_ms,
+                enable,
+            } => Ok(CommandOwned::Heartbeat {
+                interval_ms,
+                enable,
+            }),
+            Command::GetConfig => Ok(CommandOwned::GetConfig),
+            Command::ResetConfig => Ok(CommandOwned::ResetConfig),
+            Command::Identify => Ok(CommandOwned::Identify),
+            Command::Stats => Ok(CommandOwned::Stats),
+            Command::SelfTest => Ok(CommandOwned::SelfTest),
+            Command::ResetReason => Ok(CommandOwned::ResetReason),
+            Command::Info => Ok(CommandOwned::Info),
+            Command::SetResponseFormat { format } => Ok(CommandOwned::SetResponseFormat(format)),
+        }
+    }
+
+    /// Method whose handler runs this command, used to tag its response.
+    pub fn method(&self) -> Method {
+        match self {
+            CommandOwned::EchoWrite(_) => Method::Echo,
+            CommandOwned::I2cRead { .. }
+            | CommandOwned::I2cWrite { .. }
+            | CommandOwned::I2cReadMulti { .. } => Method::I2c,
+            CommandOwned::SpiRead { .. }
+            | CommandOwned::SpiWrite { .. }
+            | CommandOwned::SpiConfig { .. } => Method::Spi,
+            CommandOwned::Temperature => Method::Temp,
+            CommandOwned::UartBridge { .. } => Method::Uart,
+            CommandOwned::Heartbeat { .. } => Method::Heartbeat,
+            CommandOwned::GetConfig | CommandOwned::ResetConfig => Method::Config,
+            CommandOwned::Identify => Method::Identify,
+            CommandOwned::Stats => Method::Stats,
+            CommandOwned::SetResponseFormat(_) => Method::Format,
+            CommandOwned::SelfTest => Method::SelfTest,
+            CommandOwned::ResetReason => Method::ResetReason,
+            CommandOwned::Info => Method::Info,
+        }
+    }
+}
+
+/// Tracks buffers, timers, and state transitions for the USB CDC control loop.
+pub struct StateMachine<P> {
+    state: SystemState,
+    handshake_buf: Vec<u8, HANDSHAKE_BUFFER_SIZE>,
+    frame_reader: FrameReader<FRAME_BUFFER_SIZE>,
+    /// Frame layout the host asked for in its handshake.
+    framing: Framing,
+    /// The host asked for each response to lead with the method that produced it.
+    tagged_responses: bool,
+    /// How the host asked for responses to be shown, carried in their tags.
+    response_format: ResponseFormat,
+    /// The command to decode next, either one frame's payload or reassembled from chunks.
+    command_buf: Vec<u8, MAX_CHUNKED_COMMAND_SIZE>,
+    /// A chunked command part way through arriving.
+    chunks: ChunkAssembler<MAX_CHUNKED_COMMAND_SIZE>,
+    response: Response,
+    pending_command: Option<CommandOwned>,
+    handshake_deadline: Option<Instant>,
+    /// How long to wait for the host's handshake before reporting a timeout.
+    handshake_timeout: Duration,
+    handshake_complete: bool,
+    last_status_pattern: Option<StatusPattern>,
+    latched_pattern: Option<LatchedPattern>,
+    /// When the USB fault pattern stops overriding every other one. Survives [`Self::reset`] so a
+    /// fault that ended the session still shows after the host reconnects.
+    usb_fault_until: Option<Instant>,
+    /// While open, the identify pattern shows over every state but a USB fault.
+    identify: IdentifyWindow,
+    /// USB errors for `stats`. Disconnects survive [`Self::reset`], overflows don't.
+    link_stats: Stats,
+    handler_peripherals: P,
+    /// The command being answered opened a bridge; start bridging once the reply is sent.
+    bridge_pending: bool,
+    bridge_escape: EscapeDetector,
+    /// Idle heartbeats the host asked for, if any.
+    heartbeat: HeartbeatSchedule,
+    /// Whether the running command has reported busy and so owes the host a ready.
+    busy: BusySignal,
+    /// Framed responses held back to share a USB write. Raw writes (the handshake reply, bridged
+    /// data) send it first so nothing goes out of order.
+    batch: ResponseBatch,
+}
+
+#[derive(Clone, Copy)]
+struct LatchedPattern {
+    pattern: StatusPattern,
+    until: Instant,
+}
+
+impl<P: Peripherals> StateMachine<P> {
+    /// Create a state machine with empty buffers and no pending handshake. Responses wait up to
+    /// `batch_window` to share a USB write; zero sends each one straight away.
+    pub const fn new(
+        handler_peripherals: P,
+        handshake_timeout: Duration,
+        batch_window: Duration,
+    ) -> Self {
+        Self {
+            state: SystemState::Init,
+            handshake_buf: Vec::new(),
+            frame_reader: FrameReader::new(),
+            framing: Framing::Postcard,
+            tagged_responses: false,
+            response_format: ResponseFormat::Auto,
+            command_buf: Vec::new(),
+            chunks: ChunkAssembler::new(),
+            response: Response::new(),
+            pending_command: None,
+            handshake_deadline: None,
+            handshake_timeout,
+            handshake_complete: false,
+            last_status_pattern: None,
+            latched_pattern: None,
+            usb_fault_until: None,
+            identify: IdentifyWindow::new(),
+            link_stats: Stats::new(),
+            handler_peripherals,
+            bridge_pending: false,
+            bridge_escape: EscapeDetector::new(),
+            heartbeat: HeartbeatSchedule::new(),
+            busy: BusySignal::new(),
+            batch: ResponseBatch::new(batch_window.as_millis()),
+        }
+    }
+
+    /// Return to the initial states, clearing buffers and resetting deadlines.
+    pub fn reset(&mut self) {
+        self.handshake_buf.clear();
+        self.framing = Framing::Postcard;
+        self.frame_reader.set_framing(Framing::Postcard);
+        self.tagged_responses = false;
+        self.response_format = ResponseFormat::Auto;
+        self.command_buf.clear();
+        self.chunks.clear();
+        self.response.clear();
+        self.pending_command = None;
+        self.handshake_complete = false;
+        self.last_status_pattern = None;
+        self.latched_pattern = None;
+        self.identify.cancel();
+        self.link_stats.start_session();
+        self.handshake_deadline = None;
+        self.bridge_pending = false;
+        self.bridge_escape = EscapeDetector::new();
+        self.heartbeat = HeartbeatSchedule::new();
+        self.busy = BusySignal::new();
+        self.batch.clear();
+        self.schedule_handshake_deadline();
+        self.set_state(SystemState::Init);
+    }
+
+    fn set_state(&mut self, state: SystemState) {
+        self.state = state;
+        self.refresh_status_led();
+    }
+
+    pub fn tick(&mut self) {
+        self.refresh_status_led();
+    }
+
+    /// Show the USB fault pattern for [`USB_FAULT_HOLD_DURATION`], whatever the state does
+    /// meanwhile, so a link problem looks different from a rejected command. Nothing refreshes
+    /// the LED between sessions, so after a disabled endpoint it shows until the host is back.
+    fn note_usb_fault(&mut self) {
+        self.usb_fault_until = Some(
+            Instant::now() + status_led::hold_for(LedState::UsbFault, USB_FAULT_HOLD_DURATION),
+        );
+        self.refresh_status_led();
+    }
+
+    /// Count a USB error met reading from the host. A disabled endpoint there is the host going
+    /// away, which isn't a fault; an overflow is shown by [`Self::handle_buffer_overflow`]. The
+    /// caller still decides what the error means for the session.
+    pub fn record_read_error(&mut self, err: EndpointError) {
+        self.record_usb_error(err);
+    }
+
+    /// Count a USB error met sending to the host and show the USB fault pattern, since a reply
+    /// was cut off. The caller still decides what the error means for the session.
+    pub fn record_write_error(&mut self, err: EndpointError) {
+        self.record_usb_error(err);
+        self.note_usb_fault();
+    }
+
+    /// Count a USB error for `stats`.
+    fn record_usb_error(&mut self, err: EndpointError) {
+        self.link_stats.record(match err {
+            EndpointError::BufferOverflow => LinkEvent::Overflow,
+            EndpointError::Disabled => LinkEvent::Disconnect,
+        });
+    }
+
+    fn refresh_status_led(&mut self) {
+        let now = Instant::now();
+
+        if let Some(latch) = self.latched_pattern {
+            if now >= latch.until {
+                self.latched_pattern = None;
+            }
+        }
+
+        let (pattern, hold) = self.state_pattern();
+
+        if let Some(duration) = hold {
+            self.latched_pattern = Some(LatchedPattern {
+                pattern,
+                until: now + duration,
+            });
+        }
+
+        let mut effective = if let Some(latch) = self.latched_pattern {
+            if now < latch.until {
+                latch.pattern
+            } else {
+                self.latched_pattern = None;
+                pattern
+            }
+        } else {
+            pattern
+        };
+
+        if self.identify.is_active(now.as_millis()) {
+            effective = status_led::pattern_for(
+                LedState::Identify,
+                StatusPattern::Blink {
+                    colour: StatusColours::Identify,
+                    period: IDENTIFY_BLINK_PERIOD,
+                },
+            );
+        }
+
+        if let Some(until) = self.usb_fault_until {
+            if now < until {
+                effective = status_led::pattern_for(
+                    LedState::UsbFault,
+                    StatusPattern::Blink {
+                        colour: StatusColours::UsbFault,
+                        period: USB_FAULT_BLINK_PERIOD,
+                    },
+                );
+            } else {
+                self.usb_fault_until = None;
+            }
+        }
+
+        if self.last_status_pattern != Some(effective) {
+            status_led::signal(effective);
+            self.last_status_pattern = Some(effective);
+        }
+    }
+
+    fn state_pattern(&self) -> (StatusPattern, Option<Duration>) {
+        let (shown, colour, hold) = match self.state {
+            SystemState::Init => (
+                LedState::Starting,
+                StatusPattern::Solid(StatusColours::Idle),
+                None,
+            ),
+            SystemState::WaitForHandshake => (
+                LedState::WaitingForHost,
+                StatusPattern::Blink {
+                    colour: StatusColours::Warning,
+                    period: HANDSHAKE_BLINK_PERIOD,
+                },
+                None,
+            ),
+            SystemState::WaitForMessage => (
+                LedState::Ready,
+                StatusPattern::Solid(StatusColours::Idle),
+                None,
+            ),
+            SystemState::Bridging => (
+                LedState::Bridging,
+                StatusPattern::Solid(StatusColours::Communicating),
+                None,
+            ),
+            SystemState::ParseCommand | SystemState::ExecuteAction => (
+                LedState::Working,
+                StatusPattern::Pulse {
+                    colour: StatusColours::Communicating,
+                    period: COMMUNICATION_PULSE_PERIOD,
+                },
+                None,
+            ),
+            SystemState::SendResponse => (
+                LedState::Replied,
+                StatusPattern::Blink {
+                    colour: StatusColours::Success,
+                    period: SUCCESS_BLINK_PERIOD,
+                },
+                Some(SUCCESS_HOLD_DURATION),
+            ),
+            SystemState::Error(err) => match err {
+                Error::Timeout => (
+                    LedState::TimedOut,
+                    StatusPattern::Blink {
+                        colour: StatusColours::Warning,
+                        period: DEFAULT_BLINK_PERIOD,
+                    },
+                    Some(WARNING_HOLD_DURATION),
+                ),
+                _ => (
+                    LedState::Failed,
+                    StatusPattern::Blink {
+                        colour: StatusColours::Error,
+                        period: ERROR_BLINK_PERIOD,
+                    },
+                    Some(ERROR_HOLD_DURATION),
+                ),
+            },
+        };
+        (
+            status_led::pattern_for(shown, colour),
+            hold.map(|hold| status_led::hold_for(shown, hold)),
+        )
+    }
+
+    /// Feed newly received bytes via USB into the FSM, progressing through handshake, parsing, and reply.
+    pub async fn consume<S>(&mut self, sink: &mut S, data: &[u8]) -> Result<(), EndpointError>
+    where
+        S: PacketSink,
+    {
+        if !data.is_empty() {
+            self.heartbeat.touch(Instant::now().as_millis());
+        }
+        self.advance(sink).await?;
+
+        let mut rest = data;
+        while let Some((&byte, tail)) = rest.split_first() {
+            match self.state {
+                SystemState::WaitForHandshake => {
+                    self.step_handshake(sink, byte).await?;
+                    rest = tail;
+                }
+                SystemState::WaitForMessage => {
+                    // Frames may straddle USB reads; `advance` drains whichever are complete.
+                    let accepted = self.frame_reader.push(rest);
+                    if self.frame_reader.take_resync() {
+                        // The host is starting over, so a chunked command part way in goes too.
+                        self.chunks.clear();
+                    }
+                    if accepted == 0 {
+                        // The buffer is full and still holds no whole frame.
+                        self.frame_reader.clear();
+                        self.enter_error(Error::InvalidChecksum);
+                    }
+                    rest = &rest[accepted..];
+                }
+                SystemState::Bridging => {
+                    // Hand the UART everything up to the escape (if any) in one go.
+                    let used = self.bridge_from_host(rest).await;
+                    rest = &rest[used..];
+                }
+                _ => rest = tail,
+            }
+
+            self.advance(sink).await?;
+        }
+
+        self.advance(sink).await
+    }
+
+    /// Consume a single handshake byte, answering with the handshake response once the delimiter matches.
+    async fn step_handshake<S>(&mut self, sink: &mut S, byte: u8) -> Result<(), EndpointError>
+    where
+        S: PacketSink,
+    {
+        if self.handshake_buf.push(byte).is_err() {
+            self.handshake_buf.clear();
+            return Ok(());
+        }
+
+        let delimiter = HANDSHAKE_DELIMITER.as_bytes();
+        let buffer = self.handshake_buf.as_slice();
+
+        // Collects bytes until delimiter arrives.
+        if buffer.len() < delimiter.len() || &buffer[buffer.len() - delimiter.len()..] != delimiter
+        {
+            return Ok(());
+        }
+
+        let command_len = buffer.len() - delimiter.len();
+        let request = handshake::check_request(&buffer[..command_len]);
+
+        self.handshake_buf.clear();
+
+        match request {
+            HandshakeRequest::Compatible { framing, tagged } => {
+                let response = handshake::response(framing, tagged);
+                send_batch(sink, &mut self.batch).await?;
+                write_packet_with_retry(sink, response.as_bytes()).await?;
+                self.framing = framing;
+                self.tagged_responses = tagged;
+                self.frame_reader.set_framing(framing);
+                self.handshake_complete = true;
+                self.handshake_deadline = None;
+                self.set_state(SystemState::WaitForMessage);
+            }
+            HandshakeRequest::Incompatible { .. } => {
+                // Stay in the handshake state so a compatible host can still connect.
+                send_batch(sink, &mut self.batch).await?;
+                write_packet_with_retry(sink, HANDSHAKE_INCOMPATIBLE.as_bytes()).await?;
+            }
+            HandshakeRequest::Unrecognised => {}
+        }
+
+        Ok(())
+    }
+
+    /// Drive the FSM forward until it needs more input or I/O completes, performing work for each state.
+    async fn advance<S>(&mut self, sink: &mut S) -> Result<(), EndpointError>
+    where
+        S: PacketSink,
+    {
+        loop {
+            self.refresh_status_led();
+            match self.state {
+                SystemState::Init => {
+                    if self.handshake_deadline.is_none() {
+                        self.schedule_handshake_deadline();
+                    }
+                    self.set_state(SystemState::WaitForHandshake);
+                }
+                SystemState::WaitForHandshake | SystemState::Bridging => return Ok(()),
+                SystemState::WaitForMessage => match self.take_ready_frame() {
+                    Ok(Some(())) => {
+                        self.set_state(SystemState::ParseCommand);
+                    }
+                    Ok(None) => return Ok(()),
+                    Err(err) => {
+                        self.enter_error(err);
+                    }
+                },
+                SystemState::ParseCommand => match self.decode_pending_command() {
+                    Ok(()) => {
+                        self.set_state(SystemState::ExecuteAction);
+                    }
+                    Err(err) => {
+                        self.enter_error(err);
+                    }
+                },
+                SystemState::ExecuteAction => {
+                    // Responses held from earlier commands mustn't also wait out this one.
+                    self.send_batch_if_due(sink).await?;
+                    match self.perform_command(sink).await? {
+                        Ok(()) => {
+                            self.set_state(SystemState::SendResponse);
+                        }
+                        Err(err) => {
+                            self.enter_error(err);
+                        }
+                    }
+                }
+                SystemState::SendResponse => {
+                    self.flush_response(sink).await?;
+                    self.send_ready_if_owed(sink).await?;
+                    if core::mem::take(&mut self.bridge_pending) {
+                        // The confirmation has to reach the host ahead of any bridged bytes.
+                        send_batch(sink, &mut self.batch).await?;
+                        self.set_state(SystemState::Bridging);
+                        self.bridge_buffered_bytes().await;
+                    } else {
+                        self.set_state(SystemState::WaitForMessage);
+                    }
+                }
+                SystemState::Error(err) => {
+                    self.flush_error(sink, err).await?;
+                    self.send_ready_if_owed(sink).await?;
+                    if self.handshake_complete {
+                        self.set_state(SystemState::WaitForMessage);
+                    } else {
+                        self.schedule_handshake_deadline();
+                        self.set_state(SystemState::WaitForHandshake);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Take complete transport frames out of `frame_reader` until one finishes a command.
+    /// Returns `Ok(Some(()))` when a command was copied into `command_buf`, either a frame's payload
+    /// or the last chunk's reassembled command, `Ok(None)` when more bytes are required, and an
+    /// error when the buffered data is malformed (the frame buffer is cleared): `Error::Retransmit`
+    /// for a whole frame with a bad CRC, which the host can simply resend, `Error::InvalidChunk` for
+    /// a broken run of chunks, and `Error::InvalidChecksum` otherwise.
+    fn take_ready_frame(&mut self) -> Result<Option<()>, Error> {
+        loop {
+            let Some(payload) = self.frame_reader.next_frame().map_err(|err| {
+                if nak::wants_retransmit(&err) {
+                    Error::Retransmit
+                } else {
+                    Error::InvalidChecksum
+                }
+            })?
+            else {
+                return Ok(None); // Frame is incomplete, wait for more bytes to arrive.
+            };
+
+            let command = if chunk::is_chunk(payload) {
+                match self.chunks.push(payload) {
+                    Ok(Some(command)) => command,
+                    Ok(None) => continue, // More chunks to come; only the whole command is answered.
+                    Err(_) => return Err(Error::InvalidChunk),
+                }
+            } else {
+                payload
+            };
+
+            self.command_buf.clear();
+            if self.command_buf.extend_from_slice(command).is_err() {
+                self.frame_reader.clear();
+                return Err(Error::InvalidChecksum); // Payload is too large for the command buffer therefore surface error.
+            }
+            return Ok(Some(()));
+        }
+    }
+
+    /// Deserialize the buffered frame payload into a pending command the executor can own.
+    fn decode_pending_command(&mut self) -> Result<(), Error> {
+        match decode_command(self.command_buf.as_slice()) {
+            Ok(command) => {
+                let owned = CommandOwned::from_command(command)?;
+                self.pending_command = Some(owned);
+                self.command_buf.clear();
+                Ok(())
+            }
+            Err(err) => Err(Self::map_protocol_error(err)),
+        }
+    }
+
+    /// Execute the pending command via the handler table and capture any response bytes. A
+    /// handler still running after [`BUSY_AFTER_MS`] gets a busy frame sent on its behalf (see
+    /// `protocol::flow`); the outer error is a USB fault sending it.
+    async fn perform_command<S>(&mut self, sink: &mut S) -> Result<Result<(), Error>, EndpointError>
+    where
+        S: PacketSink,
+    {
+        let Some(command) = self.pending_command.take() else {
+            return Ok(Ok(()));
+        };
+        let opens_bridge = matches!(command, CommandOwned::UartBridge { .. });
+        let drives_led = matches!(command, CommandOwned::SelfTest);
+        self.response.clear();
+        let started = Instant::now();
+        let mut handler = pin!(handlers::execute_command(
+            command,
+            &mut self.response,
+            &mut self.handler_peripherals,
+            &mut self.heartbeat,
+            &mut self.identify,
+            &self.link_stats,
+            &mut self.response_format,
+        ));
+        let busy_after = Duration::from_millis(BUSY_AFTER_MS);
+        let (result, sent) = match select(handler.as_mut(), Timer::after(busy_after)).await {
+            Either::First(result) => (result, Ok(())),
+            Either::Second(()) => {
+                let sent = match self.busy.poll(started.elapsed().as_millis()) {
+                    Some(frame) => {
+                        // Busy is news now, so it goes out with anything held before it.
+                        let payload = flow_payload(self.tagged_responses, frame);
+                        match send_batch(sink, &mut self.batch).await {
+                            Ok(()) => send_framed_payload(sink, self.framing, &payload).await,
+                            Err(err) => Err(err),
+                        }
+                    }
+                    None => Ok(()),
+                };
+                // Let the handler finish even if the frame didn't go out; dropping it could leave
+                // a bus mid-transaction.
+                (handler.await, sent)
+            }
+        };
+        sent?;
+        if result.is_ok() {
+            self.bridge_pending = opens_bridge;
+        }
+        if drives_led {
+            // The handler signalled the LED itself, so the last pattern is no longer showing.
+            self.last_status_pattern = None;
+        }
+        Ok(result)
+    }
+
+    /// Tell the host the link is free again if the command just answered reported busy.
+    async fn send_ready_if_owed<S>(&mut self, sink: &mut S) -> Result<(), EndpointError>
+    where
+        S: PacketSink,
+    {
+        match self.busy.finish() {
+            Some(frame) => {
+                let payload = flow_payload(self.tagged_responses, frame);
+                send_or_batch(sink, &mut self.batch, self.framing, &payload).await
+            }
+            None => Ok(()),
+        }
+    }
+
+    async fn flush_response<S>(&mut self, sink: &mut S) -> Result<(), EndpointError>
+    where
+        S: PacketSink,
+    {
+        self.send_response(sink).await
+    }
+
+    /// Label any detail the handler left in the response with the error code and transmit it.
+    async fn flush_error<S>(&mut self, sink: &mut S, err: Error) -> Result<(), EndpointError>
+    where
+        S: PacketSink,
+    {
+        // Overlong detail is truncated, which is preferable to dropping the error.
+        let _ = self.response.wrap_err(err.as_str());
+        self.send_response(sink).await
+    }
+
+    /// Transmit the response, behind its tag byte when the host asked for tags, and clear it. With
+    /// batching on it may wait in the batch for a little while.
+    async fn send_response<S>(&mut self, sink: &mut S) -> Result<(), EndpointError>
+    where
+        S: PacketSink,
+    {
+        let mut tagged: Vec<u8, { MAX_COMMAND_SIZE + 1 }> = Vec::new();
+        let payload = if self.tagged_responses {
+            // Fits: the response is at most MAX_COMMAND_SIZE bytes.
+            let _ = tagged.push(response::tag_byte(
+                self.response.method(),
+                self.response_format,
+            ));
+            let _ = tagged.extend_from_slice(self.response.as_bytes());
+            tagged.as_slice()
+        } else {
+            self.response.as_bytes()
+        };
+        send_or_batch(sink, &mut self.batch, self.framing, payload).await?;
+        self.response.clear();
+        Ok(())
+    }
+
+    /// Send the held responses once the oldest has waited out the batch window.
+    pub async fn send_batch_if_due<S>(&mut self, sink: &mut S) -> Result<(), EndpointError>
+    where
+        S: PacketSink,
+    {
+        if !self.batch.is_due(Instant::now().as_millis()) {
+            return Ok(());
+        }
+        send_batch(sink, &mut self.batch).await
+    }
+
+    /// Time left before held responses have to be sent, if any are held.
+    pub fn batch_remaining(&self) -> Option<Duration> {
+        let deadline = self.batch.deadline_ms()?;
+        Some(Duration::from_millis(
+            deadline.saturating_sub(Instant::now().as_millis()),
+        ))
+    }
+
+    /// Send a heartbeat frame if one is due. Only sent while waiting for a fresh command, so it
+    /// never lands inside a response or in the middle of a bridge session.
+    pub async fn send_heartbeat_if_due<S>(&mut self, sink: &mut S) -> Result<(), EndpointError>
+    where
+        S: PacketSink,
+    {
+        if self.state != SystemState::WaitForMessage || !self.frame_reader.is_empty() {
+            return Ok(());
+        }
+        if !self.heartbeat.poll(Instant::now().as_millis()) {
+            return Ok(());
+        }
+        self.response.clear();
+        self.response.tag(Method::Heartbeat);
+        let _ = self.response.ok(HEARTBEAT);
+        self.send_response(sink).await
+    }
+
+    /// Forward host bytes to the UART until the data runs out or the escape sequence ends the
+    /// bridge. Returns how many bytes of `data` were used.
+    async fn bridge_from_host(&mut self, data: &[u8]) -> usize {
+        let mut out = [0u8; READ_BUFFER_SIZE];
+        let mut consumed = 0;
+        while consumed < data.len() {
+            let filtered = self.bridge_escape.filter(&data[consumed..], &mut out);
+            consumed += filtered.consumed;
+            // Waits for room in the UART ring buffer, so a slow baud rate throttles USB reads.
+            let _ = self
+                .handler_peripherals
+                .uart()
+                .write_all(&out[..filtered.written])
+                .await;
+            if filtered.escaped {
+                self.close_bridge();
+                break;
+            }
+        }
+        consumed
+    }
+
+    /// Bytes that arrived in the same USB read as the bridge command are raw data, not frames.
+    /// Forward them, then put back whatever follows an escape so it is parsed as frames again.
+    async fn bridge_buffered_bytes(&mut self) {
+        if self.frame_reader.is_empty() {
+            return;
+        }
+        let mut buffered: Vec<u8, FRAME_BUFFER_SIZE> = Vec::new();
+        let _ = buffered.extend_from_slice(self.frame_reader.pending());
+        self.frame_reader.clear();
+
+        let used = self.bridge_from_host(&buffered).await;
+        // Fits, since it came out of the same buffer.
+        self.frame_reader.push(&buffered[used..]);
+    }
+
+    /// Leave bridging and queue the framed confirmation that hands the link back to commands.
+    fn close_bridge(&mut self) {
+        self.bridge_escape = EscapeDetector::new();
+        self.frame_reader.clear();
+        self.response.clear();
+        self.response.tag(Method::Uart);
+        let _ = self.response.ok(BRIDGE_CLOSED);
+        self.set_state(SystemState::SendResponse);
+    }
+
+    /// Wait for data from the bridged UART. Never completes unless a bridge is open, so the serial
+    /// loop can always race it against USB reads.
+    pub async fn read_bridged(&mut self, buf: &mut [u8]) -> usize {
+        if self.state != SystemState::Bridging {
+            return core::future::pending().await;
+        }
+        // Line errors (framing, parity, overrun) drop the affected bytes; the pipe stays open.
+        self.handler_peripherals.uart().read(buf).await.unwrap_or(0)
+    }
+
+    /// Pass UART data straight to the host while bridging.
+    pub async fn forward_bridged<S>(
+        &mut self,
+        sink: &mut S,
+        data: &[u8],
+    ) -> Result<(), EndpointError>
+    where
+        S: PacketSink,
+    {
+        if self.state != SystemState::Bridging {
+            return Ok(());
+        }
+        send_batch(sink, &mut self.batch).await?;
+        send_raw_payload(sink, data).await
+    }
+
+    /// Emit a framed `ERR: <name>` payload describing the provided error.
+    fn enter_error(&mut self, err: Error) {
+        self.pending_command = None;
+        self.command_buf.clear();
+        self.set_state(SystemState::Error(err));
+    }
+
+    // TODO: More comprehensive error surfacing.
+    /// Map protocol-layer decoding failures onto user-visible error categories.
+    fn map_protocol_error(err: protocol::ProtocolError) -> Error {
+        match err {
+            protocol::ProtocolError::Empty => Error::InvalidChecksum,
+            protocol::ProtocolError::MalformedPayload { .. } => Error::InvalidChecksum,
+            protocol::ProtocolError::UnknownMethod(_) => Error::UnknownCommand,
+            protocol::ProtocolError::UnknownOperation(_) => Error::UnknownCommand,
+            protocol::ProtocolError::UnsupportedOperation { .. } => Error::UnknownCommand,
+            protocol::ProtocolError::UnknownBus(_) => Error::InvalidBus,
+            protocol::ProtocolError::InvalidAddress(_) => Error::InvalidAddress,
+            protocol::ProtocolError::UnknownChipSelect(_) => Error::InvalidChipSelect,
+        }
+    }
+
+    /// Sets the deadline for the handshake with tui host.
+    fn schedule_handshake_deadline(&mut self) {
+        self.handshake_deadline = Some(Instant::now() + self.handshake_timeout);
+    }
+
+    pub fn handshake_timeout_remaining(&self) -> Option<Duration> {
+        if self.handshake_complete {
+            return None;
+        }
+        if !matches!(
+            self.state,
+            SystemState::WaitForHandshake | SystemState::Init
+        ) {
+            return None;
+        }
+        let deadline = self.handshake_deadline?;
+        let now = Instant::now();
+        if deadline <= now {
+            Some(Duration::from_micros(0))
+        } else {
+            Some(deadline - now)
+        }
+    }
+
+    /// Recover from a handshake timeout by clearing buffers and surfacing a timeout error frame.
+    pub async fn handle_handshake_timeout<S>(&mut self, sink: &mut S) -> Result<(), EndpointError>
+    where
+        S: PacketSink,
+    {
+        self.handshake_buf.clear();
+        self.frame_reader.clear();
+        self.handshake_complete = false;
+        self.schedule_handshake_deadline();
+        self.enter_error(Error::Timeout);
+        self.advance(sink).await
+    }
+
+    /// Recover from a USB buffer overflow by dropping partial frames and flagging an invalid checksum.
+    pub async fn handle_buffer_overflow<S>(&mut self, sink: &mut S) -> Result<(), EndpointError>
+    where
+        S: PacketSink,
+    {
+        self.note_usb_fault();
+        self.frame_reader.clear();
+        if self.state == SystemState::Bridging {
+            // Raw data has no frame to reject, and an error frame would corrupt the pipe.
+            return Ok(());
+        }
+        self.enter_error(Error::InvalidChecksum);
+        self.advance(sink).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake::{frame, run, FakeBoard, Recorder};
+    use protocol::host::encode_command;
+
+    const HANDSHAKE_TIMEOUT: Duration = Duration::from_millis(20);
+
+    fn machine() -> StateMachine<FakeBoard> {
+        let mut machine = StateMachine::new(
+            FakeBoard::default(),
+            HANDSHAKE_TIMEOUT,
+            Duration::from_ticks(0),
+        );
+        machine.reset();
+        machine
+    }
+
+    fn connect(machine: &mut StateMachine<FakeBoard>, sink: &mut Recorder, framing: Framing) {
+        let request = [handshake::command(framing, false), HANDSHAKE_DELIMITER].concat();
+        run(machine.consume(sink, request.as_bytes())).unwrap();
+        assert_eq!(sink.take(), handshake::response(framing, false).as_bytes());
+        assert_eq!(machine.state, SystemState::WaitForMessage);
+    }
+
+    fn send(machine: &mut StateMachine<FakeBoard>, sink: &mut Recorder, command: &str) {
+        let payload = encode_command(command).unwrap();
+        run(machine.consume(sink, &frame(machine.framing, &payload))).unwrap();
+    }
+
+    #[test]
+    fn handshake_is_answered_and_opens_the_session() {
+        let mut machine = machine();
+        let mut sink = Recorder::default();
+        assert_eq!(machine.state, SystemState::Init);
+        connect(&mut machine, &mut sink, Framing::Postcard);
+        assert_eq!(machine.handshake_timeout_remaining(), None);
+    }
+
+    #[test]
+    fn incompatible_handshake_is_refused_and_a_later_one_accepted() {
+        let mut machine = machine();
+        let mut sink = Recorder::default();
+        run(machine.consume(&mut sink, b"SiTerm?v1\n")).unwrap();
+        assert_eq!(sink.take(), HANDSHAKE_INCOMPATIBLE.as_bytes());
+        assert_eq!(machine.state, SystemState::WaitForHandshake);
+        connect(&mut machine, &mut sink, Framing::Postcard);
+    }
+
+    #[test]
+    fn full_echo_command_produces_the_expected_frame() {
+        let mut machine = machine();
+        let mut sink = Recorder::default();
+        connect(&mut machine, &mut sink, Framing::Postcard);
+
+        send(&mut machine, &mut sink, "echo hello");
+        assert_eq!(
+            sink.take(),
+            frame(Framing::Postcard, b"hello"),
+            "echo answers with exactly one frame"
+        );
+        assert_eq!(machine.state, SystemState::WaitForMessage);
+    }
+
+    #[test]
+    fn commands_are_decoded_across_reads_and_in_the_negotiated_framing() {
+        let mut machine = machine();
+        let mut sink = Recorder::default();
+        connect(&mut machine, &mut sink, Framing::LengthPrefixed);
+
+        let payload = encode_command("echo split").unwrap();
+        for byte in frame(Framing::LengthPrefixed, &payload) {
+            run(machine.consume(&mut sink, &[byte])).unwrap();
+        }
+        assert_eq!(
+            sink.take_payloads(Framing::LengthPrefixed),
+            [b"split".to_vec()]
+        );
+    }
+
+    #[test]
+    fn unknown_command_is_answered_with_an_error_frame() {
+        let mut machine = machine();
+        let mut sink = Recorder::default();
+        connect(&mut machine, &mut sink, Framing::Postcard);
+
+        run(machine.consume(&mut sink, &frame(Framing::Postcard, &[0xFF]))).unwrap();
+        assert_eq!(
+            sink.take_payloads(Framing::Postcard),
+            [b"ERR: UnknownCommand".to_vec()]
+        );
+
+        // The session carries on.
+        assert_eq!(machine.state, SystemState::WaitForMessage);
+        send(&mut machine, &mut sink, "echo again");
+        assert_eq!(sink.take_payloads(Framing::Postcard), [b"again".to_vec()]);
+    }
+
+    #[test]
+    fn corrupt_frame_asks_for_a_resend() {
+        let mut machine = machine();
+        let mut sink = Recorder::default();
+        connect(&mut machine, &mut sink, Framing::Postcard);
+
+        let mut corrupt = frame(Framing::Postcard, &encode_command("echo hi").unwrap());
+        let last = corrupt.len() - 2;
+        corrupt[last] ^= 0xFF;
+        run(machine.consume(&mut sink, &corrupt)).unwrap();
+
+        let mut expected = b"ERR: ".to_vec();
+        expected.extend_from_slice(nak::NAK_CODE.as_bytes());
+        assert_eq!(sink.take_payloads(Framing::Postcard), [expected]);
+        assert_eq!(machine.state, SystemState::WaitForMessage);
+    }
+
+    #[test]
+    fn slow_command_is_bracketed_by_busy_and_ready() {
+        let mut machine = machine();
+        let mut sink = Recorder::default();
+        connect(&mut machine, &mut sink, Framing::Postcard);
+        machine.handler_peripherals.i2c1.registers[0x10] = 0x5A;
+        // Past the busy threshold but inside the I2C timeout.
+        machine.handler_peripherals.i2c1.delay = Duration::from_millis(BUSY_AFTER_MS * 2);
+
+        send(&mut machine, &mut sink, "i2c read 0x48 0x10 1");
+        assert_eq!(
+            sink.take_payloads(Framing::Postcard),
+            [
+                protocol::flow::BUSY.to_vec(),
+                vec![0x5A],
+                protocol::flow::READY.to_vec()
+            ]
+        );
+
+        // A command that answers in time says neither.
+        machine.handler_peripherals.i2c1.delay = Duration::from_ticks(0);
+        send(&mut machine, &mut sink, "i2c read 0x48 0x10 1");
+        assert_eq!(sink.take_payloads(Framing::Postcard), [vec![0x5A]]);
+    }
+
+    fn usb_fault_pattern() -> StatusPattern {
+        status_led::pattern_for(
+            LedState::UsbFault,
+            StatusPattern::Blink {
+                colour: StatusColours::UsbFault,
+                period: USB_FAULT_BLINK_PERIOD,
+            },
+        )
+    }
+
+    #[test]
+    fn unplugging_is_not_shown_as_a_usb_fault() {
+        let mut machine = machine();
+        let mut sink = Recorder::default();
+        connect(&mut machine, &mut sink, Framing::Postcard);
+
+        machine.record_read_error(EndpointError::Disabled);
+        assert_eq!(machine.link_stats.disconnects, 1);
+        assert_eq!(machine.usb_fault_until, None);
+        assert_ne!(machine.last_status_pattern, Some(usb_fault_pattern()));
+    }
+
+    #[test]
+    fn reply_cut_off_is_shown_as_a_usb_fault() {
+        let mut machine = machine();
+        let mut sink = Recorder::default();
+        connect(&mut machine, &mut sink, Framing::Postcard);
+
+        sink.failure = Some(EndpointError::Disabled);
+        let payload = encode_command("echo lost").unwrap();
+        let err = run(machine.consume(&mut sink, &frame(Framing::Postcard, &payload)));
+        assert_eq!(err, Err(EndpointError::Disabled));
+
+        machine.record_write_error(EndpointError::Disabled);
+        assert_eq!(machine.link_stats.disconnects, 1);
+        assert_eq!(machine.last_status_pattern, Some(usb_fault_pattern()));
+    }
+
+    #[test]
+    fn handshake_timeout_reports_timeout_and_waits_again() {
+        let mut machine = machine();
+        let mut sink = Recorder::default();
+        assert!(machine.handshake_timeout_remaining().unwrap() > Duration::from_ticks(0));
+
+        std::thread::sleep(std::time::Duration::from_millis(30));
+        assert_eq!(
+            machine.handshake_timeout_remaining(),
+            Some(Duration::from_ticks(0))
+        );
+        run(machine.handle_handshake_timeout(&mut sink)).unwrap();
+        assert_eq!(
+            sink.take_payloads(Framing::Postcard),
+            [b"ERR: Timeout".to_vec()]
+        );
+
+        // A fresh deadline, and a host arriving late still gets in.
+        assert_eq!(machine.state, SystemState::WaitForHandshake);
+        assert!(machine.handshake_timeout_remaining().unwrap() > Duration::from_ticks(0));
+        connect(&mut machine, &mut sink, Framing::Postcard);
+    }
+}
@@ -0,0 +1,141 @@
+use core::future::Future;
+
+use embassy_time::{Duration, Instant, Timer};
+use embassy_usb::class::cdc_acm::CdcAcmClass;
+use embassy_usb::driver::EndpointError;
+use protocol::{batch::FrameBatch, transport::Framing};
+
+use crate::{
+    ENCODED_FRAME_BUFFER_SIZE, RESPONSE_BATCH_SIZE, USB_PACKET_SIZE, WRITE_RETRY_TIMEOUT_MS,
+};
+
+/// Responses waiting to share a USB write.
+pub type ResponseBatch = FrameBatch<RESPONSE_BATCH_SIZE>;
+
+/// Where the state machine's packets go. On the device that is the CDC ACM class; keeping the state
+/// machine to this one method means it doesn't depend on the USB driver, so a stand-in can record
+/// what it sends.
+pub trait PacketSink {
+    /// Send one packet of at most [`USB_PACKET_SIZE`] bytes; an empty one ends a transfer.
+    fn write_packet(&mut self, data: &[u8]) -> impl Future<Output = Result<(), EndpointError>>;
+}
+
+impl<'d, D> PacketSink for CdcAcmClass<'d, D>
+where
+    D: embassy_usb::driver::Driver<'d>,
+{
+    async fn write_packet(&mut self, data: &[u8]) -> Result<(), EndpointError> {
+        CdcAcmClass::write_packet(self, data).await
+    }
+}
+
+/// Attempts to write a packet to `sink` within timeout period ([`WRITE_RETRY_TIMEOUT_MS`]).
+/// If write fails due to buffer overflow within the timeout period, it will wait 10ms before retrying.
+pub async fn write_packet_with_retry<S>(sink: &mut S, data: &[u8]) -> Result<(), EndpointError>
+where
+    S: PacketSink,
+{
+    let deadline = Instant::now() + Duration::from_millis(WRITE_RETRY_TIMEOUT_MS);
+    loop {
+        match sink.write_packet(data).await {
+            Ok(()) => return Ok(()),
+            Err(EndpointError::BufferOverflow) => {
+                if Instant::now() >= deadline {
+                    return Err(EndpointError::BufferOverflow);
+                }
+                Timer::after_millis(10).await;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Encodes payload with the negotiated `framing` and sends it over USB with timeout using [`write_packet_with_retry`].
+/// Payload is sent in chunks of size [`USB_PACKET_SIZE`].
+/// If encoding fails, no data is sent and `Ok(())` is returned.
+pub async fn send_framed_payload<S>(
+    sink: &mut S,
+    framing: Framing,
+    payload: &[u8],
+) -> Result<(), EndpointError>
+where
+    S: PacketSink,
+{
+    let mut frame_buf = [0u8; ENCODED_FRAME_BUFFER_SIZE];
+    let len = match framing.encode_into(payload, &mut frame_buf) {
+        Ok(len) => len,
+        Err(_) => return Ok(()),
+    };
+
+    write_packets(sink, &frame_buf[..len]).await
+}
+
+/// Frames `payload` into `batch` to go out with the responses around it. The batch is sent first
+/// when the frame doesn't fit, and the frame goes straight out when batching is off or it is too
+/// big for even an empty batch.
+pub async fn send_or_batch<S>(
+    sink: &mut S,
+    batch: &mut ResponseBatch,
+    framing: Framing,
+    payload: &[u8],
+) -> Result<(), EndpointError>
+where
+    S: PacketSink,
+{
+    if !batch.is_enabled() {
+        return send_framed_payload(sink, framing, payload).await;
+    }
+    let now = Instant::now().as_millis();
+    if batch.push(framing, payload, now).is_ok() {
+        return Ok(());
+    }
+    send_batch(sink, batch).await?;
+    if batch.push(framing, payload, now).is_ok() {
+        return Ok(());
+    }
+    send_framed_payload(sink, framing, payload).await
+}
+
+/// Sends every frame queued in `batch` as one transfer.
+pub async fn send_batch<S>(sink: &mut S, batch: &mut ResponseBatch) -> Result<(), EndpointError>
+where
+    S: PacketSink,
+{
+    if batch.is_empty() {
+        return Ok(());
+    }
+    write_packets(sink, batch.take()).await
+}
+
+/// Sends unframed bytes (bridged UART data) over USB in chunks of size [`USB_PACKET_SIZE`].
+pub async fn send_raw_payload<S>(sink: &mut S, data: &[u8]) -> Result<(), EndpointError>
+where
+    S: PacketSink,
+{
+    write_packets(sink, data).await
+}
+
+/// Whether a transfer of `len` bytes must be terminated with a zero-length packet.
+///
+/// A bulk IN transfer ends at the first short packet. When the data is a non-zero multiple of the
+/// max packet size every packet is full, so the host keeps waiting for more and may hold the bytes
+/// back until the next transfer arrives. With 64-byte packets that means 64, 128, ... byte frames, so
+/// the check follows [`USB_PACKET_SIZE`] rather than any fixed size.
+pub const fn needs_zero_length_packet(len: usize, max_packet_size: usize) -> bool {
+    len != 0 && len.is_multiple_of(max_packet_size)
+}
+
+/// Write `data` as packets of up to [`USB_PACKET_SIZE`] bytes (the CDC max packet size), ending
+/// with a zero-length packet when needed so the host sees the end of the transfer.
+async fn write_packets<S>(sink: &mut S, data: &[u8]) -> Result<(), EndpointError>
+where
+    S: PacketSink,
+{
+    for chunk in data.chunks(USB_PACKET_SIZE) {
+        write_packet_with_retry(sink, chunk).await?;
+    }
+    if needs_zero_length_packet(data.len(), USB_PACKET_SIZE) {
+        write_packet_with_retry(sink, &[]).await?;
+    }
+    Ok(())
+}
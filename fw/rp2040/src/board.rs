@@ -0,0 +1,231 @@
+//! The RP2040's peripherals behind the handler traits in `fw_core::handlers`.
+
+use embassy_rp::adc::{Adc, Async as AdcAsync, Channel as AdcChannel};
+use embassy_rp::gpio::Output;
+use embassy_rp::i2c::{Async, Error as I2cError, I2c, Instance};
+use embassy_rp::pac;
+use embassy_rp::peripherals::{I2C0, I2C1, SPI0, UART0};
+use embassy_rp::spi::{self, Config, Error as SpiError, Phase, Polarity, Spi};
+use embassy_rp::uart::{BufferedUart, Error as UartError};
+use embedded_hal_async::i2c::{ErrorType, Operation};
+use fw_core::handlers::{BridgeUart, I2cController, Peripherals, SpiController, TemperatureSensor};
+use protocol::reset_reason::ResetReason;
+use protocol::spi::{self as transaction, SpiTransfer};
+use protocol::SPI_CS_COUNT;
+
+/// Spins allowed for the controller to acknowledge an abort before giving up on it.
+const ABORT_SPIN_LIMIT: u32 = 10_000;
+
+pub struct HandlerPeripherals {
+    pub i2c0: I2cPort<I2C0>,
+    pub i2c1: I2cPort<I2C1>,
+    pub temperature: InternalSensor,
+    /// Hardware UART used for bridging (TX on GP0, RX on GP1).
+    pub uart: UartPort,
+    pub spi: SpiPort,
+    /// Why the board last reset, read from the reset registers at boot.
+    pub reset_reason: ResetReason,
+}
+
+impl Peripherals for HandlerPeripherals {
+    type I2c0 = I2cPort<I2C0>;
+    type I2c1 = I2cPort<I2C1>;
+    type Spi = SpiPort;
+    type Uart = UartPort;
+    type Temperature = InternalSensor;
+
+    fn i2c0(&mut self) -> &mut Self::I2c0 {
+        &mut self.i2c0
+    }
+
+    fn i2c1(&mut self) -> &mut Self::I2c1 {
+        &mut self.i2c1
+    }
+
+    fn spi(&mut self) -> &mut Self::Spi {
+        &mut self.spi
+    }
+
+    fn uart(&mut self) -> &mut Self::Uart {
+        &mut self.uart
+    }
+
+    fn temperature(&mut self) -> &mut Self::Temperature {
+        &mut self.temperature
+    }
+
+    fn reset_reason(&self) -> ResetReason {
+        self.reset_reason
+    }
+}
+
+/// An I2C controller driven through embassy-rp, which has no call to abort a transfer.
+pub struct I2cPort<T: Registers + 'static> {
+    pub bus: I2c<'static, T, Async>,
+}
+
+/// The register block of an I2C controller, which embassy-rp keeps private.
+pub trait Registers: Instance {
+    const REGS: pac::i2c::I2c;
+}
+
+impl Registers for I2C0 {
+    const REGS: pac::i2c::I2c = pac::I2C0;
+}
+
+impl Registers for I2C1 {
+    const REGS: pac::i2c::I2c = pac::I2C1;
+}
+
+impl<T: Registers + 'static> ErrorType for I2cPort<T> {
+    type Error = I2cError;
+}
+
+impl<T: Registers + 'static> embedded_hal_async::i2c::I2c for I2cPort<T> {
+    async fn read(&mut self, address: u8, read: &mut [u8]) -> Result<(), I2cError> {
+        self.bus.read_async(address, read).await
+    }
+
+    async fn write(&mut self, address: u8, write: &[u8]) -> Result<(), I2cError> {
+        self.bus.write_async(address, write.iter().copied()).await
+    }
+
+    async fn write_read(
+        &mut self,
+        address: u8,
+        write: &[u8],
+        read: &mut [u8],
+    ) -> Result<(), I2cError> {
+        self.bus
+            .write_read_async(address, write.iter().copied(), read)
+            .await
+    }
+
+    async fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), I2cError> {
+        embedded_hal_async::i2c::I2c::transaction(&mut self.bus, address, operations).await
+    }
+}
+
+impl<T: Registers + 'static> I2cController for I2cPort<T> {
+    /// The controller issues a STOP and flushes its TX FIFO, and the abort status is cleared so the
+    /// next transaction doesn't report a stale error. A device that keeps SDA low still needs a bus
+    /// clear or power cycle; the firmware can't recover that alone.
+    fn abort(&mut self) {
+        let regs = T::REGS;
+        regs.ic_enable().modify(|w| w.set_abort(true));
+        let mut spins = 0;
+        while regs.ic_enable().read().abort() && spins < ABORT_SPIN_LIMIT {
+            spins += 1;
+        }
+        let _ = regs.ic_clr_tx_abrt().read();
+        regs.ic_intr_mask()
+            .write_value(pac::i2c::regs::IcIntrMask::default());
+    }
+}
+
+/// SPI0 with a GPIO per chip select; pins and bus defaults are listed in `protocol::spi`.
+pub struct SpiPort {
+    pub spi: Spi<'static, SPI0, spi::Async>,
+    /// Active-low chip selects, indexed by a command's `cs`.
+    pub cs: [Output<'static>; SPI_CS_COUNT as usize],
+    /// SPI mode the bus is running in.
+    pub mode: u8,
+    /// Clock rate the bus is running at.
+    pub freq_khz: u16,
+}
+
+/// Bus settings for SPI `mode` at `freq_khz`, or `None` for a mode outside 0-3.
+pub fn spi_config(mode: u8, freq_khz: u16) -> Option<Config> {
+    let (cpol, cpha) = transaction::polarity_phase(mode)?;
+    let mut config = Config::default();
+    config.frequency = u32::from(freq_khz) * 1_000;
+    config.polarity = if cpol {
+        Polarity::IdleHigh
+    } else {
+        Polarity::IdleLow
+    };
+    config.phase = if cpha {
+        Phase::CaptureOnSecondTransition
+    } else {
+        Phase::CaptureOnFirstTransition
+    };
+    Some(config)
+}
+
+impl SpiTransfer for SpiPort {
+    type Error = SpiError;
+
+    async fn transfer(&mut self, cs: u8, words: &mut [u8]) -> Result<(), SpiError> {
+        // Chip selects are checked when the command is decoded.
+        let cs = &mut self.cs[usize::from(cs)];
+        cs.set_low();
+        let result = self.spi.transfer_in_place(words).await;
+        cs.set_high();
+        result
+    }
+}
+
+impl SpiController for SpiPort {
+    fn mode(&self) -> u8 {
+        self.mode
+    }
+
+    fn freq_khz(&self) -> u16 {
+        self.freq_khz
+    }
+
+    fn configure(&mut self, mode: u8, freq_khz: u16) -> bool {
+        let Some(settings) = spi_config(mode, freq_khz) else {
+            return false;
+        };
+        self.spi.set_config(&settings);
+        self.mode = mode;
+        self.freq_khz = freq_khz;
+        true
+    }
+}
+
+/// UART0, buffered both ways.
+pub struct UartPort(pub BufferedUart<'static, UART0>);
+
+impl embedded_io_async::ErrorType for UartPort {
+    type Error = UartError;
+}
+
+impl embedded_io_async::Read for UartPort {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, UartError> {
+        embedded_io_async::Read::read(&mut self.0, buf).await
+    }
+}
+
+impl embedded_io_async::Write for UartPort {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, UartError> {
+        embedded_io_async::Write::write(&mut self.0, buf).await
+    }
+
+    async fn flush(&mut self) -> Result<(), UartError> {
+        embedded_io_async::Write::flush(&mut self.0).await
+    }
+}
+
+impl BridgeUart for UartPort {
+    fn set_baudrate(&mut self, baud: u32) {
+        self.0.set_baudrate(baud);
+    }
+}
+
+/// The ADC with its channel on the internal temperature sensor.
+pub struct InternalSensor {
+    pub adc: Adc<'static, AdcAsync>,
+    pub channel: AdcChannel<'static>,
+}
+
+impl TemperatureSensor for InternalSensor {
+    async fn read_raw(&mut self) -> Option<u16> {
+        self.adc.read(&mut self.channel).await.ok()
+    }
+}
@@ -1,23 +1,32 @@
 #![no_std]
 #![no_main]
 
-mod handlers;
-mod state;
+mod board;
 mod status_led;
-mod usb_transport;
 
 // Embassy provides the async runtime and executor setup for the RP2040.
 use embassy_executor::Spawner;
 use embassy_futures::{
     join::join3,
-    select::{select, Either},
+    select::{select3, Either3},
+};
+use embassy_rp::adc::{
+    Adc, Channel as AdcChannel, Config as AdcConfig, InterruptHandler as AdcInterruptHandler,
 };
 use embassy_rp::bind_interrupts;
+use embassy_rp::gpio::{Level, Output};
 use embassy_rp::i2c::{Config as I2cConfig, I2c, InterruptHandler as I2cInterruptHandler};
-use embassy_rp::peripherals::{I2C1, PIO0, USB};
+use embassy_rp::pac;
+use embassy_rp::peripherals::{I2C0, I2C1, PIO0, UART0, USB};
 use embassy_rp::pio::{InterruptHandler as PioInterruptHandler, Pio};
 use embassy_rp::pio_programs::ws2812::{PioWs2812, PioWs2812Program};
+use embassy_rp::spi::Spi;
+use embassy_rp::uart::{
+    BufferedInterruptHandler as UartInterruptHandler, BufferedUart, Config as UartConfig,
+};
 use embassy_rp::usb::{Driver, InterruptHandler as UsbInterruptHandler};
+use protocol::reset_reason::ResetReason;
+use protocol::spi::{DEFAULT_SPI_FREQUENCY_KHZ, DEFAULT_SPI_MODE};
 
 use embassy_time::{Duration, Timer};
 
@@ -25,37 +34,124 @@ use embassy_usb::class::cdc_acm::{CdcAcmClass, State};
 use embassy_usb::driver::EndpointError;
 use embassy_usb::{Builder, Config as UsbConfig};
 
-use state::StateMachine;
+use board::{HandlerPeripherals, I2cPort, InternalSensor, SpiPort, UartPort};
+use fw_core::state::StateMachine;
+use fw_core::status_led::{StatusColours, StatusPattern};
+use fw_core::{READ_BUFFER_SIZE, USB_PACKET_SIZE};
 use static_cell::StaticCell;
-use status_led::{StatusColours, StatusLed, StatusPattern, DEFAULT_NUM_LEDS};
+use status_led::{StatusLed, DEFAULT_NUM_LEDS};
 use {defmt_rtt as _, panic_probe as _};
 
 bind_interrupts!(struct Irqs {
     USBCTRL_IRQ => UsbInterruptHandler<USB>;
     PIO0_IRQ_0 => PioInterruptHandler<PIO0>;
+    I2C0_IRQ => I2cInterruptHandler<I2C0>;
     I2C1_IRQ => I2cInterruptHandler<I2C1>;
+    ADC_IRQ_FIFO => AdcInterruptHandler;
+    UART0_IRQ => UartInterruptHandler<UART0>;
 });
 
-// Shared buffer sizes and protocol limits used by the transport/state machine modules.
-pub(crate) const READ_BUFFER_SIZE: usize = 64;
-pub(crate) const HANDSHAKE_BUFFER_SIZE: usize = 64;
-pub(crate) const ECHO_PREFIX: &[u8] = b"";
-pub(crate) const FRAME_BUFFER_SIZE: usize = 512;
-pub(crate) const MAX_COMMAND_SIZE: usize = 256;
-pub(crate) const ENCODED_FRAME_BUFFER_SIZE: usize = 320;
-pub(crate) const WRITE_RETRY_TIMEOUT_MS: u64 = 250;
-pub(crate) const STATUS_LED_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Ring buffer sizes for the bridged UART, each way.
+const UART_BUFFER_SIZE: usize = 256;
+
+/// Longest the serial loop waits for a packet before refreshing the LED. A shorter interval ends
+/// latched LED patterns closer to on time but wakes the core more often while idle, costing power;
+/// packets are never held back by it. `low-latency` shortens it.
+#[cfg(not(feature = "low-latency"))]
+const READ_POLL_INTERVAL: Duration = Duration::from_millis(100);
+#[cfg(feature = "low-latency")]
+const READ_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How long the device waits for the host's handshake before reporting a timeout. Build with
+/// `SITERM_HANDSHAKE_TIMEOUT_MS` set to raise it for a host that is slow to open the port, and
+/// give the host a `--handshake-timeout` at least as long.
+const HANDSHAKE_TIMEOUT: Duration = match option_env!("SITERM_HANDSHAKE_TIMEOUT_MS") {
+    Some(ms) => Duration::from_millis(parse_millis(ms)),
+    None => Duration::from_millis(protocol::duration_millis(protocol::HANDSHAKE_TIMEOUT)),
+};
+
+/// `ms` as a whole number of milliseconds, failing the build when it isn't one.
+const fn parse_millis(ms: &str) -> u64 {
+    let digits = ms.as_bytes();
+    assert!(
+        !digits.is_empty(),
+        "SITERM_HANDSHAKE_TIMEOUT_MS must be a whole number of milliseconds"
+    );
+    let mut value: u64 = 0;
+    let mut i = 0;
+    while i < digits.len() {
+        assert!(
+            digits[i].is_ascii_digit(),
+            "SITERM_HANDSHAKE_TIMEOUT_MS must be a whole number of milliseconds"
+        );
+        value = value * 10 + (digits[i] - b'0') as u64;
+        i += 1;
+    }
+    value
+}
+
+/// How long a finished response may wait for others to share its USB write (see
+/// `protocol::batch`). Zero sends each response as soon as it is ready, which keeps single
+/// commands fastest. A few milliseconds cuts the packet count when commands arrive in bursts, at
+/// up to that much extra latency per response.
+const RESPONSE_BATCH_WINDOW: Duration = Duration::from_millis(0);
 
 #[embassy_executor::main]
 async fn main(_spawner: Spawner) {
+    // Read first, before setup can touch the watchdog, so the reason is the one this boot came from.
+    let reset_reason = ResetReason::from_registers(
+        pac::VREG_AND_CHIP_RESET.chip_reset().read().0,
+        pac::WATCHDOG.reason().read().0,
+    );
     let p = embassy_rp::init(Default::default());
 
-    // I2C pin setup.
-    let scl = p.PIN_15;
-    let sda = p.PIN_14;
-    let i2c_bus = I2c::new_async(p.I2C1, scl, sda, Irqs, I2cConfig::default());
+    // I2C pin setup. Bus 1 is the default target; bus 0 is selected with `--bus 0`.
+    let i2c0 = I2c::new_async(p.I2C0, p.PIN_5, p.PIN_4, Irqs, I2cConfig::default());
+    let i2c1 = I2c::new_async(p.I2C1, p.PIN_15, p.PIN_14, Irqs, I2cConfig::default());
+
+    let adc = Adc::new(p.ADC, Irqs, AdcConfig::default());
+    let temp_sensor = AdcChannel::new_temp_sensor(p.ADC_TEMP_SENSOR);
 
-    let peris = handlers::HandlerPeripherals { i2c: i2c_bus };
+    // UART0 on GP0 (TX) / GP1 (RX) for bridging; the baud rate is set by each bridge command.
+    static UART_TX_BUF: StaticCell<[u8; UART_BUFFER_SIZE]> = StaticCell::new();
+    static UART_RX_BUF: StaticCell<[u8; UART_BUFFER_SIZE]> = StaticCell::new();
+    let uart = BufferedUart::new(
+        p.UART0,
+        Irqs,
+        p.PIN_0,
+        p.PIN_1,
+        UART_TX_BUF.init([0; UART_BUFFER_SIZE]),
+        UART_RX_BUF.init([0; UART_BUFFER_SIZE]),
+        UartConfig::default(),
+    );
+
+    // SPI0 on GP18 (SCK) / GP19 (MOSI) / GP20 (MISO), with chip selects on GP17 and GP21 held
+    // high until a transaction selects one. `spi config` changes the mode and clock later.
+    let spi_config =
+        board::spi_config(DEFAULT_SPI_MODE, DEFAULT_SPI_FREQUENCY_KHZ).unwrap_or_default();
+    let spi = SpiPort {
+        spi: Spi::new(
+            p.SPI0, p.PIN_18, p.PIN_19, p.PIN_20, p.DMA_CH1, p.DMA_CH2, spi_config,
+        ),
+        cs: [
+            Output::new(p.PIN_17, Level::High),
+            Output::new(p.PIN_21, Level::High),
+        ],
+        mode: DEFAULT_SPI_MODE,
+        freq_khz: DEFAULT_SPI_FREQUENCY_KHZ,
+    };
+
+    let peris = HandlerPeripherals {
+        i2c0: I2cPort { bus: i2c0 },
+        i2c1: I2cPort { bus: i2c1 },
+        temperature: InternalSensor {
+            adc,
+            channel: temp_sensor,
+        },
+        uart: UartPort(uart),
+        spi,
+        reset_reason,
+    };
 
     // Status led pin setup.
     let mut pio = Pio::new(p.PIO0, Irqs);
@@ -67,7 +163,7 @@ async fn main(_spawner: Spawner) {
         p.PIN_16,
         &program,
     ));
-    status_led::signal(StatusPattern::Solid(StatusColours::Idle));
+    fw_core::status_led::signal(StatusPattern::Solid(StatusColours::Idle));
 
     // USB CDC needs the USB peripheral and its interrupt handler.
     let driver = Driver::new(p.USB, Irqs);
@@ -77,12 +173,12 @@ async fn main(_spawner: Spawner) {
     config.product = Some("SiTerm RP2040");
     config.serial_number = Some("0001");
     config.max_power = 100;
-    config.max_packet_size_0 = 64;
+    config.max_packet_size_0 = USB_PACKET_SIZE as u8;
 
     // Descriptor/state buffers must live for the lifetime of the USB device.
     let mut config_descriptor = [0; 256];
     let mut bos_descriptor = [0; 256];
-    let mut control_buf = [0; 64];
+    let mut control_buf = [0; USB_PACKET_SIZE];
     let mut state = State::new();
 
     let mut builder = Builder::new(
@@ -95,7 +191,7 @@ async fn main(_spawner: Spawner) {
     );
 
     // CDC-ACM class exposes a USB serial port to the host.
-    let mut class = CdcAcmClass::new(&mut builder, &mut state, 64);
+    let mut class = CdcAcmClass::new(&mut builder, &mut state, USB_PACKET_SIZE as u16);
     let mut device = builder.build();
 
     // USB device task runs independently from the serial state machine task.
@@ -103,8 +199,10 @@ async fn main(_spawner: Spawner) {
 
     let serial_fut = async {
         let mut read_buf = [0u8; READ_BUFFER_SIZE];
-        static STATE_MACHINE: StaticCell<StateMachine> = StaticCell::new();
-        let mut machine = STATE_MACHINE.init_with(|| StateMachine::new(peris));
+        let mut uart_buf = [0u8; READ_BUFFER_SIZE];
+        static STATE_MACHINE: StaticCell<StateMachine<HandlerPeripherals>> = StaticCell::new();
+        let mut machine = STATE_MACHINE
+            .init_with(|| StateMachine::new(peris, HANDSHAKE_TIMEOUT, RESPONSE_BATCH_WINDOW));
 
         // Service connections forever; each iteration waits for a new host session.
         loop {
@@ -113,6 +211,7 @@ async fn main(_spawner: Spawner) {
 
             // Kick the state machine once so it can emit any immediate errors (e.g. timeout).
             if let Err(err) = machine.consume(&mut class, &[]).await {
+                machine.record_write_error(err);
                 if matches!(err, EndpointError::Disabled) {
                     continue;
                 }
@@ -121,39 +220,75 @@ async fn main(_spawner: Spawner) {
             'connected: loop {
                 machine.tick();
 
-                let mut wait = STATUS_LED_POLL_INTERVAL;
-                if let Some(timeout) = machine.handshake_timeout_remaining() {
-                    wait = timeout.min(wait);
-                }
-
-                let wait = nonzero_duration(wait);
+                let wait = poll_wait(
+                    READ_POLL_INTERVAL,
+                    machine.handshake_timeout_remaining(),
+                    machine.batch_remaining(),
+                );
 
-                // Drive handshake timeouts and LED latch expiry by racing USB reads against a timer tick.
-                let len_result = match select(Timer::after(wait), class.read_packet(&mut read_buf))
-                    .await
+                // Drive handshake timeouts and LED latch expiry by racing USB reads against a timer
+                // tick. The bridged UART only produces data while a bridge is open. `wait` is the
+                // min of the poll interval and the handshake deadline, so retuning the interval
+                // must keep that min or the timeout fires late.
+                let len_result = match select3(
+                    Timer::after(wait),
+                    class.read_packet(&mut read_buf),
+                    machine.read_bridged(&mut uart_buf),
+                )
+                .await
                 {
-                    Either::First(_) => {
+                    Either3::First(_) => {
                         if let Some(timeout) = machine.handshake_timeout_remaining() {
                             if timeout.as_ticks() == 0 {
                                 if let Err(err) = machine.handle_handshake_timeout(&mut class).await
                                 {
+                                    machine.record_write_error(err);
                                     if matches!(err, EndpointError::Disabled) {
                                         break 'connected;
                                     }
                                 }
                             }
                         }
+                        if let Err(err) = machine.send_batch_if_due(&mut class).await {
+                            machine.record_write_error(err);
+                            if matches!(err, EndpointError::Disabled) {
+                                break 'connected;
+                            }
+                        }
+                        if let Err(err) = machine.send_heartbeat_if_due(&mut class).await {
+                            machine.record_write_error(err);
+                            if matches!(err, EndpointError::Disabled) {
+                                break 'connected;
+                            }
+                        }
+                        continue;
+                    }
+                    Either3::Second(result) => result,
+                    Either3::Third(len) => {
+                        if let Err(err) =
+                            machine.forward_bridged(&mut class, &uart_buf[..len]).await
+                        {
+                            machine.record_write_error(err);
+                            if matches!(err, EndpointError::Disabled) {
+                                break 'connected;
+                            }
+                        }
                         continue;
                     }
-                    Either::Second(result) => result,
                 };
 
                 let len = match len_result {
                     Ok(len) => len,
-                    Err(EndpointError::Disabled) => break 'connected,
-                    Err(EndpointError::BufferOverflow) => {
+                    Err(err @ EndpointError::Disabled) => {
+                        // The host went away; the next session starts clean.
+                        machine.record_read_error(err);
+                        break 'connected;
+                    }
+                    Err(err @ EndpointError::BufferOverflow) => {
+                        machine.record_read_error(err);
                         // Surface overflows to the host rather than silently dropping bytes.
                         if let Err(err) = machine.handle_buffer_overflow(&mut class).await {
+                            machine.record_write_error(err);
                             if matches!(err, EndpointError::Disabled) {
                                 break 'connected;
                             }
@@ -169,6 +304,7 @@ async fn main(_spawner: Spawner) {
 
                 // Feed new bytes into the state machine; bail out if the host disconnects.
                 if let Err(err) = machine.consume(&mut class, &read_buf[..len]).await {
+                    machine.record_write_error(err);
                     if matches!(err, EndpointError::Disabled) {
                         break 'connected;
                     }
@@ -177,12 +313,27 @@ async fn main(_spawner: Spawner) {
         }
     };
 
-    let led_fut = status_led::drive(status_led);
+    let led_fut = fw_core::status_led::drive(status_led);
 
     // Execute the USB driver task, serial state machine, and LED driver together.
     let _ = join3(usb_fut, serial_fut, led_fut).await;
 }
 
+/// Pick how long to wait for USB data before the next timer tick.
+/// The remaining handshake or batch time wins whenever it is shorter than the poll interval so the
+/// timeout fires and held responses go out on schedule regardless of how the interval is tuned.
+fn poll_wait(
+    interval: Duration,
+    handshake_remaining: Option<Duration>,
+    batch_remaining: Option<Duration>,
+) -> Duration {
+    let wait = [handshake_remaining, batch_remaining]
+        .into_iter()
+        .flatten()
+        .fold(interval, Duration::min);
+    nonzero_duration(wait)
+}
+
 fn nonzero_duration(duration: Duration) -> Duration {
     if duration.as_ticks() == 0 {
         Duration::from_micros(1)
@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    // Anything that decodes must also render, since the TUI displays decoded commands.
+    if let Ok(command) = protocol::decode_command(data) {
+        let _ = command.to_string();
+    }
+});
@@ -0,0 +1,29 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use protocol::{
+    host::decode_transport_frame_resyncing,
+    transport::{FrameReader, Framing},
+};
+
+fuzz_target!(|data: &[u8]| {
+    for framing in Framing::ALL {
+        let _ = framing.take_from_bytes(data);
+        let _ = decode_transport_frame_resyncing(data, framing);
+
+        // The firmware sees the same bytes split across 64-byte USB reads.
+        let mut reader = FrameReader::<512>::new();
+        reader.set_framing(framing);
+        for chunk in data.chunks(64) {
+            let mut rest = chunk;
+            while !rest.is_empty() {
+                let accepted = reader.push(rest);
+                while let Ok(Some(_)) = reader.next_frame() {}
+                if accepted == 0 {
+                    reader.clear();
+                }
+                rest = &rest[accepted..];
+            }
+        }
+    }
+});
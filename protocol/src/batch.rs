@@ -0,0 +1,136 @@
+//! Sending several small responses in one USB write.
+//!
+//! Every USB write costs a transaction however few bytes it carries, so a burst of short replies
+//! spends most of the link on overhead. A [`FrameBatch`] collects encoded frames back to back and
+//! holds them for at most its window after the first one arrives, then they go out together. Frames
+//! keep their own length and CRC, so the host's reader splits a batch exactly as it would the same
+//! frames sent one at a time. A zero window turns batching off.
+
+use crate::transport::{FrameError, Framing, PostcardError};
+
+/// Encoded frames waiting to be sent together. Times are milliseconds on any monotonic clock.
+#[derive(Debug, Clone)]
+pub struct FrameBatch<const N: usize> {
+    buffer: [u8; N],
+    len: usize,
+    window_ms: u64,
+    /// When the oldest queued frame has to be sent by.
+    deadline_ms: Option<u64>,
+}
+
+impl<const N: usize> FrameBatch<N> {
+    pub const fn new(window_ms: u64) -> Self {
+        Self {
+            buffer: [0; N],
+            len: 0,
+            window_ms,
+            deadline_ms: None,
+        }
+    }
+
+    /// Whether frames are held at all. When not, every frame should be sent straight away.
+    pub const fn is_enabled(&self) -> bool {
+        self.window_ms > 0
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Frame `payload` onto the end of the batch at `now_ms`. A frame that doesn't fit in the
+    /// space left is refused and the batch is left as it was; send the batch and try again.
+    pub fn push(
+        &mut self,
+        framing: Framing,
+        payload: &[u8],
+        now_ms: u64,
+    ) -> Result<(), FrameError> {
+        if !self.is_enabled() {
+            return Err(FrameError::Serialize(PostcardError::SerializeBufferFull));
+        }
+        let written = framing.encode_into(payload, &mut self.buffer[self.len..])?;
+        self.len += written;
+        self.deadline_ms.get_or_insert(now_ms + self.window_ms);
+        Ok(())
+    }
+
+    /// When the batch has to be sent, if it holds anything. Later frames never push it back.
+    pub const fn deadline_ms(&self) -> Option<u64> {
+        self.deadline_ms
+    }
+
+    /// Whether the oldest frame has waited out the window by `now_ms`.
+    pub fn is_due(&self, now_ms: u64) -> bool {
+        self.deadline_ms.is_some_and(|deadline| now_ms >= deadline)
+    }
+
+    /// Empty the batch, returning the frames to send.
+    pub fn take(&mut self) -> &[u8] {
+        let len = core::mem::take(&mut self.len);
+        self.deadline_ms = None;
+        &self.buffer[..len]
+    }
+
+    /// Drop anything queued, e.g. when the session it was meant for has ended.
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.deadline_ms = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::FrameReader;
+
+    #[test]
+    fn a_batch_splits_back_into_its_frames() {
+        let payloads: [&[u8]; 4] = [b"\x2a", b"OK", b"", b"a longer reply of a few words"];
+        for framing in Framing::ALL {
+            let mut batch = FrameBatch::<256>::new(2);
+            for payload in payloads {
+                batch.push(framing, payload, 0).unwrap();
+            }
+
+            // The host reads whatever USB packets the batch was split into.
+            let mut reader = FrameReader::<256>::new();
+            reader.set_framing(framing);
+            let mut received = Vec::new();
+            for packet in batch.take().chunks(5) {
+                assert_eq!(reader.push(packet), packet.len());
+                while let Some(payload) = reader.next_frame().unwrap() {
+                    received.push(payload.to_vec());
+                }
+            }
+            assert_eq!(received, payloads, "{framing:?}");
+            assert!(reader.is_empty());
+            assert!(batch.is_empty());
+        }
+    }
+
+    #[test]
+    fn the_window_runs_from_the_first_frame() {
+        let mut batch = FrameBatch::<64>::new(3);
+        assert_eq!(batch.deadline_ms(), None);
+        batch.push(Framing::Postcard, b"one", 10).unwrap();
+        batch.push(Framing::Postcard, b"two", 12).unwrap();
+        assert_eq!(batch.deadline_ms(), Some(13));
+        assert!(!batch.is_due(12));
+        assert!(batch.is_due(13));
+        batch.take();
+        assert_eq!(batch.deadline_ms(), None);
+    }
+
+    #[test]
+    fn a_frame_that_does_not_fit_leaves_the_batch_alone() {
+        let mut batch = FrameBatch::<16>::new(3);
+        batch.push(Framing::LengthPrefixed, b"12345678", 0).unwrap();
+        assert!(batch.push(Framing::LengthPrefixed, b"12345678", 0).is_err());
+        assert_eq!(batch.take().len(), 8 + 4);
+
+        let mut off = FrameBatch::<16>::new(0);
+        assert!(!off.is_enabled());
+        assert!(off.push(Framing::Postcard, b"hi", 0).is_err());
+        assert!(off.is_empty());
+    }
+}
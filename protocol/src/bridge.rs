@@ -0,0 +1,182 @@
+//! Transparent UART bridging.
+//!
+//! `uart bridge <baud>` turns the firmware into a USB-serial adapter for a device wired to its
+//! hardware UART. The data path while bridged is:
+//!
+//! ```text
+//! host ──USB CDC──▶ firmware ──EscapeDetector──▶ UART TX
+//! host ◀──USB CDC── firmware ◀───────────────── UART RX
+//! ```
+//!
+//! Framing is suspended for the whole session. The firmware answers the bridge command with a
+//! normal framed response ([`opened_response`]); every byte after that frame is raw UART data in
+//! both directions. To leave, the host sends [`BRIDGE_ESCAPE`]. The firmware swallows the escape,
+//! stops bridging and sends a framed [`BRIDGE_CLOSED`] response, after which the link carries
+//! frames again. Raw bytes still in flight when the bridge closes are skipped by the host's frame
+//! resynchronisation.
+//!
+//! Since the escape is three copies of one byte, a shorter run of [`BRIDGE_ESCAPE_BYTE`] is passed
+//! through to the UART unchanged. The escape itself can never reach the bridged device.
+
+/// Byte repeated to form the escape sequence: `0x1D`, the `Ctrl+]` of classic serial terminals.
+pub const BRIDGE_ESCAPE_BYTE: u8 = 0x1D;
+pub const BRIDGE_ESCAPE_LEN: usize = 3;
+/// Sequence that ends a bridge session and returns the link to framed commands.
+pub const BRIDGE_ESCAPE: [u8; BRIDGE_ESCAPE_LEN] = [BRIDGE_ESCAPE_BYTE; BRIDGE_ESCAPE_LEN];
+
+const BRIDGE_OPENED_PREFIX: &str = "bridge open ";
+/// Framed response sent once the bridge has closed.
+pub const BRIDGE_CLOSED: &[u8] = b"bridge closed";
+
+/// Write the framed response that confirms a bridge at `baud`, e.g. `bridge open 115200`.
+pub fn opened_response(out: &mut impl core::fmt::Write, baud: u32) -> core::fmt::Result {
+    write!(out, "{BRIDGE_OPENED_PREFIX}{baud}")
+}
+
+/// Parse a response written by [`opened_response`], returning the baud rate.
+pub fn parse_opened_response(payload: &[u8]) -> Option<u32> {
+    let digits = payload.strip_prefix(BRIDGE_OPENED_PREFIX.as_bytes())?;
+    core::str::from_utf8(digits).ok()?.parse().ok()
+}
+
+/// Result of one [`EscapeDetector::filter`] pass.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Filtered {
+    /// Input bytes used, including the escape if one was found.
+    pub consumed: usize,
+    /// Bytes written to the output that should go to the UART.
+    pub written: usize,
+    /// The escape sequence ended at `consumed`.
+    pub escaped: bool,
+}
+
+/// Finds [`BRIDGE_ESCAPE`] in host data, even when it is split across USB reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct EscapeDetector {
+    /// Escape bytes seen at the end of the data so far, held back until the run is decided.
+    run: usize,
+}
+
+impl EscapeDetector {
+    pub const fn new() -> Self {
+        Self { run: 0 }
+    }
+
+    /// Copy the bytes of `data` that belong to the bridged device into `out`.
+    ///
+    /// Stops early when the escape completes or `out` is full; call again with the unconsumed
+    /// input. `out` must hold at least [`BRIDGE_ESCAPE_LEN`] bytes so a held-back run always fits.
+    pub fn filter(&mut self, data: &[u8], out: &mut [u8]) -> Filtered {
+        let mut written = 0;
+        for (idx, &byte) in data.iter().enumerate() {
+            if byte == BRIDGE_ESCAPE_BYTE {
+                self.run += 1;
+                if self.run == BRIDGE_ESCAPE_LEN {
+                    self.run = 0;
+                    return Filtered {
+                        consumed: idx + 1,
+                        written,
+                        escaped: true,
+                    };
+                }
+                continue;
+            }
+
+            // The run was too short to be the escape, so it was data after all.
+            if written + self.run + 1 > out.len() {
+                return Filtered {
+                    consumed: idx,
+                    written,
+                    escaped: false,
+                };
+            }
+            out[written..written + self.run].fill(BRIDGE_ESCAPE_BYTE);
+            written += self.run;
+            self.run = 0;
+            out[written] = byte;
+            written += 1;
+        }
+        Filtered {
+            consumed: data.len(),
+            written,
+            escaped: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_data_passes_through() {
+        let mut detector = EscapeDetector::default();
+        let mut out = [0; 16];
+        let filtered = detector.filter(b"hello", &mut out);
+        assert_eq!(
+            filtered,
+            Filtered {
+                consumed: 5,
+                written: 5,
+                escaped: false
+            }
+        );
+        assert_eq!(&out[..5], b"hello");
+    }
+
+    #[test]
+    fn escape_is_swallowed_and_stops_the_scan() {
+        let mut detector = EscapeDetector::default();
+        let mut out = [0; 16];
+        let filtered = detector.filter(b"ab\x1d\x1d\x1dframes", &mut out);
+        assert_eq!(
+            filtered,
+            Filtered {
+                consumed: 5,
+                written: 2,
+                escaped: true
+            }
+        );
+        assert_eq!(&out[..2], b"ab");
+    }
+
+    #[test]
+    fn escape_split_across_reads_is_found() {
+        let mut detector = EscapeDetector::default();
+        let mut out = [0; 16];
+        let first = detector.filter(b"x\x1d\x1d", &mut out);
+        assert_eq!(first.written, 1);
+        assert!(!first.escaped);
+
+        let second = detector.filter(b"\x1d", &mut out);
+        assert!(second.escaped);
+        assert_eq!(second.written, 0);
+    }
+
+    #[test]
+    fn short_runs_are_forwarded() {
+        let mut detector = EscapeDetector::default();
+        let mut out = [0; 16];
+        assert_eq!(detector.filter(b"\x1d\x1d", &mut out).written, 0);
+        let filtered = detector.filter(b"z", &mut out);
+        assert_eq!(&out[..filtered.written], b"\x1d\x1dz");
+    }
+
+    #[test]
+    fn full_output_pauses_the_scan() {
+        let mut detector = EscapeDetector::default();
+        let mut out = [0; BRIDGE_ESCAPE_LEN];
+        let filtered = detector.filter(b"abcdef", &mut out);
+        assert_eq!(filtered.consumed, 3);
+        assert_eq!(filtered.written, 3);
+    }
+
+    #[test]
+    fn opened_response_round_trips() {
+        let mut text = String::new();
+        opened_response(&mut text, 115_200).unwrap();
+        assert_eq!(text, "bridge open 115200");
+        assert_eq!(parse_opened_response(text.as_bytes()), Some(115_200));
+        assert_eq!(parse_opened_response(BRIDGE_CLOSED), None);
+    }
+}
@@ -0,0 +1,292 @@
+//! Commands too long for one frame, sent as a run of chunks.
+//!
+//! An encoded command longer than [`MAX_COMMAND_LEN`](crate::MAX_COMMAND_LEN) is split by the host
+//! into chunk frames, and the firmware puts them back together before decoding the command, up to
+//! [`MAX_CHUNKED_COMMAND_LEN`] bytes in all. Each chunk's payload is
+//! `[CHUNK_MARKER, kind, seq, total_len (u16 BE), data...]`:
+//!
+//! - `kind` is [`CHUNK_START`] for the first chunk, [`CHUNK_END`] for the last and
+//!   [`CHUNK_CONTINUE`] for any between. A command that fits one chunk is a lone start.
+//! - `seq` counts chunks from zero, wrapping after 255.
+//! - `total_len` is the length of the whole command, the same in every chunk.
+//!
+//! Only the finished command gets a response. A chunk out of sequence, a continue or end without a
+//! start, a total the firmware has no room for, or data that doesn't add up to the total drops
+//! the partial command and is answered with [`CHUNK_ERROR_CODE`]. A start always begins a new
+//! command, so a host can start over after an error.
+
+use core::fmt;
+
+/// First byte of a chunk. No command starts with it, since it isn't a [`Method`](crate::Method).
+pub const CHUNK_MARKER: u8 = 0x1B;
+pub const CHUNK_START: u8 = 0x01;
+pub const CHUNK_CONTINUE: u8 = 0x02;
+pub const CHUNK_END: u8 = 0x03;
+/// Bytes in front of the data of every chunk.
+pub const CHUNK_HEADER_LEN: usize = 5;
+/// Longest command the firmware reassembles from chunks.
+pub const MAX_CHUNKED_COMMAND_LEN: usize = 512;
+/// Error code the firmware answers a broken run of chunks with.
+pub const CHUNK_ERROR_CODE: &str = "InvalidChunk";
+
+/// Whether a frame payload is a chunk rather than a whole command.
+pub fn is_chunk(payload: &[u8]) -> bool {
+    payload.first() == Some(&CHUNK_MARKER)
+}
+
+/// Why a run of chunks was dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkError {
+    /// Shorter than the header, or an unknown kind.
+    Malformed,
+    /// A continue or end arrived with no command started.
+    NotStarted,
+    /// A chunk arrived with the wrong sequence number, so one went missing or came out of order.
+    OutOfOrder { expected: u8, received: u8 },
+    /// The command is longer than the reassembly buffer.
+    TooLong { total_len: usize },
+    /// The data doesn't add up to the total length, or a chunk changed the total.
+    LengthMismatch,
+}
+
+impl fmt::Display for ChunkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChunkError::Malformed => f.write_str("malformed chunk"),
+            ChunkError::NotStarted => f.write_str("chunk without a start"),
+            ChunkError::OutOfOrder { expected, received } => {
+                write!(f, "expected chunk {expected}, got {received}")
+            }
+            ChunkError::TooLong { total_len } => write!(f, "{total_len} bytes is too long"),
+            ChunkError::LengthMismatch => f.write_str("chunk lengths don't add up"),
+        }
+    }
+}
+
+/// Puts a chunked command back together in a buffer of `N` bytes.
+#[derive(Debug, Clone)]
+pub struct ChunkAssembler<const N: usize> {
+    buffer: [u8; N],
+    len: usize,
+    /// Total length and next sequence number of the command being assembled.
+    pending: Option<(usize, u8)>,
+}
+
+impl<const N: usize> Default for ChunkAssembler<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> ChunkAssembler<N> {
+    pub const fn new() -> Self {
+        Self {
+            buffer: [0; N],
+            len: 0,
+            pending: None,
+        }
+    }
+
+    /// Whether a command is part way through arriving.
+    pub const fn is_assembling(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Add one chunk. Returns the whole command once its last chunk is in, `None` while more are
+    /// to come. Any error drops the partial command.
+    pub fn push(&mut self, chunk: &[u8]) -> Result<Option<&[u8]>, ChunkError> {
+        let result = self.accept(chunk);
+        if result.is_err() {
+            self.clear();
+        }
+        match result {
+            Ok(true) => {
+                let len = core::mem::take(&mut self.len);
+                self.pending = None;
+                Ok(Some(&self.buffer[..len]))
+            }
+            Ok(false) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Drop any partial command, e.g. when the session ends.
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.pending = None;
+    }
+
+    /// Store a chunk's data, returning whether it completed the command.
+    fn accept(&mut self, chunk: &[u8]) -> Result<bool, ChunkError> {
+        let [CHUNK_MARKER, kind, seq, len_hi, len_lo, ref data @ ..] = *chunk else {
+            return Err(ChunkError::Malformed);
+        };
+        let total_len = usize::from(u16::from_be_bytes([len_hi, len_lo]));
+        match kind {
+            CHUNK_START => {
+                if seq != 0 {
+                    return Err(ChunkError::OutOfOrder {
+                        expected: 0,
+                        received: seq,
+                    });
+                }
+                if total_len > N {
+                    return Err(ChunkError::TooLong { total_len });
+                }
+                self.len = 0;
+                self.pending = Some((total_len, 0));
+            }
+            CHUNK_CONTINUE | CHUNK_END => {
+                let Some((pending_len, expected)) = self.pending else {
+                    return Err(ChunkError::NotStarted);
+                };
+                if seq != expected {
+                    return Err(ChunkError::OutOfOrder {
+                        expected,
+                        received: seq,
+                    });
+                }
+                if total_len != pending_len {
+                    return Err(ChunkError::LengthMismatch);
+                }
+            }
+            _ => return Err(ChunkError::Malformed),
+        }
+
+        let end = self.len + data.len();
+        if end > total_len {
+            return Err(ChunkError::LengthMismatch);
+        }
+        self.buffer[self.len..end].copy_from_slice(data);
+        self.len = end;
+        self.pending = Some((total_len, seq.wrapping_add(1)));
+
+        if self.len == total_len {
+            return Ok(true);
+        }
+        if kind == CHUNK_END {
+            return Err(ChunkError::LengthMismatch);
+        }
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host::split_into_chunks;
+
+    #[test]
+    fn chunks_reassemble_into_the_command() {
+        let command: Vec<u8> = (0..=255u8).cycle().take(500).collect();
+        let chunks = split_into_chunks(&command, 64);
+        assert_eq!(chunks.len(), 500usize.div_ceil(64 - CHUNK_HEADER_LEN));
+        assert!(chunks.iter().all(|chunk| chunk.len() <= 64));
+
+        let mut assembler = ChunkAssembler::<MAX_CHUNKED_COMMAND_LEN>::new();
+        let (last, rest) = chunks.split_last().unwrap();
+        for chunk in rest {
+            assert_eq!(assembler.push(chunk), Ok(None));
+            assert!(assembler.is_assembling());
+        }
+        assert_eq!(assembler.push(last), Ok(Some(command.as_slice())));
+        assert!(!assembler.is_assembling());
+    }
+
+    #[test]
+    fn a_missing_or_repeated_chunk_drops_the_command() {
+        let command = [0xAB; 100];
+        let chunks = split_into_chunks(&command, 32);
+        let mut assembler = ChunkAssembler::<MAX_CHUNKED_COMMAND_LEN>::new();
+
+        assembler.push(&chunks[0]).unwrap();
+        assert_eq!(
+            assembler.push(&chunks[2]),
+            Err(ChunkError::OutOfOrder {
+                expected: 1,
+                received: 2
+            })
+        );
+        assert!(!assembler.is_assembling());
+        assert_eq!(assembler.push(&chunks[1]), Err(ChunkError::NotStarted));
+
+        assembler.push(&chunks[0]).unwrap();
+        assembler.push(&chunks[1]).unwrap();
+        assert_eq!(
+            assembler.push(&chunks[1]),
+            Err(ChunkError::OutOfOrder {
+                expected: 2,
+                received: 1
+            })
+        );
+
+        // A start begins again from scratch.
+        for chunk in &chunks[..chunks.len() - 1] {
+            assert_eq!(assembler.push(chunk), Ok(None));
+        }
+        assert_eq!(
+            assembler.push(chunks.last().unwrap()),
+            Ok(Some(command.as_slice()))
+        );
+    }
+
+    #[test]
+    fn lengths_are_checked_against_the_total_and_the_buffer() {
+        let mut assembler = ChunkAssembler::<16>::new();
+        let too_long = split_into_chunks(&[0; 17], 64);
+        assert_eq!(
+            assembler.push(&too_long[0]),
+            Err(ChunkError::TooLong { total_len: 17 })
+        );
+
+        // An end that leaves the command short.
+        let short = [CHUNK_MARKER, CHUNK_END, 1, 0, 8, 0xAA];
+        assembler
+            .push(&[CHUNK_MARKER, CHUNK_START, 0, 0, 8, 1, 2])
+            .unwrap();
+        assert_eq!(assembler.push(&short), Err(ChunkError::LengthMismatch));
+
+        // More data than the total.
+        assembler
+            .push(&[CHUNK_MARKER, CHUNK_START, 0, 0, 2])
+            .unwrap();
+        assert_eq!(
+            assembler.push(&[CHUNK_MARKER, CHUNK_END, 1, 0, 2, 1, 2, 3]),
+            Err(ChunkError::LengthMismatch)
+        );
+
+        assert_eq!(
+            assembler.push(&[CHUNK_MARKER, 0x09, 0, 0, 1]),
+            Err(ChunkError::Malformed)
+        );
+        assert_eq!(
+            assembler.push(&[CHUNK_MARKER, 0x01]),
+            Err(ChunkError::Malformed)
+        );
+    }
+
+    #[test]
+    fn a_command_that_fits_one_chunk_is_a_lone_start() {
+        let chunks = split_into_chunks(&[0x01, 0x00, b'h', b'i'], 64);
+        assert_eq!(
+            chunks,
+            [vec![
+                CHUNK_MARKER,
+                CHUNK_START,
+                0,
+                0,
+                4,
+                0x01,
+                0x00,
+                b'h',
+                b'i'
+            ]]
+        );
+
+        let mut assembler = ChunkAssembler::<16>::new();
+        assert_eq!(
+            assembler.push(&chunks[0]),
+            Ok(Some([0x01, 0x00, b'h', b'i'].as_slice()))
+        );
+    }
+}
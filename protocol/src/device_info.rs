@@ -0,0 +1,351 @@
+//! Capabilities the firmware advertises about itself, and the settings it is running with.
+//!
+//! Wire layout of [`DeviceConfig`]: `[spi_mode, spi_freq_khz (u16 BE), heartbeat_ms (u16 BE)]`,
+//! where a heartbeat interval of 0 means heartbeats are off.
+//!
+//! Wire layout of [`DeviceInfo`], the `info` response:
+//! `[max_i2c_read, config, has_uart, max_baud (u32 BE), parities, label]`, where `config` is a
+//! [`DeviceConfig`], `max_baud` and `parities` are only present when `has_uart` is 1, `parities` is
+//! a [`Parity`] bitmask and the UTF-8 [`DeviceLabel`] takes the rest.
+
+use core::fmt;
+
+use crate::spi::{DEFAULT_SPI_FREQUENCY_KHZ, DEFAULT_SPI_MODE};
+
+/// UART parity modes, usable as a bitmask.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Parity {
+    None = 0x01,
+    Even = 0x02,
+    Odd = 0x04,
+}
+
+impl Parity {
+    pub const ALL: [Parity; 3] = [Parity::None, Parity::Even, Parity::Odd];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Parity::None => "none",
+            Parity::Even => "even",
+            Parity::Odd => "odd",
+        }
+    }
+}
+
+/// What the device's hardware UART can do when bridged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UartCapabilities {
+    pub max_baud: u32,
+    /// Bitmask of supported [`Parity`] modes.
+    pub parities: u8,
+}
+
+impl UartCapabilities {
+    pub const fn supports(&self, parity: Parity) -> bool {
+        self.parities & parity as u8 != 0
+    }
+
+    pub fn supports_baud(&self, baud: u32) -> bool {
+        baud <= self.max_baud
+    }
+}
+
+/// Settings that commands can change while the device runs, as read back by `config`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceConfig {
+    /// SPI mode, 0 to 3.
+    pub spi_mode: u8,
+    pub spi_freq_khz: u16,
+    /// Interval between idle heartbeats, or `None` when they are off.
+    pub heartbeat_ms: Option<u16>,
+}
+
+/// Length of an encoded [`DeviceConfig`].
+pub const DEVICE_CONFIG_LEN: usize = 5;
+
+impl DeviceConfig {
+    /// The settings the firmware starts with.
+    pub const DEFAULT: Self = Self {
+        spi_mode: DEFAULT_SPI_MODE,
+        spi_freq_khz: DEFAULT_SPI_FREQUENCY_KHZ,
+        heartbeat_ms: None,
+    };
+
+    pub fn encode(&self) -> [u8; DEVICE_CONFIG_LEN] {
+        let [freq_hi, freq_lo] = self.spi_freq_khz.to_be_bytes();
+        let [beat_hi, beat_lo] = self.heartbeat_ms.unwrap_or(0).to_be_bytes();
+        [self.spi_mode, freq_hi, freq_lo, beat_hi, beat_lo]
+    }
+
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let [spi_mode, freq_hi, freq_lo, beat_hi, beat_lo] = *bytes else {
+            return None;
+        };
+        let heartbeat_ms = u16::from_be_bytes([beat_hi, beat_lo]);
+        Some(Self {
+            spi_mode,
+            spi_freq_khz: u16::from_be_bytes([freq_hi, freq_lo]),
+            heartbeat_ms: (heartbeat_ms != 0).then_some(heartbeat_ms),
+        })
+    }
+}
+
+impl Default for DeviceConfig {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Reads e.g. `spi mode0 at 1000 kHz, heartbeat every 500 ms`.
+impl fmt::Display for DeviceConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::spi::confirmation(f, self.spi_mode, self.spi_freq_khz)?;
+        match self.heartbeat_ms {
+            Some(interval_ms) => write!(f, ", heartbeat every {interval_ms} ms"),
+            None => f.write_str(", heartbeat off"),
+        }
+    }
+}
+
+/// Longest [`DeviceLabel`], in bytes.
+pub const MAX_DEVICE_LABEL_LEN: usize = 24;
+
+/// What the firmware calls itself, e.g. `SiTerm RP2040 v0.1.0`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct DeviceLabel {
+    bytes: [u8; MAX_DEVICE_LABEL_LEN],
+    len: u8,
+}
+
+impl DeviceLabel {
+    /// `None` when `label` is longer than [`MAX_DEVICE_LABEL_LEN`].
+    pub const fn new(label: &str) -> Option<Self> {
+        let label = label.as_bytes();
+        if label.len() > MAX_DEVICE_LABEL_LEN {
+            return None;
+        }
+        let mut bytes = [0; MAX_DEVICE_LABEL_LEN];
+        let mut idx = 0;
+        while idx < label.len() {
+            bytes[idx] = label[idx];
+            idx += 1;
+        }
+        Some(Self {
+            bytes,
+            len: label.len() as u8,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        // Only built from a `&str`, so always valid UTF-8.
+        core::str::from_utf8(&self.bytes[..usize::from(self.len)]).unwrap_or_default()
+    }
+}
+
+impl Default for DeviceLabel {
+    fn default() -> Self {
+        Self {
+            bytes: [0; MAX_DEVICE_LABEL_LEN],
+            len: 0,
+        }
+    }
+}
+
+impl fmt::Debug for DeviceLabel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for DeviceLabel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceInfo {
+    /// Longest I2C read answered in one response.
+    pub max_i2c_read: u8,
+    /// Settings in effect when the info was sent.
+    pub config: DeviceConfig,
+    /// `None` when the device has no UART to bridge.
+    pub uart: Option<UartCapabilities>,
+    pub label: DeviceLabel,
+}
+
+/// Longest encoding of a [`DeviceInfo`].
+pub const DEVICE_INFO_MAX_LEN: usize = 7 + DEVICE_CONFIG_LEN + MAX_DEVICE_LABEL_LEN;
+
+impl DeviceInfo {
+    /// Encode into `buffer`, returning the number of bytes written.
+    pub fn encode(&self, buffer: &mut [u8; DEVICE_INFO_MAX_LEN]) -> usize {
+        const UART: usize = 1 + DEVICE_CONFIG_LEN;
+        buffer[0] = self.max_i2c_read;
+        buffer[1..UART].copy_from_slice(&self.config.encode());
+        let label_start = match self.uart {
+            Some(uart) => {
+                buffer[UART] = 1;
+                buffer[UART + 1..UART + 5].copy_from_slice(&uart.max_baud.to_be_bytes());
+                buffer[UART + 5] = uart.parities;
+                UART + 6
+            }
+            None => {
+                buffer[UART] = 0;
+                UART + 1
+            }
+        };
+        let label = self.label.as_str().as_bytes();
+        buffer[label_start..label_start + label.len()].copy_from_slice(label);
+        label_start + label.len()
+    }
+
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let (&max_i2c_read, rest) = bytes.split_first()?;
+        let config = DeviceConfig::decode(rest.get(..DEVICE_CONFIG_LEN)?)?;
+        let (uart, label) = match rest[DEVICE_CONFIG_LEN..] {
+            [0, ref label @ ..] => (None, label),
+            [1, b0, b1, b2, b3, parities, ref label @ ..] => (
+                Some(UartCapabilities {
+                    max_baud: u32::from_be_bytes([b0, b1, b2, b3]),
+                    parities,
+                }),
+                label,
+            ),
+            _ => return None,
+        };
+        Some(Self {
+            max_i2c_read,
+            config,
+            uart,
+            label: DeviceLabel::new(core::str::from_utf8(label).ok()?)?,
+        })
+    }
+}
+
+/// Reads e.g. `SiTerm RP2040 v0.1.0: i2c reads up to 32 bytes, uart up to 7812500 baud, spi mode0
+/// at 1000 kHz, heartbeat off`.
+impl fmt::Display for DeviceInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.label.as_str().is_empty() {
+            write!(f, "{}: ", self.label)?;
+        }
+        write!(f, "i2c reads up to {} bytes, ", self.max_i2c_read)?;
+        match self.uart {
+            Some(uart) => write!(f, "uart up to {} baud, ", uart.max_baud)?,
+            None => f.write_str("no uart, ")?,
+        }
+        write!(f, "{}", self.config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn device_info_round_trips() {
+        let uart = UartCapabilities {
+            max_baud: 921_600,
+            parities: Parity::None as u8 | Parity::Even as u8,
+        };
+        let label = DeviceLabel::new("x".repeat(MAX_DEVICE_LABEL_LEN).as_str()).unwrap();
+        for info in [
+            DeviceInfo {
+                max_i2c_read: 32,
+                config: DeviceConfig::DEFAULT,
+                uart: Some(uart),
+                label,
+            },
+            DeviceInfo {
+                max_i2c_read: u8::MAX,
+                config: DeviceConfig {
+                    spi_mode: 3,
+                    spi_freq_khz: 8_000,
+                    heartbeat_ms: Some(250),
+                },
+                uart: None,
+                label: DeviceLabel::default(),
+            },
+        ] {
+            let mut buffer = [0; DEVICE_INFO_MAX_LEN];
+            let len = info.encode(&mut buffer);
+            assert_eq!(DeviceInfo::decode(&buffer[..len]), Some(info));
+        }
+        // A UART flag with the capabilities cut short.
+        assert_eq!(
+            DeviceInfo::decode(&[32, 0, 0x03, 0xE8, 0, 0, 1, 0, 0]),
+            None
+        );
+        assert_eq!(DeviceInfo::decode(&[32, 0, 0x03, 0xE8, 0, 0]), None);
+        assert_eq!(
+            DeviceInfo::decode(&[32, 0, 0x03, 0xE8, 0, 0, 0, 0xFF]),
+            None
+        );
+        assert_eq!(DeviceInfo::decode(&[32]), None);
+        assert_eq!(DeviceInfo::decode(&[]), None);
+        assert!(DeviceLabel::new("x".repeat(MAX_DEVICE_LABEL_LEN + 1).as_str()).is_none());
+    }
+
+    #[test]
+    fn device_info_reads_as_a_sentence() {
+        let info = DeviceInfo {
+            max_i2c_read: 32,
+            config: DeviceConfig::DEFAULT,
+            uart: Some(UartCapabilities {
+                max_baud: 7_812_500,
+                parities: Parity::None as u8,
+            }),
+            label: DeviceLabel::new("SiTerm RP2040 v0.1.0").unwrap(),
+        };
+        assert_eq!(
+            info.to_string(),
+            "SiTerm RP2040 v0.1.0: i2c reads up to 32 bytes, uart up to 7812500 baud, spi mode0 at \
+             1000 kHz, heartbeat off"
+        );
+        let bare = DeviceInfo {
+            uart: None,
+            label: DeviceLabel::default(),
+            ..info
+        };
+        assert_eq!(
+            bare.to_string(),
+            "i2c reads up to 32 bytes, no uart, spi mode0 at 1000 kHz, heartbeat off"
+        );
+    }
+
+    #[test]
+    fn device_config_round_trips() {
+        let config = DeviceConfig {
+            spi_mode: 1,
+            spi_freq_khz: 62_500,
+            heartbeat_ms: Some(1_000),
+        };
+        assert_eq!(config.encode(), [1, 0xF4, 0x24, 0x03, 0xE8]);
+        for config in [config, DeviceConfig::default()] {
+            assert_eq!(DeviceConfig::decode(&config.encode()), Some(config));
+        }
+        assert_eq!(DeviceConfig::decode(&[0, 0x03, 0xE8, 0]), None);
+        assert_eq!(
+            config.to_string(),
+            "spi mode1 at 62500 kHz, heartbeat every 1000 ms"
+        );
+        assert_eq!(
+            DeviceConfig::DEFAULT.to_string(),
+            "spi mode0 at 1000 kHz, heartbeat off"
+        );
+    }
+
+    #[test]
+    fn parity_mask_is_honoured() {
+        let uart = UartCapabilities {
+            max_baud: 115_200,
+            parities: Parity::Odd as u8,
+        };
+        assert!(uart.supports(Parity::Odd));
+        assert!(!uart.supports(Parity::Even));
+        assert!(uart.supports_baud(115_200));
+        assert!(!uart.supports_baud(230_400));
+    }
+}
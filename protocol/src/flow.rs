@@ -0,0 +1,99 @@
+//! Flow control around slow commands.
+//!
+//! The firmware reads nothing from USB while a handler runs, so a host that keeps writing during a
+//! slow command fills the link and the frames behind it arrive mangled. When a handler is still
+//! running after [`BUSY_AFTER_MS`], the firmware sends a framed [`BUSY`] payload; once the
+//! command's response (or error) has gone out it follows with [`READY`]. A host holds further
+//! commands between the two.
+//!
+//! Both are unsolicited frames, so a host drops them before treating frames as responses. Tagged
+//! sessions send them untagged. `READY` always follows a `BUSY`, but a host shouldn't rely on it
+//! alone: a session can end between the two, so a host waits at most [`MAX_BUSY_WAIT_MS`] before
+//! sending anyway.
+
+/// Payload sent when a handler has been running for [`BUSY_AFTER_MS`].
+pub const BUSY: &[u8] = b"\x1Bbusy";
+/// Payload sent after the response of a command that reported [`BUSY`].
+pub const READY: &[u8] = b"\x1Bready";
+/// How long a handler runs before the firmware reports itself busy.
+pub const BUSY_AFTER_MS: u64 = 20;
+/// Longest a host should hold commands after a [`BUSY`] without hearing [`READY`].
+pub const MAX_BUSY_WAIT_MS: u64 = 5_000;
+
+/// The firmware's side of one command: whether it has said [`BUSY`] and so owes a [`READY`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BusySignal {
+    busy: bool,
+}
+
+impl BusySignal {
+    pub const fn new() -> Self {
+        Self { busy: false }
+    }
+
+    /// The handler has been running for `elapsed_ms`. Returns [`BUSY`] the first time that passes
+    /// [`BUSY_AFTER_MS`].
+    pub fn poll(&mut self, elapsed_ms: u64) -> Option<&'static [u8]> {
+        if self.busy || elapsed_ms < BUSY_AFTER_MS {
+            return None;
+        }
+        self.busy = true;
+        Some(BUSY)
+    }
+
+    /// The command's reply has been sent. Returns [`READY`] if [`BUSY`] was.
+    pub fn finish(&mut self) -> Option<&'static [u8]> {
+        core::mem::take(&mut self.busy).then_some(READY)
+    }
+
+    pub const fn is_busy(&self) -> bool {
+        self.busy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Drive a signal the way the firmware does around a handler that finishes after
+    /// `handler_ms`, checking in every `tick_ms`. Returns the flow frames sent around the reply.
+    fn run_handler(
+        handler_ms: u64,
+        tick_ms: u64,
+    ) -> (Option<&'static [u8]>, Option<&'static [u8]>) {
+        let mut signal = BusySignal::new();
+        let mut busy = None;
+        let mut elapsed = 0;
+        while elapsed < handler_ms {
+            if let Some(frame) = signal.poll(elapsed) {
+                assert!(busy.is_none(), "busy sent twice");
+                busy = Some(frame);
+            }
+            elapsed += tick_ms;
+        }
+        (busy, signal.finish())
+    }
+
+    #[test]
+    fn long_handlers_are_bracketed_by_busy_and_ready() {
+        assert_eq!(run_handler(250, 5), (Some(BUSY), Some(READY)));
+        assert_eq!(run_handler(BUSY_AFTER_MS + 1, 1), (Some(BUSY), Some(READY)));
+    }
+
+    #[test]
+    fn quick_handlers_send_neither() {
+        assert_eq!(run_handler(BUSY_AFTER_MS, 1), (None, None));
+        assert_eq!(run_handler(0, 1), (None, None));
+    }
+
+    #[test]
+    fn ready_is_owed_once() {
+        let mut signal = BusySignal::new();
+        assert_eq!(signal.finish(), None);
+        assert_eq!(signal.poll(BUSY_AFTER_MS), Some(BUSY));
+        assert!(signal.is_busy());
+        assert_eq!(signal.finish(), Some(READY));
+        assert_eq!(signal.finish(), None);
+        assert!(!signal.is_busy());
+    }
+}
@@ -0,0 +1,311 @@
+//! Version negotiation for the connection handshake.
+//!
+//! The host sends `SiTerm?v<major>` and the firmware accepts it only when the major versions
+//! match, answering with [`HANDSHAKE_RESPONSE`] or [`HANDSHAKE_INCOMPATIBLE`]. A host that sends
+//! the bare legacy `SiTerm?` predates versioned handshakes and the frame CRC, so it is answered as
+//! incompatible too.
+//!
+//! A host that wants a framing other than postcard appends `;framing=<name>` to its handshake
+//! (see [`Framing::name`]) and the firmware echoes the suffix in its response. Firmware that
+//! predates the suffix reads it as an unknown version and answers incompatible, so a host never
+//! ends up speaking a framing the device doesn't.
+//!
+//! A host that wants each response tagged with the method that produced it (see
+//! [`crate::response::split_tag`]) ends its handshake with `;tagged`, after any framing suffix,
+//! and the firmware echoes that too. Older firmware refuses it the same way.
+
+use crate::{
+    HANDSHAKE_COMMAND, HANDSHAKE_COMMAND_PREFIX, HANDSHAKE_INCOMPATIBLE, HANDSHAKE_RESPONSE,
+    PROTOCOL_VERSION_MAJOR, transport::Framing,
+};
+
+const FRAMING_SEPARATOR: &[u8] = b";framing=";
+const LENGTH_FRAMED_COMMAND: &str = "SiTerm?v3;framing=length";
+const LENGTH_FRAMED_RESPONSE: &str = "SiTerm v3.0;framing=length";
+const TAGGED_SUFFIX: &[u8] = b";tagged";
+const TAGGED_COMMAND: &str = "SiTerm?v3;tagged";
+const TAGGED_RESPONSE: &str = "SiTerm v3.0;tagged";
+const LENGTH_FRAMED_TAGGED_COMMAND: &str = "SiTerm?v3;framing=length;tagged";
+const LENGTH_FRAMED_TAGGED_RESPONSE: &str = "SiTerm v3.0;framing=length;tagged";
+
+/// Handshake line the host sends to ask for `framing`, with tagged responses if `tagged`
+/// (delimiter not included).
+pub const fn command(framing: Framing, tagged: bool) -> &'static str {
+    match (framing, tagged) {
+        (Framing::Postcard, false) => HANDSHAKE_COMMAND,
+        (Framing::Postcard, true) => TAGGED_COMMAND,
+        (Framing::LengthPrefixed, false) => LENGTH_FRAMED_COMMAND,
+        (Framing::LengthPrefixed, true) => LENGTH_FRAMED_TAGGED_COMMAND,
+    }
+}
+
+/// Response the firmware sends to accept a handshake that asked for `framing` and `tagged`.
+pub const fn response(framing: Framing, tagged: bool) -> &'static str {
+    match (framing, tagged) {
+        (Framing::Postcard, false) => HANDSHAKE_RESPONSE,
+        (Framing::Postcard, true) => TAGGED_RESPONSE,
+        (Framing::LengthPrefixed, false) => LENGTH_FRAMED_RESPONSE,
+        (Framing::LengthPrefixed, true) => LENGTH_FRAMED_TAGGED_RESPONSE,
+    }
+}
+
+/// What the firmware should do with a delimited handshake line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandshakeRequest {
+    /// Versions are compatible; reply with the response for the requested framing and tagging.
+    Compatible { framing: Framing, tagged: bool },
+    /// A handshake for a different major version; reply with the incompatibility marker.
+    Incompatible { host_major: Option<u8> },
+    /// Not a handshake at all; ignore it.
+    Unrecognised,
+}
+
+/// Classify a handshake line received by the firmware (delimiter already stripped).
+pub fn check_request(line: &[u8]) -> HandshakeRequest {
+    let Some(rest) = line.strip_prefix(HANDSHAKE_COMMAND_PREFIX.as_bytes()) else {
+        return HandshakeRequest::Unrecognised;
+    };
+    if rest.is_empty() {
+        return HandshakeRequest::Incompatible { host_major: None };
+    }
+    let Some(rest) = rest.strip_prefix(b"v") else {
+        return HandshakeRequest::Unrecognised;
+    };
+    let (rest, tagged) = match rest.strip_suffix(TAGGED_SUFFIX) {
+        Some(rest) => (rest, true),
+        None => (rest, false),
+    };
+    let (version, framing) = match find(rest, FRAMING_SEPARATOR) {
+        Some(idx) => {
+            let name = &rest[idx + FRAMING_SEPARATOR.len()..];
+            let framing = core::str::from_utf8(name).ok().and_then(Framing::from_name);
+            (&rest[..idx], framing)
+        }
+        None => (rest, Some(Framing::Postcard)),
+    };
+
+    match (parse_major(version), framing) {
+        (Some(major), Some(framing)) if major == PROTOCOL_VERSION_MAJOR => {
+            HandshakeRequest::Compatible { framing, tagged }
+        }
+        (host_major, _) => HandshakeRequest::Incompatible { host_major },
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// The firmware's answer as seen by the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandshakeReply {
+    /// The bytes so far are a prefix of a known reply; keep reading.
+    Pending,
+    Accepted,
+    Incompatible,
+    /// The bytes can't be completed into any known reply.
+    Invalid,
+}
+
+/// Classify the bytes the host has read back after asking for `framing` and `tagged`.
+pub fn check_reply(bytes: &[u8], framing: Framing, tagged: bool) -> HandshakeReply {
+    let known = [
+        (response(framing, tagged), HandshakeReply::Accepted),
+        (HANDSHAKE_INCOMPATIBLE, HandshakeReply::Incompatible),
+    ];
+    let mut pending = false;
+    for (reply, outcome) in known {
+        if bytes == reply.as_bytes() {
+            return outcome;
+        }
+        pending |= reply.as_bytes().starts_with(bytes);
+    }
+    if pending {
+        HandshakeReply::Pending
+    } else {
+        HandshakeReply::Invalid
+    }
+}
+
+/// Longest reply the host may need to read before it can classify the handshake.
+pub const MAX_REPLY_LEN: usize =
+    if LENGTH_FRAMED_TAGGED_RESPONSE.len() > HANDSHAKE_INCOMPATIBLE.len() {
+        LENGTH_FRAMED_TAGGED_RESPONSE.len()
+    } else {
+        HANDSHAKE_INCOMPATIBLE.len()
+    };
+
+/// Parse the major component of `<major>[.<minor>]`.
+fn parse_major(version: &[u8]) -> Option<u8> {
+    let major = version.split(|byte| *byte == b'.').next()?;
+    if major.is_empty() || !major.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    core::str::from_utf8(major).ok()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POSTCARD: HandshakeRequest = HandshakeRequest::Compatible {
+        framing: Framing::Postcard,
+        tagged: false,
+    };
+
+    #[test]
+    fn current_host_version_is_accepted() {
+        assert_eq!(check_request(HANDSHAKE_COMMAND.as_bytes()), POSTCARD);
+        assert_eq!(check_request(b"SiTerm?v3.4"), POSTCARD);
+    }
+
+    #[test]
+    fn legacy_bare_handshake_is_incompatible() {
+        assert_eq!(
+            check_request(b"SiTerm?"),
+            HandshakeRequest::Incompatible { host_major: None }
+        );
+    }
+
+    #[test]
+    fn handshake_lines_carry_the_protocol_version() {
+        let versioned = format!("{HANDSHAKE_COMMAND_PREFIX}v{PROTOCOL_VERSION_MAJOR}");
+        let accepted = format!("SiTerm v{PROTOCOL_VERSION_MAJOR}.0");
+        for framing in Framing::ALL {
+            for tagged in [false, true] {
+                assert!(command(framing, tagged).starts_with(&versioned));
+                assert!(response(framing, tagged).starts_with(&accepted));
+            }
+        }
+        assert_eq!(HANDSHAKE_COMMAND, versioned);
+    }
+
+    #[test]
+    fn framing_suffix_is_negotiated() {
+        for framing in Framing::ALL {
+            for tagged in [false, true] {
+                assert_eq!(
+                    check_request(command(framing, tagged).as_bytes()),
+                    HandshakeRequest::Compatible { framing, tagged }
+                );
+                assert_eq!(
+                    check_reply(response(framing, tagged).as_bytes(), framing, tagged),
+                    HandshakeReply::Accepted
+                );
+                assert!(response(framing, tagged).len() <= MAX_REPLY_LEN);
+            }
+        }
+        assert_eq!(
+            LENGTH_FRAMED_COMMAND.as_bytes(),
+            [
+                HANDSHAKE_COMMAND.as_bytes(),
+                FRAMING_SEPARATOR,
+                Framing::LengthPrefixed.name().as_bytes()
+            ]
+            .concat()
+        );
+        assert_eq!(
+            LENGTH_FRAMED_TAGGED_RESPONSE.as_bytes(),
+            [LENGTH_FRAMED_RESPONSE.as_bytes(), TAGGED_SUFFIX].concat()
+        );
+    }
+
+    #[test]
+    fn tagging_goes_after_the_framing() {
+        assert!(matches!(
+            check_request(b"SiTerm?v3;tagged;framing=length"),
+            HandshakeRequest::Incompatible { .. }
+        ));
+    }
+
+    #[test]
+    fn untagged_reply_does_not_accept_a_tagged_request() {
+        assert_eq!(
+            check_reply(HANDSHAKE_RESPONSE.as_bytes(), Framing::Postcard, true),
+            HandshakeReply::Pending
+        );
+        assert_eq!(
+            check_reply(TAGGED_RESPONSE.as_bytes(), Framing::Postcard, false),
+            HandshakeReply::Invalid
+        );
+    }
+
+    #[test]
+    fn unknown_framing_is_incompatible() {
+        assert_eq!(
+            check_request(b"SiTerm?v3;framing=cobs"),
+            HandshakeRequest::Incompatible {
+                host_major: Some(3)
+            }
+        );
+    }
+
+    #[test]
+    fn reply_for_other_framing_is_not_accepted() {
+        // A length-framed host must not take the plain response as agreement.
+        assert_eq!(
+            check_reply(
+                HANDSHAKE_RESPONSE.as_bytes(),
+                Framing::LengthPrefixed,
+                false
+            ),
+            HandshakeReply::Pending
+        );
+        assert_eq!(
+            check_reply(LENGTH_FRAMED_RESPONSE.as_bytes(), Framing::Postcard, false),
+            HandshakeReply::Invalid
+        );
+    }
+
+    #[test]
+    fn mismatched_host_major_is_incompatible() {
+        assert_eq!(
+            check_request(b"SiTerm?v2"),
+            HandshakeRequest::Incompatible {
+                host_major: Some(2)
+            }
+        );
+        assert_eq!(
+            check_request(b"SiTerm?v0.9"),
+            HandshakeRequest::Incompatible {
+                host_major: Some(0)
+            }
+        );
+        assert_eq!(
+            check_request(b"SiTerm?vX"),
+            HandshakeRequest::Incompatible { host_major: None }
+        );
+    }
+
+    #[test]
+    fn other_lines_are_unrecognised() {
+        assert_eq!(check_request(b""), HandshakeRequest::Unrecognised);
+        assert_eq!(check_request(b"hello"), HandshakeRequest::Unrecognised);
+        assert_eq!(check_request(b"SiTerm?x1"), HandshakeRequest::Unrecognised);
+    }
+
+    #[test]
+    fn replies_are_classified_incrementally() {
+        let postcard = Framing::Postcard;
+        assert_eq!(check_reply(b"", postcard, false), HandshakeReply::Pending);
+        assert_eq!(
+            check_reply(b"SiTerm ", postcard, false),
+            HandshakeReply::Pending
+        );
+        assert_eq!(
+            check_reply(HANDSHAKE_RESPONSE.as_bytes(), postcard, false),
+            HandshakeReply::Accepted
+        );
+        assert_eq!(
+            check_reply(HANDSHAKE_INCOMPATIBLE.as_bytes(), postcard, false),
+            HandshakeReply::Incompatible
+        );
+        assert_eq!(
+            check_reply(b"garbage", postcard, false),
+            HandshakeReply::Invalid
+        );
+        assert!(MAX_REPLY_LEN >= HANDSHAKE_RESPONSE.len());
+    }
+}
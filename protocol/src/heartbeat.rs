@@ -0,0 +1,123 @@
+//! Opt-in idle heartbeats from the firmware.
+//!
+//! `heartbeat on <ms>` asks the firmware to send a framed [`HEARTBEAT`] payload every `<ms>`
+//! milliseconds while the link is idle, so the host can tell a quiet link from a dead one without
+//! sending anything itself. `heartbeat off` stops them. Any traffic from the host restarts the
+//! wait, and a heartbeat is only ever sent between commands, never in place of a response.
+//!
+//! The payload is an unsolicited frame, so a host that enabled heartbeats should drop it before
+//! treating frames as responses. Tagged sessions tag it with [`Method::Heartbeat`](crate::Method).
+
+/// Payload of every heartbeat frame.
+pub const HEARTBEAT: &[u8] = b"\x1Bheartbeat";
+/// Shortest interval the firmware honours; shorter requests are raised to it.
+pub const MIN_HEARTBEAT_INTERVAL_MS: u16 = 100;
+
+/// Write the response to a heartbeat command given the interval now in effect, e.g.
+/// `heartbeat every 2000 ms` or `heartbeat off`.
+pub fn confirmation(
+    out: &mut impl core::fmt::Write,
+    interval_ms: Option<u16>,
+) -> core::fmt::Result {
+    match interval_ms {
+        Some(interval) => write!(out, "heartbeat every {interval} ms"),
+        None => out.write_str("heartbeat off"),
+    }
+}
+
+/// When the next heartbeat is due. Times are milliseconds on any monotonic clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HeartbeatSchedule {
+    /// Interval while enabled.
+    interval_ms: Option<u16>,
+    next_ms: u64,
+}
+
+impl HeartbeatSchedule {
+    pub const fn new() -> Self {
+        Self {
+            interval_ms: None,
+            next_ms: 0,
+        }
+    }
+
+    /// Apply a heartbeat command received at `now_ms`.
+    pub fn configure(&mut self, enable: bool, interval_ms: u16, now_ms: u64) {
+        self.interval_ms = enable.then_some(interval_ms.max(MIN_HEARTBEAT_INTERVAL_MS));
+        self.touch(now_ms);
+    }
+
+    pub const fn is_enabled(&self) -> bool {
+        self.interval_ms.is_some()
+    }
+
+    /// Interval in effect, after raising it to [`MIN_HEARTBEAT_INTERVAL_MS`].
+    pub const fn interval_ms(&self) -> Option<u16> {
+        self.interval_ms
+    }
+
+    /// Note link activity at `now_ms`, which pushes the next heartbeat back a full interval.
+    pub fn touch(&mut self, now_ms: u64) {
+        if let Some(interval) = self.interval_ms {
+            self.next_ms = now_ms.saturating_add(u64::from(interval));
+        }
+    }
+
+    /// Whether a heartbeat should be sent at `now_ms`. A `true` schedules the one after it.
+    pub fn poll(&mut self, now_ms: u64) -> bool {
+        if !self.is_enabled() || now_ms < self.next_ms {
+            return false;
+        }
+        self.touch(now_ms);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::Framing;
+
+    #[test]
+    fn heartbeat_frame_serializes_in_both_framings() {
+        for framing in Framing::ALL {
+            let mut buffer = [0u8; 32];
+            let len = framing.encode_into(HEARTBEAT, &mut buffer).unwrap();
+            let (frame, rest) = framing.take_from_bytes(&buffer[..len]).unwrap();
+            assert_eq!(frame.payload, HEARTBEAT);
+            assert!(rest.is_empty());
+        }
+    }
+
+    #[test]
+    fn heartbeats_are_gated_by_the_enable_flag() {
+        let mut schedule = HeartbeatSchedule::new();
+        assert!(!schedule.poll(10_000));
+
+        schedule.configure(true, 1_000, 0);
+        assert!(!schedule.poll(999));
+        assert!(schedule.poll(1_000));
+        assert!(!schedule.poll(1_500));
+        assert!(schedule.poll(2_000));
+
+        schedule.configure(false, 1_000, 2_000);
+        assert!(!schedule.poll(10_000));
+    }
+
+    #[test]
+    fn activity_postpones_the_next_heartbeat() {
+        let mut schedule = HeartbeatSchedule::new();
+        schedule.configure(true, 1_000, 0);
+        schedule.touch(900);
+        assert!(!schedule.poll(1_000));
+        assert!(schedule.poll(1_900));
+    }
+
+    #[test]
+    fn short_intervals_are_raised_to_the_minimum() {
+        let mut schedule = HeartbeatSchedule::new();
+        schedule.configure(true, 1, 0);
+        assert!(!schedule.poll(u64::from(MIN_HEARTBEAT_INTERVAL_MS) - 1));
+        assert!(schedule.poll(u64::from(MIN_HEARTBEAT_INTERVAL_MS)));
+    }
+}
@@ -0,0 +1,149 @@
+//! The data bytes of `i2c write` and `spi write`.
+//!
+//! Data can be typed in either of two layouts, told apart by its first character:
+//!
+//! - Starting with `{`, a C array literal: `{0xAA, 0x00, 0xFF}`. Elements are separated by commas
+//!   with optional whitespace around them, a trailing comma is allowed, and the closing `}` must
+//!   end the arguments.
+//! - Anything else, a whitespace-separated list: `0xAA 0x00 0xFF` or `170 0 255`.
+//!
+//! Each element of either layout is one number in the usual argument syntax (see
+//! [`parse_u16`](super::parse_u16)), so decimal, `0x` hex, `0b` binary and `0o` or `o` octal can be
+//! mixed
+//! freely. Mixing the layouts can't: a comma or brace in a plain list, or whitespace without a
+//! comma between array elements, is a [`ByteListError`], so a half-pasted array is never read as
+//! something else.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::{EncodeError, parse_u8};
+
+/// Why data bytes couldn't be read as either layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteListError {
+    /// An array literal with no closing `}`, or arguments after it.
+    Unclosed,
+    /// An array literal with no elements, or an empty element between commas.
+    EmptyElement,
+    /// Commas or braces in a plain list, or array elements separated only by whitespace.
+    MixedLayout,
+    /// Array element `element` (counting from 1) isn't a number from 0 to 255.
+    InvalidElement { element: usize },
+}
+
+impl fmt::Display for ByteListError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ByteListError::Unclosed => f.write_str("array literal must end with `}`"),
+            ByteListError::EmptyElement => f.write_str("array literal has an empty element"),
+            ByteListError::MixedLayout => {
+                f.write_str("mixes layouts; separate bytes with spaces, or write `{0xAA, 0x00}`")
+            }
+            ByteListError::InvalidElement { element } => {
+                write!(f, "array element {element} is not a byte")
+            }
+        }
+    }
+}
+
+/// Parse data bytes in either layout. `index` is the argument position the data starts at, used
+/// for errors; a plain list reports a bad number at its own position, as other arguments do.
+pub(super) fn parse_data_bytes(arguments: &str, index: usize) -> Result<Vec<u8>, EncodeError> {
+    let arguments = arguments.trim();
+    if arguments.is_empty() {
+        return Err(EncodeError::MissingArgument { index });
+    }
+    let invalid = |reason| EncodeError::InvalidByteList { index, reason };
+
+    let Some(inner) = arguments.strip_prefix('{') else {
+        if arguments.contains([',', '{', '}']) {
+            return Err(invalid(ByteListError::MixedLayout));
+        }
+        return arguments
+            .split_ascii_whitespace()
+            .enumerate()
+            .map(|(offset, token)| parse_u8(token, index + offset))
+            .collect();
+    };
+
+    let inner = inner
+        .strip_suffix('}')
+        .ok_or(invalid(ByteListError::Unclosed))?;
+    if inner.contains(['{', '}']) {
+        return Err(invalid(ByteListError::Unclosed));
+    }
+    let inner = inner.trim_end();
+    let inner = inner.strip_suffix(',').unwrap_or(inner);
+    inner
+        .split(',')
+        .map(str::trim)
+        .enumerate()
+        .map(|(offset, element)| {
+            if element.is_empty() {
+                return Err(invalid(ByteListError::EmptyElement));
+            }
+            if element.contains(|c: char| c.is_ascii_whitespace()) {
+                return Err(invalid(ByteListError::MixedLayout));
+            }
+            parse_u8(element, 0).map_err(|_| {
+                invalid(ByteListError::InvalidElement {
+                    element: offset + 1,
+                })
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reason(arguments: &str) -> ByteListError {
+        match parse_data_bytes(arguments, 2) {
+            Err(EncodeError::InvalidByteList { index: 2, reason }) => reason,
+            other => panic!("`{arguments}` gave {other:?}"),
+        }
+    }
+
+    #[test]
+    fn plain_lists_take_any_number_syntax() {
+        assert_eq!(parse_data_bytes("170 0 255", 2), Ok(vec![0xAA, 0x00, 0xFF]));
+        assert_eq!(
+            parse_data_bytes("0xAA 0 0b1111_1111", 2),
+            Ok(vec![0xAA, 0x00, 0xFF])
+        );
+        assert_eq!(
+            parse_data_bytes("0xAA 256", 2),
+            Err(EncodeError::InvalidArgument { index: 3 })
+        );
+    }
+
+    #[test]
+    fn array_literals_are_read_whole() {
+        assert_eq!(
+            parse_data_bytes("{0xAA, 0x00, 0xFF}", 2),
+            Ok(vec![0xAA, 0x00, 0xFF])
+        );
+        assert_eq!(
+            parse_data_bytes("{170,0,255,}", 2),
+            Ok(vec![0xAA, 0x00, 0xFF])
+        );
+        assert_eq!(parse_data_bytes("{ 0x01 }", 2), Ok(vec![0x01]));
+    }
+
+    #[test]
+    fn malformed_and_mixed_lists_are_refused() {
+        assert_eq!(reason("0xAA, 0x00"), ByteListError::MixedLayout);
+        assert_eq!(reason("0xAA 0x00}"), ByteListError::MixedLayout);
+        assert_eq!(reason("{0xAA 0x00}"), ByteListError::MixedLayout);
+        assert_eq!(reason("{0xAA, 0x00"), ByteListError::Unclosed);
+        assert_eq!(reason("{0xAA} 0x00"), ByteListError::Unclosed);
+        assert_eq!(reason("{}"), ByteListError::EmptyElement);
+        assert_eq!(reason("{0xAA,,0x00}"), ByteListError::EmptyElement);
+        assert_eq!(
+            reason("{0xAA, 0x100}"),
+            ByteListError::InvalidElement { element: 2 }
+        );
+    }
+}
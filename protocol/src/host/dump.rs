@@ -0,0 +1,262 @@
+//! Host-side `i2c dump`, which reads a register range as a series of ordinary `i2c read`s.
+//!
+//! `i2c dump [--bus <index>] <address> <start> <length>` never reaches the firmware. The host
+//! splits the range with [`plan_chunks`], sends one read per chunk and joins the responses.
+//! Register addresses are 8 bits, so a range that runs past `0xFF` continues at `0x00` with a fresh
+//! read instead of relying on the device to wrap its own address pointer.
+
+use alloc::vec::Vec;
+
+use super::{
+    EncodeError,
+    i2c::{parse_address, split_bus},
+    parse_u8, parse_u16, split_token, strip_comment,
+};
+use crate::{MAX_I2C_READ_LEN, Method, Operation, device_info::DeviceInfo};
+
+const DUMP_KEYWORD: &str = "dump";
+
+/// Longest dump accepted: every 8-bit register once.
+pub const MAX_DUMP_LEN: u16 = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DumpRequest {
+    pub bus: u8,
+    pub address: u8,
+    pub start: u8,
+    pub length: u16,
+}
+
+/// One `i2c read` of a dump.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadChunk {
+    pub register: u8,
+    pub length: u8,
+}
+
+impl DumpRequest {
+    /// Encoded `i2c read` command for one chunk of this dump.
+    pub fn read_command(&self, chunk: ReadChunk) -> Vec<u8> {
+        Vec::from([
+            Method::I2c.as_byte(),
+            Operation::Read.as_byte(),
+            self.bus,
+            self.address,
+            chunk.register,
+            chunk.length,
+        ])
+    }
+}
+
+/// Parse an `i2c dump` line. Returns `None` for any other command so the caller can encode it
+/// normally.
+pub fn parse_dump(input: &str) -> Option<Result<DumpRequest, EncodeError>> {
+    let (method, rest) = split_token(strip_comment(input).trim());
+    let (operation, remainder) = split_token(rest);
+    if Method::try_from(method) != Ok(Method::I2c) || !operation.eq_ignore_ascii_case(DUMP_KEYWORD)
+    {
+        return None;
+    }
+    // Arguments start after `i2c dump`, and after any `--bus` flag within them.
+    Some(parse_dump_args(remainder).map_err(|err| err.shifted(2)))
+}
+
+fn parse_dump_args(remainder: &str) -> Result<DumpRequest, EncodeError> {
+    let (bus, first, remainder) = split_bus(remainder)?;
+    parse_dump_range(bus, remainder).map_err(|err| err.shifted(first))
+}
+
+fn parse_dump_range(bus: u8, remainder: &str) -> Result<DumpRequest, EncodeError> {
+    const EXPECTED_ARGS: usize = 3;
+
+    let mut args = remainder.split_ascii_whitespace();
+    let address = parse_address(args.next().unwrap_or_default(), 0)?;
+    let start = parse_u8(args.next().unwrap_or_default(), 1)?;
+    let length = parse_u16(args.next().unwrap_or_default(), 2)?;
+    if args.next().is_some() {
+        return Err(EncodeError::UnexpectedArgument {
+            index: EXPECTED_ARGS,
+        });
+    }
+    if length == 0 || length > MAX_DUMP_LEN {
+        return Err(EncodeError::InvalidArgument { index: 2 });
+    }
+
+    Ok(DumpRequest {
+        bus,
+        address,
+        start,
+        length,
+    })
+}
+
+/// Longest read to use per chunk, taken from the device when it reported one.
+pub fn chunk_len(info: Option<&DeviceInfo>) -> u8 {
+    info.map_or(MAX_I2C_READ_LEN, |info| info.max_i2c_read)
+        .max(1)
+}
+
+/// Split `length` registers from `start` into reads of at most `max_read` bytes. No read crosses
+/// from `0xFF` back to `0x00`.
+pub fn plan_chunks(start: u8, length: u16, max_read: u8) -> Vec<ReadChunk> {
+    let max_read = usize::from(max_read.max(1));
+    let mut chunks = Vec::new();
+    let mut register = start;
+    let mut remaining = usize::from(length);
+    while remaining > 0 {
+        let before_wrap = 0x100 - usize::from(register);
+        let len = remaining.min(max_read).min(before_wrap);
+        // `len` is at most `max_read`, so it fits in a u8.
+        chunks.push(ReadChunk {
+            register,
+            length: len as u8,
+        });
+        register = register.wrapping_add(len as u8);
+        remaining -= len;
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device_info::{DeviceConfig, DeviceLabel};
+
+    fn lengths(chunks: &[ReadChunk]) -> Vec<u8> {
+        chunks.iter().map(|chunk| chunk.length).collect()
+    }
+
+    #[test]
+    fn short_dump_is_one_read() {
+        let chunks = plan_chunks(0x10, 8, 32);
+        assert_eq!(
+            chunks,
+            [ReadChunk {
+                register: 0x10,
+                length: 8
+            }]
+        );
+    }
+
+    #[test]
+    fn exact_multiple_of_max_read_has_no_empty_chunk() {
+        let chunks = plan_chunks(0x00, 64, 32);
+        assert_eq!(lengths(&chunks), [32, 32]);
+        assert_eq!(chunks[1].register, 0x20);
+    }
+
+    #[test]
+    fn final_chunk_may_be_partial() {
+        let chunks = plan_chunks(0x00, 70, 32);
+        assert_eq!(lengths(&chunks), [32, 32, 6]);
+        assert_eq!(chunks[2].register, 0x40);
+    }
+
+    #[test]
+    fn reads_split_at_register_wraparound() {
+        let chunks = plan_chunks(0xF8, 16, 32);
+        assert_eq!(
+            chunks,
+            [
+                ReadChunk {
+                    register: 0xF8,
+                    length: 8
+                },
+                ReadChunk {
+                    register: 0x00,
+                    length: 8
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn full_register_space_respects_firmware_limit() {
+        let chunks = plan_chunks(0x00, MAX_DUMP_LEN, MAX_I2C_READ_LEN);
+        assert_eq!(lengths(&chunks), [255, 1]);
+        assert_eq!(chunks[1].register, 0xFF);
+        let total: usize = chunks.iter().map(|chunk| usize::from(chunk.length)).sum();
+        assert_eq!(total, usize::from(MAX_DUMP_LEN));
+    }
+
+    #[test]
+    fn zero_max_read_still_makes_progress() {
+        assert_eq!(lengths(&plan_chunks(0x00, 3, 0)), [1, 1, 1]);
+    }
+
+    #[test]
+    fn chunk_len_prefers_device_info() {
+        let info = DeviceInfo {
+            max_i2c_read: 16,
+            config: DeviceConfig::DEFAULT,
+            uart: None,
+            label: DeviceLabel::default(),
+        };
+        assert_eq!(chunk_len(Some(&info)), 16);
+        assert_eq!(chunk_len(None), MAX_I2C_READ_LEN);
+    }
+
+    #[test]
+    fn parse_dump_reads_arguments() {
+        assert_eq!(
+            parse_dump("i2c dump 0x50 0x00 64 # eeprom"),
+            Some(Ok(DumpRequest {
+                bus: crate::DEFAULT_I2C_BUS,
+                address: 0x50,
+                start: 0x00,
+                length: 64,
+            }))
+        );
+        assert_eq!(
+            parse_dump("i2c dump --bus 0 0x50 0x10 256").map(|request| request.map(|r| r.bus)),
+            Some(Ok(0))
+        );
+        assert_eq!(parse_dump("i2c read 0x50 0x00 4"), None);
+        assert_eq!(parse_dump("echo dump"), None);
+    }
+
+    #[test]
+    fn parse_dump_rejects_bad_lengths() {
+        assert_eq!(
+            parse_dump("i2c dump 0x50 0x00 0"),
+            Some(Err(EncodeError::InvalidArgument { index: 4 }))
+        );
+        assert_eq!(
+            parse_dump("i2c dump --bus 1 0x50 0x00 257"),
+            Some(Err(EncodeError::InvalidArgument { index: 6 }))
+        );
+        assert_eq!(
+            parse_dump("i2c dump 0xA0 0x00 16"),
+            Some(Err(EncodeError::InvalidAddress {
+                index: 2,
+                address: 0xA0
+            }))
+        );
+        assert_eq!(
+            parse_dump("i2c dump 0x50 0x00"),
+            Some(Err(EncodeError::MissingArgument { index: 4 }))
+        );
+        assert_eq!(
+            parse_dump("i2c dump 0x50 0x00 4 5"),
+            Some(Err(EncodeError::UnexpectedArgument { index: 5 }))
+        );
+    }
+
+    #[test]
+    fn read_command_decodes_as_i2c_read() {
+        let request = parse_dump("i2c dump 0x50 0x00 64").unwrap().unwrap();
+        let encoded = request.read_command(ReadChunk {
+            register: 0x20,
+            length: 16,
+        });
+        assert_eq!(
+            crate::decode_command(&encoded),
+            Ok(crate::Command::I2cRead {
+                bus: crate::I2cBus::DEFAULT,
+                address: 0x50,
+                register: 0x20,
+                length: 16,
+            })
+        );
+    }
+}
@@ -0,0 +1,367 @@
+//! Host-side rendering hints for command responses.
+//!
+//! A command may end in `as <type>` (for example `i2c read 0x48 0x00 2 as i16be`) to ask the host
+//! to show the response decoded as that type next to the raw bytes. The hint is stripped before
+//! encoding and never reaches the device.
+//!
+//! Commands whose response has a fixed meaning get a hint without asking; see [`default_hint`].
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+
+use super::{EncodeError, split_token, strip_comment};
+use crate::{
+    Method, Operation,
+    device_info::{DEVICE_CONFIG_LEN, DEVICE_INFO_MAX_LEN, DeviceConfig, DeviceInfo},
+    i2c_read_multi_pairs,
+    temperature::DeciCelsius,
+};
+
+const HINT_KEYWORD: &str = "as";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueHint {
+    U8,
+    I8,
+    U16Be,
+    U16Le,
+    I16Be,
+    I16Le,
+    /// Tenths of a degree Celsius as a big-endian `i16`, the `temp` response format.
+    Celsius,
+    /// A [`DeviceConfig`], the `config` response format.
+    Config,
+    /// A [`DeviceInfo`], the `info` response format.
+    Info,
+    /// `[register, value]` pairs, the `i2c readm` response format.
+    Registers,
+}
+
+impl ValueHint {
+    pub const ALL: [ValueHint; 10] = [
+        ValueHint::U8,
+        ValueHint::I8,
+        ValueHint::U16Be,
+        ValueHint::U16Le,
+        ValueHint::I16Be,
+        ValueHint::I16Le,
+        ValueHint::Celsius,
+        ValueHint::Config,
+        ValueHint::Info,
+        ValueHint::Registers,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ValueHint::U8 => "u8",
+            ValueHint::I8 => "i8",
+            ValueHint::U16Be => "u16be",
+            ValueHint::U16Le => "u16le",
+            ValueHint::I16Be => "i16be",
+            ValueHint::I16Le => "i16le",
+            ValueHint::Celsius => "celsius",
+            ValueHint::Config => "config",
+            ValueHint::Info => "info",
+            ValueHint::Registers => "registers",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|hint| hint.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Bytes consumed per decoded value, or the most an info takes.
+    pub fn width(&self) -> usize {
+        match self {
+            ValueHint::U8 | ValueHint::I8 => 1,
+            ValueHint::Config => DEVICE_CONFIG_LEN,
+            ValueHint::Info => DEVICE_INFO_MAX_LEN,
+            _ => 2,
+        }
+    }
+
+    /// Decode `bytes` as a sequence of values of this type. A config decodes to its SPI mode, SPI
+    /// frequency and heartbeat interval (0 when off), an info to its longest I2C read and highest
+    /// UART baud rate (0 without a UART), and registers to each register followed by its value.
+    /// Returns `None` when the response is empty or not a whole number of values.
+    pub fn decode(&self, bytes: &[u8]) -> Option<Vec<i32>> {
+        if *self == ValueHint::Config {
+            let config = DeviceConfig::decode(bytes)?;
+            return Some(vec![
+                i32::from(config.spi_mode),
+                i32::from(config.spi_freq_khz),
+                i32::from(config.heartbeat_ms.unwrap_or(0)),
+            ]);
+        }
+        if *self == ValueHint::Info {
+            let info = DeviceInfo::decode(bytes)?;
+            let max_baud = info.uart.map_or(0, |uart| uart.max_baud);
+            return Some(vec![
+                i32::from(info.max_i2c_read),
+                i32::try_from(max_baud).unwrap_or(i32::MAX),
+            ]);
+        }
+        if *self == ValueHint::Registers {
+            let pairs = i2c_read_multi_pairs(bytes)?;
+            return Some(
+                pairs
+                    .flat_map(|(register, value)| [i32::from(register), i32::from(value)])
+                    .collect(),
+            );
+        }
+        if bytes.is_empty() || !bytes.len().is_multiple_of(self.width()) {
+            return None;
+        }
+        let values = bytes
+            .chunks_exact(self.width())
+            .map(|chunk| match self {
+                ValueHint::U8 => i32::from(chunk[0]),
+                ValueHint::I8 => i32::from(chunk[0] as i8),
+                ValueHint::U16Be => i32::from(u16::from_be_bytes([chunk[0], chunk[1]])),
+                ValueHint::U16Le => i32::from(u16::from_le_bytes([chunk[0], chunk[1]])),
+                ValueHint::I16Be | ValueHint::Celsius => {
+                    i32::from(i16::from_be_bytes([chunk[0], chunk[1]]))
+                }
+                ValueHint::I16Le => i32::from(i16::from_le_bytes([chunk[0], chunk[1]])),
+                ValueHint::Config | ValueHint::Info | ValueHint::Registers => {
+                    unreachable!("decoded whole above")
+                }
+            })
+            .collect();
+        Some(values)
+    }
+
+    /// Render `bytes` as `<type>: v0, v1, ...`, or explain why they don't fit the type.
+    pub fn render(&self, bytes: &[u8]) -> String {
+        if *self == ValueHint::Config {
+            return match DeviceConfig::decode(bytes) {
+                Some(config) => config.to_string(),
+                None => format!(
+                    "config: expected {DEVICE_CONFIG_LEN} bytes, got {}",
+                    bytes.len()
+                ),
+            };
+        }
+        if *self == ValueHint::Info {
+            return match DeviceInfo::decode(bytes) {
+                Some(info) => info.to_string(),
+                None => format!("info: {} byte(s) is not a device info", bytes.len()),
+            };
+        }
+        if *self == ValueHint::Registers {
+            let Some(pairs) = i2c_read_multi_pairs(bytes) else {
+                return format!(
+                    "registers: {} byte(s) is not a list of register, value pairs",
+                    bytes.len()
+                );
+            };
+            let rendered: Vec<String> = pairs
+                .map(|(register, value)| format!("{register:#04x} = {value:#04x}"))
+                .collect();
+            return rendered.join(", ");
+        }
+        match self.decode(bytes) {
+            Some(values) if *self == ValueHint::Celsius => {
+                let rendered: Vec<String> = values
+                    .iter()
+                    .map(|value| format!("{}", DeciCelsius(*value as i16)))
+                    .collect();
+                rendered.join(", ")
+            }
+            Some(values) => {
+                let rendered: Vec<String> = values.iter().map(|value| format!("{value}")).collect();
+                format!("{}: {}", self.name(), rendered.join(", "))
+            }
+            None => format!(
+                "{}: {} byte(s) is not a multiple of {}",
+                self.name(),
+                bytes.len(),
+                self.width()
+            ),
+        }
+    }
+}
+
+/// Split a trailing `as <type>` hint off a command line.
+///
+/// Returns the command without the hint (comments are left in place) and the parsed hint. Echo
+/// payloads are free text, so `echo` lines are never treated as carrying a hint.
+pub fn split_value_hint(input: &str) -> Result<(&str, Option<ValueHint>), EncodeError> {
+    let command = strip_comment(input).trim_end();
+    let (method, _) = split_token(command.trim_start());
+    if Method::try_from(method) == Ok(Method::Echo) {
+        return Ok((input, None));
+    }
+
+    let Some((head, hint_name)) = command.rsplit_once(|c: char| c.is_ascii_whitespace()) else {
+        return Ok((input, None));
+    };
+    let head = head.trim_end();
+    let Some((rest, keyword)) = head.rsplit_once(|c: char| c.is_ascii_whitespace()) else {
+        return Ok((input, None));
+    };
+    if !keyword.eq_ignore_ascii_case(HINT_KEYWORD) {
+        return Ok((input, None));
+    }
+
+    let hint = ValueHint::from_name(hint_name).ok_or(EncodeError::UnknownValueHint)?;
+    Ok((rest.trim_end(), Some(hint)))
+}
+
+/// Hint implied by the command itself, used when the line doesn't carry an explicit one.
+pub fn default_hint(command: &str) -> Option<ValueHint> {
+    let (method, rest) = split_token(strip_comment(command).trim_start());
+    match Method::try_from(method) {
+        Ok(Method::Temp) => Some(ValueHint::Celsius),
+        Ok(Method::Config) => Some(ValueHint::Config),
+        Ok(Method::Info) => Some(ValueHint::Info),
+        Ok(Method::I2c) if Operation::try_from(split_token(rest).0) == Ok(Operation::ReadMulti) => {
+            Some(ValueHint::Registers)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hints_round_trip_through_names() {
+        for hint in ValueHint::ALL {
+            assert_eq!(ValueHint::from_name(hint.name()), Some(hint));
+        }
+        assert_eq!(ValueHint::from_name("I16BE"), Some(ValueHint::I16Be));
+        assert_eq!(ValueHint::from_name("u32"), None);
+    }
+
+    #[test]
+    fn split_removes_trailing_hint() {
+        assert_eq!(
+            split_value_hint("i2c read 0x48 0x00 2 as i16be"),
+            Ok(("i2c read 0x48 0x00 2", Some(ValueHint::I16Be)))
+        );
+        assert_eq!(
+            split_value_hint("i2c read 0x48 0x00 2\tAS  u8 # temp"),
+            Ok(("i2c read 0x48 0x00 2", Some(ValueHint::U8)))
+        );
+        assert_eq!(
+            split_value_hint("i2c read 0x48 0x00 2"),
+            Ok(("i2c read 0x48 0x00 2", None))
+        );
+    }
+
+    #[test]
+    fn split_rejects_unknown_hint() {
+        assert_eq!(
+            split_value_hint("i2c read 0x48 0x00 4 as f32"),
+            Err(EncodeError::UnknownValueHint)
+        );
+    }
+
+    #[test]
+    fn echo_payloads_are_never_hinted() {
+        assert_eq!(
+            split_value_hint("echo speak as u8"),
+            Ok(("echo speak as u8", None))
+        );
+    }
+
+    #[test]
+    fn decode_and_render_each_hint() {
+        let bytes = [0xFF, 0x38];
+        let cases = [
+            (ValueHint::U8, "u8: 255, 56"),
+            (ValueHint::I8, "i8: -1, 56"),
+            (ValueHint::U16Be, "u16be: 65336"),
+            (ValueHint::U16Le, "u16le: 14591"),
+            (ValueHint::I16Be, "i16be: -200"),
+            (ValueHint::I16Le, "i16le: 14591"),
+            (ValueHint::Celsius, "-20.0 °C"),
+        ];
+        for (hint, expected) in cases {
+            assert_eq!(hint.render(&bytes), expected, "{hint:?}");
+        }
+    }
+
+    #[test]
+    fn temp_defaults_to_celsius() {
+        assert_eq!(default_hint("temp"), Some(ValueHint::Celsius));
+        assert_eq!(default_hint("  TEMP # board"), Some(ValueHint::Celsius));
+        assert_eq!(default_hint("i2c read 0x48 0x00 2"), None);
+    }
+
+    #[test]
+    fn config_is_rendered_as_settings() {
+        assert_eq!(default_hint("config"), Some(ValueHint::Config));
+        let bytes = [2, 0x1F, 0x40, 0x01, 0xF4];
+        assert_eq!(ValueHint::Config.decode(&bytes), Some(vec![2, 8_000, 500]));
+        assert_eq!(
+            ValueHint::Config.render(&bytes),
+            "spi mode2 at 8000 kHz, heartbeat every 500 ms"
+        );
+        assert_eq!(
+            ValueHint::Config.render(&bytes[..3]),
+            "config: expected 5 bytes, got 3"
+        );
+    }
+
+    #[test]
+    fn read_multi_is_rendered_as_register_values() {
+        assert_eq!(
+            default_hint("i2c readm 0x48 0x00 0x05"),
+            Some(ValueHint::Registers)
+        );
+        assert_eq!(
+            default_hint("I2C READM --bus 1 0x48 0x00"),
+            Some(ValueHint::Registers)
+        );
+        let bytes = [0x00, 0x12, 0x05, 0x34];
+        assert_eq!(
+            ValueHint::Registers.decode(&bytes),
+            Some(vec![0x00, 0x12, 0x05, 0x34])
+        );
+        assert_eq!(
+            ValueHint::Registers.render(&bytes),
+            "0x00 = 0x12, 0x05 = 0x34"
+        );
+        assert_eq!(
+            ValueHint::Registers.render(&bytes[..3]),
+            "registers: 3 byte(s) is not a list of register, value pairs"
+        );
+    }
+
+    #[test]
+    fn info_is_rendered_as_a_sentence() {
+        assert_eq!(default_hint("info"), Some(ValueHint::Info));
+        let bytes = [
+            32, 0, 0x03, 0xE8, 0, 0, 1, 0, 0x01, 0xC2, 0x00, 0x01, b'b', b'o', b'a', b'r', b'd',
+        ];
+        assert_eq!(ValueHint::Info.decode(&bytes), Some(vec![32, 115_200]));
+        assert_eq!(
+            ValueHint::Info.render(&bytes),
+            "board: i2c reads up to 32 bytes, uart up to 115200 baud, spi mode0 at 1000 kHz, \
+             heartbeat off"
+        );
+        assert_eq!(
+            ValueHint::Info.render(&bytes[..3]),
+            "info: 3 byte(s) is not a device info"
+        );
+    }
+
+    #[test]
+    fn render_reports_partial_values() {
+        assert_eq!(ValueHint::I16Be.decode(&[0x01, 0x02, 0x03]), None);
+        assert_eq!(
+            ValueHint::I16Be.render(&[0x01, 0x02, 0x03]),
+            "i16be: 3 byte(s) is not a multiple of 2"
+        );
+        assert_eq!(ValueHint::U8.decode(&[]), None);
+    }
+}
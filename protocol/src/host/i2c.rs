@@ -1,8 +1,43 @@
 use alloc::vec::Vec;
 
-use super::{EncodeError, parse_u8};
+use super::{EncodeError, bytes::parse_data_bytes, parse_u8, split_token};
+use crate::{DEFAULT_I2C_BUS, I2C_BUS_COUNT, MAX_I2C_ADDRESS, MAX_I2C_READ_MULTI};
 
+const BUS_FLAG: &str = "--bus";
+
+/// Take an optional leading `--bus <index>` off the arguments, defaulting to [`DEFAULT_I2C_BUS`].
+/// Also returns how many tokens were taken, which is where the remaining arguments start.
+pub(super) fn split_bus(remainder: &str) -> Result<(u8, usize, &str), EncodeError> {
+    let (token, rest) = split_token(remainder);
+    if token != BUS_FLAG {
+        return Ok((DEFAULT_I2C_BUS, 0, remainder));
+    }
+    let (index, rest) = split_token(rest);
+    let bus = parse_u8(index, 0).map_err(|_| EncodeError::InvalidBus)?;
+    if bus >= I2C_BUS_COUNT {
+        return Err(EncodeError::InvalidBus);
+    }
+    Ok((bus, 2, rest))
+}
+
+/// Parse a 7-bit device address. A byte above [`MAX_I2C_ADDRESS`] is most likely an 8-bit address
+/// copied from a datasheet, so it gets its own error rather than being sent for the bus to NAK.
+pub(super) fn parse_address(token: &str, index: usize) -> Result<u8, EncodeError> {
+    let address = parse_u8(token, index)?;
+    if address > MAX_I2C_ADDRESS {
+        return Err(EncodeError::InvalidAddress { index, address });
+    }
+    Ok(address)
+}
+
+/// Encode the arguments of `i2c read`. Argument indices in errors count from the first token of
+/// `remainder`, including any `--bus` flag.
 pub fn encode_i2c_read(remainder: &str, output: &mut Vec<u8>) -> Result<usize, EncodeError> {
+    let (bus, first, remainder) = split_bus(remainder)?;
+    encode_read_args(bus, remainder, output).map_err(|err| err.shifted(first))
+}
+
+fn encode_read_args(bus: u8, remainder: &str, output: &mut Vec<u8>) -> Result<usize, EncodeError> {
     const EXPECTED_ARGS: usize = 3;
 
     let mut args = remainder.split_ascii_whitespace();
@@ -22,47 +57,79 @@ pub fn encode_i2c_read(remainder: &str, output: &mut Vec<u8>) -> Result<usize, E
         });
     }
 
-    let address = parse_u8(addr_str, 0)?;
+    let address = parse_address(addr_str, 0)?;
     let register = parse_u8(register_str, 1)?;
     let length = parse_u8(length_str, 2)?;
 
-    output.reserve(4);
-    output.push(address);
-    output.push(register);
-    output.push(length);
+    output.extend_from_slice(&[bus, address, register, length]);
 
     Ok(output.len())
 }
 
+/// Encode the arguments of `i2c write`, counting argument indices as [`encode_i2c_read`] does.
 pub fn encode_i2c_write(remainder: &str, output: &mut Vec<u8>) -> Result<usize, EncodeError> {
+    let (bus, first, remainder) = split_bus(remainder)?;
+    encode_write_args(bus, remainder, output).map_err(|err| err.shifted(first))
+}
+
+/// Encode the arguments of `i2c readm`, counting argument indices as [`encode_i2c_read`] does.
+pub fn encode_i2c_read_multi(remainder: &str, output: &mut Vec<u8>) -> Result<usize, EncodeError> {
+    let (bus, first, remainder) = split_bus(remainder)?;
+    encode_read_multi_args(bus, remainder, output).map_err(|err| err.shifted(first))
+}
+
+fn encode_read_multi_args(
+    bus: u8,
+    remainder: &str,
+    output: &mut Vec<u8>,
+) -> Result<usize, EncodeError> {
     let mut args = remainder.split_ascii_whitespace();
     let addr_str = args
         .next()
         .ok_or(EncodeError::MissingArgument { index: 0 })?;
-    let register_str = args
-        .next()
-        .ok_or(EncodeError::MissingArgument { index: 1 })?;
 
-    let payload_tokens: Vec<&str> = args.collect();
-    if payload_tokens.is_empty() {
-        return Err(EncodeError::MissingArgument { index: 2 });
+    let register_tokens: Vec<&str> = args.collect();
+    if register_tokens.is_empty() {
+        return Err(EncodeError::MissingArgument { index: 1 });
     }
-    if payload_tokens.len() > u8::MAX as usize {
-        return Err(EncodeError::InvalidArgument { index: 2 });
+    if register_tokens.len() > usize::from(MAX_I2C_READ_MULTI) {
+        return Err(EncodeError::UnexpectedArgument {
+            index: 1 + usize::from(MAX_I2C_READ_MULTI),
+        });
     }
 
-    let address = parse_u8(addr_str, 0)?;
-    let register = parse_u8(register_str, 1)?;
+    let address = parse_address(addr_str, 0)?;
+
+    output.extend_from_slice(&[bus, address, register_tokens.len() as u8]);
+    output.reserve(register_tokens.len());
+
+    for (i, token) in register_tokens.into_iter().enumerate() {
+        let register = parse_u8(token, 1 + i)?;
+        output.push(register);
+    }
 
-    output.reserve(3 + payload_tokens.len());
-    output.push(address);
-    output.push(register);
-    output.push(payload_tokens.len() as u8);
+    Ok(output.len())
+}
 
-    for (i, token) in payload_tokens.into_iter().enumerate() {
-        let byte = parse_u8(token, 2 + i)?;
-        output.push(byte);
+/// The data may be a plain list or an array literal; see [`super::bytes`].
+fn encode_write_args(bus: u8, remainder: &str, output: &mut Vec<u8>) -> Result<usize, EncodeError> {
+    let (addr_str, rest) = split_token(remainder);
+    if addr_str.is_empty() {
+        return Err(EncodeError::MissingArgument { index: 0 });
+    }
+    let (register_str, data) = split_token(rest);
+    if register_str.is_empty() {
+        return Err(EncodeError::MissingArgument { index: 1 });
     }
 
+    let address = parse_address(addr_str, 0)?;
+    let register = parse_u8(register_str, 1)?;
+    let payload = parse_data_bytes(data, 2)?;
+    let count =
+        u8::try_from(payload.len()).map_err(|_| EncodeError::InvalidArgument { index: 2 })?;
+
+    output.extend_from_slice(&[bus, address, register, count]);
+    output.extend_from_slice(&payload);
+
     Ok(output.len())
 }
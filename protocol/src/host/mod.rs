@@ -1,13 +1,28 @@
-use alloc::vec::Vec;
+use alloc::{vec, vec::Vec};
 use postcard::{self, Error as PostcardError};
 
 use crate::{
-    COMMAND_DICTIONARY, Method, Operation,
-    transport::{self, Frame as TransportFrame, FrameError},
+    CommandDefinition, MAX_ECHO_LEN, Method, Operation,
+    chunk::{
+        CHUNK_CONTINUE, CHUNK_END, CHUNK_HEADER_LEN, CHUNK_MARKER, CHUNK_START,
+        MAX_CHUNKED_COMMAND_LEN,
+    },
+    response::ResponseFormat,
+    transport::{Frame as TransportFrame, FrameError, Framing, LENGTH_PREFIXED_OVERHEAD},
 };
 
+pub mod bytes;
+pub mod dump;
+pub mod hint;
 pub mod i2c;
+pub mod spi;
 
+/// Why a command line couldn't be encoded.
+///
+/// The `index` of an argument error is the zero-based position of the offending token in the whole
+/// line, counting the method and operation keywords and any `--bus` flag and its value. In
+/// `i2c read --bus 1 0x48 0x00 4`, `i2c` is token 0 and the length `4` is token 6. A missing
+/// argument is reported at the position it would have taken.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum EncodeError {
     Empty,
@@ -28,23 +43,95 @@ pub enum EncodeError {
         index: usize,
     },
     OutputTooSmall,
+    /// The trailing `as <type>` names a type with no [`hint::ValueHint`].
+    UnknownValueHint,
+    /// `--bus` is missing its index or names a bus the firmware doesn't have.
+    InvalidBus,
+    /// The I2C address at `index` is a byte but not a 7-bit address; see
+    /// [`MAX_I2C_ADDRESS`](crate::MAX_I2C_ADDRESS).
+    InvalidAddress {
+        index: usize,
+        address: u8,
+    },
+    /// The data bytes starting at `index` are in neither layout [`bytes`] accepts.
+    InvalidByteList {
+        index: usize,
+        reason: bytes::ByteListError,
+    },
+}
+
+impl EncodeError {
+    /// Move an argument index from a token position within `by` skipped tokens to one in the
+    /// line they were skipped from.
+    fn shifted(self, by: usize) -> Self {
+        match self {
+            Self::MissingArgument { index } => Self::MissingArgument { index: index + by },
+            Self::UnexpectedArgument { index } => Self::UnexpectedArgument { index: index + by },
+            Self::InvalidArgument { index } => Self::InvalidArgument { index: index + by },
+            Self::InvalidAddress { index, address } => Self::InvalidAddress {
+                index: index + by,
+                address,
+            },
+            Self::InvalidByteList { index, reason } => Self::InvalidByteList {
+                index: index + by,
+                reason,
+            },
+            other => other,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum TransportCodecError {
     Encode(PostcardError),
     Decode(PostcardError),
+    Checksum,
 }
 
-pub fn encode_transport_frame(payload: &[u8]) -> Result<Vec<u8>, TransportCodecError> {
-    let frame = TransportFrame::new(payload);
-    postcard::to_allocvec(&frame).map_err(TransportCodecError::Encode)
+/// Most bytes [`decode_transport_frame_resyncing`] will discard looking for a frame boundary
+/// before giving up on the stream.
+pub const MAX_RESYNC_SKIP: usize = 256;
+
+/// A frame recovered from a byte stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedFrame {
+    pub payload: Vec<u8>,
+    /// Bytes to drop from the front of the buffer, including any skipped ones.
+    pub consumed: usize,
+    /// Corrupt bytes discarded before the frame started.
+    pub skipped: usize,
+}
+
+pub fn encode_transport_frame(
+    payload: &[u8],
+    framing: Framing,
+) -> Result<Vec<u8>, TransportCodecError> {
+    match framing {
+        Framing::Postcard => {
+            let frame = TransportFrame::new(payload);
+            postcard::to_allocvec(&frame).map_err(TransportCodecError::Encode)
+        }
+        Framing::LengthPrefixed => {
+            let mut buffer = vec![0; payload.len() + LENGTH_PREFIXED_OVERHEAD];
+            let len = framing
+                .encode_into(payload, &mut buffer)
+                .map_err(|err| match err {
+                    FrameError::Serialize(err) | FrameError::Deserialize(err) => {
+                        TransportCodecError::Encode(err)
+                    }
+                    FrameError::Checksum => TransportCodecError::Checksum,
+                })?;
+            buffer.truncate(len);
+            Ok(buffer)
+        }
+    }
 }
 
 pub fn try_decode_transport_frame(
     buffer: &[u8],
+    framing: Framing,
 ) -> Result<Option<(Vec<u8>, usize)>, TransportCodecError> {
-    match transport::take_from_bytes(buffer) {
+    match framing.take_from_bytes(buffer) {
         Ok((frame, remaining)) => {
             let consumed = buffer.len() - remaining.len();
             Ok(Some((frame.payload.to_vec(), consumed)))
@@ -57,6 +144,51 @@ pub fn try_decode_transport_frame(
             }
         }
         Err(FrameError::Serialize(err)) => Err(TransportCodecError::Decode(err)),
+        Err(FrameError::Checksum) => Err(TransportCodecError::Checksum),
+    }
+}
+
+/// Decode the next frame, skipping corrupt bytes to find the next valid frame boundary.
+///
+/// When the front of the buffer is not a valid frame the search advances one byte at a time
+/// (at most [`MAX_RESYNC_SKIP`] bytes) until a frame with a matching CRC decodes. If none does
+/// but one could still complete with more data, `Ok(None)` asks the caller to read more; the
+/// original error is returned once the search window is exhausted.
+pub fn decode_transport_frame_resyncing(
+    buffer: &[u8],
+    framing: Framing,
+) -> Result<Option<DecodedFrame>, TransportCodecError> {
+    let error = match try_decode_transport_frame(buffer, framing) {
+        Ok(Some((payload, consumed))) => {
+            return Ok(Some(DecodedFrame {
+                payload,
+                consumed,
+                skipped: 0,
+            }));
+        }
+        Ok(None) => return Ok(None),
+        Err(err) => err,
+    };
+
+    let mut may_complete = false;
+    for skipped in 1..=MAX_RESYNC_SKIP.min(buffer.len()) {
+        match try_decode_transport_frame(&buffer[skipped..], framing) {
+            Ok(Some((payload, consumed))) => {
+                return Ok(Some(DecodedFrame {
+                    payload,
+                    consumed: skipped + consumed,
+                    skipped,
+                }));
+            }
+            Ok(None) => may_complete = true,
+            Err(_) => {}
+        }
+    }
+
+    if may_complete && buffer.len() <= MAX_RESYNC_SKIP {
+        Ok(None)
+    } else {
+        Err(error)
     }
 }
 
@@ -67,56 +199,140 @@ pub fn encode_command(input: &str) -> Result<Vec<u8>, EncodeError> {
     Ok(buffer)
 }
 
+/// Encode a single command line. Tokens may be separated by any run of ASCII whitespace and a `#`
+/// starts a comment that runs to the end of the line (see [`strip_comment`]). A trailing
+/// `as <type>` rendering hint is validated and dropped (see [`hint::split_value_hint`]).
 pub fn encode_command_into(input: &str, output: &mut Vec<u8>) -> Result<usize, EncodeError> {
-    let trimmed = input.trim();
+    let (command, _) = hint::split_value_hint(input)?;
+    let trimmed = strip_comment(command).trim();
     if trimmed.is_empty() {
         return Err(EncodeError::Empty);
     }
 
-    let mut parts = trimmed.splitn(2, " ");
-    let method_keyword = parts.next().unwrap_or("");
-    let post_method_remaining = parts.next().unwrap_or("").trim_start();
+    let (method_keyword, post_method_remaining) = split_token(trimmed);
 
     let method = Method::try_from(method_keyword).map_err(|_| EncodeError::UnknownMethod)?;
 
-    let (operation, post_operation_remaining) = if method == Method::Echo {
-        (Operation::Write, post_method_remaining)
-    } else {
-        if post_method_remaining.is_empty() {
-            return Err(EncodeError::MissingOperation);
-        }
+    let (operation_keyword, remainder) = split_token(post_method_remaining);
+    let operation = Operation::try_from(operation_keyword);
 
-        let mut op_parts = post_method_remaining.splitn(2, " ");
-        let operation_keyword = op_parts.next().unwrap_or("");
-        let remainder = op_parts.next().unwrap_or("").trim_start();
+    // Commands whose dictionary name is their method alone take no operation keyword, unless it
+    // names another command of that method, like `config reset`.
+    let (definition, post_operation_remaining) = match CommandDefinition::find_by_method(method) {
+        Some(definition) => match operation
+            .ok()
+            .and_then(|operation| CommandDefinition::find(method, operation))
+            .filter(|named| named.keyword_count() == 2)
+        {
+            Some(named) => (named, remainder),
+            None => (definition, post_method_remaining),
+        },
+        None => {
+            if post_method_remaining.is_empty() {
+                return Err(EncodeError::MissingOperation);
+            }
 
-        let operation =
-            Operation::try_from(operation_keyword).map_err(|_| EncodeError::UnknownOperation)?;
-        (operation, remainder)
+            let operation = operation.map_err(|_| EncodeError::UnknownOperation)?;
+            let definition = CommandDefinition::find(method, operation)
+                .ok_or(EncodeError::UnsupportedOperation { method, operation })?;
+            (definition, remainder)
+        }
     };
-
-    let supported = COMMAND_DICTIONARY
-        .iter()
-        .any(|def| def.method == method && def.operation == operation)
-        || matches!(
-            (method, operation),
-            (Method::I2c, Operation::Read | Operation::Write)
-        );
-
-    if !supported {
-        return Err(EncodeError::UnsupportedOperation { method, operation });
-    }
+    let operation = definition.operation;
+    let first_argument = definition.keyword_count();
 
     output.clear();
 
     output.push(method.as_byte());
     output.push(operation.as_byte());
 
-    match (method, operation) {
+    // The encoders below count argument indices from their own first argument.
+    let encoded = match (method, operation) {
         (Method::Echo, Operation::Write) => encode_echo(post_operation_remaining, output),
         (Method::I2c, Operation::Read) => i2c::encode_i2c_read(post_operation_remaining, output),
         (Method::I2c, Operation::Write) => i2c::encode_i2c_write(post_operation_remaining, output),
+        (Method::I2c, Operation::ReadMulti) => {
+            i2c::encode_i2c_read_multi(post_operation_remaining, output)
+        }
+        (Method::Spi, Operation::Read) => spi::encode_spi_read(post_operation_remaining, output),
+        (Method::Spi, Operation::Write) => spi::encode_spi_write(post_operation_remaining, output),
+        (Method::Spi, Operation::Config) => {
+            spi::encode_spi_config(post_operation_remaining, output)
+        }
+        (Method::Temp, Operation::Read)
+        | (Method::Config, Operation::Read)
+        | (Method::Config, Operation::Reset)
+        | (Method::Identify, Operation::Write)
+        | (Method::Stats, Operation::Read)
+        | (Method::SelfTest, Operation::Read)
+        | (Method::ResetReason, Operation::Read)
+        | (Method::Info, Operation::Read) => encode_no_arguments(post_operation_remaining, output),
+        (Method::Uart, Operation::Bridge) => encode_uart_bridge(post_operation_remaining, output),
+        (Method::Heartbeat, Operation::Write) => encode_heartbeat(post_operation_remaining, output),
+        (Method::Format, Operation::Write) => encode_format(post_operation_remaining, output),
         _ => Err(EncodeError::UnsupportedOperation { method, operation }),
+    };
+    encoded.map_err(|err| err.shifted(first_argument))
+}
+
+/// Longest encoded command of `command`'s kind the firmware runs. Chunks carry up to
+/// [`MAX_CHUNKED_COMMAND_LEN`] bytes, but an echo has to fit one response, so it stops at
+/// [`MAX_ECHO_LEN`] bytes of payload. I2C and SPI writes are held to what their handlers take by
+/// the one-byte data count.
+pub fn max_command_len(command: &[u8]) -> usize {
+    match command.first().copied().and_then(Method::from_byte) {
+        Some(Method::Echo) => 2 + MAX_ECHO_LEN,
+        _ => MAX_CHUNKED_COMMAND_LEN,
+    }
+}
+
+/// Split an encoded command into chunk payloads of at most `chunk_len` bytes each, to be framed
+/// and sent in order. See [`crate::chunk`] for the layout. `chunk_len` has to leave room for data
+/// after the header, and the command has to fit the `u16` total length.
+pub fn split_into_chunks(command: &[u8], chunk_len: usize) -> Vec<Vec<u8>> {
+    assert!(chunk_len > CHUNK_HEADER_LEN, "chunks need room for data");
+    let total_len = u16::try_from(command.len()).expect("chunked command longer than u16::MAX");
+    let data_len = chunk_len - CHUNK_HEADER_LEN;
+    let count = command.len().div_ceil(data_len).max(1);
+
+    (0..count)
+        .map(|index| {
+            let kind = match index {
+                0 => CHUNK_START,
+                _ if index + 1 == count => CHUNK_END,
+                _ => CHUNK_CONTINUE,
+            };
+            let start = index * data_len;
+            let data = &command[start..command.len().min(start + data_len)];
+            let mut chunk = Vec::with_capacity(CHUNK_HEADER_LEN + data.len());
+            chunk.extend_from_slice(&[CHUNK_MARKER, kind, index as u8]);
+            chunk.extend_from_slice(&total_len.to_be_bytes());
+            chunk.extend_from_slice(data);
+            chunk
+        })
+        .collect()
+}
+
+/// Drop a trailing `#` comment from a command line.
+/// A `#` only opens a comment at the start of the line or directly after whitespace, so payloads
+/// such as `echo a#b` are sent untouched.
+pub fn strip_comment(input: &str) -> &str {
+    let mut previous_is_space = true;
+    for (idx, ch) in input.char_indices() {
+        if ch == '#' && previous_is_space {
+            return &input[..idx];
+        }
+        previous_is_space = ch.is_ascii_whitespace();
+    }
+    input
+}
+
+/// Split off the first whitespace-delimited token.
+/// Returns the token and the rest of the input with its leading whitespace removed.
+fn split_token(input: &str) -> (&str, &str) {
+    match input.find(|c: char| c.is_ascii_whitespace()) {
+        Some(idx) => (&input[..idx], input[idx..].trim_start()),
+        None => (input, ""),
     }
 }
 
@@ -125,30 +341,188 @@ fn encode_echo(remainder: &str, output: &mut Vec<u8>) -> Result<usize, EncodeErr
     Ok(output.len())
 }
 
+fn encode_no_arguments(remainder: &str, output: &[u8]) -> Result<usize, EncodeError> {
+    if !remainder.is_empty() {
+        return Err(EncodeError::UnexpectedArgument { index: 0 });
+    }
+    Ok(output.len())
+}
+
+fn encode_uart_bridge(remainder: &str, output: &mut Vec<u8>) -> Result<usize, EncodeError> {
+    let (token, rest) = split_token(remainder);
+    if token.is_empty() {
+        return Err(EncodeError::MissingArgument { index: 0 });
+    }
+    let baud = parse_unsigned(token, 0)?;
+    if baud == 0 {
+        return Err(EncodeError::InvalidArgument { index: 0 });
+    }
+    if !rest.is_empty() {
+        return Err(EncodeError::UnexpectedArgument { index: 1 });
+    }
+    output.extend_from_slice(&baud.to_be_bytes());
+    Ok(output.len())
+}
+
+/// `on <ms>` or `off`. An `off` command still carries an interval so the layout stays fixed.
+fn encode_heartbeat(remainder: &str, output: &mut Vec<u8>) -> Result<usize, EncodeError> {
+    let (switch, rest) = split_token(remainder);
+    let (enable, interval_ms) = if switch.eq_ignore_ascii_case("on") {
+        let (token, rest) = split_token(rest);
+        let interval_ms = parse_u16(token, 1)?;
+        if interval_ms == 0 {
+            return Err(EncodeError::InvalidArgument { index: 1 });
+        }
+        if !rest.is_empty() {
+            return Err(EncodeError::UnexpectedArgument { index: 2 });
+        }
+        (true, interval_ms)
+    } else if switch.eq_ignore_ascii_case("off") {
+        if !rest.is_empty() {
+            return Err(EncodeError::UnexpectedArgument { index: 1 });
+        }
+        (false, 0)
+    } else if switch.is_empty() {
+        return Err(EncodeError::MissingArgument { index: 0 });
+    } else {
+        return Err(EncodeError::InvalidArgument { index: 0 });
+    };
+    output.push(u8::from(enable));
+    output.extend_from_slice(&interval_ms.to_be_bytes());
+    Ok(output.len())
+}
+
+fn encode_format(remainder: &str, output: &mut Vec<u8>) -> Result<usize, EncodeError> {
+    let (name, rest) = split_token(remainder);
+    if name.is_empty() {
+        return Err(EncodeError::MissingArgument { index: 0 });
+    }
+    let format = ResponseFormat::ALL
+        .into_iter()
+        .find(|format| format.name().eq_ignore_ascii_case(name))
+        .ok_or(EncodeError::InvalidArgument { index: 0 })?;
+    if !rest.is_empty() {
+        return Err(EncodeError::UnexpectedArgument { index: 1 });
+    }
+    output.push(format.as_byte());
+    Ok(output.len())
+}
+
+/// Parse a byte-sized argument. See [`parse_u16`] for the accepted number syntax.
 pub(super) fn parse_u8(token: &str, index: usize) -> Result<u8, EncodeError> {
+    let value = parse_unsigned(token, index)?;
+    u8::try_from(value).map_err(|_| EncodeError::InvalidArgument { index })
+}
+
+/// Parse a 16-bit argument.
+///
+/// Numbers are decimal unless prefixed with `0x` (hex), `0b` (binary) or `0o` or `o` (octal);
+/// prefixes are case-insensitive. Underscores may separate digits (`0xFF_00`, `1_000`) but cannot lead or trail
+/// the digits. `index` is reported back in any error.
+pub fn parse_u16(token: &str, index: usize) -> Result<u16, EncodeError> {
+    let value = parse_unsigned(token, index)?;
+    u16::try_from(value).map_err(|_| EncodeError::InvalidArgument { index })
+}
+
+fn parse_unsigned(token: &str, index: usize) -> Result<u32, EncodeError> {
     let token = token.trim();
     if token.is_empty() {
         return Err(EncodeError::MissingArgument { index });
     }
 
-    let (radix, digits) = if let Some(stripped) = token.strip_prefix("0x") {
-        (16, stripped)
-    } else if let Some(stripped) = token.strip_prefix("0b") {
-        (2, stripped)
-    } else {
-        (10, token)
+    let (radix, digits) = match token.get(..2) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("0x") => (16, &token[2..]),
+        Some(prefix) if prefix.eq_ignore_ascii_case("0b") => (2, &token[2..]),
+        Some(prefix) if prefix.eq_ignore_ascii_case("0o") => (8, &token[2..]),
+        _ if token.starts_with(['o', 'O']) => (8, &token[1..]),
+        _ => (10, token),
     };
 
-    if digits.is_empty() {
+    if digits.is_empty() || digits.starts_with('_') || digits.ends_with('_') {
         return Err(EncodeError::InvalidArgument { index });
     }
 
-    u8::from_str_radix(digits, radix).map_err(|_| EncodeError::InvalidArgument { index })
+    let mut value: u32 = 0;
+    for ch in digits.chars().filter(|&ch| ch != '_') {
+        let digit = ch
+            .to_digit(radix)
+            .ok_or(EncodeError::InvalidArgument { index })?;
+        value = value
+            .checked_mul(radix)
+            .and_then(|value| value.checked_add(digit))
+            .ok_or(EncodeError::InvalidArgument { index })?;
+    }
+    Ok(value)
 }
 
 #[cfg(test)]
 mod tests {
+    use alloc::{format, string::String};
+
     use super::*;
+    use crate::{ArgumentKind, COMMAND_DICTIONARY};
+
+    /// A line naming `def` with `count` valid arguments, repeating the last as the schema allows.
+    fn schema_line(def: &CommandDefinition, count: usize) -> String {
+        let mut line = String::from(def.name);
+        for index in 0..count {
+            // Commands without arguments still get one, to test that it's refused.
+            let kind = def
+                .arguments
+                .get(index)
+                .or(def.arguments.last())
+                .map_or(ArgumentKind::U8, |argument| argument.kind);
+            let sample = match kind {
+                ArgumentKind::U8 => "1",
+                ArgumentKind::U16 | ArgumentKind::U32 => "100",
+                ArgumentKind::Keyword(words) => words[0],
+                ArgumentKind::Text => "hello",
+            };
+            line.push(' ');
+            line.push_str(sample);
+        }
+        line
+    }
+
+    #[test]
+    fn schema_matches_what_the_encoder_accepts() {
+        for def in COMMAND_DICTIONARY {
+            let first = def.keyword_count();
+            let full = schema_line(def, def.max_args);
+            let payload = encode_command(&full).unwrap_or_else(|err| panic!("{full}: {err:?}"));
+            assert_eq!(
+                (payload[0], payload[1]),
+                (def.method.as_byte(), def.operation.as_byte()),
+                "{full}"
+            );
+            let decoded =
+                crate::decode_command(&payload).unwrap_or_else(|err| panic!("{full}: {err:?}"));
+            assert!(format!("{decoded}").starts_with(def.name), "{full}");
+
+            if def.min_args > 0 {
+                let short = schema_line(def, def.min_args - 1);
+                assert_eq!(
+                    encode_command(&short),
+                    Err(EncodeError::MissingArgument {
+                        index: first + def.min_args - 1
+                    }),
+                    "{short}"
+                );
+            }
+
+            let last = def.arguments.last().map(|argument| argument.kind);
+            if last != Some(ArgumentKind::Text) {
+                let long = schema_line(def, def.max_args + 1);
+                assert!(encode_command(&long).is_err(), "{long}");
+            }
+
+            if def.bus_flag {
+                let (name, arguments) = full.split_at(def.name.len());
+                let flagged = format!("{name} --bus 0{arguments}");
+                assert!(encode_command(&flagged).is_ok(), "{flagged}");
+            }
+        }
+    }
 
     #[test]
     fn encode_echo_roundtrip() {
@@ -161,13 +535,14 @@ mod tests {
 
     #[test]
     fn encode_i2c_read_hex_args() {
-        let buf = encode_command("i2c read 0x80 0x11 0x04").unwrap();
+        let buf = encode_command("i2c read 0x40 0x11 0x04").unwrap();
         assert_eq!(
             buf,
             vec![
                 Method::I2c.as_byte(),
                 Operation::Read.as_byte(),
-                0x80,
+                crate::DEFAULT_I2C_BUS,
+                0x40,
                 0x11,
                 0x04
             ]
@@ -177,22 +552,66 @@ mod tests {
     #[test]
     fn encode_i2c_read_errors_on_missing_argument() {
         let mut buf = Vec::new();
-        let err = encode_command_into("i2c read 0x80", &mut buf).unwrap_err();
-        assert!(matches!(err, EncodeError::MissingArgument { index: 1 }));
+        let err = encode_command_into("i2c read 0x40", &mut buf).unwrap_err();
+        assert!(matches!(err, EncodeError::MissingArgument { index: 3 }));
 
-        let err = encode_command_into("i2c read 0x80 0x11", &mut buf).unwrap_err();
-        assert!(matches!(err, EncodeError::MissingArgument { index: 2 }));
+        let err = encode_command_into("i2c read 0x40 0x11", &mut buf).unwrap_err();
+        assert!(matches!(err, EncodeError::MissingArgument { index: 4 }));
+    }
+
+    #[test]
+    fn argument_indices_point_at_the_token_in_the_line() {
+        for (line, bad_token) in [
+            ("i2c read 0xZZ 0x11 4", "0xZZ"),
+            ("i2c read 0x40 0x11 4 5", "5"),
+            ("i2c read --bus 1 0x40 0x11 0x100", "0x100"),
+            ("i2c write 0x40 0x11 0x01 0x02 0xZZ", "0xZZ"),
+            ("i2c write --bus 0 0x40 0x1G 0x01", "0x1G"),
+            ("  i2c\tread   0x40 x 4  # comment", "x"),
+            ("heartbeat on 0", "0"),
+            ("uart bridge 9600 8N1", "8N1"),
+            ("temp now", "now"),
+        ] {
+            let index = match encode_command(line) {
+                Err(
+                    EncodeError::InvalidArgument { index }
+                    | EncodeError::UnexpectedArgument { index },
+                ) => index,
+                other => panic!("`{line}` gave {other:?}"),
+            };
+            assert_eq!(
+                line.split_whitespace().nth(index),
+                Some(bad_token),
+                "{line}"
+            );
+        }
+
+        // A missing argument is reported where it would have gone.
+        for (line, index) in [
+            ("i2c read 0x40 0x11", 4),
+            ("i2c read --bus 1 0x40", 5),
+            ("i2c write 0x40 0x11", 4),
+            ("uart bridge", 2),
+            ("heartbeat", 1),
+        ] {
+            assert_eq!(
+                encode_command(line),
+                Err(EncodeError::MissingArgument { index }),
+                "{line}"
+            );
+        }
     }
 
     #[test]
     fn encode_i2c_write_basic() {
-        let buf = encode_command("i2c write 0x80 0x11 0x01 0x02").unwrap();
+        let buf = encode_command("i2c write 0x40 0x11 0x01 0x02").unwrap();
         assert_eq!(
             buf,
             vec![
                 Method::I2c.as_byte(),
                 Operation::Write.as_byte(),
-                0x80,
+                crate::DEFAULT_I2C_BUS,
+                0x40,
                 0x11,
                 0x02,
                 0x01,
@@ -201,18 +620,332 @@ mod tests {
         );
     }
 
+    #[test]
+    fn encode_i2c_addresses_are_seven_bit() {
+        let buf = encode_command("i2c read 0x7F 0x00 1").unwrap();
+        assert_eq!(buf[3], crate::MAX_I2C_ADDRESS);
+
+        assert_eq!(
+            encode_command("i2c read 0x80 0x00 1"),
+            Err(EncodeError::InvalidAddress {
+                index: 2,
+                address: 0x80
+            })
+        );
+        assert_eq!(
+            encode_command("i2c write --bus 0 0x90 0x00 0x01"),
+            Err(EncodeError::InvalidAddress {
+                index: 4,
+                address: 0x90
+            })
+        );
+        assert_eq!(
+            encode_command("i2c readm 0xFF 0x00"),
+            Err(EncodeError::InvalidAddress {
+                index: 2,
+                address: 0xFF
+            })
+        );
+        // Past a byte it's an ordinary bad argument.
+        assert_eq!(
+            encode_command("i2c read 0x100 0x00 1"),
+            Err(EncodeError::InvalidArgument { index: 2 })
+        );
+    }
+
+    #[test]
+    fn encode_i2c_bus_flag() {
+        let buf = encode_command("i2c read --bus 0 0x40 0x11 4").unwrap();
+        assert_eq!(&buf[2..], &[0x00, 0x40, 0x11, 0x04]);
+
+        assert_eq!(
+            encode_command("i2c read --bus 2 0x40 0x11 4"),
+            Err(EncodeError::InvalidBus)
+        );
+        assert_eq!(
+            encode_command("i2c write --bus x 0x40 0x11 0x01"),
+            Err(EncodeError::InvalidBus)
+        );
+    }
+
+    #[test]
+    fn encode_spi_config() {
+        assert_eq!(
+            encode_command("spi config mode3 4000").unwrap(),
+            [
+                Method::Spi.as_byte(),
+                Operation::Config.as_byte(),
+                3,
+                0x0F,
+                0xA0
+            ]
+        );
+        assert_eq!(
+            encode_command("spi config mode4 1000"),
+            Err(EncodeError::InvalidArgument { index: 2 })
+        );
+        assert_eq!(
+            encode_command("spi config 0 70000"),
+            Err(EncodeError::InvalidArgument { index: 3 })
+        );
+        assert_eq!(
+            encode_command("spi config mode0"),
+            Err(EncodeError::MissingArgument { index: 3 })
+        );
+    }
+
+    #[test]
+    fn encode_accepts_tabs_and_runs_of_spaces() {
+        let expected = encode_command("i2c read 0x40 0x11 0x04").unwrap();
+        assert_eq!(
+            encode_command("i2c\tread\t0x40\t0x11\t0x04").unwrap(),
+            expected
+        );
+        assert_eq!(
+            encode_command("  i2c   read  0x40 \t 0x11    0x04  ").unwrap(),
+            expected
+        );
+
+        let echo = encode_command("echo\t  hello  world").unwrap();
+        assert_eq!(&echo[2..], b"hello  world");
+    }
+
+    #[test]
+    fn encode_strips_trailing_comments() {
+        let expected = encode_command("i2c read 0x40 0x11 4").unwrap();
+        assert_eq!(
+            encode_command("i2c read 0x40 0x11 4 # read status").unwrap(),
+            expected
+        );
+        assert_eq!(
+            encode_command("i2c read 0x40 0x11 4\t#status").unwrap(),
+            expected
+        );
+
+        let echo = encode_command("echo hello # greeting").unwrap();
+        assert_eq!(&echo[2..], b"hello");
+    }
+
+    #[test]
+    fn encode_keeps_hash_inside_tokens() {
+        let echo = encode_command("echo a#b").unwrap();
+        assert_eq!(&echo[2..], b"a#b");
+    }
+
+    #[test]
+    fn encode_drops_value_hint() {
+        assert_eq!(
+            encode_command("i2c read 0x48 0x00 2 as i16be"),
+            encode_command("i2c read 0x48 0x00 2")
+        );
+        assert_eq!(
+            encode_command("i2c read 0x48 0x00 2 as f64"),
+            Err(EncodeError::UnknownValueHint)
+        );
+    }
+
+    #[test]
+    fn encode_comment_only_line_is_empty() {
+        assert_eq!(encode_command("# just a note"), Err(EncodeError::Empty));
+        assert_eq!(
+            encode_command("   # indented note"),
+            Err(EncodeError::Empty)
+        );
+    }
+
+    #[test]
+    fn encode_blank_line_is_empty() {
+        for blank in ["", "   ", "\t\t"] {
+            assert_eq!(encode_command(blank), Err(EncodeError::Empty), "{blank:?}");
+        }
+    }
+
+    #[test]
+    fn parse_accepts_uppercase_prefixes() {
+        assert_eq!(parse_u8("0XFF", 0), Ok(0xFF));
+        assert_eq!(parse_u8("0xfe", 0), Ok(0xFE));
+        assert_eq!(parse_u8("0B1010", 0), Ok(0b1010));
+        assert_eq!(parse_u8("0O17", 0), Ok(0o17));
+        assert_eq!(parse_u8("0o377", 0), Ok(0xFF));
+        assert_eq!(parse_u8("o17", 0), Ok(0o17));
+        assert_eq!(parse_u16("O177_777", 0), Ok(u16::MAX));
+    }
+
+    #[test]
+    fn parse_accepts_underscore_separators() {
+        assert_eq!(parse_u8("0b1010_0101", 0), Ok(0b1010_0101));
+        assert_eq!(parse_u16("0xFF_00", 0), Ok(0xFF00));
+        assert_eq!(parse_u16("65_535", 0), Ok(u16::MAX));
+        assert_eq!(
+            parse_u16("0x_FF", 3),
+            Err(EncodeError::InvalidArgument { index: 3 })
+        );
+        assert_eq!(
+            parse_u16("1_", 3),
+            Err(EncodeError::InvalidArgument { index: 3 })
+        );
+    }
+
+    #[test]
+    fn parse_rejects_overflow_with_argument_index() {
+        assert_eq!(
+            parse_u8("0x1_00", 2),
+            Err(EncodeError::InvalidArgument { index: 2 })
+        );
+        assert_eq!(
+            parse_u8("0o400", 1),
+            Err(EncodeError::InvalidArgument { index: 1 })
+        );
+        assert_eq!(
+            parse_u16("0X1_0000", 4),
+            Err(EncodeError::InvalidArgument { index: 4 })
+        );
+        assert_eq!(
+            parse_u16("99999999999", 0),
+            Err(EncodeError::InvalidArgument { index: 0 })
+        );
+    }
+
+    #[test]
+    fn parse_rejects_bare_prefix_and_bad_digits() {
+        assert_eq!(
+            parse_u8("0x", 0),
+            Err(EncodeError::InvalidArgument { index: 0 })
+        );
+        assert_eq!(
+            parse_u8("0b102", 0),
+            Err(EncodeError::InvalidArgument { index: 0 })
+        );
+        assert_eq!(
+            parse_u8("0o8", 0),
+            Err(EncodeError::InvalidArgument { index: 0 })
+        );
+        for bare_octal in ["o", "o8", "o_7", "oo7"] {
+            assert_eq!(
+                parse_u8(bare_octal, 0),
+                Err(EncodeError::InvalidArgument { index: 0 }),
+                "{bare_octal}"
+            );
+        }
+        assert_eq!(
+            parse_u8(" ", 5),
+            Err(EncodeError::MissingArgument { index: 5 })
+        );
+    }
+
     #[test]
     fn encode_unknown_command() {
         let err = encode_command("foo").unwrap_err();
         assert!(matches!(err, EncodeError::UnknownMethod));
     }
 
+    #[test]
+    fn display_reencodes_to_same_bytes() {
+        let inputs = [
+            "echo hello world",
+            "i2c read 0x40 0x11 0x04",
+            "i2c read 0 1 255",
+            "i2c write 0x50 0x20 0xAA 0xBB",
+            "i2c write 0x50 0x20 0",
+            "i2c read --bus 0 0x48 0x00 2",
+            "i2c write --bus 0 0x50 0x20 0xAA",
+        ];
+
+        for input in inputs {
+            let encoded = encode_command(input).unwrap();
+            let described = crate::decode_command(&encoded).unwrap().to_string();
+            assert_eq!(encode_command(&described).unwrap(), encoded, "{input}");
+        }
+    }
+
+    #[test]
+    fn display_i2c_read_uses_host_grammar() {
+        let command = crate::Command::I2cRead {
+            bus: crate::I2cBus::DEFAULT,
+            address: 0x40,
+            register: 0x11,
+            length: 4,
+        };
+        assert_eq!(command.to_string(), "i2c read 0x40 0x11 4");
+
+        let command = crate::Command::I2cWrite {
+            bus: crate::I2cBus::I2c0,
+            address: 0x50,
+            register: 0x20,
+            payload: &[0xAA],
+        };
+        assert_eq!(command.to_string(), "i2c write --bus 0 0x50 0x20 0xaa");
+    }
+
     #[test]
     fn transport_roundtrip() {
         let payload = vec![0xAA, 0x00, 0x55];
-        let encoded = encode_transport_frame(&payload).unwrap();
-        let (decoded, used) = try_decode_transport_frame(&encoded).unwrap().unwrap();
+        let encoded = encode_transport_frame(&payload, Framing::Postcard).unwrap();
+        let (decoded, used) = try_decode_transport_frame(&encoded, Framing::Postcard)
+            .unwrap()
+            .unwrap();
         assert_eq!(used, encoded.len());
         assert_eq!(decoded, payload);
     }
+
+    #[test]
+    fn transport_roundtrip_in_every_framing() {
+        for framing in Framing::ALL {
+            let encoded = encode_transport_frame(b"interop", framing).unwrap();
+            let (decoded, used) = try_decode_transport_frame(&encoded, framing)
+                .unwrap()
+                .unwrap();
+            assert_eq!(used, encoded.len());
+            assert_eq!(decoded, b"interop");
+        }
+    }
+
+    #[test]
+    fn resync_finds_length_prefixed_frames() {
+        let framing = Framing::LengthPrefixed;
+        // A one-byte frame whose CRC is wrong.
+        let mut stream = vec![0x00, 0x01, 0xAA, 0x00, 0x00];
+        stream.extend_from_slice(&encode_transport_frame(&[0x10, 0x20], framing).unwrap());
+
+        let decoded = decode_transport_frame_resyncing(&stream, framing)
+            .unwrap()
+            .unwrap();
+        assert_eq!(decoded.payload, [0x10, 0x20]);
+        assert_eq!(decoded.skipped, 5);
+    }
+
+    #[test]
+    fn resync_skips_corruption_before_valid_frame() {
+        let frame = encode_transport_frame(&[0x10, 0x20], Framing::Postcard).unwrap();
+        let mut corrupted = encode_transport_frame(b"lost", Framing::Postcard).unwrap();
+        corrupted[1] ^= 0xFF;
+
+        let mut stream = corrupted.clone();
+        stream.extend_from_slice(&frame);
+        assert!(try_decode_transport_frame(&stream, Framing::Postcard).is_err());
+
+        let decoded = decode_transport_frame_resyncing(&stream, Framing::Postcard)
+            .unwrap()
+            .unwrap();
+        assert_eq!(decoded.payload, [0x10, 0x20]);
+        assert_eq!(decoded.skipped, corrupted.len());
+        assert_eq!(decoded.consumed, stream.len());
+    }
+
+    #[test]
+    fn resync_waits_for_partial_frame() {
+        let frame = encode_transport_frame(&[0x10, 0x20], Framing::Postcard).unwrap();
+        let mut stream = vec![0xFF; 3];
+        stream.extend_from_slice(&frame[..frame.len() - 1]);
+        assert_eq!(
+            decode_transport_frame_resyncing(&stream, Framing::Postcard),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn resync_gives_up_after_bounded_search() {
+        let garbage = vec![0xFF; MAX_RESYNC_SKIP + 16];
+        assert!(decode_transport_frame_resyncing(&garbage, Framing::Postcard).is_err());
+    }
 }
@@ -0,0 +1,105 @@
+use alloc::vec::Vec;
+
+use super::{EncodeError, bytes::parse_data_bytes, parse_u8, parse_u16, split_token};
+use crate::{
+    SPI_CS_COUNT,
+    spi::{config_error, parse_mode},
+};
+
+fn parse_chip_select(token: &str) -> Result<u8, EncodeError> {
+    let cs = parse_u8(token, 0)?;
+    if cs >= SPI_CS_COUNT {
+        return Err(EncodeError::InvalidArgument { index: 0 });
+    }
+    Ok(cs)
+}
+
+/// Encode the arguments of `spi read <cs> <register> <length>`. Argument indices in errors count
+/// from the first token of `remainder`.
+pub fn encode_spi_read(remainder: &str, output: &mut Vec<u8>) -> Result<usize, EncodeError> {
+    const EXPECTED_ARGS: usize = 3;
+
+    let mut args = remainder.split_ascii_whitespace();
+    let cs_str = args
+        .next()
+        .ok_or(EncodeError::MissingArgument { index: 0 })?;
+    let register_str = args
+        .next()
+        .ok_or(EncodeError::MissingArgument { index: 1 })?;
+    let length_str = args
+        .next()
+        .ok_or(EncodeError::MissingArgument { index: 2 })?;
+
+    if args.next().is_some() {
+        return Err(EncodeError::UnexpectedArgument {
+            index: EXPECTED_ARGS,
+        });
+    }
+
+    let cs = parse_chip_select(cs_str)?;
+    let register = parse_u8(register_str, 1)?;
+    let length = parse_u8(length_str, 2)?;
+
+    output.reserve(3);
+    output.push(cs);
+    output.push(register);
+    output.push(length);
+
+    Ok(output.len())
+}
+
+/// Encode the arguments of `spi write <cs> <register> <byte>...`, counting argument indices as
+/// [`encode_spi_read`] does.
+pub fn encode_spi_write(remainder: &str, output: &mut Vec<u8>) -> Result<usize, EncodeError> {
+    let (cs_str, rest) = split_token(remainder);
+    if cs_str.is_empty() {
+        return Err(EncodeError::MissingArgument { index: 0 });
+    }
+    let (register_str, data) = split_token(rest);
+    if register_str.is_empty() {
+        return Err(EncodeError::MissingArgument { index: 1 });
+    }
+
+    let cs = parse_chip_select(cs_str)?;
+    let register = parse_u8(register_str, 1)?;
+    let payload = parse_data_bytes(data, 2)?;
+    let count =
+        u8::try_from(payload.len()).map_err(|_| EncodeError::InvalidArgument { index: 2 })?;
+
+    output.reserve(3 + payload.len());
+    output.push(cs);
+    output.push(register);
+    output.push(count);
+    output.extend_from_slice(&payload);
+
+    Ok(output.len())
+}
+
+/// Encode the arguments of `spi config <mode> <kHz>`. The mode is `mode0` to `mode3` or the bare
+/// digit, and both are checked against the limits in [`config_error`].
+pub fn encode_spi_config(remainder: &str, output: &mut Vec<u8>) -> Result<usize, EncodeError> {
+    const EXPECTED_ARGS: usize = 2;
+
+    let mut args = remainder.split_ascii_whitespace();
+    let mode_str = args
+        .next()
+        .ok_or(EncodeError::MissingArgument { index: 0 })?;
+    let freq_str = args
+        .next()
+        .ok_or(EncodeError::MissingArgument { index: 1 })?;
+    if args.next().is_some() {
+        return Err(EncodeError::UnexpectedArgument {
+            index: EXPECTED_ARGS,
+        });
+    }
+
+    let mode = parse_mode(mode_str).ok_or(EncodeError::InvalidArgument { index: 0 })?;
+    let freq_khz = parse_u16(freq_str, 1)?;
+    if config_error(mode, freq_khz).is_some() {
+        return Err(EncodeError::InvalidArgument { index: 1 });
+    }
+
+    output.push(mode);
+    output.extend_from_slice(&freq_khz.to_be_bytes());
+    Ok(output.len())
+}
@@ -0,0 +1,80 @@
+//! Finding one board among several.
+//!
+//! `identify` makes the firmware flash its status LED in a pattern no other state uses for
+//! [`IDENTIFY_DURATION_MS`], then go back to whatever the LED was showing. The reply is the
+//! [`confirmation`], sent as soon as the flashing starts.
+
+/// How long the LED flashes after an `identify`.
+pub const IDENTIFY_DURATION_MS: u64 = 5_000;
+
+/// Write the response to an `identify`, e.g. `identifying for 5 s`.
+pub fn confirmation(out: &mut impl core::fmt::Write) -> core::fmt::Result {
+    write!(out, "identifying for {} s", IDENTIFY_DURATION_MS / 1_000)
+}
+
+/// Whether the identify pattern is showing. Times are milliseconds on any monotonic clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct IdentifyWindow {
+    until_ms: Option<u64>,
+}
+
+impl IdentifyWindow {
+    pub const fn new() -> Self {
+        Self { until_ms: None }
+    }
+
+    /// Start flashing at `now_ms`. Another `identify` while flashing starts the time again.
+    pub fn start(&mut self, now_ms: u64) {
+        self.until_ms = Some(now_ms + IDENTIFY_DURATION_MS);
+    }
+
+    /// Whether the pattern should still show at `now_ms`. Once the time is up the window closes
+    /// for good, so the LED reverts.
+    pub fn is_active(&mut self, now_ms: u64) -> bool {
+        match self.until_ms {
+            Some(until) if now_ms < until => true,
+            Some(_) => {
+                self.until_ms = None;
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Stop flashing now.
+    pub fn cancel(&mut self) {
+        self.until_ms = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_pattern_reverts_once_the_time_is_up() {
+        let mut window = IdentifyWindow::new();
+        assert!(!window.is_active(0));
+        window.start(1_000);
+        assert!(window.is_active(1_000));
+        assert!(window.is_active(1_000 + IDENTIFY_DURATION_MS - 1));
+        assert!(!window.is_active(1_000 + IDENTIFY_DURATION_MS));
+        assert_eq!(window, IdentifyWindow::new());
+    }
+
+    #[test]
+    fn identifying_again_restarts_the_time() {
+        let mut window = IdentifyWindow::new();
+        window.start(0);
+        window.start(4_000);
+        assert!(window.is_active(IDENTIFY_DURATION_MS));
+        assert!(!window.is_active(4_000 + IDENTIFY_DURATION_MS));
+        window.start(0);
+        window.cancel();
+        assert!(!window.is_active(1));
+
+        let mut reply = String::new();
+        confirmation(&mut reply).unwrap();
+        assert_eq!(reply, "identifying for 5 s");
+    }
+}
@@ -15,6 +15,7 @@ uart will have byte mode and string mode
 #[cfg(feature = "alloc")]
 extern crate alloc;
 
+use core::fmt;
 use core::time::Duration;
 
 pub mod transport {
@@ -23,17 +24,28 @@ pub mod transport {
 
     pub use postcard::Error as PostcardError;
 
+    use crate::resync::ResyncDetector;
+
     /// Small wrapper around a payload that gets serialized with postcard to
-    /// provide framing for arbitrary byte streams.
+    /// provide framing for arbitrary byte streams. The CRC lets a reader tell a real frame
+    /// boundary from corrupted or misaligned bytes.
     #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
     pub struct Frame<'a> {
         #[serde(borrow)]
         pub payload: &'a [u8],
+        pub crc: u16,
     }
 
     impl<'a> Frame<'a> {
         pub const fn new(payload: &'a [u8]) -> Self {
-            Self { payload }
+            Self {
+                payload,
+                crc: crc16(payload),
+            }
+        }
+
+        pub const fn is_intact(&self) -> bool {
+            crc16(self.payload) == self.crc
         }
     }
 
@@ -41,8 +53,82 @@ pub mod transport {
     pub enum FrameError {
         Serialize(PostcardError),
         Deserialize(PostcardError),
+        /// The frame parsed but its CRC doesn't match the payload.
+        Checksum,
+    }
+
+    /// How frames are laid out on the wire. The host picks one during the handshake.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub enum Framing {
+        /// A postcard-serialized [`Frame`]: varint payload length, payload, varint CRC.
+        #[default]
+        Postcard,
+        /// `[len u16 BE][payload][crc16 BE]`, for tools without a postcard implementation.
+        LengthPrefixed,
+    }
+
+    /// Bytes of a [`Framing::LengthPrefixed`] frame around the payload.
+    pub const LENGTH_PREFIXED_OVERHEAD: usize = 4;
+
+    impl Framing {
+        pub const ALL: [Framing; 2] = [Framing::Postcard, Framing::LengthPrefixed];
+
+        pub const fn name(self) -> &'static str {
+            match self {
+                Framing::Postcard => "postcard",
+                Framing::LengthPrefixed => "length",
+            }
+        }
+
+        pub fn from_name(name: &str) -> Option<Self> {
+            Self::ALL
+                .into_iter()
+                .find(|framing| framing.name().eq_ignore_ascii_case(name))
+        }
+
+        /// Frame `payload` into `buffer`, returning the number of bytes written.
+        pub fn encode_into(self, payload: &[u8], buffer: &mut [u8]) -> Result<usize, FrameError> {
+            match self {
+                Framing::Postcard => encode_into(payload, buffer),
+                Framing::LengthPrefixed => encode_length_prefixed(payload, buffer),
+            }
+        }
+
+        /// Take one frame off the front of `bytes`. Both framings report a frame that needs more
+        /// bytes as `Deserialize(DeserializeUnexpectedEnd)`.
+        pub fn take_from_bytes<'a>(
+            self,
+            bytes: &'a [u8],
+        ) -> Result<(Frame<'a>, &'a [u8]), FrameError> {
+            match self {
+                Framing::Postcard => take_from_bytes(bytes),
+                Framing::LengthPrefixed => take_length_prefixed(bytes),
+            }
+        }
+
+        /// The payload length the frame at the front of `bytes` declares, once enough of its
+        /// header has arrived to read it. Lets a reader refuse a frame before buffering it.
+        pub fn claimed_payload_len(self, bytes: &[u8]) -> Option<usize> {
+            match self {
+                Framing::Postcard => {
+                    let mut len = 0u64;
+                    for (idx, &byte) in bytes.iter().take(5).enumerate() {
+                        len |= u64::from(byte & 0x7F) << (7 * idx);
+                        if byte & 0x80 == 0 {
+                            return usize::try_from(len).ok();
+                        }
+                    }
+                    None
+                }
+                Framing::LengthPrefixed => {
+                    let header = bytes.get(..2)?;
+                    Some(usize::from(u16::from_be_bytes([header[0], header[1]])))
+                }
+            }
+        }
     }
 
+    /// Frame `payload` with [`Framing::Postcard`].
     pub fn encode_into(payload: &[u8], buffer: &mut [u8]) -> Result<usize, FrameError> {
         let frame = Frame::new(payload);
         postcard::to_slice(&frame, buffer)
@@ -50,17 +136,290 @@ pub mod transport {
             .map_err(FrameError::Serialize)
     }
 
+    /// Take one [`Framing::Postcard`] frame off the front of `bytes`.
     pub fn take_from_bytes<'a>(bytes: &'a [u8]) -> Result<(Frame<'a>, &'a [u8]), FrameError> {
-        postcard::take_from_bytes::<Frame<'a>>(bytes).map_err(FrameError::Deserialize)
+        let (frame, remaining) =
+            postcard::take_from_bytes::<Frame<'a>>(bytes).map_err(FrameError::Deserialize)?;
+        if !frame.is_intact() {
+            return Err(FrameError::Checksum);
+        }
+        Ok((frame, remaining))
+    }
+
+    fn encode_length_prefixed(payload: &[u8], buffer: &mut [u8]) -> Result<usize, FrameError> {
+        let too_long = || FrameError::Serialize(PostcardError::SerializeBufferFull);
+        let len = u16::try_from(payload.len()).map_err(|_| too_long())?;
+        let end = payload.len() + 2;
+        if buffer.len() < payload.len() + LENGTH_PREFIXED_OVERHEAD {
+            return Err(too_long());
+        }
+        buffer[..2].copy_from_slice(&len.to_be_bytes());
+        buffer[2..end].copy_from_slice(payload);
+        buffer[end..end + 2].copy_from_slice(&crc16(payload).to_be_bytes());
+        Ok(end + 2)
+    }
+
+    fn take_length_prefixed(bytes: &[u8]) -> Result<(Frame<'_>, &[u8]), FrameError> {
+        let incomplete = FrameError::Deserialize(PostcardError::DeserializeUnexpectedEnd);
+        let Some((len, rest)) = bytes.split_first_chunk::<2>() else {
+            return Err(incomplete);
+        };
+        let len = usize::from(u16::from_be_bytes(*len));
+        if rest.len() < len + 2 {
+            return Err(incomplete);
+        }
+        let (payload, rest) = rest.split_at(len);
+        let (crc, remaining) = rest.split_at(2);
+        let frame = Frame {
+            payload,
+            crc: u16::from_be_bytes([crc[0], crc[1]]),
+        };
+        if !frame.is_intact() {
+            return Err(FrameError::Checksum);
+        }
+        Ok((frame, remaining))
+    }
+
+    /// Reassembles frames from a byte stream that arrives in arbitrary chunks.
+    ///
+    /// Bytes are appended with [`FrameReader::push`] and whole frames taken out with
+    /// [`FrameReader::next_frame`]; a frame split across pushes is returned once, after its last
+    /// byte arrives. The payload borrows the buffer, and its bytes are released on the next call.
+    /// A [`RESYNC`](crate::resync::RESYNC) among the pushed bytes empties the buffer.
+    #[derive(Debug, Clone)]
+    pub struct FrameReader<const N: usize> {
+        framing: Framing,
+        buffer: [u8; N],
+        len: usize,
+        /// Length of the frame last returned, dropped before the buffer is touched again.
+        taken: usize,
+        resync: ResyncDetector,
+        /// Error found while a resync may have been arriving, reported if the run stops short.
+        deferred: Option<FrameError>,
+        /// A resync completed since [`FrameReader::take_resync`] was last called.
+        resynced: bool,
+    }
+
+    impl<const N: usize> Default for FrameReader<N> {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl<const N: usize> FrameReader<N> {
+        pub const fn new() -> Self {
+            Self {
+                framing: Framing::Postcard,
+                buffer: [0; N],
+                len: 0,
+                taken: 0,
+                resync: ResyncDetector::new(),
+                deferred: None,
+                resynced: false,
+            }
+        }
+
+        /// Switch framing. Buffered bytes are dropped, since they were framed the old way.
+        pub fn set_framing(&mut self, framing: Framing) {
+            self.framing = framing;
+            self.clear();
+            self.resync = ResyncDetector::new();
+            self.deferred = None;
+            self.resynced = false;
+        }
+
+        /// Bytes buffered but not yet returned as part of a frame.
+        pub fn pending(&self) -> &[u8] {
+            &self.buffer[self.taken..self.len]
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.pending().is_empty()
+        }
+
+        pub fn clear(&mut self) {
+            self.len = 0;
+            self.taken = 0;
+        }
+
+        /// Append as much of `data` as fits and return how many bytes were accepted. A resync
+        /// completing stops the push there, so the bytes after it land in an empty buffer.
+        pub fn push(&mut self, data: &[u8]) -> usize {
+            self.release_taken();
+            let room = data.len().min(N - self.len);
+            if let Some(end) = self.resync.scan(&data[..room]) {
+                self.clear();
+                self.deferred = None;
+                self.resynced = true;
+                return end;
+            }
+            self.buffer[self.len..self.len + room].copy_from_slice(&data[..room]);
+            self.len += room;
+            room
+        }
+
+        /// Whether a resync has emptied the buffer since the last call.
+        pub fn take_resync(&mut self) -> bool {
+            core::mem::take(&mut self.resynced)
+        }
+
+        /// Take the next complete frame's payload, or `Ok(None)` if more bytes are needed.
+        ///
+        /// A malformed frame clears the buffer, since there is no reliable boundary to resume from.
+        /// While a resync that fits in the buffer may be arriving its error is held back (see
+        /// [`crate::resync`]).
+        pub fn next_frame(&mut self) -> Result<Option<&[u8]>, FrameError> {
+            self.release_taken();
+            if !self.resync.in_run()
+                && let Some(err) = self.deferred.take()
+            {
+                return Err(err);
+            }
+            // Decode to offsets first so the buffer is free to be cleared on error.
+            let decoded =
+                self.framing
+                    .take_from_bytes(&self.buffer[..self.len])
+                    .map(|(frame, remaining)| {
+                        let start = frame.payload.as_ptr() as usize - self.buffer.as_ptr() as usize;
+                        (
+                            start..start + frame.payload.len(),
+                            self.len - remaining.len(),
+                        )
+                    });
+            match decoded {
+                Ok((payload, taken)) => {
+                    self.taken = taken;
+                    Ok(Some(&self.buffer[payload]))
+                }
+                Err(FrameError::Deserialize(PostcardError::DeserializeUnexpectedEnd)) => Ok(None),
+                Err(err) if self.resync_may_complete() => {
+                    // The resync may be what broke the frame; whether it completes decides.
+                    self.clear();
+                    self.deferred.get_or_insert(err);
+                    Ok(None)
+                }
+                Err(err) => {
+                    self.clear();
+                    Err(err)
+                }
+            }
+        }
+
+        /// Whether the run of resync bytes at the end of the buffer could still become a whole
+        /// resync without overflowing it.
+        fn resync_may_complete(&self) -> bool {
+            self.resync.in_run() && self.resync.remaining() <= N - self.len
+        }
+
+        fn release_taken(&mut self) {
+            if self.taken == 0 {
+                return;
+            }
+            self.buffer.copy_within(self.taken..self.len, 0);
+            self.len -= self.taken;
+            self.taken = 0;
+        }
+    }
+
+    /// CRC-16/CCITT-FALSE (polynomial 0x1021, initial value 0xFFFF).
+    pub const fn crc16(bytes: &[u8]) -> u16 {
+        let mut crc: u16 = 0xFFFF;
+        let mut idx = 0;
+        while idx < bytes.len() {
+            crc ^= (bytes[idx] as u16) << 8;
+            let mut bit = 0;
+            while bit < 8 {
+                crc = if crc & 0x8000 != 0 {
+                    (crc << 1) ^ 0x1021
+                } else {
+                    crc << 1
+                };
+                bit += 1;
+            }
+            idx += 1;
+        }
+        crc
     }
 }
 
-pub const HANDSHAKE_COMMAND: &str = "SiTerm?";
-pub const HANDSHAKE_RESPONSE: &str = "SiTerm v1.0";
+/// Major protocol version; hosts and firmware only talk to each other when these match. Bump it
+/// with any change to what goes over the wire, such as the frame CRC (version 2) and the I2C bus
+/// byte (version 3).
+pub const PROTOCOL_VERSION_MAJOR: u8 = 3;
+/// Start of every handshake command. Sent bare by hosts that predate versioned handshakes.
+pub const HANDSHAKE_COMMAND_PREFIX: &str = "SiTerm?";
+pub const HANDSHAKE_COMMAND: &str = "SiTerm?v3";
+pub const HANDSHAKE_RESPONSE: &str = "SiTerm v3.0";
+/// Firmware reply to a handshake from a host with a different major version. It stays the same
+/// across versions so a host of any version recognises it.
+pub const HANDSHAKE_INCOMPATIBLE: &str = "SiTerm incompatible v1";
 pub const HANDSHAKE_DELIMITER: &str = "\n";
+/// Default time either side waits on the other during a handshake. A host should never wait less
+/// than the device does, or it gives up before the device's own timeout error can arrive.
 pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(3);
 
+/// Whole milliseconds in `duration`, keeping any part of a second, for clocks counted in `u64`
+/// milliseconds such as the firmware's. Saturates instead of wrapping.
+pub const fn duration_millis(duration: Duration) -> u64 {
+    let millis = duration.as_millis();
+    if millis > u64::MAX as u128 {
+        u64::MAX
+    } else {
+        millis as u64
+    }
+}
+
+/// Number of I2C buses the firmware exposes; bus indices start at zero.
+pub const I2C_BUS_COUNT: u8 = 2;
+/// Index of the bus targeted by I2C commands that don't name one.
+pub const DEFAULT_I2C_BUS: u8 = I2cBus::DEFAULT.index();
+
+/// An I2C bus the firmware has. Decoded commands carry one, so a handler never sees an index it
+/// can't serve.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum I2cBus {
+    I2c0 = 0,
+    I2c1 = 1,
+}
+
+impl I2cBus {
+    /// Every bus, in index order.
+    pub const ALL: [I2cBus; I2C_BUS_COUNT as usize] = [I2cBus::I2c0, I2cBus::I2c1];
+    /// Bus targeted by I2C commands that don't name one.
+    pub const DEFAULT: Self = Self::I2c1;
+
+    pub const fn from_index(index: u8) -> Option<Self> {
+        match index {
+            0 => Some(Self::I2c0),
+            1 => Some(Self::I2c1),
+            _ => None,
+        }
+    }
+
+    /// Index of the bus on the wire and in `--bus`.
+    pub const fn index(self) -> u8 {
+        self as u8
+    }
+}
+/// Highest I2C device address. Addresses are 7-bit everywhere in the protocol, as on the bus
+/// before the read/write bit is appended; a datasheet's 8-bit address (such as `0x90`) is written
+/// shifted right by one (`0x48`).
+pub const MAX_I2C_ADDRESS: u8 = 0x7F;
+/// Longest encoded command the firmware accepts in one frame. A longer one is sent as chunks (see
+/// [`chunk`]), up to [`chunk::MAX_CHUNKED_COMMAND_LEN`].
+pub const MAX_COMMAND_LEN: usize = 256;
+/// Longest `echo` payload the firmware takes. The echo comes back in a single response, so a
+/// chunked command can't carry a longer one.
+pub const MAX_ECHO_LEN: usize = response::MAX_RESPONSE_LEN;
+/// Longest I2C read the firmware answers in a single response.
+pub const MAX_I2C_READ_LEN: u8 = u8::MAX;
+/// Most registers one `i2c readm` reads. Each takes two bytes of the response, which leaves room
+/// for an error message in the firmware's response buffer.
+pub const MAX_I2C_READ_MULTI: u8 = 32;
+/// Number of SPI chip select lines the firmware drives; see [`spi`].
+pub const SPI_CS_COUNT: u8 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 #[repr(u8)]
 pub enum Method {
     Echo = 0x01,
@@ -68,6 +427,24 @@ pub enum Method {
     Spi = 0x03,
     Uart = 0x04,
     Pwm = 0x05,
+    /// RP2040 internal temperature sensor; see [`temperature`].
+    Temp = 0x06,
+    /// Idle heartbeats from the firmware; see [`heartbeat`].
+    Heartbeat = 0x07,
+    /// Settings changed at runtime; see [`device_info::DeviceConfig`].
+    Config = 0x08,
+    /// Flash the status LED to find the board; see [`identify`].
+    Identify = 0x09,
+    /// USB error counts; see [`stats`].
+    Stats = 0x0A,
+    /// How the host wants responses shown; see [`response::ResponseFormat`].
+    Format = 0x0B,
+    /// Bring-up checks of the board's peripherals; see [`self_test`].
+    SelfTest = 0x0C,
+    /// Why the board last reset; see [`reset_reason`].
+    ResetReason = 0x0D,
+    /// What the device can do and how it is set up; see [`device_info::DeviceInfo`].
+    Info = 0x0E,
 }
 
 impl TryFrom<&str> for Method {
@@ -83,6 +460,24 @@ impl TryFrom<&str> for Method {
             Ok(Self::Uart)
         } else if value.eq_ignore_ascii_case("pwm") {
             Ok(Self::Pwm)
+        } else if value.eq_ignore_ascii_case("temp") {
+            Ok(Self::Temp)
+        } else if value.eq_ignore_ascii_case("heartbeat") {
+            Ok(Self::Heartbeat)
+        } else if value.eq_ignore_ascii_case("config") {
+            Ok(Self::Config)
+        } else if value.eq_ignore_ascii_case("identify") {
+            Ok(Self::Identify)
+        } else if value.eq_ignore_ascii_case("stats") {
+            Ok(Self::Stats)
+        } else if value.eq_ignore_ascii_case("format") {
+            Ok(Self::Format)
+        } else if value.eq_ignore_ascii_case("selftest") {
+            Ok(Self::SelfTest)
+        } else if value.eq_ignore_ascii_case("resetreason") {
+            Ok(Self::ResetReason)
+        } else if value.eq_ignore_ascii_case("info") {
+            Ok(Self::Info)
         } else {
             Err(())
         }
@@ -101,9 +496,38 @@ impl Method {
             x if x == Self::Spi as u8 => Some(Self::Spi),
             x if x == Self::Uart as u8 => Some(Self::Uart),
             x if x == Self::Pwm as u8 => Some(Self::Pwm),
+            x if x == Self::Temp as u8 => Some(Self::Temp),
+            x if x == Self::Heartbeat as u8 => Some(Self::Heartbeat),
+            x if x == Self::Config as u8 => Some(Self::Config),
+            x if x == Self::Identify as u8 => Some(Self::Identify),
+            x if x == Self::Stats as u8 => Some(Self::Stats),
+            x if x == Self::Format as u8 => Some(Self::Format),
+            x if x == Self::SelfTest as u8 => Some(Self::SelfTest),
+            x if x == Self::ResetReason as u8 => Some(Self::ResetReason),
+            x if x == Self::Info as u8 => Some(Self::Info),
             _ => None,
         }
     }
+
+    /// Keyword that selects this method in the host command grammar.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Echo => "echo",
+            Self::I2c => "i2c",
+            Self::Spi => "spi",
+            Self::Uart => "uart",
+            Self::Pwm => "pwm",
+            Self::Temp => "temp",
+            Self::Heartbeat => "heartbeat",
+            Self::Config => "config",
+            Self::Identify => "identify",
+            Self::Stats => "stats",
+            Self::Format => "format",
+            Self::SelfTest => "selftest",
+            Self::ResetReason => "resetreason",
+            Self::Info => "info",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -111,6 +535,14 @@ impl Method {
 pub enum Operation {
     Read = 0x01,
     Write = 0x02,
+    /// Hand the link over to a raw pipe; see [`bridge`].
+    Bridge = 0x03,
+    /// Change how a peripheral is set up, e.g. the SPI mode; see [`spi`].
+    Config = 0x04,
+    /// Read one byte from each of several registers in one command.
+    ReadMulti = 0x05,
+    /// Put settings back to how the firmware starts, e.g. `config reset`.
+    Reset = 0x06,
 }
 
 impl TryFrom<&str> for Operation {
@@ -120,6 +552,14 @@ impl TryFrom<&str> for Operation {
             Ok(Self::Read)
         } else if value.eq_ignore_ascii_case("w") || value.eq_ignore_ascii_case("write") {
             Ok(Self::Write)
+        } else if value.eq_ignore_ascii_case("bridge") {
+            Ok(Self::Bridge)
+        } else if value.eq_ignore_ascii_case("config") {
+            Ok(Self::Config)
+        } else if value.eq_ignore_ascii_case("readm") {
+            Ok(Self::ReadMulti)
+        } else if value.eq_ignore_ascii_case("reset") {
+            Ok(Self::Reset)
         } else {
             Err(())
         }
@@ -135,29 +575,296 @@ impl Operation {
         match byte {
             x if x == Self::Read as u8 => Some(Self::Read),
             x if x == Self::Write as u8 => Some(Self::Write),
+            x if x == Self::Bridge as u8 => Some(Self::Bridge),
+            x if x == Self::Config as u8 => Some(Self::Config),
+            x if x == Self::ReadMulti as u8 => Some(Self::ReadMulti),
+            x if x == Self::Reset as u8 => Some(Self::Reset),
             _ => None,
         }
     }
 }
 
+/// Kind of value a command argument takes, as typed on the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgumentKind {
+    /// A number from 0 to 255, written in decimal, hex (`0x`) or binary (`0b`).
+    U8,
+    /// A number from 0 to 65535.
+    U16,
+    /// A number from 0 to `u32::MAX`.
+    U32,
+    /// One of these words, in any case.
+    Keyword(&'static [&'static str]),
+    /// The rest of the line, sent as typed.
+    Text,
+}
+
+/// One argument of a command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Argument {
+    pub name: &'static str,
+    pub kind: ArgumentKind,
+}
+
+impl Argument {
+    const fn new(name: &'static str, kind: ArgumentKind) -> Self {
+        Self { name, kind }
+    }
+}
+
+/// The shape of a command as typed on the host: what it is called and the arguments it takes.
+///
+/// Counts exclude the command's own keywords and any `--bus` flag. When `max_args` is larger
+/// than `arguments.len()`, the last argument repeats to fill the difference. Some commands narrow
+/// these further; `heartbeat off`, for one, takes no interval.
 #[derive(Debug)]
 pub struct CommandDefinition {
     pub method: Method,
     pub operation: Operation,
+    /// Keywords that name the command, e.g. `i2c read`, or the method alone, e.g. `temp`, when the
+    /// method has no other operations to tell apart.
+    pub name: &'static str,
+    /// Whether the arguments may start with `--bus <index>`.
+    pub bus_flag: bool,
+    pub arguments: &'static [Argument],
+    pub min_args: usize,
+    pub max_args: usize,
 }
 
+impl CommandDefinition {
+    /// The definition of `method` with `operation`, if the host can send it.
+    pub fn find(method: Method, operation: Operation) -> Option<&'static CommandDefinition> {
+        COMMAND_DICTIONARY
+            .iter()
+            .find(|def| def.method == method && def.operation == operation)
+    }
+
+    /// The definition named by `method` alone, for commands that take no operation keyword.
+    pub fn find_by_method(method: Method) -> Option<&'static CommandDefinition> {
+        COMMAND_DICTIONARY
+            .iter()
+            .find(|def| def.method == method && def.keyword_count() == 1)
+    }
+
+    /// How many tokens of the line name the command; its arguments start after them.
+    pub fn keyword_count(&self) -> usize {
+        self.name.split_ascii_whitespace().count()
+    }
+}
+
+/// Writes the usage line, e.g. `i2c write [--bus <index>] <address> <register> <byte>...`.
+impl fmt::Display for CommandDefinition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name)?;
+        if self.bus_flag {
+            f.write_str(" [--bus <index>]")?;
+        }
+        for (index, argument) in self.arguments.iter().enumerate() {
+            let optional = index >= self.min_args;
+            f.write_str(if optional { " [<" } else { " <" })?;
+            match argument.kind {
+                ArgumentKind::Keyword(words) => {
+                    for (position, word) in words.iter().enumerate() {
+                        if position > 0 {
+                            f.write_str("|")?;
+                        }
+                        f.write_str(word)?;
+                    }
+                }
+                _ => f.write_str(argument.name)?,
+            }
+            f.write_str(if optional { ">]" } else { ">" })?;
+        }
+        if self.max_args > self.arguments.len() {
+            f.write_str("...")?;
+        }
+        Ok(())
+    }
+}
+
+const ADDRESS: Argument = Argument::new("address", ArgumentKind::U8);
+const REGISTER: Argument = Argument::new("register", ArgumentKind::U8);
+const LENGTH: Argument = Argument::new("length", ArgumentKind::U8);
+const BYTE: Argument = Argument::new("byte", ArgumentKind::U8);
+const CHIP_SELECT: Argument = Argument::new("cs", ArgumentKind::U8);
+/// Most bytes one write carries, as its count goes out in a single byte.
+const MAX_WRITE_ARGS: usize = 2 + u8::MAX as usize;
+
+/// Every command the host can send, keyed by `(method, operation)`.
 pub const COMMAND_DICTIONARY: &[CommandDefinition] = &[
     CommandDefinition {
         method: Method::Echo,
         operation: Operation::Write,
+        name: "echo",
+        bus_flag: false,
+        arguments: &[Argument::new("text", ArgumentKind::Text)],
+        min_args: 0,
+        max_args: 1,
     },
     CommandDefinition {
         method: Method::I2c,
         operation: Operation::Read,
+        name: "i2c read",
+        bus_flag: true,
+        arguments: &[ADDRESS, REGISTER, LENGTH],
+        min_args: 3,
+        max_args: 3,
+    },
+    CommandDefinition {
+        method: Method::I2c,
+        operation: Operation::Write,
+        name: "i2c write",
+        bus_flag: true,
+        arguments: &[ADDRESS, REGISTER, BYTE],
+        min_args: 3,
+        max_args: MAX_WRITE_ARGS,
     },
     CommandDefinition {
         method: Method::I2c,
+        operation: Operation::ReadMulti,
+        name: "i2c readm",
+        bus_flag: true,
+        arguments: &[ADDRESS, REGISTER],
+        min_args: 2,
+        max_args: 1 + MAX_I2C_READ_MULTI as usize,
+    },
+    CommandDefinition {
+        method: Method::Spi,
+        operation: Operation::Read,
+        name: "spi read",
+        bus_flag: false,
+        arguments: &[CHIP_SELECT, REGISTER, LENGTH],
+        min_args: 3,
+        max_args: 3,
+    },
+    CommandDefinition {
+        method: Method::Spi,
         operation: Operation::Write,
+        name: "spi write",
+        bus_flag: false,
+        arguments: &[CHIP_SELECT, REGISTER, BYTE],
+        min_args: 3,
+        max_args: MAX_WRITE_ARGS,
+    },
+    CommandDefinition {
+        method: Method::Spi,
+        operation: Operation::Config,
+        name: "spi config",
+        bus_flag: false,
+        arguments: &[
+            Argument::new(
+                "mode",
+                ArgumentKind::Keyword(&["mode0", "mode1", "mode2", "mode3"]),
+            ),
+            Argument::new("khz", ArgumentKind::U16),
+        ],
+        min_args: 2,
+        max_args: 2,
+    },
+    CommandDefinition {
+        method: Method::Temp,
+        operation: Operation::Read,
+        name: "temp",
+        bus_flag: false,
+        arguments: &[],
+        min_args: 0,
+        max_args: 0,
+    },
+    CommandDefinition {
+        method: Method::Uart,
+        operation: Operation::Bridge,
+        name: "uart bridge",
+        bus_flag: false,
+        arguments: &[Argument::new("baud", ArgumentKind::U32)],
+        min_args: 1,
+        max_args: 1,
+    },
+    CommandDefinition {
+        method: Method::Heartbeat,
+        operation: Operation::Write,
+        name: "heartbeat",
+        bus_flag: false,
+        arguments: &[
+            Argument::new("switch", ArgumentKind::Keyword(&["on", "off"])),
+            Argument::new("ms", ArgumentKind::U16),
+        ],
+        min_args: 1,
+        max_args: 2,
+    },
+    CommandDefinition {
+        method: Method::Config,
+        operation: Operation::Read,
+        name: "config",
+        bus_flag: false,
+        arguments: &[],
+        min_args: 0,
+        max_args: 0,
+    },
+    CommandDefinition {
+        method: Method::Config,
+        operation: Operation::Reset,
+        name: "config reset",
+        bus_flag: false,
+        arguments: &[],
+        min_args: 0,
+        max_args: 0,
+    },
+    CommandDefinition {
+        method: Method::Identify,
+        operation: Operation::Write,
+        name: "identify",
+        bus_flag: false,
+        arguments: &[],
+        min_args: 0,
+        max_args: 0,
+    },
+    CommandDefinition {
+        method: Method::Stats,
+        operation: Operation::Read,
+        name: "stats",
+        bus_flag: false,
+        arguments: &[],
+        min_args: 0,
+        max_args: 0,
+    },
+    CommandDefinition {
+        method: Method::Format,
+        operation: Operation::Write,
+        name: "format",
+        bus_flag: false,
+        arguments: &[Argument::new(
+            "format",
+            ArgumentKind::Keyword(&["auto", "text", "bytes"]),
+        )],
+        min_args: 1,
+        max_args: 1,
+    },
+    CommandDefinition {
+        method: Method::SelfTest,
+        operation: Operation::Read,
+        name: "selftest",
+        bus_flag: false,
+        arguments: &[],
+        min_args: 0,
+        max_args: 0,
+    },
+    CommandDefinition {
+        method: Method::ResetReason,
+        operation: Operation::Read,
+        name: "resetreason",
+        bus_flag: false,
+        arguments: &[],
+        min_args: 0,
+        max_args: 0,
+    },
+    CommandDefinition {
+        method: Method::Info,
+        operation: Operation::Read,
+        name: "info",
+        bus_flag: false,
+        arguments: &[],
+        min_args: 0,
+        max_args: 0,
     },
 ];
 
@@ -174,23 +881,239 @@ pub enum ProtocolError {
         method: Method,
         operation: Operation,
     },
+    /// An I2C command named a bus index at or beyond [`I2C_BUS_COUNT`].
+    UnknownBus(u8),
+    /// An I2C command named an address above [`MAX_I2C_ADDRESS`].
+    InvalidAddress(u8),
+    /// An SPI command named a chip select at or beyond [`SPI_CS_COUNT`].
+    UnknownChipSelect(u8),
 }
 
+/// Decoded command. Wire layouts (after the method and operation bytes):
+///
+/// - `EchoWrite`: `[payload...]`
+/// - In the I2C commands `address` is 7-bit, at most [`MAX_I2C_ADDRESS`].
+/// - `I2cRead`: `[bus, address, register, length]`
+/// - `I2cWrite`: `[bus, address, register, count, b0, b1, ...]` where `count` must equal the number
+///   of payload bytes that follow.
+/// - `I2cReadMulti`: `[bus, address, count, r0, r1, ...]` where `count` must equal the number of
+///   registers that follow and be 1 to [`MAX_I2C_READ_MULTI`].
+/// - `SpiRead`: `[cs, register, length]`
+/// - `SpiWrite`: `[cs, register, count, b0, b1, ...]`, laid out like `I2cWrite`.
+/// - `SpiConfig`: `[mode, freq_khz (u16 BE)]`
+/// - `Temperature`: `[]`
+/// - `UartBridge`: `[baud (u32 BE)]`
+/// - `Heartbeat`: `[enable (0 or 1), interval_ms (u16 BE)]`
+/// - `GetConfig`: `[]`
+/// - `ResetConfig`: `[]`
+/// - `Identify`: `[]`
+/// - `Stats`: `[]`
+/// - `SetResponseFormat`: `[format]`, see [`response::ResponseFormat::as_byte`]
+/// - `SelfTest`: `[]`
+/// - `ResetReason`: `[]`
+/// - `Info`: `[]`
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Command<'a> {
     EchoWrite {
         payload: &'a [u8],
     },
     I2cRead {
+        bus: I2cBus,
         address: u8,
         register: u8,
         length: u8,
     },
     I2cWrite {
+        bus: I2cBus,
         address: u8,
         register: u8,
         payload: &'a [u8],
     },
+    /// Read one byte from each of `registers`, each in its own transaction. The response is a
+    /// `[register, value]` pair per register, in the order asked; see [`i2c_read_multi_pairs`].
+    I2cReadMulti {
+        bus: I2cBus,
+        address: u8,
+        registers: &'a [u8],
+    },
+    /// Send `register` and read `length` bytes back with chip select `cs` held; see [`spi`].
+    SpiRead {
+        cs: u8,
+        register: u8,
+        length: u8,
+    },
+    /// Send `register` followed by `payload` with chip select `cs` held; see [`spi`].
+    SpiWrite {
+        cs: u8,
+        register: u8,
+        payload: &'a [u8],
+    },
+    /// Reconfigure the SPI bus for SPI `mode` (0-3) at `freq_khz`. The firmware checks both; see
+    /// [`spi::config_error`].
+    SpiConfig {
+        mode: u8,
+        freq_khz: u16,
+    },
+    /// Read the internal temperature sensor. The response is a [`temperature`] reading.
+    Temperature,
+    /// Bridge the hardware UART to the host at `baud`; see [`bridge`].
+    UartBridge {
+        baud: u32,
+    },
+    /// Start or stop idle heartbeats every `interval_ms`; see [`heartbeat`].
+    Heartbeat {
+        interval_ms: u16,
+        enable: bool,
+    },
+    /// Read back every runtime setting. The response is a [`device_info::DeviceConfig`].
+    GetConfig,
+    /// Put every runtime setting back to [`device_info::DeviceConfig::DEFAULT`], leaving the
+    /// session itself alone. The response is the [`device_info::DeviceConfig`] now in effect.
+    ResetConfig,
+    /// Flash the status LED for [`identify::IDENTIFY_DURATION_MS`].
+    Identify,
+    /// Read back the USB error counts. The response is a [`stats::Stats`] as text.
+    Stats,
+    /// Tag the session's handler responses with how the host should show them; see [`response`].
+    SetResponseFormat {
+        format: response::ResponseFormat,
+    },
+    /// Run the bring-up checks. The response is a [`self_test::SelfTestReport`] as text.
+    SelfTest,
+    /// Report why the board last reset. The response is a [`reset_reason::ResetReason`] as text.
+    ResetReason,
+    /// Report what the device can do and the settings it runs with. The response is an encoded
+    /// [`device_info::DeviceInfo`].
+    Info,
+}
+
+impl Command<'_> {
+    /// Method whose handler runs this command.
+    pub const fn method(&self) -> Method {
+        match self {
+            Command::EchoWrite { .. } => Method::Echo,
+            Command::I2cRead { .. } | Command::I2cWrite { .. } | Command::I2cReadMulti { .. } => {
+                Method::I2c
+            }
+            Command::SpiRead { .. } | Command::SpiWrite { .. } | Command::SpiConfig { .. } => {
+                Method::Spi
+            }
+            Command::Temperature => Method::Temp,
+            Command::UartBridge { .. } => Method::Uart,
+            Command::Heartbeat { .. } => Method::Heartbeat,
+            Command::GetConfig | Command::ResetConfig => Method::Config,
+            Command::Identify => Method::Identify,
+            Command::Stats => Method::Stats,
+            Command::SetResponseFormat { .. } => Method::Format,
+            Command::SelfTest => Method::SelfTest,
+            Command::ResetReason => Method::ResetReason,
+            Command::Info => Method::Info,
+        }
+    }
+}
+
+/// Renders commands in the host command grammar so the output can be fed back into
+/// `host::encode_command`. Echo payloads that are not valid UTF-8 fall back to `\xNN` escapes, and
+/// echo payloads containing a whitespace-led `#` read back as a comment; neither form round-trips.
+impl fmt::Display for Command<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Command::EchoWrite { payload } => {
+                f.write_str("echo")?;
+                if payload.is_empty() {
+                    return Ok(());
+                }
+                f.write_str(" ")?;
+                match core::str::from_utf8(payload) {
+                    Ok(text) => f.write_str(text),
+                    Err(_) => payload.iter().try_for_each(|byte| {
+                        if byte.is_ascii() && !byte.is_ascii_control() {
+                            write!(f, "{}", *byte as char)
+                        } else {
+                            write!(f, "\\x{:02X}", byte)
+                        }
+                    }),
+                }
+            }
+            Command::I2cRead {
+                bus,
+                address,
+                register,
+                length,
+            } => {
+                f.write_str("i2c read")?;
+                write_bus_flag(f, *bus)?;
+                write!(f, " {:#04x} {:#04x} {}", address, register, length)
+            }
+            Command::I2cWrite {
+                bus,
+                address,
+                register,
+                payload,
+            } => {
+                f.write_str("i2c write")?;
+                write_bus_flag(f, *bus)?;
+                write!(f, " {:#04x} {:#04x}", address, register)?;
+                payload
+                    .iter()
+                    .try_for_each(|byte| write!(f, " {:#04x}", byte))
+            }
+            Command::I2cReadMulti {
+                bus,
+                address,
+                registers,
+            } => {
+                f.write_str("i2c readm")?;
+                write_bus_flag(f, *bus)?;
+                write!(f, " {:#04x}", address)?;
+                registers
+                    .iter()
+                    .try_for_each(|register| write!(f, " {:#04x}", register))
+            }
+            Command::SpiRead {
+                cs,
+                register,
+                length,
+            } => write!(f, "spi read {} {:#04x} {}", cs, register, length),
+            Command::SpiWrite {
+                cs,
+                register,
+                payload,
+            } => {
+                write!(f, "spi write {} {:#04x}", cs, register)?;
+                payload
+                    .iter()
+                    .try_for_each(|byte| write!(f, " {:#04x}", byte))
+            }
+            Command::SpiConfig { mode, freq_khz } => {
+                write!(f, "spi config mode{} {}", mode, freq_khz)
+            }
+            Command::Temperature => f.write_str("temp"),
+            Command::UartBridge { baud } => write!(f, "uart bridge {baud}"),
+            Command::Heartbeat {
+                interval_ms,
+                enable: true,
+            } => write!(f, "heartbeat on {interval_ms}"),
+            Command::Heartbeat { enable: false, .. } => f.write_str("heartbeat off"),
+            Command::GetConfig => f.write_str("config"),
+            Command::ResetConfig => f.write_str("config reset"),
+            Command::Identify => f.write_str("identify"),
+            Command::Stats => f.write_str("stats"),
+            Command::SetResponseFormat { format } => write!(f, "format {}", format.name()),
+            Command::SelfTest => f.write_str("selftest"),
+            Command::ResetReason => f.write_str("resetreason"),
+            Command::Info => f.write_str("info"),
+        }
+    }
+}
+
+/// The default bus is left implicit so rendered commands stay in the short form.
+fn write_bus_flag(f: &mut fmt::Formatter<'_>, bus: I2cBus) -> fmt::Result {
+    if bus == I2cBus::DEFAULT {
+        Ok(())
+    } else {
+        write!(f, " --bus {}", bus.index())
+    }
 }
 
 pub fn decode_command(buffer: &[u8]) -> Result<Command<'_>, ProtocolError> {
@@ -201,52 +1124,293 @@ pub fn decode_command(buffer: &[u8]) -> Result<Command<'_>, ProtocolError> {
     let operation = Operation::from_byte(operation_byte)
         .ok_or(ProtocolError::UnknownOperation(operation_byte))?;
 
+    // Fixed-size fields are taken with `split_first_chunk` so a short payload can't be indexed.
+    let malformed = ProtocolError::MalformedPayload { method, operation };
     match (method, operation) {
         (Method::Echo, Operation::Write) => Ok(Command::EchoWrite { payload }),
         (Method::I2c, Operation::Read) => {
-            if payload.len() < 3 {
-                return Err(ProtocolError::MalformedPayload { method, operation });
-            }
-
-            let address = payload[0];
-            let register = payload[1];
-            let length = payload[2];
+            let (&[bus, address, register, length], _) =
+                payload.split_first_chunk::<4>().ok_or(malformed)?;
 
             Ok(Command::I2cRead {
-                address,
+                bus: decode_bus(bus)?,
+                address: decode_address(address)?,
                 register,
                 length,
             })
         }
         (Method::I2c, Operation::Write) => {
-            if payload.len() < 3 {
-                return Err(ProtocolError::MalformedPayload { method, operation });
-            }
-            let address = payload[0];
-            let register = payload[1];
-            let length = payload[2] as usize;
+            let (&[bus, address, register, length], data) =
+                payload.split_first_chunk::<4>().ok_or(malformed)?;
+            let bus = decode_bus(bus)?;
+            let address = decode_address(address)?;
 
-            if payload.len() != 3 + length {
-                return Err(ProtocolError::MalformedPayload { method, operation });
+            if data.len() != usize::from(length) {
+                return Err(malformed);
             }
 
             Ok(Command::I2cWrite {
+                bus,
                 address,
                 register,
-                payload: &payload[3..],
+                payload: data,
+            })
+        }
+        (Method::I2c, Operation::ReadMulti) => {
+            let (&[bus, address, count], registers) =
+                payload.split_first_chunk::<3>().ok_or(malformed)?;
+            let bus = decode_bus(bus)?;
+            let address = decode_address(address)?;
+
+            if count == 0 || count > MAX_I2C_READ_MULTI || registers.len() != usize::from(count) {
+                return Err(malformed);
+            }
+
+            Ok(Command::I2cReadMulti {
+                bus,
+                address,
+                registers,
             })
         }
+        (Method::Spi, Operation::Read) => {
+            let &[cs, register, length] = payload else {
+                return Err(malformed);
+            };
+            Ok(Command::SpiRead {
+                cs: decode_chip_select(cs)?,
+                register,
+                length,
+            })
+        }
+        (Method::Spi, Operation::Write) => {
+            let (&[cs, register, length], data) =
+                payload.split_first_chunk::<3>().ok_or(malformed)?;
+            let cs = decode_chip_select(cs)?;
+
+            if data.len() != usize::from(length) {
+                return Err(malformed);
+            }
+
+            Ok(Command::SpiWrite {
+                cs,
+                register,
+                payload: data,
+            })
+        }
+        (Method::Spi, Operation::Config) => {
+            let &[mode, hi, lo] = payload else {
+                return Err(malformed);
+            };
+            Ok(Command::SpiConfig {
+                mode,
+                freq_khz: u16::from_be_bytes([hi, lo]),
+            })
+        }
+        (Method::Temp, Operation::Read) => {
+            if !payload.is_empty() {
+                return Err(malformed);
+            }
+            Ok(Command::Temperature)
+        }
+        (Method::Uart, Operation::Bridge) => {
+            let baud: [u8; 4] = payload.try_into().map_err(|_| malformed)?;
+            Ok(Command::UartBridge {
+                baud: u32::from_be_bytes(baud),
+            })
+        }
+        (Method::Heartbeat, Operation::Write) => {
+            let &[enable, hi, lo] = payload else {
+                return Err(malformed);
+            };
+            let enable = match enable {
+                0 => false,
+                1 => true,
+                _ => return Err(malformed),
+            };
+            Ok(Command::Heartbeat {
+                interval_ms: u16::from_be_bytes([hi, lo]),
+                enable,
+            })
+        }
+        (Method::Config, Operation::Read) => {
+            if !payload.is_empty() {
+                return Err(malformed);
+            }
+            Ok(Command::GetConfig)
+        }
+        (Method::Config, Operation::Reset) => {
+            if !payload.is_empty() {
+                return Err(malformed);
+            }
+            Ok(Command::ResetConfig)
+        }
+        (Method::Identify, Operation::Write) => {
+            if !payload.is_empty() {
+                return Err(malformed);
+            }
+            Ok(Command::Identify)
+        }
+        (Method::Stats, Operation::Read) => {
+            if !payload.is_empty() {
+                return Err(malformed);
+            }
+            Ok(Command::Stats)
+        }
+        (Method::Format, Operation::Write) => {
+            let &[format] = payload else {
+                return Err(malformed);
+            };
+            let format = response::ResponseFormat::from_byte(format).ok_or(malformed)?;
+            Ok(Command::SetResponseFormat { format })
+        }
+        (Method::SelfTest, Operation::Read) => {
+            if !payload.is_empty() {
+                return Err(malformed);
+            }
+            Ok(Command::SelfTest)
+        }
+        (Method::ResetReason, Operation::Read) => {
+            if !payload.is_empty() {
+                return Err(malformed);
+            }
+            Ok(Command::ResetReason)
+        }
+        (Method::Info, Operation::Read) => {
+            if !payload.is_empty() {
+                return Err(malformed);
+            }
+            Ok(Command::Info)
+        }
         _ => Err(ProtocolError::UnsupportedOperation { method, operation }),
     }
 }
 
+/// Split an `i2c readm` response into its `(register, value)` pairs, or `None` if it isn't made of
+/// whole pairs.
+pub fn i2c_read_multi_pairs(response: &[u8]) -> Option<impl Iterator<Item = (u8, u8)> + '_> {
+    let (pairs, rest) = response.as_chunks::<2>();
+    if pairs.is_empty() || !rest.is_empty() {
+        return None;
+    }
+    Some(pairs.iter().map(|&[register, value]| (register, value)))
+}
+
+fn decode_bus(bus: u8) -> Result<I2cBus, ProtocolError> {
+    I2cBus::from_index(bus).ok_or(ProtocolError::UnknownBus(bus))
+}
+
+fn decode_address(address: u8) -> Result<u8, ProtocolError> {
+    if address <= MAX_I2C_ADDRESS {
+        Ok(address)
+    } else {
+        Err(ProtocolError::InvalidAddress(address))
+    }
+}
+
+fn decode_chip_select(cs: u8) -> Result<u8, ProtocolError> {
+    if cs < SPI_CS_COUNT {
+        Ok(cs)
+    } else {
+        Err(ProtocolError::UnknownChipSelect(cs))
+    }
+}
+
+pub mod batch;
+pub mod bridge;
+pub mod chunk;
+pub mod device_info;
+pub mod flow;
+pub mod handshake;
+pub mod heartbeat;
 #[cfg(feature = "alloc")]
 pub mod host;
+pub mod identify;
+pub mod nak;
+pub mod reset_reason;
+pub mod response;
+pub mod resync;
+pub mod self_test;
+pub mod spi;
+pub mod stats;
+pub mod temperature;
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn durations_convert_to_milliseconds_without_losing_part_seconds() {
+        assert_eq!(duration_millis(Duration::from_millis(2_500)), 2_500);
+        assert_eq!(duration_millis(HANDSHAKE_TIMEOUT), 3_000);
+        assert_eq!(duration_millis(Duration::from_micros(1_999)), 1);
+        assert_eq!(duration_millis(Duration::MAX), u64::MAX);
+    }
+
+    #[test]
+    fn usage_lines_come_from_the_schema() {
+        let usage = |method, operation| {
+            CommandDefinition::find(method, operation)
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(
+            usage(Method::I2c, Operation::Write),
+            "i2c write [--bus <index>] <address> <register> <byte>..."
+        );
+        assert_eq!(
+            usage(Method::Spi, Operation::Config),
+            "spi config <mode0|mode1|mode2|mode3> <khz>"
+        );
+        assert_eq!(
+            usage(Method::Heartbeat, Operation::Write),
+            "heartbeat <on|off> [<ms>]"
+        );
+        assert_eq!(usage(Method::Echo, Operation::Write), "echo [<text>]");
+        assert_eq!(usage(Method::Temp, Operation::Read), "temp");
+        assert_eq!(
+            usage(Method::I2c, Operation::ReadMulti),
+            "i2c readm [--bus <index>] <address> <register>..."
+        );
+    }
+
+    #[test]
+    fn schema_names_match_their_keywords() {
+        for def in COMMAND_DICTIONARY {
+            let mut words = def.name.split_ascii_whitespace();
+            assert_eq!(Method::try_from(words.next().unwrap()), Ok(def.method));
+            match words.next() {
+                Some(operation) => assert_eq!(Operation::try_from(operation), Ok(def.operation)),
+                None => assert_eq!(
+                    CommandDefinition::find_by_method(def.method).map(|d| d.operation),
+                    Some(def.operation)
+                ),
+            }
+            assert!(def.min_args <= def.max_args, "{}", def.name);
+            assert!(def.arguments.len() <= def.max_args, "{}", def.name);
+        }
+    }
+
+    #[test]
+    fn decoder_supports_exactly_the_dictionary() {
+        let methods = (0..=u8::MAX).filter_map(Method::from_byte);
+        for method in methods {
+            for operation in (0..=u8::MAX).filter_map(Operation::from_byte) {
+                let mut buffer = [0u8; 16];
+                buffer[0] = method.as_byte();
+                buffer[1] = operation.as_byte();
+                let supported = !matches!(
+                    decode_command(&buffer),
+                    Err(ProtocolError::UnsupportedOperation { .. })
+                );
+                assert_eq!(
+                    supported,
+                    CommandDefinition::find(method, operation).is_some(),
+                    "{method:?} {operation:?}"
+                );
+            }
+        }
+    }
+
     #[test]
     fn decode_echo() {
         let payload = [
@@ -270,7 +1434,8 @@ mod tests {
         let payload = [
             Method::I2c.as_byte(),
             Operation::Read.as_byte(),
-            0x80,
+            DEFAULT_I2C_BUS,
+            0x40,
             0x11,
             0x04,
         ];
@@ -278,11 +1443,13 @@ mod tests {
 
         match command {
             Command::I2cRead {
+                bus,
                 address,
                 register,
                 length,
             } => {
-                assert_eq!(address, 0x80);
+                assert_eq!(bus, I2cBus::DEFAULT);
+                assert_eq!(address, 0x40);
                 assert_eq!(register, 0x11);
                 assert_eq!(length, 0x04);
             }
@@ -295,6 +1462,7 @@ mod tests {
         let payload = [
             Method::I2c.as_byte(),
             Operation::Write.as_byte(),
+            0x00,
             0x50,
             0x20,
             0x02,
@@ -305,10 +1473,12 @@ mod tests {
 
         match command {
             Command::I2cWrite {
+                bus,
                 address,
                 register,
                 payload,
             } => {
+                assert_eq!(bus, I2cBus::I2c0);
                 assert_eq!(address, 0x50);
                 assert_eq!(register, 0x20);
                 assert_eq!(payload, &[0xAA, 0xBB]);
@@ -317,10 +1487,385 @@ mod tests {
         }
     }
 
+    #[test]
+    fn decode_i2c_read_multi() {
+        let payload = [
+            Method::I2c.as_byte(),
+            Operation::ReadMulti.as_byte(),
+            DEFAULT_I2C_BUS,
+            0x48,
+            0x03,
+            0x00,
+            0x01,
+            0x02,
+        ];
+        assert_eq!(
+            decode_command(&payload),
+            Ok(Command::I2cReadMulti {
+                bus: I2cBus::DEFAULT,
+                address: 0x48,
+                registers: &[0x00, 0x01, 0x02],
+            })
+        );
+
+        let malformed = Err(ProtocolError::MalformedPayload {
+            method: Method::I2c,
+            operation: Operation::ReadMulti,
+        });
+        // An empty register list, a count that disagrees with the list, and one over the limit.
+        assert_eq!(decode_command(&payload[..5]), malformed);
+        assert_eq!(decode_command(&payload[..7]), malformed);
+        let mut too_many = payload[..4].to_vec();
+        too_many.push(MAX_I2C_READ_MULTI + 1);
+        too_many.extend(0..=MAX_I2C_READ_MULTI);
+        assert_eq!(decode_command(&too_many), malformed);
+    }
+
+    #[test]
+    fn read_multi_responses_split_into_pairs() {
+        let pairs: Vec<_> = i2c_read_multi_pairs(&[0x00, 0x12, 0x05, 0x34])
+            .unwrap()
+            .collect();
+        assert_eq!(pairs, [(0x00, 0x12), (0x05, 0x34)]);
+        assert!(i2c_read_multi_pairs(&[0x00, 0x12, 0x05]).is_none());
+        assert!(i2c_read_multi_pairs(&[]).is_none());
+    }
+
+    #[test]
+    fn decode_rejects_out_of_range_bus() {
+        let read = [
+            Method::I2c.as_byte(),
+            Operation::Read.as_byte(),
+            I2C_BUS_COUNT,
+            0x40,
+            0x11,
+            0x04,
+        ];
+        assert_eq!(
+            decode_command(&read),
+            Err(ProtocolError::UnknownBus(I2C_BUS_COUNT))
+        );
+
+        let write = [
+            Method::I2c.as_byte(),
+            Operation::Write.as_byte(),
+            0xFF,
+            0x50,
+            0x20,
+            0x01,
+            0xAA,
+        ];
+        assert_eq!(decode_command(&write), Err(ProtocolError::UnknownBus(0xFF)));
+    }
+
+    #[test]
+    fn decode_takes_seven_bit_addresses_only() {
+        let read = |address| match decode_command(&[
+            Method::I2c.as_byte(),
+            Operation::Read.as_byte(),
+            DEFAULT_I2C_BUS,
+            address,
+            0x00,
+            0x01,
+        ]) {
+            Ok(Command::I2cRead { address, .. }) => Ok(address),
+            Ok(other) => panic!("decoded {other:?}"),
+            Err(err) => Err(err),
+        };
+        assert_eq!(read(MAX_I2C_ADDRESS), Ok(0x7F));
+        assert_eq!(read(0x80), Err(ProtocolError::InvalidAddress(0x80)));
+
+        let read_multi = [
+            Method::I2c.as_byte(),
+            Operation::ReadMulti.as_byte(),
+            DEFAULT_I2C_BUS,
+            0x90,
+            0x01,
+            0x00,
+        ];
+        assert_eq!(
+            decode_command(&read_multi),
+            Err(ProtocolError::InvalidAddress(0x90))
+        );
+    }
+
     #[test]
     fn decode_unknown_method() {
         let payload = [0xFF];
         let err = decode_command(&payload).unwrap_err();
         assert!(matches!(err, ProtocolError::UnknownMethod(0xFF)));
     }
+
+    #[test]
+    fn crc16_matches_ccitt_false_check_value() {
+        assert_eq!(transport::crc16(b"123456789"), 0x29B1);
+        assert_eq!(transport::crc16(b""), 0xFFFF);
+    }
+
+    fn encoded(payload: &[u8]) -> ([u8; 64], usize) {
+        let mut buffer = [0u8; 64];
+        let len = transport::encode_into(payload, &mut buffer).unwrap();
+        (buffer, len)
+    }
+
+    #[test]
+    fn frame_split_across_pushes_decodes_once() {
+        let (frame, len) = encoded(&[0x01, 0x02, 0x03, 0x04]);
+        let mut reader = transport::FrameReader::<64>::new();
+
+        reader.push(&frame[..len / 2]);
+        assert_eq!(reader.next_frame(), Ok(None));
+        reader.push(&frame[len / 2..len]);
+        assert_eq!(
+            reader.next_frame(),
+            Ok(Some([0x01, 0x02, 0x03, 0x04].as_slice()))
+        );
+        assert_eq!(reader.next_frame(), Ok(None));
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn frame_ending_on_push_boundary_leaves_next_frame_intact() {
+        let (first, first_len) = encoded(b"one");
+        let (second, second_len) = encoded(b"two");
+        let mut reader = transport::FrameReader::<64>::new();
+
+        reader.push(&first[..first_len]);
+        reader.push(&second[..1]);
+        assert_eq!(reader.next_frame(), Ok(Some(b"one".as_slice())));
+        assert_eq!(reader.next_frame(), Ok(None));
+        reader.push(&second[1..second_len]);
+        assert_eq!(reader.next_frame(), Ok(Some(b"two".as_slice())));
+        assert_eq!(reader.next_frame(), Ok(None));
+    }
+
+    #[test]
+    fn several_frames_in_one_push_drain_in_order() {
+        let (first, first_len) = encoded(b"a");
+        let (second, second_len) = encoded(b"b");
+        let mut stream = [0u8; 64];
+        stream[..first_len].copy_from_slice(&first[..first_len]);
+        stream[first_len..first_len + second_len].copy_from_slice(&second[..second_len]);
+
+        let mut reader = transport::FrameReader::<64>::new();
+        assert_eq!(
+            reader.push(&stream[..first_len + second_len]),
+            first_len + second_len
+        );
+        assert_eq!(reader.next_frame(), Ok(Some(b"a".as_slice())));
+        assert_eq!(reader.next_frame(), Ok(Some(b"b".as_slice())));
+        assert_eq!(reader.next_frame(), Ok(None));
+    }
+
+    #[test]
+    fn reader_keeps_unread_bytes_untouched() {
+        let (frame, len) = encoded(b"partial");
+        let mut reader = transport::FrameReader::<64>::new();
+        reader.push(&frame[..len - 1]);
+        assert_eq!(reader.next_frame(), Ok(None));
+        assert_eq!(reader.pending(), &frame[..len - 1]);
+    }
+
+    #[test]
+    fn taking_a_frame_shifts_the_tail_down_exactly() {
+        let (frame, len) = encoded(b"head");
+        let (next, next_len) = encoded(b"tail");
+        let mut reader = transport::FrameReader::<64>::new();
+        reader.push(&frame[..len]);
+        reader.push(&next[..3]);
+
+        assert_eq!(reader.next_frame(), Ok(Some(b"head".as_slice())));
+        assert_eq!(reader.pending(), &next[..3]);
+        reader.push(&next[3..next_len]);
+        assert_eq!(reader.pending(), &next[..next_len]);
+        assert_eq!(reader.next_frame(), Ok(Some(b"tail".as_slice())));
+    }
+
+    #[test]
+    fn taking_the_whole_buffer_frees_all_of_it() {
+        let (frame, len) = encoded(&[0xAB; 8]);
+        let mut reader = transport::FrameReader::<64>::new();
+        reader.push(&frame[..len]);
+        assert!(reader.next_frame().unwrap().is_some());
+        assert!(reader.is_empty());
+        assert_eq!(reader.push(&[0; 64]), 64);
+    }
+
+    #[test]
+    fn reader_accepts_only_what_fits() {
+        let mut reader = transport::FrameReader::<4>::new();
+        assert_eq!(reader.push(&[0; 6]), 4);
+        assert_eq!(reader.push(&[0]), 0);
+        reader.clear();
+        assert_eq!(reader.push(&[0]), 1);
+    }
+
+    #[test]
+    fn corrupt_frame_clears_reader() {
+        let (mut frame, len) = encoded(&[0x01, 0x02]);
+        frame[1] ^= 0xFF;
+        let mut reader = transport::FrameReader::<64>::new();
+        reader.push(&frame[..len]);
+        assert!(reader.next_frame().is_err());
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn every_framing_round_trips() {
+        let payloads: [&[u8]; 4] = [&[], &[0x00], b"hello", &[0xA5; 200]];
+        for framing in transport::Framing::ALL {
+            for payload in payloads {
+                let mut buffer = [0u8; 256];
+                let len = framing.encode_into(payload, &mut buffer).unwrap();
+                let (frame, remaining) = framing.take_from_bytes(&buffer[..len]).unwrap();
+                assert_eq!(frame.payload, payload, "{framing:?}");
+                assert!(remaining.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn claimed_length_is_read_from_the_header_alone() {
+        for framing in transport::Framing::ALL {
+            let mut buffer = [0u8; 512];
+            let len = framing.encode_into(&[0x5A; 300], &mut buffer).unwrap();
+            assert_eq!(framing.claimed_payload_len(&buffer[..len]), Some(300));
+            assert_eq!(
+                framing.claimed_payload_len(&buffer[..1]),
+                None,
+                "{framing:?}"
+            );
+            assert_eq!(framing.claimed_payload_len(&[]), None);
+        }
+        assert_eq!(
+            transport::Framing::Postcard.claimed_payload_len(&[0x80; 6]),
+            None
+        );
+    }
+
+    #[test]
+    fn length_prefixed_layout_is_pinned() {
+        let mut buffer = [0u8; 16];
+        let len = transport::Framing::LengthPrefixed
+            .encode_into(&[0x01, 0x02], &mut buffer)
+            .unwrap();
+        let crc = transport::crc16(&[0x01, 0x02]).to_be_bytes();
+        assert_eq!(&buffer[..len], [0x00, 0x02, 0x01, 0x02, crc[0], crc[1]]);
+    }
+
+    #[test]
+    fn framings_do_not_read_each_other() {
+        let mut postcard = [0u8; 32];
+        let len = transport::Framing::Postcard
+            .encode_into(b"frame", &mut postcard)
+            .unwrap();
+        assert!(
+            transport::Framing::LengthPrefixed
+                .take_from_bytes(&postcard[..len])
+                .is_err()
+        );
+
+        let mut prefixed = [0u8; 32];
+        let len = transport::Framing::LengthPrefixed
+            .encode_into(b"frame", &mut prefixed)
+            .unwrap();
+        assert!(
+            transport::Framing::Postcard
+                .take_from_bytes(&prefixed[..len])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn length_prefixed_frames_reassemble_across_pushes() {
+        let mut buffer = [0u8; 32];
+        let len = transport::Framing::LengthPrefixed
+            .encode_into(b"split", &mut buffer)
+            .unwrap();
+        let mut reader = transport::FrameReader::<64>::new();
+        reader.set_framing(transport::Framing::LengthPrefixed);
+
+        reader.push(&buffer[..1]);
+        assert_eq!(reader.next_frame(), Ok(None));
+        reader.push(&buffer[1..len]);
+        assert_eq!(reader.next_frame(), Ok(Some(b"split".as_slice())));
+    }
+
+    #[test]
+    fn resync_mid_frame_empties_the_reader_without_an_error() {
+        use crate::resync::{RESYNC, RESYNC_BYTE};
+
+        for framing in transport::Framing::ALL {
+            let mut frame = [0u8; 32];
+            let len = framing.encode_into(b"fresh", &mut frame).unwrap();
+            let mut reader = transport::FrameReader::<512>::new();
+            reader.set_framing(framing);
+
+            // Half a frame, then the resync in packet-sized reads, then a whole frame.
+            reader.push(&[0x00, 0x09, 0x01, 0x02]);
+            for packet in RESYNC.chunks(64) {
+                let mut rest = packet;
+                while !rest.is_empty() {
+                    rest = &rest[reader.push(rest)..];
+                    assert_eq!(reader.next_frame(), Ok(None), "{framing:?}");
+                }
+            }
+            assert!(reader.take_resync());
+            assert!(reader.is_empty());
+            assert!(!reader.take_resync());
+            reader.push(&frame[..len]);
+            assert_eq!(reader.next_frame(), Ok(Some(b"fresh".as_slice())));
+
+            // A run that stops short was data after all, and the broken frame is still reported.
+            reader.push(&[
+                0x00,
+                0x02,
+                RESYNC_BYTE,
+                RESYNC_BYTE,
+                RESYNC_BYTE,
+                RESYNC_BYTE,
+            ]);
+            assert_eq!(reader.next_frame(), Ok(None));
+            reader.push(&[0x00]);
+            assert!(reader.next_frame().is_err(), "{framing:?}");
+            assert!(!reader.take_resync());
+        }
+    }
+
+    #[test]
+    fn length_prefixed_rejects_small_buffer_and_bad_crc() {
+        let mut small = [0u8; 5];
+        assert!(
+            transport::Framing::LengthPrefixed
+                .encode_into(b"abc", &mut small)
+                .is_err()
+        );
+
+        let mut buffer = [0u8; 16];
+        let len = transport::Framing::LengthPrefixed
+            .encode_into(b"abc", &mut buffer)
+            .unwrap();
+        buffer[len - 1] ^= 0xFF;
+        assert_eq!(
+            transport::Framing::LengthPrefixed
+                .take_from_bytes(&buffer[..len])
+                .map(|(frame, _)| frame.payload),
+            Err(transport::FrameError::Checksum)
+        );
+    }
+
+    #[test]
+    fn corrupted_frame_fails_checksum() {
+        let mut buffer = [0u8; 16];
+        let len = transport::encode_into(&[0x01, 0x02, 0x03], &mut buffer).unwrap();
+        let (frame, rest) = transport::take_from_bytes(&buffer[..len]).unwrap();
+        assert_eq!(frame.payload, &[0x01, 0x02, 0x03]);
+        assert!(rest.is_empty());
+
+        buffer[2] ^= 0x40;
+        assert_eq!(
+            transport::take_from_bytes(&buffer[..len]),
+            Err(transport::FrameError::Checksum)
+        );
+    }
 }
@@ -0,0 +1,59 @@
+//! Negative acknowledgement of corrupted commands.
+//!
+//! When a command frame arrives whole but its CRC doesn't match, the firmware answers with [`NAK`]
+//! instead of a response, and the host can resend the frame it just wrote straight away rather than
+//! waiting for a timeout. The NAK carries nothing to say which frame it refers to, so a host that
+//! retransmits holds each command until the previous one is answered. Other framing errors lose the
+//! frame boundary, leaving nothing to resend, and keep the plain `InvalidChecksum` error.
+//!
+//! The NAK is an ordinary `ERR:` response, so a host that doesn't retransmit just shows the error.
+
+use crate::transport::FrameError;
+
+/// Error code the firmware sends in a NAK.
+pub const NAK_CODE: &str = "Retransmit";
+/// Payload of a NAK frame: [`NAK_CODE`] behind the usual error prefix.
+pub const NAK: &[u8] = b"ERR: Retransmit";
+
+/// Whether a failure to read a command frame should be answered with a [`NAK`].
+pub const fn wants_retransmit(err: &FrameError) -> bool {
+    matches!(err, FrameError::Checksum)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        response::ERROR_PREFIX,
+        transport::{FrameReader, Framing},
+    };
+
+    #[test]
+    fn nak_is_an_error_response_with_its_code() {
+        assert_eq!(NAK.strip_prefix(ERROR_PREFIX), Some(NAK_CODE.as_bytes()));
+    }
+
+    #[test]
+    fn a_bad_crc_asks_for_a_retransmit() {
+        for framing in Framing::ALL {
+            let mut frame = [0u8; 16];
+            let len = framing.encode_into(b"\x01\x02hi", &mut frame).unwrap();
+            // Flip a payload bit; the length and layout still line up.
+            frame[3] ^= 0x20;
+
+            let mut reader = FrameReader::<32>::new();
+            reader.set_framing(framing);
+            reader.push(&frame[..len]);
+            let err = reader.next_frame().unwrap_err();
+            assert!(wants_retransmit(&err), "{framing:?}: {err:?}");
+        }
+    }
+
+    #[test]
+    fn a_lost_frame_boundary_does_not() {
+        let mut reader = FrameReader::<32>::new();
+        reader.push(&[0xFF; 12]);
+        let err = reader.next_frame().unwrap_err();
+        assert!(!wants_retransmit(&err), "{err:?}");
+    }
+}
@@ -0,0 +1,130 @@
+//! Why the board last reset, as answered by `resetreason`.
+//!
+//! The firmware reads two RP2040 registers at boot, before anything can change them:
+//! `CHIP_RESET` in the voltage regulator block, which records chip-level resets, and `REASON` in
+//! the watchdog, which records resets the watchdog caused. A watchdog reset leaves `CHIP_RESET`
+//! as the previous chip-level reset set it, so the watchdog is checked first. The reply is text,
+//! e.g. `last reset: watchdog timeout`.
+//!
+//! The RP2040 can't tell a brown-out from power coming up; both hold the chip in power-on reset.
+
+use core::fmt;
+
+/// `CHIP_RESET.HAD_POR`: power-on or brown-out reset.
+pub const CHIP_RESET_HAD_POR: u32 = 1 << 8;
+/// `CHIP_RESET.HAD_RUN`: the RUN pin was pulled low.
+pub const CHIP_RESET_HAD_RUN: u32 = 1 << 16;
+/// `CHIP_RESET.HAD_PSM_RESTART`: a debugger restarted the chip through the rescue debug port.
+pub const CHIP_RESET_HAD_PSM_RESTART: u32 = 1 << 20;
+/// `WATCHDOG.REASON.TIMER`: the watchdog counted down to zero.
+pub const WATCHDOG_REASON_TIMER: u32 = 1 << 0;
+/// `WATCHDOG.REASON.FORCE`: software forced a watchdog reset.
+pub const WATCHDOG_REASON_FORCE: u32 = 1 << 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResetReason {
+    /// Power came up, or the supply dipped far enough for the brown-out detector to reset.
+    PowerOn,
+    /// The RUN pin was pulled low, e.g. by a reset button.
+    RunPin,
+    /// A debugger restarted the chip through the rescue debug port.
+    DebugRestart,
+    /// The watchdog wasn't fed in time.
+    WatchdogTimeout,
+    /// Firmware asked the watchdog for a reset, as a reboot into the bootloader does.
+    WatchdogForced,
+    /// Neither register named a cause. The raw values are kept for a bug report.
+    Unknown {
+        chip_reset: u32,
+        watchdog_reason: u32,
+    },
+}
+
+impl ResetReason {
+    /// The cause the two registers record. Bits other than the documented flags are ignored.
+    pub const fn from_registers(chip_reset: u32, watchdog_reason: u32) -> Self {
+        if watchdog_reason & WATCHDOG_REASON_FORCE != 0 {
+            Self::WatchdogForced
+        } else if watchdog_reason & WATCHDOG_REASON_TIMER != 0 {
+            Self::WatchdogTimeout
+        } else if chip_reset & CHIP_RESET_HAD_PSM_RESTART != 0 {
+            Self::DebugRestart
+        } else if chip_reset & CHIP_RESET_HAD_RUN != 0 {
+            Self::RunPin
+        } else if chip_reset & CHIP_RESET_HAD_POR != 0 {
+            Self::PowerOn
+        } else {
+            Self::Unknown {
+                chip_reset,
+                watchdog_reason,
+            }
+        }
+    }
+}
+
+/// Reads e.g. `last reset: watchdog timeout`.
+impl fmt::Display for ResetReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("last reset: ")?;
+        match self {
+            Self::PowerOn => f.write_str("power-on or brown-out"),
+            Self::RunPin => f.write_str("RUN pin"),
+            Self::DebugRestart => f.write_str("debugger restart"),
+            Self::WatchdogTimeout => f.write_str("watchdog timeout"),
+            Self::WatchdogForced => f.write_str("forced watchdog reset"),
+            Self::Unknown {
+                chip_reset,
+                watchdog_reason,
+            } => write!(
+                f,
+                "unknown (CHIP_RESET {chip_reset:#010x}, watchdog REASON {watchdog_reason:#010x})"
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn register_values_map_to_their_cause() {
+        for (chip_reset, watchdog_reason, reason) in [
+            (0x0000_0100, 0, ResetReason::PowerOn),
+            (0x0001_0000, 0, ResetReason::RunPin),
+            (0x0010_0000, 0, ResetReason::DebugRestart),
+            // A watchdog reset leaves the previous chip-level flag behind.
+            (0x0000_0100, 0x1, ResetReason::WatchdogTimeout),
+            (0x0001_0000, 0x2, ResetReason::WatchdogForced),
+            (0, 0x3, ResetReason::WatchdogForced),
+            // PSM_RESTART_FLAG (bit 24) is a request from the debugger, not a record of a reset.
+            (0x0100_0100, 0, ResetReason::PowerOn),
+        ] {
+            assert_eq!(
+                ResetReason::from_registers(chip_reset, watchdog_reason),
+                reason,
+                "CHIP_RESET {chip_reset:#x}, REASON {watchdog_reason:#x}"
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_causes_keep_the_register_values() {
+        let reason = ResetReason::from_registers(0x0100_0000, 0x4);
+        assert_eq!(
+            reason,
+            ResetReason::Unknown {
+                chip_reset: 0x0100_0000,
+                watchdog_reason: 0x4,
+            }
+        );
+        assert_eq!(
+            reason.to_string(),
+            "last reset: unknown (CHIP_RESET 0x01000000, watchdog REASON 0x00000004)"
+        );
+        assert_eq!(
+            ResetReason::WatchdogTimeout.to_string(),
+            "last reset: watchdog timeout"
+        );
+    }
+}
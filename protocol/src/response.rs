@@ -0,0 +1,444 @@
+//! Allocation-free construction of firmware responses.
+//!
+//! A successful response is the raw payload. A failure is text of the form
+//! `ERR: <code>` or `ERR: <code>: <detail>`. Both handlers and the firmware state machine build
+//! responses through [`ResponseBuilder`] so the format lives in one place.
+//!
+//! On a session that negotiated tagged responses (see [`crate::handshake`]) every response is
+//! preceded by one byte naming the [`Method`] whose handler produced it, or [`UNTAGGED`] when no
+//! handler did, such as for a command that failed to decode. Once the host has sent `format text`
+//! or `format bytes`, handler responses also set [`FORMAT_TEXT_FLAG`] or [`FORMAT_BYTES_FLAG`] in
+//! that byte so the host knows how to show them; `format auto` clears them again.
+
+use core::fmt;
+
+use crate::Method;
+
+/// Prefix that marks a response as an error.
+pub const ERROR_PREFIX: &[u8] = b"ERR: ";
+/// Separator between the error code and its detail.
+pub const ERROR_DETAIL_SEPARATOR: &[u8] = b": ";
+/// Tag byte of a response that no handler produced.
+pub const UNTAGGED: u8 = 0x00;
+/// Tag bit of a response the host asked to be shown as text.
+pub const FORMAT_TEXT_FLAG: u8 = 0x80;
+/// Tag bit of a response the host asked to be shown as bytes.
+pub const FORMAT_BYTES_FLAG: u8 = 0x40;
+/// Longest response the firmware builds, not counting the tag byte of a tagged session.
+pub const MAX_RESPONSE_LEN: usize = crate::MAX_COMMAND_LEN;
+
+/// How the host wants responses shown, as set by `format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum ResponseFormat {
+    /// No preference: the host shows bytes as text when they decode as UTF-8.
+    #[default]
+    Auto,
+    Text,
+    Bytes,
+}
+
+impl ResponseFormat {
+    pub const ALL: [Self; 3] = [Self::Auto, Self::Text, Self::Bytes];
+
+    /// Keyword that names the format in a `format` command.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Text => "text",
+            Self::Bytes => "bytes",
+        }
+    }
+
+    pub const fn as_byte(self) -> u8 {
+        match self {
+            Self::Auto => 0,
+            Self::Text => 1,
+            Self::Bytes => 2,
+        }
+    }
+
+    pub const fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Self::Auto),
+            1 => Some(Self::Text),
+            2 => Some(Self::Bytes),
+            _ => None,
+        }
+    }
+
+    const fn tag_flag(self) -> u8 {
+        match self {
+            Self::Auto => 0,
+            Self::Text => FORMAT_TEXT_FLAG,
+            Self::Bytes => FORMAT_BYTES_FLAG,
+        }
+    }
+}
+
+/// Write the response to a `format` command, e.g. `responses as text`.
+pub fn confirmation(out: &mut impl fmt::Write, format: ResponseFormat) -> fmt::Result {
+    match format {
+        ResponseFormat::Auto => out.write_str("responses in auto format"),
+        format => write!(out, "responses as {}", format.name()),
+    }
+}
+
+/// The response did not fit in the builder's buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CapacityError;
+
+/// Fixed-capacity response buffer.
+#[derive(Debug, Clone)]
+pub struct ResponseBuilder<const N: usize> {
+    buffer: [u8; N],
+    len: usize,
+    method: Option<Method>,
+}
+
+impl<const N: usize> Default for ResponseBuilder<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> ResponseBuilder<N> {
+    pub const fn new() -> Self {
+        Self {
+            buffer: [0; N],
+            len: 0,
+            method: None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buffer[..self.len]
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    pub fn remaining(&self) -> usize {
+        N - self.len
+    }
+
+    /// Empty the response and drop its tag.
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.method = None;
+    }
+
+    /// Attribute the response to the handler for `method`. The tag survives [`Self::ok`] and the
+    /// error builders, so a handler's reply keeps it whichever way the handler finishes.
+    pub fn tag(&mut self, method: Method) {
+        self.method = Some(method);
+    }
+
+    /// Method of the handler that produced the response, if one did.
+    pub fn method(&self) -> Option<Method> {
+        self.method
+    }
+
+    /// Append bytes to the response. Nothing is written if they don't all fit.
+    pub fn extend(&mut self, bytes: &[u8]) -> Result<(), CapacityError> {
+        let end = self.len + bytes.len();
+        if end > N {
+            return Err(CapacityError);
+        }
+        self.buffer[self.len..end].copy_from_slice(bytes);
+        self.len = end;
+        Ok(())
+    }
+
+    /// Lengthen the response by `len` zeroed bytes and return them, so a handler can read straight
+    /// into the response instead of through a buffer of its own. Nothing changes if they don't fit.
+    pub fn grow(&mut self, len: usize) -> Result<&mut [u8], CapacityError> {
+        let start = self.len;
+        let end = start + len;
+        if end > N {
+            return Err(CapacityError);
+        }
+        self.len = end;
+        let tail = &mut self.buffer[start..end];
+        tail.fill(0);
+        Ok(tail)
+    }
+
+    /// Drop everything past the first `len` bytes. A `len` beyond the current length changes
+    /// nothing.
+    pub fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len);
+    }
+
+    /// Replace the contents with a successful response. The builder is left empty on overflow.
+    pub fn ok(&mut self, payload: &[u8]) -> Result<(), CapacityError> {
+        self.len = 0;
+        self.extend(payload)
+    }
+
+    /// Replace the contents with an error response.
+    ///
+    /// If the detail doesn't fit it is truncated so a well-formed error is always left behind,
+    /// and `Err` reports the truncation.
+    pub fn err(&mut self, code: &str, detail: &[u8]) -> Result<(), CapacityError> {
+        self.len = 0;
+        self.write_error_head(code, !detail.is_empty())?;
+        let fitted = detail.len().min(self.remaining());
+        self.extend(&detail[..fitted])?;
+        if fitted < detail.len() {
+            Err(CapacityError)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Turn the current contents into the detail of an error response, in place.
+    ///
+    /// Handlers record what went wrong in the builder before failing; the caller then labels it
+    /// with the error code. Detail that no longer fits after the prefix is truncated from the end.
+    pub fn wrap_err(&mut self, code: &str) -> Result<(), CapacityError> {
+        let detail_len = self.len;
+        let head_len = ERROR_PREFIX.len()
+            + code.len()
+            + if detail_len > 0 {
+                ERROR_DETAIL_SEPARATOR.len()
+            } else {
+                0
+            };
+        if head_len > N {
+            self.len = 0;
+            return Err(CapacityError);
+        }
+
+        let kept = detail_len.min(N - head_len);
+        self.buffer.copy_within(..kept, head_len);
+        self.len = 0;
+        self.write_error_head(code, detail_len > 0)?;
+        self.len += kept;
+        if kept < detail_len {
+            Err(CapacityError)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn write_error_head(&mut self, code: &str, with_detail: bool) -> Result<(), CapacityError> {
+        let result = self
+            .extend(ERROR_PREFIX)
+            .and_then(|()| self.extend(code.as_bytes()))
+            .and_then(|()| {
+                if with_detail {
+                    self.extend(ERROR_DETAIL_SEPARATOR)
+                } else {
+                    Ok(())
+                }
+            });
+        if result.is_err() {
+            self.len = 0;
+        }
+        result
+    }
+}
+
+/// Tag byte sent ahead of a response produced by `method`'s handler while the session is set to
+/// `format`. A response no handler produced never carries a format.
+pub const fn tag_byte(method: Option<Method>, format: ResponseFormat) -> u8 {
+    match method {
+        Some(method) => method.as_byte() | format.tag_flag(),
+        None => UNTAGGED,
+    }
+}
+
+/// Split the tag off a response received on a tagged session. An unknown method reads as untagged
+/// and a tag with both format bits set as [`ResponseFormat::Auto`]; `None` means the payload was
+/// empty, which a tagged session never sends.
+pub fn split_tag(payload: &[u8]) -> Option<(Option<Method>, ResponseFormat, &[u8])> {
+    let (&tag, rest) = payload.split_first()?;
+    let format = match tag & (FORMAT_TEXT_FLAG | FORMAT_BYTES_FLAG) {
+        FORMAT_TEXT_FLAG => ResponseFormat::Text,
+        FORMAT_BYTES_FLAG => ResponseFormat::Bytes,
+        _ => ResponseFormat::Auto,
+    };
+    let method = Method::from_byte(tag & !(FORMAT_TEXT_FLAG | FORMAT_BYTES_FLAG));
+    Some((method, format, rest))
+}
+
+impl<const N: usize> fmt::Write for ResponseBuilder<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.extend(s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::fmt::Write;
+
+    #[test]
+    fn ok_replaces_contents_with_payload() {
+        let mut response = ResponseBuilder::<8>::new();
+        response.extend(b"stale").unwrap();
+        response.ok(&[0xAA, 0xBB]).unwrap();
+        assert_eq!(response.as_bytes(), &[0xAA, 0xBB]);
+    }
+
+    #[test]
+    fn grow_hands_out_the_new_tail() {
+        let mut response = ResponseBuilder::<4>::new();
+        response.extend(b"ab").unwrap();
+        response.ok(&[0x01]).unwrap();
+        response.grow(2).unwrap().copy_from_slice(&[0x02, 0x03]);
+        assert_eq!(response.as_bytes(), &[0x01, 0x02, 0x03]);
+        assert_eq!(response.grow(1).unwrap(), &mut [0]);
+        assert_eq!(response.grow(1), Err(CapacityError));
+        assert_eq!(response.len(), 4);
+    }
+
+    #[test]
+    fn truncate_only_shortens() {
+        let mut response = ResponseBuilder::<4>::new();
+        response.extend(b"abc").unwrap();
+        response.truncate(5);
+        assert_eq!(response.as_bytes(), b"abc");
+        response.truncate(1);
+        assert_eq!(response.as_bytes(), b"a");
+        assert_eq!(response.remaining(), 3);
+    }
+
+    #[test]
+    fn ok_overflow_leaves_builder_empty() {
+        let mut response = ResponseBuilder::<2>::new();
+        assert_eq!(response.ok(b"abc"), Err(CapacityError));
+        assert!(response.is_empty());
+    }
+
+    #[test]
+    fn err_formats_code_and_detail() {
+        let mut response = ResponseBuilder::<64>::new();
+        response.err("Timeout", b"").unwrap();
+        assert_eq!(response.as_bytes(), b"ERR: Timeout");
+        response.err("ExecutionFailed", b"bus busy").unwrap();
+        assert_eq!(response.as_bytes(), b"ERR: ExecutionFailed: bus busy");
+    }
+
+    #[test]
+    fn err_truncates_detail_that_does_not_fit() {
+        let mut response = ResponseBuilder::<16>::new();
+        assert_eq!(response.err("Code", b"long detail"), Err(CapacityError));
+        assert_eq!(response.as_bytes(), b"ERR: Code: long ");
+
+        let mut tiny = ResponseBuilder::<4>::new();
+        assert_eq!(tiny.err("Code", b""), Err(CapacityError));
+        assert!(tiny.is_empty());
+    }
+
+    #[test]
+    fn wrap_err_prefixes_buffered_detail() {
+        let mut response = ResponseBuilder::<64>::new();
+        write!(response, "i2c error: {}", 7).unwrap();
+        response.wrap_err("ExecutionFailed").unwrap();
+        assert_eq!(
+            response.as_bytes(),
+            b"ERR: ExecutionFailed: i2c error: 7".as_slice()
+        );
+
+        response.clear();
+        response.wrap_err("Timeout").unwrap();
+        assert_eq!(response.as_bytes(), b"ERR: Timeout");
+    }
+
+    #[test]
+    fn wrap_err_truncates_full_buffer() {
+        let mut response = ResponseBuilder::<12>::new();
+        response.extend(b"0123456789").unwrap();
+        assert_eq!(response.wrap_err("E"), Err(CapacityError));
+        assert_eq!(response.as_bytes(), b"ERR: E: 0123");
+    }
+
+    #[test]
+    fn tag_survives_ok_and_err_but_not_clear() {
+        let mut response = ResponseBuilder::<32>::new();
+        assert_eq!(response.method(), None);
+        response.tag(Method::I2c);
+        response.ok(&[0x12, 0x34]).unwrap();
+        assert_eq!(response.method(), Some(Method::I2c));
+        response.extend(b"nack").unwrap();
+        response.wrap_err("ExecutionFailed").unwrap();
+        assert_eq!(response.method(), Some(Method::I2c));
+        response.clear();
+        assert_eq!(response.method(), None);
+    }
+
+    #[test]
+    fn tag_round_trips_through_the_wire_byte() {
+        for method in [
+            Method::Echo,
+            Method::I2c,
+            Method::Uart,
+            Method::Temp,
+            Method::Heartbeat,
+            Method::Config,
+            Method::Identify,
+            Method::Stats,
+            Method::Format,
+            Method::SelfTest,
+            Method::ResetReason,
+            Method::Info,
+        ] {
+            let wire = [tag_byte(Some(method), ResponseFormat::Auto), 0xAB];
+            assert_eq!(
+                split_tag(&wire),
+                Some((Some(method), ResponseFormat::Auto, [0xAB].as_slice()))
+            );
+        }
+        assert_eq!(
+            split_tag(&[tag_byte(None, ResponseFormat::Auto), 0xAB]),
+            Some((None, ResponseFormat::Auto, [0xAB].as_slice()))
+        );
+        assert_eq!(
+            split_tag(&[0xEE]),
+            Some((None, ResponseFormat::Auto, [].as_slice()))
+        );
+        assert_eq!(split_tag(&[]), None);
+    }
+
+    #[test]
+    fn the_tag_carries_the_format_the_host_set() {
+        for format in ResponseFormat::ALL {
+            let wire = [tag_byte(Some(Method::Echo), format), b'h', b'i'];
+            assert_eq!(
+                split_tag(&wire),
+                Some((Some(Method::Echo), format, b"hi".as_slice()))
+            );
+            // A response no handler produced, like a decode failure, is left alone.
+            assert_eq!(tag_byte(None, format), UNTAGGED);
+        }
+        assert_eq!(
+            tag_byte(Some(Method::Echo), ResponseFormat::Text),
+            0x01 | FORMAT_TEXT_FLAG
+        );
+        let both = Method::Echo.as_byte() | FORMAT_TEXT_FLAG | FORMAT_BYTES_FLAG;
+        assert_eq!(
+            split_tag(&[both]),
+            Some((Some(Method::Echo), ResponseFormat::Auto, [].as_slice()))
+        );
+        for format in ResponseFormat::ALL {
+            assert_eq!(ResponseFormat::from_byte(format.as_byte()), Some(format));
+        }
+    }
+
+    #[test]
+    fn formatted_writes_respect_capacity() {
+        let mut response = ResponseBuilder::<4>::new();
+        assert!(write!(response, "{}", 12345).is_err());
+        assert!(response.is_empty());
+    }
+}
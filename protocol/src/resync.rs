@@ -0,0 +1,88 @@
+//! Forcing the firmware's frame reader back to a frame boundary.
+//!
+//! After trouble on the link the firmware may be holding part of a frame the host has given up
+//! on, and reads whatever the host sends next as the rest of it. Sending [`RESYNC`] makes the
+//! [`FrameReader`](crate::transport::FrameReader) drop everything it has buffered and wait for a
+//! fresh frame. Nothing is sent back.
+//!
+//! [`RESYNC`] is a run of one byte, like the bridge escape, but a run too long for any frame the
+//! host sends to contain. A frame's payload is at most [`MAX_COMMAND_LEN`] bytes, longer commands
+//! going as chunks, and either framing adds at most five bytes around it. A run can't carry on from
+//! one frame into the next either: a postcard frame ends with the last byte of a varint, which is
+//! below `0x80`, and a length-prefixed frame starts with the high byte of its length, which is
+//! `0x00` or `0x01`.
+//!
+//! A framing error found while a run of [`RESYNC_BYTE`] is arriving may only be the run cutting a
+//! partial frame short, so the reader holds it back: it is dropped if the run completes and
+//! reported once the run stops short. Only a run with room to complete in the reader's buffer is
+//! given that benefit; anything else is reported straight away.
+
+use crate::MAX_COMMAND_LEN;
+
+/// Byte repeated to form [`RESYNC`].
+pub const RESYNC_BYTE: u8 = 0xFF;
+/// Length of [`RESYNC`], longer than any frame the host sends.
+pub const RESYNC_LEN: usize = MAX_COMMAND_LEN + 8;
+/// Sequence that clears the firmware's frame reader.
+pub const RESYNC: [u8; RESYNC_LEN] = [RESYNC_BYTE; RESYNC_LEN];
+
+/// Counts the run of [`RESYNC_BYTE`] at the end of the bytes seen so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResyncDetector {
+    run: usize,
+}
+
+impl ResyncDetector {
+    pub const fn new() -> Self {
+        Self { run: 0 }
+    }
+
+    /// Count the run through `data`. When it completes, returns the number of bytes of `data` up
+    /// to and including the one that completed it; the bytes after that aren't looked at.
+    pub fn scan(&mut self, data: &[u8]) -> Option<usize> {
+        for (idx, &byte) in data.iter().enumerate() {
+            if byte != RESYNC_BYTE {
+                self.run = 0;
+                continue;
+            }
+            self.run += 1;
+            if self.run == RESYNC_LEN {
+                self.run = 0;
+                return Some(idx + 1);
+            }
+        }
+        None
+    }
+
+    /// Bytes of [`RESYNC_BYTE`] still needed to complete the current run.
+    pub const fn remaining(&self) -> usize {
+        RESYNC_LEN - self.run
+    }
+
+    /// Whether the latest byte seen could belong to a resync still arriving.
+    pub const fn in_run(&self) -> bool {
+        self.run > 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_a_full_run_completes() {
+        let mut detector = ResyncDetector::new();
+        assert_eq!(detector.scan(&RESYNC[1..]), None);
+        assert!(detector.in_run());
+        assert_eq!(detector.remaining(), 1);
+        assert_eq!(detector.scan(b"\x01"), None);
+        assert!(!detector.in_run());
+
+        // Split across reads, with bytes after the run left unscanned.
+        assert_eq!(detector.scan(&RESYNC[..100]), None);
+        let mut rest = [RESYNC_BYTE; RESYNC_LEN - 100 + 2];
+        rest[RESYNC_LEN - 100] = 0x00;
+        assert_eq!(detector.scan(&rest), Some(RESYNC_LEN - 100));
+        assert!(!detector.in_run());
+    }
+}
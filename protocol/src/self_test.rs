@@ -0,0 +1,225 @@
+//! Bring-up checks run by `selftest`.
+//!
+//! The firmware exercises what it can without a host-side fixture and replies with a
+//! [`SelfTestReport`] as text: a verdict, then one `<subsystem> <pass|FAIL> (<detail>)` entry per
+//! check, e.g.
+//!
+//! ```text
+//! self-test passed, 4 of 4 checks: led pass (8 colours shown), i2c0 pass (no devices), i2c1 pass (0x48 0x50), adc pass (23.1 °C)
+//! ```
+//!
+//! The checks are:
+//!
+//! - `led`: the status LED steps through every colour the firmware uses. The LED can't be read
+//!   back, so this passes once the colours are shown; watch the board to confirm them.
+//! - `i2c0` and `i2c1`: every address from [`SCAN_FIRST_ADDRESS`] to [`SCAN_LAST_ADDRESS`] is
+//!   probed with a one-byte read. Nothing is ever written, so unknown devices are left as they
+//!   were. The scan passes if it gets through every address, listing those that answered; a probe
+//!   that hangs or fails with anything but a NAK ends the scan and fails it.
+//! - `adc`: the internal temperature sensor is read and must fall between
+//!   [`PLAUSIBLE_MIN_DECI_CELSIUS`] and [`PLAUSIBLE_MAX_DECI_CELSIUS`], the RP2040's rated range.
+//!   A reading outside it usually means the ADC is returning a stuck code.
+
+use core::fmt;
+
+use crate::{I2C_BUS_COUNT, temperature::DeciCelsius};
+
+/// Lowest address probed by the I2C scan; `0x00` to `0x07` are reserved.
+pub const SCAN_FIRST_ADDRESS: u8 = 0x08;
+/// Highest address probed by the I2C scan; `0x78` to `0x7F` are reserved.
+pub const SCAN_LAST_ADDRESS: u8 = 0x77;
+/// Coldest believable sensor reading, in tenths of a degree.
+pub const PLAUSIBLE_MIN_DECI_CELSIUS: i16 = -400;
+/// Hottest believable sensor reading, in tenths of a degree.
+pub const PLAUSIBLE_MAX_DECI_CELSIUS: i16 = 850;
+/// Addresses listed for a bus before the rest are only counted.
+const MAX_LISTED_DEVICES: u32 = 6;
+
+/// How one I2C bus scan went.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusScan {
+    /// Every address was probed. Bit `n` of `found` is set if address `n` answered.
+    Finished { found: u128 },
+    /// The probe of `address` didn't finish in time, so something is holding the bus.
+    Stuck { address: u8 },
+    /// The probe of `address` failed with something other than a NAK, such as lost arbitration.
+    Faulted { address: u8 },
+}
+
+impl BusScan {
+    pub const fn passed(&self) -> bool {
+        matches!(self, Self::Finished { .. })
+    }
+}
+
+impl fmt::Display for BusScan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Finished { found: 0 } => f.write_str("no devices"),
+            Self::Finished { found } => {
+                let addresses =
+                    (0..=SCAN_LAST_ADDRESS).filter(|&address| found >> address & 1 == 1);
+                for (listed, address) in addresses.take(MAX_LISTED_DEVICES as usize).enumerate() {
+                    if listed > 0 {
+                        f.write_str(" ")?;
+                    }
+                    write!(f, "{address:#04x}")?;
+                }
+                let unlisted = found.count_ones().saturating_sub(MAX_LISTED_DEVICES);
+                if unlisted > 0 {
+                    write!(f, " and {unlisted} more")?;
+                }
+                Ok(())
+            }
+            Self::Stuck { address } => write!(f, "bus held at {address:#04x}"),
+            Self::Faulted { address } => write!(f, "bus error at {address:#04x}"),
+        }
+    }
+}
+
+/// How the temperature sensor read went.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdcCheck {
+    Reading(DeciCelsius),
+    ConversionFailed,
+}
+
+impl AdcCheck {
+    pub const fn passed(&self) -> bool {
+        match self {
+            Self::Reading(DeciCelsius(reading)) => {
+                *reading >= PLAUSIBLE_MIN_DECI_CELSIUS && *reading <= PLAUSIBLE_MAX_DECI_CELSIUS
+            }
+            Self::ConversionFailed => false,
+        }
+    }
+}
+
+impl fmt::Display for AdcCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Reading(reading) if self.passed() => write!(f, "{reading}"),
+            Self::Reading(reading) => write!(f, "{reading} is implausible"),
+            Self::ConversionFailed => f.write_str("conversion failed"),
+        }
+    }
+}
+
+/// Results of every check, in the order they run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SelfTestReport {
+    /// Colours the status LED was stepped through.
+    pub led_colours: u8,
+    /// One scan per bus, indexed by bus number.
+    pub i2c: [BusScan; I2C_BUS_COUNT as usize],
+    pub adc: AdcCheck,
+}
+
+impl SelfTestReport {
+    /// Number of checks in a report.
+    pub const CHECKS: usize = 2 + I2C_BUS_COUNT as usize;
+
+    /// Number of checks that passed.
+    pub fn passed_checks(&self) -> usize {
+        let buses = self.i2c.iter().filter(|scan| scan.passed()).count();
+        1 + buses + usize::from(self.adc.passed())
+    }
+
+    pub fn passed(&self) -> bool {
+        self.passed_checks() == Self::CHECKS
+    }
+}
+
+impl fmt::Display for SelfTestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verdict = |passed: bool| if passed { "pass" } else { "FAIL" };
+        write!(
+            f,
+            "self-test {}, {} of {} checks: led pass ({} colours shown)",
+            if self.passed() { "passed" } else { "failed" },
+            self.passed_checks(),
+            Self::CHECKS,
+            self.led_colours
+        )?;
+        for (bus, scan) in self.i2c.iter().enumerate() {
+            write!(f, ", i2c{bus} {} ({scan})", verdict(scan.passed()))?;
+        }
+        write!(f, ", adc {} ({})", verdict(self.adc.passed()), self.adc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn found(addresses: &[u8]) -> BusScan {
+        BusScan::Finished {
+            found: addresses
+                .iter()
+                .fold(0, |found, &address| found | 1 << address),
+        }
+    }
+
+    #[test]
+    fn a_healthy_board_passes_every_check() {
+        let report = SelfTestReport {
+            led_colours: 8,
+            i2c: [found(&[]), found(&[0x48, 0x50])],
+            adc: AdcCheck::Reading(DeciCelsius(231)),
+        };
+        assert!(report.passed());
+        assert_eq!(
+            report.to_string(),
+            "self-test passed, 4 of 4 checks: led pass (8 colours shown), i2c0 pass (no devices), \
+             i2c1 pass (0x48 0x50), adc pass (23.1 °C)"
+        );
+    }
+
+    #[test]
+    fn faked_failures_show_in_the_report() {
+        let report = SelfTestReport {
+            led_colours: 8,
+            i2c: [
+                BusScan::Stuck { address: 0x08 },
+                BusScan::Faulted { address: 0x3C },
+            ],
+            adc: AdcCheck::Reading(DeciCelsius(4372)),
+        };
+        assert!(!report.passed());
+        assert_eq!(report.passed_checks(), 1);
+        assert_eq!(
+            report.to_string(),
+            "self-test failed, 1 of 4 checks: led pass (8 colours shown), i2c0 FAIL (bus held at \
+             0x08), i2c1 FAIL (bus error at 0x3c), adc FAIL (437.2 °C is implausible)"
+        );
+
+        let conversion = SelfTestReport {
+            adc: AdcCheck::ConversionFailed,
+            i2c: [found(&[]); 2],
+            ..report
+        };
+        assert_eq!(conversion.passed_checks(), 3);
+        assert!(
+            conversion
+                .to_string()
+                .ends_with("adc FAIL (conversion failed)")
+        );
+    }
+
+    #[test]
+    fn long_device_lists_are_cut_short() {
+        let crowded = found(&[0x08, 0x10, 0x20, 0x30, 0x40, 0x50, 0x60, 0x70]);
+        assert_eq!(
+            crowded.to_string(),
+            "0x08 0x10 0x20 0x30 0x40 0x50 and 2 more"
+        );
+    }
+
+    #[test]
+    fn plausible_readings_are_the_rated_range() {
+        assert!(AdcCheck::Reading(DeciCelsius(PLAUSIBLE_MIN_DECI_CELSIUS)).passed());
+        assert!(AdcCheck::Reading(DeciCelsius(PLAUSIBLE_MAX_DECI_CELSIUS)).passed());
+        assert!(!AdcCheck::Reading(DeciCelsius(PLAUSIBLE_MAX_DECI_CELSIUS + 1)).passed());
+        assert!(!AdcCheck::Reading(DeciCelsius(-14798)).passed());
+    }
+}
@@ -0,0 +1,213 @@
+//! Register transactions on the firmware's SPI bus.
+//!
+//! The bus is SPI0 with SCK on GP18, MOSI on GP19 and MISO on GP20. Chip selects are plain GPIO
+//! outputs, active low: CS 0 is GP17 and CS 1 is GP21 (see [`CS_PINS`]). The bus starts in mode 0
+//! (CPOL 0, CPHA 0) at 1 MHz, and `spi config <mode> <kHz>` changes both for every later
+//! transaction (see [`config_error`] for the limits).
+//!
+//! Each command is one transaction with its chip select held low throughout. A read clocks out the
+//! register byte followed by `length` [`FILL_BYTE`]s and keeps what the device shifts back during
+//! the fill; a write clocks out the register byte followed by the payload and ignores what comes
+//! back. The register byte is sent as given, so devices that flag reads with a bit in it (often the
+//! top bit) need that bit set in the command.
+//!
+//! Both are built on [`SpiTransfer`], the one full-duplex operation the bus has to provide.
+
+use crate::SPI_CS_COUNT;
+
+/// GPIO driving each chip select, indexed by the `cs` of a command.
+pub const CS_PINS: [u8; SPI_CS_COUNT as usize] = [17, 21];
+/// SPI mode the bus starts in; modes 0-3 are the usual CPOL/CPHA pairs.
+pub const DEFAULT_SPI_MODE: u8 = 0;
+/// Clock rate the bus starts at.
+pub const DEFAULT_SPI_FREQUENCY_KHZ: u16 = 1_000;
+/// Slowest clock the bus can divide down to from the 125 MHz peripheral clock.
+pub const MIN_SPI_FREQUENCY_KHZ: u16 = 2;
+/// Fastest clock the bus runs at, half the peripheral clock.
+pub const MAX_SPI_FREQUENCY_KHZ: u16 = 62_500;
+/// Byte clocked out while a read shifts data in.
+pub const FILL_BYTE: u8 = 0x00;
+
+/// Clock polarity and phase of SPI `mode`: `(cpol, cpha)`, where CPOL 1 idles the clock high and
+/// CPHA 1 samples on the second edge.
+pub const fn polarity_phase(mode: u8) -> Option<(bool, bool)> {
+    match mode {
+        0 => Some((false, false)),
+        1 => Some((false, true)),
+        2 => Some((true, false)),
+        3 => Some((true, true)),
+        _ => None,
+    }
+}
+
+/// Parse a mode as typed in `spi config`: `mode0` to `mode3`, or the bare digit.
+pub fn parse_mode(token: &str) -> Option<u8> {
+    let digit = match token.get(..4) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("mode") => &token[4..],
+        _ => token,
+    };
+    let mode = digit.parse().ok()?;
+    polarity_phase(mode).map(|_| mode)
+}
+
+/// Why an `spi config` can't be applied, or `None` if it can.
+pub const fn config_error(mode: u8, freq_khz: u16) -> Option<&'static str> {
+    if polarity_phase(mode).is_none() {
+        Some("mode must be 0 to 3")
+    } else if freq_khz < MIN_SPI_FREQUENCY_KHZ || freq_khz > MAX_SPI_FREQUENCY_KHZ {
+        Some("frequency must be 2 to 62500 kHz")
+    } else {
+        None
+    }
+}
+
+/// Write the response to an applied `spi config`, e.g. `spi mode0 at 1000 kHz`.
+pub fn confirmation(out: &mut impl core::fmt::Write, mode: u8, freq_khz: u16) -> core::fmt::Result {
+    write!(out, "spi mode{mode} at {freq_khz} kHz")
+}
+
+/// A full-duplex SPI bus with a chip select per device.
+pub trait SpiTransfer {
+    type Error;
+
+    /// Clock `words` out with chip select `cs` held low, replacing each with the byte clocked in
+    /// alongside it.
+    fn transfer(
+        &mut self,
+        cs: u8,
+        words: &mut [u8],
+    ) -> impl Future<Output = Result<(), Self::Error>>;
+}
+
+/// Read `frame.len() - 1` bytes starting at `register`. `frame` holds the whole transaction and the
+/// bytes read are returned from its tail.
+pub async fn read_register<'a, B: SpiTransfer>(
+    bus: &mut B,
+    cs: u8,
+    register: u8,
+    frame: &'a mut [u8],
+) -> Result<&'a [u8], B::Error> {
+    let Some((first, fill)) = frame.split_first_mut() else {
+        return Ok(&[]);
+    };
+    *first = register;
+    fill.fill(FILL_BYTE);
+    bus.transfer(cs, frame).await?;
+    Ok(&frame[1..])
+}
+
+/// Write `payload` starting at `register`, building the transaction in `frame`.
+///
+/// # Panics
+///
+/// If `frame` is shorter than `payload.len() + 1`.
+pub async fn write_register<B: SpiTransfer>(
+    bus: &mut B,
+    cs: u8,
+    register: u8,
+    payload: &[u8],
+    frame: &mut [u8],
+) -> Result<(), B::Error> {
+    let frame = &mut frame[..payload.len() + 1];
+    frame[0] = register;
+    frame[1..].copy_from_slice(payload);
+    bus.transfer(cs, frame).await
+}
+
+#[cfg(test)]
+mod tests {
+    use core::{
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+
+    use super::*;
+
+    /// Logs every transaction and answers each byte after the register with a count up from it,
+    /// the way a device auto-incrementing through its registers would.
+    #[derive(Default)]
+    struct FakeSpi {
+        transactions: Vec<(u8, Vec<u8>)>,
+        broken: bool,
+    }
+
+    impl SpiTransfer for FakeSpi {
+        type Error = &'static str;
+
+        async fn transfer(&mut self, cs: u8, words: &mut [u8]) -> Result<(), Self::Error> {
+            if self.broken {
+                return Err("bus fault");
+            }
+            self.transactions.push((cs, words.to_vec()));
+            let register = words.first().copied().unwrap_or_default();
+            for (offset, word) in (0u8..).zip(words.iter_mut()) {
+                *word = register.wrapping_add(offset).wrapping_sub(1);
+            }
+            Ok(())
+        }
+    }
+
+    /// Run a future that never waits, as everything on the fake bus is.
+    fn ready<F: Future>(future: F) -> F::Output {
+        match pin!(future).poll(&mut Context::from_waker(Waker::noop())) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("fake bus never waits"),
+        }
+    }
+
+    #[test]
+    fn read_sends_the_register_then_fill_bytes() {
+        let mut bus = FakeSpi::default();
+        let mut frame = [0xEE; 4];
+        let read = ready(read_register(&mut bus, 1, 0x20, &mut frame)).unwrap();
+        assert_eq!(read, [0x20, 0x21, 0x22]);
+        assert_eq!(
+            bus.transactions,
+            [(1, vec![0x20, FILL_BYTE, FILL_BYTE, FILL_BYTE])]
+        );
+    }
+
+    #[test]
+    fn write_sends_the_register_then_the_payload() {
+        let mut bus = FakeSpi::default();
+        let mut frame = [0u8; 8];
+        ready(write_register(&mut bus, 0, 0x0F, &[0xAA, 0xBB], &mut frame)).unwrap();
+        assert_eq!(bus.transactions, [(0, vec![0x0F, 0xAA, 0xBB])]);
+    }
+
+    #[test]
+    fn modes_map_to_polarity_and_phase() {
+        assert_eq!(polarity_phase(DEFAULT_SPI_MODE), Some((false, false)));
+        assert_eq!(polarity_phase(3), Some((true, true)));
+        assert_eq!(parse_mode("MODE1"), Some(1));
+        assert_eq!(parse_mode("2"), Some(2));
+        assert_eq!(parse_mode("mode4"), None);
+        assert_eq!(parse_mode("mode"), None);
+        assert_eq!(
+            config_error(4, DEFAULT_SPI_FREQUENCY_KHZ),
+            Some("mode must be 0 to 3")
+        );
+        assert_eq!(
+            config_error(0, MIN_SPI_FREQUENCY_KHZ - 1),
+            Some("frequency must be 2 to 62500 kHz")
+        );
+        assert_eq!(config_error(0, MAX_SPI_FREQUENCY_KHZ), None);
+    }
+
+    #[test]
+    fn bus_errors_are_passed_back() {
+        let mut bus = FakeSpi {
+            broken: true,
+            ..FakeSpi::default()
+        };
+        let mut frame = [0u8; 2];
+        assert_eq!(
+            ready(read_register(&mut bus, 0, 0x00, &mut frame)),
+            Err("bus fault")
+        );
+        assert_eq!(
+            ready(write_register(&mut bus, 0, 0x00, &[1], &mut frame)),
+            Err("bus fault")
+        );
+    }
+}
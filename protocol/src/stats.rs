@@ -0,0 +1,95 @@
+//! USB link trouble seen by the firmware, read back with `stats`.
+//!
+//! An overflow means USB data arrived faster than the firmware could take it, or a response could
+//! not be written before the retry timeout. Many of them point at a host writing without pacing.
+//! Overflows count from the start of the session; disconnects end a session, so they count from
+//! power-up. The reply is the [`Stats`] as text, e.g. `12 USB overflows this session, 1 disconnect
+//! since power-up`.
+
+use core::fmt;
+
+/// A USB error, without the driver's error type so the counting can be shared and tested.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkEvent {
+    /// Data didn't fit a buffer on the way in, or a write gave up waiting for room.
+    Overflow,
+    /// The endpoint went away, ending the session.
+    Disconnect,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Stats {
+    pub overflows: u32,
+    pub disconnects: u32,
+}
+
+impl Stats {
+    pub const fn new() -> Self {
+        Self {
+            overflows: 0,
+            disconnects: 0,
+        }
+    }
+
+    pub fn record(&mut self, event: LinkEvent) {
+        let count = match event {
+            LinkEvent::Overflow => &mut self.overflows,
+            LinkEvent::Disconnect => &mut self.disconnects,
+        };
+        *count = count.saturating_add(1);
+    }
+
+    /// Start a new session. Disconnects carry over, since each one ended a session.
+    pub fn start_session(&mut self) {
+        self.overflows = 0;
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let plural = |count: u32| if count == 1 { "" } else { "s" };
+        write!(
+            f,
+            "{} USB overflow{} this session, {} disconnect{} since power-up",
+            self.overflows,
+            plural(self.overflows),
+            self.disconnects,
+            plural(self.disconnects)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn an_overflow_shows_in_the_report() {
+        let mut stats = Stats::new();
+        assert_eq!(
+            stats.to_string(),
+            "0 USB overflows this session, 0 disconnects since power-up"
+        );
+        stats.record(LinkEvent::Overflow);
+        assert_eq!(stats.overflows, 1);
+        assert_eq!(
+            stats.to_string(),
+            "1 USB overflow this session, 0 disconnects since power-up"
+        );
+    }
+
+    #[test]
+    fn a_new_session_keeps_the_disconnects() {
+        let mut stats = Stats::new();
+        stats.record(LinkEvent::Overflow);
+        stats.record(LinkEvent::Disconnect);
+        stats.start_session();
+        assert_eq!(
+            stats,
+            Stats {
+                overflows: 0,
+                disconnects: 1,
+            }
+        );
+    }
+}
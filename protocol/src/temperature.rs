@@ -0,0 +1,103 @@
+//! Conversion for the RP2040 internal temperature sensor.
+//!
+//! The sensor is read through ADC channel 4 as a 12-bit sample against the 3.3 V reference. The
+//! RP2040 datasheet (section 4.9.5) gives the sensor voltage as 0.706 V at 27 °C with a slope of
+//! -1.721 mV/°C, so
+//!
+//! ```text
+//! V = raw * 3.3 / 4096
+//! T = 27 - (V - 0.706) / 0.001721
+//! ```
+//!
+//! The firmware does this in integer arithmetic and replies with the result in tenths of a degree
+//! Celsius as a big-endian `i16` (see [`DeciCelsius`]). The sensor is only accurate to a few
+//! degrees, so one decimal place is already more precision than it delivers.
+
+use core::fmt;
+
+/// ADC reference voltage in microvolts.
+pub const ADC_REFERENCE_MICROVOLTS: i64 = 3_300_000;
+/// Number of ADC codes; samples are 12 bits.
+pub const ADC_FULL_SCALE: i64 = 4096;
+/// Sensor voltage at [`SENSOR_REFERENCE_DECI_CELSIUS`], in microvolts.
+pub const SENSOR_REFERENCE_MICROVOLTS: i64 = 706_000;
+/// Temperature at which the sensor reads [`SENSOR_REFERENCE_MICROVOLTS`], in tenths of a degree.
+pub const SENSOR_REFERENCE_DECI_CELSIUS: i64 = 270;
+/// Sensor slope in microvolts per degree Celsius (the voltage falls as temperature rises).
+pub const SENSOR_SLOPE_MICROVOLTS_PER_CELSIUS: i64 = 1_721;
+
+/// Length of a temperature response on the wire.
+pub const RESPONSE_LEN: usize = 2;
+
+/// Temperature in tenths of a degree Celsius.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeciCelsius(pub i16);
+
+impl DeciCelsius {
+    /// Convert a raw 12-bit ADC sample from the sensor channel, rounding to the nearest tenth.
+    pub fn from_raw(raw: u16) -> Self {
+        // Scale both sides by the ADC full scale so the sample voltage never gets truncated.
+        let numerator = (SENSOR_REFERENCE_MICROVOLTS * ADC_FULL_SCALE
+            - i64::from(raw) * ADC_REFERENCE_MICROVOLTS)
+            * 10;
+        let denominator = SENSOR_SLOPE_MICROVOLTS_PER_CELSIUS * ADC_FULL_SCALE;
+        let offset = if numerator >= 0 {
+            (numerator + denominator / 2) / denominator
+        } else {
+            (numerator - denominator / 2) / denominator
+        };
+        // Every 12-bit sample maps to roughly -148 °C..437 °C, well inside an i16 of tenths.
+        Self((SENSOR_REFERENCE_DECI_CELSIUS + offset) as i16)
+    }
+
+    pub const fn to_be_bytes(self) -> [u8; RESPONSE_LEN] {
+        self.0.to_be_bytes()
+    }
+
+    /// Parse a temperature response. Returns `None` unless it is exactly [`RESPONSE_LEN`] bytes.
+    pub fn from_be_bytes(bytes: &[u8]) -> Option<Self> {
+        let bytes: [u8; RESPONSE_LEN] = bytes.try_into().ok()?;
+        Some(Self(i16::from_be_bytes(bytes)))
+    }
+}
+
+/// Renders as `27.3 °C`.
+impl fmt::Display for DeciCelsius {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let magnitude = self.0.unsigned_abs();
+        write!(f, "{sign}{}.{} °C", magnitude / 10, magnitude % 10)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_samples_convert_to_expected_temperature() {
+        // 876 counts is 0.7058 V, just under the 0.706 V that marks 27 °C.
+        assert_eq!(DeciCelsius::from_raw(876), DeciCelsius(271));
+        // 0.6720 V, 34 mV below the reference.
+        assert_eq!(DeciCelsius::from_raw(834), DeciCelsius(468));
+        assert_eq!(DeciCelsius::from_raw(0), DeciCelsius(4372));
+        assert_eq!(DeciCelsius::from_raw(4095), DeciCelsius(-14798));
+    }
+
+    #[test]
+    fn display_uses_one_decimal_place() {
+        assert_eq!(format!("{}", DeciCelsius(273)), "27.3 °C");
+        assert_eq!(format!("{}", DeciCelsius(-5)), "-0.5 °C");
+        assert_eq!(format!("{}", DeciCelsius(-120)), "-12.0 °C");
+    }
+
+    #[test]
+    fn wire_bytes_round_trip() {
+        let reading = DeciCelsius(-14798);
+        assert_eq!(
+            DeciCelsius::from_be_bytes(&reading.to_be_bytes()),
+            Some(reading)
+        );
+        assert_eq!(DeciCelsius::from_be_bytes(&[0x01]), None);
+    }
+}
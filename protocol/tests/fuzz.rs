@@ -0,0 +1,140 @@
+//! Randomized robustness checks for the parsers that see raw USB input on the firmware.
+//!
+//! Every buffer must come back as `Ok` or `Err`; a panic here is a panic the firmware could hit.
+//! The default run is short enough for every `cargo test`. Set `SITERM_FUZZ_ITERATIONS` for a
+//! longer soak, or use the cargo-fuzz targets in `protocol/fuzz` for coverage-guided fuzzing.
+
+use protocol::{
+    COMMAND_DICTIONARY, Method, Operation, decode_command,
+    host::decode_transport_frame_resyncing,
+    transport::{FrameReader, Framing},
+};
+
+const DEFAULT_ITERATIONS: usize = 5_000;
+/// Longest random buffer; covers a full firmware frame buffer.
+const MAX_LEN: usize = 512;
+
+/// Inputs that have tripped up parsers before or sit on a boundary.
+fn seeds() -> Vec<Vec<u8>> {
+    vec![
+        Vec::new(),
+        vec![0x00],
+        vec![0xFF],
+        vec![Method::I2c.as_byte()],
+        vec![Method::I2c.as_byte(), Operation::Read.as_byte()],
+        vec![
+            Method::I2c.as_byte(),
+            Operation::Write.as_byte(),
+            0,
+            0x50,
+            0x00,
+            0xFF,
+        ],
+        vec![Method::Uart.as_byte(), Operation::Bridge.as_byte(), 0x00],
+        vec![0xFF; MAX_LEN],
+        vec![0x00; MAX_LEN],
+        // Lengths that claim far more data than follows, in both framings.
+        vec![0xFF, 0xFF, 0xFF, 0xFF, 0x0F],
+        vec![0xFF, 0xFF, 0x01],
+    ]
+}
+
+/// xorshift64*, so failures reproduce from the printed seed without pulling in a crate.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn bytes(&mut self) -> Vec<u8> {
+        let len = (self.next() % (MAX_LEN as u64 + 1)) as usize;
+        (0..len).map(|_| self.next() as u8).collect()
+    }
+
+    /// Random bytes behind a real method and operation half of the time, so the argument parsers
+    /// get exercised and not just the method lookup.
+    fn command_bytes(&mut self) -> Vec<u8> {
+        let mut bytes = self.bytes();
+        if self.next().is_multiple_of(2) && bytes.len() >= 2 {
+            let def = &COMMAND_DICTIONARY[self.next() as usize % COMMAND_DICTIONARY.len()];
+            bytes[0] = def.method.as_byte();
+            bytes[1] = def.operation.as_byte();
+            bytes.truncate(2 + (self.next() % 16) as usize);
+        }
+        bytes
+    }
+}
+
+fn iterations() -> usize {
+    std::env::var("SITERM_FUZZ_ITERATIONS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_ITERATIONS)
+}
+
+fn inputs() -> impl Iterator<Item = Vec<u8>> {
+    let mut rng = Rng(0x5172_7E12_D0C0_FFEE);
+    seeds()
+        .into_iter()
+        .chain((0..iterations()).map(move |_| rng.command_bytes()))
+}
+
+#[test]
+fn decode_command_never_panics() {
+    for input in inputs() {
+        if let Ok(command) = decode_command(&input) {
+            let _ = command.to_string();
+        }
+    }
+}
+
+#[test]
+fn frame_decoders_never_panic() {
+    for input in inputs() {
+        for framing in Framing::ALL {
+            let _ = framing.take_from_bytes(&input);
+            let _ = decode_transport_frame_resyncing(&input, framing);
+        }
+    }
+}
+
+#[test]
+fn frame_reader_survives_arbitrary_reads() {
+    for input in inputs() {
+        for framing in Framing::ALL {
+            let mut reader = FrameReader::<MAX_LEN>::new();
+            reader.set_framing(framing);
+            for chunk in input.chunks(64) {
+                let mut rest = chunk;
+                while !rest.is_empty() {
+                    let accepted = reader.push(rest);
+                    while let Ok(Some(_)) = reader.next_frame() {}
+                    if accepted == 0 {
+                        reader.clear();
+                    }
+                    rest = &rest[accepted..];
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn valid_frames_survive_random_payloads() {
+    let mut rng = Rng(0xA11C_E5EE_D5EE_D5ED);
+    for _ in 0..iterations() / 10 {
+        let mut payload = rng.bytes();
+        payload.truncate(256);
+        for framing in Framing::ALL {
+            let mut buffer = [0u8; MAX_LEN];
+            let len = framing.encode_into(&payload, &mut buffer).unwrap();
+            let (frame, remaining) = framing.take_from_bytes(&buffer[..len]).unwrap();
+            assert_eq!(frame.payload, payload.as_slice());
+            assert!(remaining.is_empty());
+        }
+    }
+}
@@ -0,0 +1,550 @@
+//! Round-trip checks between the host encoder and the firmware decoder.
+//!
+//! Adding a command should only require a new row in `VALID` (and, if it has failure modes of its
+//! own, in `MALFORMED_INPUT`/`MALFORMED_WIRE`).
+
+use protocol::{
+    Command, DEFAULT_I2C_BUS, I2C_BUS_COUNT, I2cBus, Method, Operation, ProtocolError,
+    SPI_CS_COUNT, decode_command,
+    host::{EncodeError, encode_command},
+    response::{ResponseBuilder, ResponseFormat, split_tag, tag_byte},
+};
+
+const VALID: &[(&str, Command<'static>)] = &[
+    (
+        "echo hello world",
+        Command::EchoWrite {
+            payload: b"hello world",
+        },
+    ),
+    ("echo", Command::EchoWrite { payload: b"" }),
+    (
+        "i2c read 0x40 0x11 0x04",
+        Command::I2cRead {
+            bus: I2cBus::DEFAULT,
+            address: 0x40,
+            register: 0x11,
+            length: 4,
+        },
+    ),
+    (
+        "i2c r 0 1 255",
+        Command::I2cRead {
+            bus: I2cBus::DEFAULT,
+            address: 0x00,
+            register: 0x01,
+            length: 255,
+        },
+    ),
+    (
+        "i2c write 0x50 0x20 0xAA 0xBB",
+        Command::I2cWrite {
+            bus: I2cBus::DEFAULT,
+            address: 0x50,
+            register: 0x20,
+            payload: &[0xAA, 0xBB],
+        },
+    ),
+    (
+        "i2c read --bus 0 0x48 0x00 2",
+        Command::I2cRead {
+            bus: I2cBus::I2c0,
+            address: 0x48,
+            register: 0x00,
+            length: 2,
+        },
+    ),
+    (
+        "i2c write --bus 0 0x50 0x20 0xAA",
+        Command::I2cWrite {
+            bus: I2cBus::I2c0,
+            address: 0x50,
+            register: 0x20,
+            payload: &[0xAA],
+        },
+    ),
+    (
+        "i2c w 0x50 0x20 0b1",
+        Command::I2cWrite {
+            bus: I2cBus::DEFAULT,
+            address: 0x50,
+            register: 0x20,
+            payload: &[0x01],
+        },
+    ),
+    (
+        "i2c readm 0x48 0x00 0x01 0x02",
+        Command::I2cReadMulti {
+            bus: I2cBus::DEFAULT,
+            address: 0x48,
+            registers: &[0x00, 0x01, 0x02],
+        },
+    ),
+    (
+        "i2c readm --bus 0 0x48 0x0F",
+        Command::I2cReadMulti {
+            bus: I2cBus::I2c0,
+            address: 0x48,
+            registers: &[0x0F],
+        },
+    ),
+    (
+        "spi read 0 0x8F 2",
+        Command::SpiRead {
+            cs: 0,
+            register: 0x8F,
+            length: 2,
+        },
+    ),
+    (
+        "spi w 1 0x20 0x47 0x00",
+        Command::SpiWrite {
+            cs: 1,
+            register: 0x20,
+            payload: &[0x47, 0x00],
+        },
+    ),
+    (
+        "spi config mode1 8000",
+        Command::SpiConfig {
+            mode: 1,
+            freq_khz: 8_000,
+        },
+    ),
+    ("temp", Command::Temperature),
+    ("TEMP # board sensor", Command::Temperature),
+    ("config", Command::GetConfig),
+    ("config reset", Command::ResetConfig),
+    ("CONFIG Reset # back to defaults", Command::ResetConfig),
+    ("identify", Command::Identify),
+    ("stats", Command::Stats),
+    ("selftest", Command::SelfTest),
+    ("ResetReason", Command::ResetReason),
+    ("info", Command::Info),
+    (
+        "format text",
+        Command::SetResponseFormat {
+            format: ResponseFormat::Text,
+        },
+    ),
+    (
+        "FORMAT Bytes",
+        Command::SetResponseFormat {
+            format: ResponseFormat::Bytes,
+        },
+    ),
+    (
+        "format auto",
+        Command::SetResponseFormat {
+            format: ResponseFormat::Auto,
+        },
+    ),
+    ("uart bridge 115200", Command::UartBridge { baud: 115_200 }),
+    ("uart bridge 0x2580", Command::UartBridge { baud: 9_600 }),
+    (
+        "heartbeat on 2000",
+        Command::Heartbeat {
+            interval_ms: 2_000,
+            enable: true,
+        },
+    ),
+    (
+        "heartbeat off",
+        Command::Heartbeat {
+            interval_ms: 0,
+            enable: false,
+        },
+    ),
+];
+
+const MALFORMED_INPUT: &[(&str, EncodeError)] = &[
+    ("", EncodeError::Empty),
+    ("foo", EncodeError::UnknownMethod),
+    ("i2c", EncodeError::MissingOperation),
+    ("heartbeat", EncodeError::MissingArgument { index: 1 }),
+    ("heartbeat on 0", EncodeError::InvalidArgument { index: 2 }),
+    ("i2c peek 0x40", EncodeError::UnknownOperation),
+    ("i2c read 0x40", EncodeError::MissingArgument { index: 3 }),
+    (
+        "i2c read 0x40 0x11 4 5",
+        EncodeError::UnexpectedArgument { index: 5 },
+    ),
+    (
+        "i2c write 0x40 0x11",
+        EncodeError::MissingArgument { index: 4 },
+    ),
+    (
+        "i2c write 0x40 0x11 0x100",
+        EncodeError::InvalidArgument { index: 4 },
+    ),
+    ("i2c read --bus 2 0x40 0x11 4", EncodeError::InvalidBus),
+    (
+        "i2c read 0x90 0x11 4",
+        EncodeError::InvalidAddress {
+            index: 2,
+            address: 0x90,
+        },
+    ),
+    ("i2c write --bus", EncodeError::InvalidBus),
+    (
+        "spi read 2 0x0F 1",
+        EncodeError::InvalidArgument { index: 2 },
+    ),
+    ("spi read 0 0x0F", EncodeError::MissingArgument { index: 4 }),
+    (
+        "spi write 0 0x20",
+        EncodeError::MissingArgument { index: 4 },
+    ),
+    ("i2c readm 0x48", EncodeError::MissingArgument { index: 3 }),
+    (
+        "i2c readm 0x48 0x100",
+        EncodeError::InvalidArgument { index: 3 },
+    ),
+    ("temp read", EncodeError::UnexpectedArgument { index: 1 }),
+    ("config spi", EncodeError::UnexpectedArgument { index: 1 }),
+    (
+        "config reset all",
+        EncodeError::UnexpectedArgument { index: 2 },
+    ),
+    ("format", EncodeError::MissingArgument { index: 1 }),
+    ("format utf8", EncodeError::InvalidArgument { index: 1 }),
+    (
+        "format text now",
+        EncodeError::UnexpectedArgument { index: 2 },
+    ),
+    ("uart bridge", EncodeError::MissingArgument { index: 2 }),
+    ("uart bridge 0", EncodeError::InvalidArgument { index: 2 }),
+    (
+        "uart bridge 9600 8N1",
+        EncodeError::UnexpectedArgument { index: 3 },
+    ),
+    (
+        "uart read",
+        EncodeError::UnsupportedOperation {
+            method: Method::Uart,
+            operation: Operation::Read,
+        },
+    ),
+];
+
+const MALFORMED_WIRE: &[(&[u8], ProtocolError)] = &[
+    (&[], ProtocolError::Empty),
+    (&[0xFF], ProtocolError::UnknownMethod(0xFF)),
+    (&[Method::I2c.as_byte()], ProtocolError::Empty),
+    (
+        &[Method::I2c.as_byte(), 0xFF],
+        ProtocolError::UnknownOperation(0xFF),
+    ),
+    (
+        &[Method::I2c.as_byte(), Operation::Read.as_byte(), 0x40, 0x11],
+        ProtocolError::MalformedPayload {
+            method: Method::I2c,
+            operation: Operation::Read,
+        },
+    ),
+    (
+        &[
+            Method::I2c.as_byte(),
+            Operation::ReadMulti.as_byte(),
+            DEFAULT_I2C_BUS,
+            0x48,
+            0x00,
+        ],
+        ProtocolError::MalformedPayload {
+            method: Method::I2c,
+            operation: Operation::ReadMulti,
+        },
+    ),
+    (
+        &[
+            Method::I2c.as_byte(),
+            Operation::Write.as_byte(),
+            DEFAULT_I2C_BUS,
+            0x40,
+            0x11,
+            0x02,
+            0xAA,
+        ],
+        ProtocolError::MalformedPayload {
+            method: Method::I2c,
+            operation: Operation::Write,
+        },
+    ),
+    (
+        &[
+            Method::I2c.as_byte(),
+            Operation::Read.as_byte(),
+            I2C_BUS_COUNT,
+            0x40,
+            0x11,
+            0x04,
+        ],
+        ProtocolError::UnknownBus(I2C_BUS_COUNT),
+    ),
+    (
+        &[
+            Method::I2c.as_byte(),
+            Operation::Write.as_byte(),
+            DEFAULT_I2C_BUS,
+            0x90,
+            0x11,
+            0x01,
+            0xAA,
+        ],
+        ProtocolError::InvalidAddress(0x90),
+    ),
+    (
+        &[
+            Method::Spi.as_byte(),
+            Operation::Read.as_byte(),
+            SPI_CS_COUNT,
+            0x0F,
+            0x01,
+        ],
+        ProtocolError::UnknownChipSelect(SPI_CS_COUNT),
+    ),
+    (
+        &[
+            Method::Spi.as_byte(),
+            Operation::Write.as_byte(),
+            0x00,
+            0x20,
+            0x02,
+            0xAA,
+        ],
+        ProtocolError::MalformedPayload {
+            method: Method::Spi,
+            operation: Operation::Write,
+        },
+    ),
+    (
+        &[Method::Temp.as_byte(), Operation::Read.as_byte(), 0x00],
+        ProtocolError::MalformedPayload {
+            method: Method::Temp,
+            operation: Operation::Read,
+        },
+    ),
+    (
+        &[Method::Config.as_byte(), Operation::Read.as_byte(), 0x00],
+        ProtocolError::MalformedPayload {
+            method: Method::Config,
+            operation: Operation::Read,
+        },
+    ),
+    (
+        &[Method::Identify.as_byte(), Operation::Write.as_byte(), 0x00],
+        ProtocolError::MalformedPayload {
+            method: Method::Identify,
+            operation: Operation::Write,
+        },
+    ),
+    (
+        &[Method::Config.as_byte(), Operation::Reset.as_byte(), 0x00],
+        ProtocolError::MalformedPayload {
+            method: Method::Config,
+            operation: Operation::Reset,
+        },
+    ),
+    (
+        &[Method::Format.as_byte(), Operation::Write.as_byte(), 0x03],
+        ProtocolError::MalformedPayload {
+            method: Method::Format,
+            operation: Operation::Write,
+        },
+    ),
+    (
+        &[Method::Stats.as_byte(), Operation::Read.as_byte(), 0x00],
+        ProtocolError::MalformedPayload {
+            method: Method::Stats,
+            operation: Operation::Read,
+        },
+    ),
+    (
+        &[Method::SelfTest.as_byte(), Operation::Read.as_byte(), 0x00],
+        ProtocolError::MalformedPayload {
+            method: Method::SelfTest,
+            operation: Operation::Read,
+        },
+    ),
+    (
+        &[
+            Method::ResetReason.as_byte(),
+            Operation::Read.as_byte(),
+            0x00,
+        ],
+        ProtocolError::MalformedPayload {
+            method: Method::ResetReason,
+            operation: Operation::Read,
+        },
+    ),
+    (
+        &[Method::Info.as_byte(), Operation::Read.as_byte(), 0x00],
+        ProtocolError::MalformedPayload {
+            method: Method::Info,
+            operation: Operation::Read,
+        },
+    ),
+    (
+        &[
+            Method::Uart.as_byte(),
+            Operation::Bridge.as_byte(),
+            0x00,
+            0x01,
+        ],
+        ProtocolError::MalformedPayload {
+            method: Method::Uart,
+            operation: Operation::Bridge,
+        },
+    ),
+    (
+        &[Method::Echo.as_byte(), Operation::Read.as_byte()],
+        ProtocolError::UnsupportedOperation {
+            method: Method::Echo,
+            operation: Operation::Read,
+        },
+    ),
+];
+
+#[test]
+fn encoded_commands_decode_to_expected_variant() {
+    for (input, expected) in VALID {
+        let encoded = encode_command(input).unwrap_or_else(|err| panic!("{input}: {err:?}"));
+        let decoded = decode_command(&encoded).unwrap_or_else(|err| panic!("{input}: {err:?}"));
+        assert_eq!(&decoded, expected, "{input}");
+    }
+}
+
+#[test]
+fn malformed_input_is_rejected_by_encoder() {
+    for (input, expected) in MALFORMED_INPUT {
+        assert_eq!(encode_command(input), Err(*expected), "{input:?}");
+    }
+}
+
+#[test]
+fn malformed_wire_bytes_are_rejected_by_decoder() {
+    for (bytes, expected) in MALFORMED_WIRE {
+        assert_eq!(decode_command(bytes), Err(*expected), "{bytes:02X?}");
+    }
+}
+
+#[test]
+fn truncated_encodings_never_decode() {
+    for (input, _) in VALID
+        .iter()
+        .filter(|(_, cmd)| !matches!(cmd, Command::EchoWrite { .. }))
+    {
+        let encoded = encode_command(input).unwrap();
+        for len in 0..encoded.len() {
+            assert!(
+                decode_command(&encoded[..len]).is_err(),
+                "{input} truncated to {len} bytes decoded"
+            );
+        }
+    }
+}
+
+#[test]
+fn one_byte_short_payloads_are_malformed() {
+    // Echo has no minimum length and temp, config, config reset, identify, stats, selftest,
+    // resetreason and info have no payload to cut short.
+    for (input, _) in VALID.iter().filter(|(_, cmd)| {
+        !matches!(
+            cmd,
+            Command::EchoWrite { .. }
+                | Command::Temperature
+                | Command::GetConfig
+                | Command::ResetConfig
+                | Command::Identify
+                | Command::Stats
+                | Command::SelfTest
+                | Command::ResetReason
+                | Command::Info
+        )
+    }) {
+        let encoded = encode_command(input).unwrap();
+        let method = Method::from_byte(encoded[0]).unwrap();
+        let operation = Operation::from_byte(encoded[1]).unwrap();
+        assert_eq!(
+            decode_command(&encoded[..encoded.len() - 1]),
+            Err(ProtocolError::MalformedPayload { method, operation }),
+            "{input}"
+        );
+    }
+}
+
+/// The firmware tags a response with the method of the command it ran; the host reads it back.
+fn tagged_reply(input: &str, payload: &[u8]) -> Option<Method> {
+    let encoded = encode_command(input).unwrap();
+    let command = decode_command(&encoded).unwrap();
+    let mut response = ResponseBuilder::<16>::new();
+    response.tag(command.method());
+    response.ok(payload).unwrap();
+
+    let wire = [
+        &[tag_byte(response.method(), ResponseFormat::Auto)],
+        response.as_bytes(),
+    ]
+    .concat();
+    let (method, _, rest) = split_tag(&wire).unwrap();
+    assert_eq!(rest, payload);
+    method
+}
+
+#[test]
+fn responses_are_tagged_with_the_command_method() {
+    assert_eq!(
+        tagged_reply("i2c read 0x48 0x00 2", &[0x12, 0x34]),
+        Some(Method::I2c)
+    );
+    assert_eq!(tagged_reply("echo hi", b"hi"), Some(Method::Echo));
+    assert_eq!(tagged_reply("temp", &[0x00, 0xFA]), Some(Method::Temp));
+}
+
+#[test]
+fn i2c_write_wire_layout_is_pinned() {
+    let encoded = encode_command("i2c write 0x50 0x20 0x01 0x02 0x03").unwrap();
+    assert_eq!(
+        encoded,
+        [
+            Method::I2c.as_byte(),
+            Operation::Write.as_byte(),
+            DEFAULT_I2C_BUS,
+            0x50,
+            0x20,
+            0x03,
+            0x01,
+            0x02,
+            0x03,
+        ]
+    );
+
+    // The count byte always describes exactly the bytes that follow it.
+    let count = encoded[5] as usize;
+    assert_eq!(count, encoded.len() - 6);
+    match decode_command(&encoded).unwrap() {
+        Command::I2cWrite { payload, .. } => assert_eq!(payload.len(), count),
+        other => panic!("unexpected command {other:?}"),
+    }
+}
+
+#[test]
+fn i2c_write_count_mismatch_is_rejected() {
+    let malformed = ProtocolError::MalformedPayload {
+        method: Method::I2c,
+        operation: Operation::Write,
+    };
+    let encoded = encode_command("i2c write 0x50 0x20 0xAA 0xBB").unwrap();
+
+    let mut overstated = encoded.clone();
+    overstated[5] += 1;
+    assert_eq!(decode_command(&overstated), Err(malformed));
+
+    let mut understated = encoded.clone();
+    understated[5] -= 1;
+    assert_eq!(decode_command(&understated), Err(malformed));
+
+    let mut trailing = encoded;
+    trailing.push(0xCC);
+    assert_eq!(decode_command(&trailing), Err(malformed));
+}
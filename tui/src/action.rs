@@ -1,6 +1,9 @@
+use protocol::{Method, device_info::DeviceInfo, response::ResponseFormat};
 use serde::{Deserialize, Serialize};
 use strum::Display;
 
+use crate::transport::ConnectionInfo;
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum DeviceMessage {
     Text(String),
@@ -23,11 +26,38 @@ pub enum Action {
     ShowError(String),
     RefreshPorts,
     PortsUpdated(Vec<String>),
-    Connect { port: String, baud_rate: u32 },
-    ConnectionEstablished { port: String, baud_rate: u32 },
+    /// Listing the serial ports failed; the message says why.
+    PortListFailed(String),
+    Connect {
+        port: String,
+        baud_rate: u32,
+    },
+    ConnectionEstablished(ConnectionInfo),
+    /// What the connected device reported about itself when the session started.
+    #[serde(skip)]
+    DeviceInfo(DeviceInfo),
     ConnectionFailed(String),
+    /// Close the session on purpose and go back to the preconnect screen.
+    Disconnect,
+    /// A session closed by [`Action::Disconnect`] ended; the message summarises it.
+    Disconnected(String),
     SendCommand(String),
+    /// Bytes typed in keystroke mode, for the bridged UART.
+    SendKeystrokes(Vec<u8>),
     CommandSent(String),
+    /// The serial writer put this many bytes on the wire for the last command.
+    FrameSent(usize),
     IncomingMessage(DeviceMessage),
+    /// A response the device tagged with the method whose handler produced it and the format the
+    /// session asked for.
+    TaggedResponse(Method, ResponseFormat, Vec<u8>),
+    /// The device opened (`Some(baud)`) or closed a UART bridge.
+    BridgeChanged(Option<u32>),
+    /// The device sent an idle heartbeat; it only refreshes the link indicator.
+    Heartbeat,
+    /// Bytes read so far and in total by a running `i2c dump`; `None` once it finishes.
+    DumpProgress(Option<(usize, usize)>),
     ToggleHelp,
+    /// Switch between sending commands and only showing the bytes they encode to.
+    ToggleDryRun,
 }
@@ -0,0 +1,229 @@
+//! User-defined command aliases.
+//!
+//! Aliases are read from [`ALIASES_FILE`] in the config directory, one `name = command` per line,
+//! with blank lines and `#` comments ignored:
+//!
+//! ```text
+//! # Two bytes from the sensor at 0x48
+//! sensor = i2c read 0x48 0x00 2
+//! poke = i2c write 0x50 $1 $2
+//! ```
+//!
+//! A typed line whose first word is an alias is expanded before it is encoded. `$1` to `$9` in the
+//! expansion are replaced by the words typed after the alias, and words no placeholder used are
+//! appended, so `sensor as i16be` becomes `i2c read 0x48 0x00 2 as i16be`. An expansion may start
+//! with another alias, up to [`MAX_ALIAS_DEPTH`] deep.
+
+use std::{collections::HashMap, fmt};
+
+use color_eyre::Result;
+use protocol::host::strip_comment;
+
+use crate::config;
+
+pub const ALIASES_FILE: &str = "aliases.siterm";
+/// Most aliases one line may expand through.
+pub const MAX_ALIAS_DEPTH: usize = 8;
+
+/// Why a line could not be expanded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AliasError {
+    /// The aliases named expand into each other; the first name is repeated at the end.
+    Cycle(Vec<String>),
+    /// The line went through more than [`MAX_ALIAS_DEPTH`] aliases.
+    TooDeep(String),
+    /// The alias uses `$index` but fewer arguments were given.
+    MissingArgument { alias: String, index: usize },
+}
+
+impl fmt::Display for AliasError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AliasError::Cycle(chain) => write!(f, "alias cycle: {}", chain.join(" -> ")),
+            AliasError::TooDeep(alias) => write!(
+                f,
+                "alias `{alias}` expands through more than {MAX_ALIAS_DEPTH} aliases"
+            ),
+            AliasError::MissingArgument { alias, index } => {
+                write!(f, "alias `{alias}` needs an argument for ${index}")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Aliases {
+    definitions: HashMap<String, String>,
+}
+
+impl Aliases {
+    /// Parse alias definitions. The error names the offending line.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut definitions = HashMap::new();
+        for (idx, line) in text.lines().enumerate() {
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            let number = idx + 1;
+            let Some((name, command)) = line.split_once('=') else {
+                return Err(format!("line {number}: expected `name = command`"));
+            };
+            let (name, command) = (name.trim(), command.trim());
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                return Err(format!(
+                    "line {number}: alias names are a single word, got `{name}`"
+                ));
+            }
+            if command.is_empty() {
+                return Err(format!("line {number}: alias `{name}` has no command"));
+            }
+            if definitions
+                .insert(name.to_owned(), command.to_owned())
+                .is_some()
+            {
+                return Err(format!("line {number}: alias `{name}` is already defined"));
+            }
+        }
+        Ok(Self { definitions })
+    }
+
+    /// Read the aliases file from the config directory. A missing file means no aliases.
+    pub fn load() -> Result<Self> {
+        config::load_file(ALIASES_FILE, Self::parse)
+    }
+
+    /// Expand `line` until its first word is no longer an alias. A line that doesn't start with an
+    /// alias is returned unchanged.
+    pub fn expand(&self, line: &str) -> Result<String, AliasError> {
+        let mut chain: Vec<String> = Vec::new();
+        let mut current = line.to_owned();
+        loop {
+            let mut words = current.split_whitespace();
+            let Some((name, body)) = words
+                .next()
+                .and_then(|name| self.definitions.get_key_value(name))
+            else {
+                return Ok(current);
+            };
+            if let Some(start) = chain.iter().position(|seen| seen == name) {
+                let mut cycle = chain.split_off(start);
+                cycle.push(name.clone());
+                return Err(AliasError::Cycle(cycle));
+            }
+            if chain.len() == MAX_ALIAS_DEPTH {
+                return Err(AliasError::TooDeep(chain[0].clone()));
+            }
+            let args: Vec<&str> = words.collect();
+            let expanded = substitute(name, body, &args)?;
+            chain.push(name.clone());
+            current = expanded;
+        }
+    }
+}
+
+/// Fill `$1`..`$9` in `body` from `args` and append the arguments after the last one used.
+fn substitute(alias: &str, body: &str, args: &[&str]) -> Result<String, AliasError> {
+    let mut expanded = String::with_capacity(body.len());
+    let mut used = 0;
+    let mut chars = body.chars().peekable();
+    while let Some(ch) = chars.next() {
+        let index = match (ch, chars.peek().and_then(|next| next.to_digit(10))) {
+            ('$', Some(digit @ 1..=9)) => digit as usize,
+            _ => {
+                expanded.push(ch);
+                continue;
+            }
+        };
+        chars.next();
+        let Some(arg) = args.get(index - 1) else {
+            return Err(AliasError::MissingArgument {
+                alias: alias.to_owned(),
+                index,
+            });
+        };
+        expanded.push_str(arg);
+        used = used.max(index);
+    }
+    for arg in &args[used..] {
+        expanded.push(' ');
+        expanded.push_str(arg);
+    }
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aliases(text: &str) -> Aliases {
+        Aliases::parse(text).unwrap()
+    }
+
+    #[test]
+    fn simple_alias_expands_and_keeps_trailing_words() {
+        let aliases = aliases("# sensor\nsensor = i2c read 0x48 0x00 2 # two bytes\n");
+        assert_eq!(aliases.expand("sensor").unwrap(), "i2c read 0x48 0x00 2");
+        assert_eq!(
+            aliases.expand("  sensor as i16be").unwrap(),
+            "i2c read 0x48 0x00 2 as i16be"
+        );
+        assert_eq!(aliases.expand("echo sensor").unwrap(), "echo sensor");
+    }
+
+    #[test]
+    fn positional_arguments_are_substituted() {
+        let aliases = aliases("poke = i2c write 0x50 $1 $2\nswap = echo $2 $1");
+        assert_eq!(
+            aliases.expand("poke 0x10 0xAA").unwrap(),
+            "i2c write 0x50 0x10 0xAA"
+        );
+        assert_eq!(aliases.expand("swap a b c").unwrap(), "echo b a c");
+        assert_eq!(
+            aliases.expand("poke 0x10"),
+            Err(AliasError::MissingArgument {
+                alias: "poke".into(),
+                index: 2
+            })
+        );
+    }
+
+    #[test]
+    fn aliases_expand_recursively() {
+        let aliases = aliases("reg = i2c read 0x48 $1\nsensor = reg 0x00 2");
+        assert_eq!(aliases.expand("sensor").unwrap(), "i2c read 0x48 0x00 2");
+    }
+
+    #[test]
+    fn cycles_and_deep_chains_are_errors() {
+        let cyclic = aliases("a = b 1\nb = c\nc = a");
+        assert_eq!(
+            cyclic.expand("b").unwrap_err().to_string(),
+            "alias cycle: b -> c -> a -> b"
+        );
+
+        let chain: String = (0..=MAX_ALIAS_DEPTH)
+            .map(|idx| format!("a{idx} = a{}\n", idx + 1))
+            .collect();
+        assert_eq!(
+            aliases(&chain).expand("a0"),
+            Err(AliasError::TooDeep("a0".into()))
+        );
+    }
+
+    #[test]
+    fn bad_definitions_name_their_line() {
+        assert_eq!(
+            Aliases::parse("ok = echo\n\nnot an alias").unwrap_err(),
+            "line 3: expected `name = command`"
+        );
+        assert_eq!(
+            Aliases::parse("two words = echo").unwrap_err(),
+            "line 1: alias names are a single word, got `two words`"
+        );
+        assert_eq!(
+            Aliases::parse("x = echo\nx = temp").unwrap_err(),
+            "line 2: alias `x` is already defined"
+        );
+    }
+}
@@ -1,51 +1,95 @@
 use color_eyre::Result;
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 use ratatui::{
+    Frame,
     layout::{Alignment, Constraint, Direction, Layout},
     prelude::Rect,
-    style::{Color, Modifier, Style, Stylize},
+    style::{Modifier, Stylize},
     text::{Line, Span},
-    widgets::{Block, Borders, Clear, Paragraph, Wrap},
+    widgets::{Block, Clear, Paragraph, Wrap},
 };
 use serde::{Deserialize, Serialize};
 use std::str;
-use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader};
-use tokio::sync::mpsc;
-use tokio::time::timeout;
-use tokio_serial::{SerialPort, SerialPortBuilderExt, SerialStream};
-use tracing::debug;
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, Instant, timeout, timeout_at};
+use tracing::{Instrument, debug, info_span, trace, warn};
 
 use crate::{
     action::{Action, DeviceMessage},
+    alias::{ALIASES_FILE, AliasError, Aliases},
     components::{
-        Component, connecting::ConnectingScreen, error_view::ErrorScreen,
-        preconnect::PreconnectScreen, terminal::TerminalScreen,
+        Component,
+        connecting::ConnectingScreen,
+        error_view::ErrorScreen,
+        preconnect::PreconnectScreen,
+        terminal::{COMPACT_BELOW_ROWS, TerminalScreen, format_hex},
+    },
+    config::{Config, WritePacing},
+    favorites::FAVORITES_FILE,
+    keymap::{KEYMAP_FILE, KeyAction, Keymap},
+    labels::LABELS_FILE,
+    last_connection::LastConnection,
+    latency::{LatencyStats, PING_TIMEOUT, ping_command, pong_payload},
+    pipeline::{
+        Inbound, Outbound, Outgoing, Received, Retransmit, bridge_bytes, default_max_payload,
+        dry_run_lines, format_encode_error, format_transport_error, is_blank_line, is_chunked,
+        payload_to_action, prepare_command, skipped_warning, stalled_warning,
+    },
+    queue::{self, QueueReceiver, QueueSender},
+    session_summary::SessionCounters,
+    simulator::{SIMULATED_PORT, Simulator},
+    theme::Palette,
+    transport::{
+        ConnectionInfo, Serial, SessionMode, Tcp, Transport, link_error, port_list_error,
+        tcp_address, tcp_port_name,
     },
     tui::{Event, Tui},
 };
 
 use protocol::{
-    HANDSHAKE_COMMAND, HANDSHAKE_DELIMITER, HANDSHAKE_RESPONSE, HANDSHAKE_TIMEOUT,
+    COMMAND_DICTIONARY, HANDSHAKE_DELIMITER, PROTOCOL_VERSION_MAJOR,
+    bridge::{BRIDGE_CLOSED, parse_opened_response},
+    device_info::DeviceInfo,
+    flow::{BUSY, MAX_BUSY_WAIT_MS, READY},
+    handshake::{self, HandshakeReply},
+    heartbeat::HEARTBEAT,
     host::{
-        EncodeError, TransportCodecError, encode_command, encode_transport_frame,
-        try_decode_transport_frame,
+        dump::{DumpRequest, chunk_len, plan_chunks},
+        encode_command, encode_transport_frame,
     },
+    nak::NAK,
+    response::ERROR_PREFIX,
+    transport::Framing,
 };
+/// Longest quitting waits for queued commands to go out before dropping them.
+const SHUTDOWN_FLUSH_TIMEOUT: Duration = Duration::from_millis(500);
+/// Longest to wait for each read of an `i2c dump` before abandoning the rest of it.
+const DUMP_READ_TIMEOUT: Duration = Duration::from_secs(1);
+/// Longest to wait for the device's answer to `info` when a session starts.
+const INFO_TIMEOUT: Duration = Duration::from_secs(1);
+/// Longest the writer holds commands for a device that reported busy.
+const MAX_BUSY_WAIT: Duration = Duration::from_millis(MAX_BUSY_WAIT_MS);
+/// Longest the writer holds the next command for the device to answer the last one. A handler
+/// that runs longer reports busy well before this, and the busy wait takes over.
+const ANSWER_TIMEOUT: Duration = Duration::from_secs(1);
+/// Most bytes of boot banner or other chatter skipped while looking for the handshake reply.
+const MAX_HANDSHAKE_CHATTER: usize = 1024;
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
 pub enum Mode {
+    #[default]
     Preconnect,
     Connecting,
     Main,
     Error,
 }
 
-impl Default for Mode {
-    fn default() -> Self {
-        Mode::Preconnect
-    }
-}
-
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 enum HelpContext {
     Preconnect,
@@ -62,7 +106,22 @@ pub struct App {
     help_overlay: Option<HelpContext>,
     action_tx: mpsc::UnboundedSender<Action>,
     action_rx: mpsc::UnboundedReceiver<Action>,
-    serial_tx: Option<mpsc::UnboundedSender<String>>,
+    serial_tx: Option<QueueSender>,
+    /// The task running the current connection, from opening the port until it closes.
+    session_task: Option<JoinHandle<()>>,
+    /// Traffic of the current connection, summarised when it drops.
+    session_counters: Arc<SessionCounters>,
+    /// When the current connection was established, if it was.
+    session_started: Option<Instant>,
+    script: Vec<String>,
+    config: Config,
+    /// Commands are encoded and shown instead of sent.
+    dry_run: bool,
+    aliases: Aliases,
+    /// Whether the session is bridged to the UART, so typed lines are sent as they are.
+    bridged: bool,
+    /// Last error logged from [`Action::Error`], so one that repeats every frame is logged once.
+    last_error: Option<String>,
 }
 
 impl App {
@@ -84,9 +143,37 @@ impl App {
             action_tx,
             action_rx,
             serial_tx: None,
+            session_task: None,
+            session_counters: Arc::default(),
+            session_started: None,
+            script: Vec::new(),
+            config: Config::default(),
+            dry_run: false,
+            aliases: Aliases::default(),
+            bridged: false,
+            last_error: None,
         })
     }
 
+    /// Queue commands to replay once the first connection is established.
+    pub fn script(mut self, commands: Vec<String>) -> Self {
+        self.script = commands;
+        self
+    }
+
+    /// Aliases expanded in each typed command.
+    pub fn aliases(mut self, aliases: Aliases) -> Self {
+        self.aliases = aliases;
+        self
+    }
+
+    /// Settings shared with every component.
+    pub fn config(mut self, config: Config) -> Self {
+        self.dry_run = config.dry_run;
+        self.config = config;
+        self
+    }
+
     pub async fn run(&mut self) -> Result<()> {
         let mut tui = Tui::new()?
             // .mouse(true) // uncomment this line to enable mouse support
@@ -97,6 +184,9 @@ impl App {
         for component in self.components.iter_mut() {
             component.register_action_handler(self.action_tx.clone())?;
         }
+        for component in self.components.iter_mut() {
+            component.register_config_handler(self.config.clone())?;
+        }
         for component in self.components.iter_mut() {
             component.init(tui.size()?)?;
         }
@@ -108,7 +198,7 @@ impl App {
                     match maybe_event {
                         Some(event) => {
                             self.handle_event(event)?;
-                            self.drain_pending_actions(&mut tui)?;
+                            self.drain_pending_actions(&mut tui).await?;
                         }
                         None => break,
                     }
@@ -116,8 +206,8 @@ impl App {
                 maybe_action = self.action_rx.recv() => {
                     match maybe_action {
                         Some(action) => {
-                            self.handle_action(&mut tui, action)?;
-                            self.drain_pending_actions(&mut tui)?;
+                            self.handle_action(&mut tui, action).await?;
+                            self.drain_pending_actions(&mut tui).await?;
                         }
                         None => break,
                     }
@@ -134,7 +224,15 @@ impl App {
                 break;
             }
         }
+        let dropped = self.close_session().await;
         tui.exit()?;
+        if let Some(dropped) = dropped {
+            warn!(dropped, "quit before queued commands were sent");
+            eprintln!(
+                "Warning: {dropped} queued command(s) were not sent within {} ms of quitting.",
+                SHUTDOWN_FLUSH_TIMEOUT.as_millis()
+            );
+        }
         Ok(())
     }
 
@@ -147,7 +245,7 @@ impl App {
             Event::Render => action_tx.send(Action::Render)?,
             Event::Resize(x, y) => action_tx.send(Action::Resize(*x, *y))?,
             Event::Key(key) => {
-                event_consumed = self.handle_key_event(key.clone())?;
+                event_consumed = self.handle_key_event(*key)?;
             }
             _ => {}
         }
@@ -177,7 +275,7 @@ impl App {
         Ok(false)
     }
 
-    fn handle_action(&mut self, tui: &mut Tui, action: Action) -> Result<()> {
+    async fn handle_action(&mut self, tui: &mut Tui, action: Action) -> Result<()> {
         if !matches!(action, Action::Tick | Action::Render) {
             debug!("{action:?}");
         }
@@ -212,49 +310,150 @@ impl App {
                 self.action_tx.send(Action::Render)?;
             }
             Action::RefreshPorts => {
-                let ports = tokio_serial::available_ports()
-                    .map(|v| v.iter().map(|p| p.port_name.clone()).collect())?;
+                let mut failure = None;
+                let ports = if self.config.simulate {
+                    vec![SIMULATED_PORT.to_owned()]
+                } else {
+                    // A failed listing leaves the UI up with whatever can still be offered.
+                    let mut ports = match tokio_serial::available_ports() {
+                        Ok(ports) => ports.into_iter().map(|p| p.port_name).collect(),
+                        Err(err) => {
+                            warn!(error = ?err, "failed to list serial ports");
+                            failure = Some(port_list_error(
+                                &err,
+                                self.config.keymap.binding(KeyAction::RefreshPorts),
+                            ));
+                            Vec::new()
+                        }
+                    };
+                    ports.extend(self.config.tcp.as_deref().map(tcp_port_name));
+                    ports
+                };
                 self.action_tx.send(Action::PortsUpdated(ports))?;
+                // After the list, whose own status line would otherwise replace the reason.
+                if let Some(message) = failure {
+                    self.action_tx.send(Action::PortListFailed(message))?;
+                }
             }
-            Action::PortsUpdated(_) => {}
+            Action::PortsUpdated(_) | Action::PortListFailed(_) => {}
             Action::Connect { port, baud_rate } => {
+                // A reconnect replaces a running session, which must let go of the port first.
+                if let Some(dropped) = self.close_session().await {
+                    warn!(dropped, "reconnected before queued commands were sent");
+                }
+                self.drop_closed_session_failures();
                 self.mode = Mode::Connecting;
+                self.bridged = false;
                 self.action_tx.send(Action::ShowConnecting)?;
-                self.serial_tx = None;
                 self.spawn_connection_task(port, baud_rate);
             }
-            Action::ConnectionEstablished { port, baud_rate } => {
+            Action::ConnectionEstablished(info) => {
+                self.session_started = Some(Instant::now());
+                // The simulator isn't a port worth coming back to on a real run.
+                if !self.config.simulate {
+                    let last = LastConnection {
+                        port: info.port.clone(),
+                        baud_rate: info.baud_rate,
+                    };
+                    if let Err(err) = last.save() {
+                        warn!(error = ?err, "failed to remember the connection");
+                    }
+                }
                 self.action_tx.send(Action::ShowMain)?;
                 self.action_tx
                     .send(Action::IncomingMessage(DeviceMessage::Text(format!(
-                        "Connected to {port} @ {baud_rate} baud"
+                        "Connected to {}",
+                        info.summary()
                     ))))?;
+                for command in std::mem::take(&mut self.script) {
+                    self.action_tx.send(Action::SendCommand(command))?;
+                }
             }
             Action::ConnectionFailed(message) => {
                 self.serial_tx = None;
-                self.action_tx.send(Action::ShowError(message.clone()))?;
+                let message = match self.session_started.take() {
+                    Some(started) => format!(
+                        "{message}\n\n{}",
+                        self.session_counters.snapshot().summary(started.elapsed())
+                    ),
+                    None => message,
+                };
+                self.action_tx.send(Action::ShowError(message))?;
             }
-            Action::SendCommand(command) => match &self.serial_tx {
-                Some(tx) => match tx.send(command.clone()) {
-                    Ok(_) => {
-                        self.action_tx.send(Action::CommandSent(command))?;
+            Action::Disconnect => self.disconnect().await?,
+            Action::Disconnected(_) => {}
+            Action::ToggleDryRun => self.dry_run = !self.dry_run,
+            // Enter on whitespace, from any source, sends nothing and records nothing.
+            Action::SendCommand(command) if is_blank_line(&command) => {}
+            Action::SendCommand(command) => match self.expand_aliases(&command) {
+                Ok(command) if self.dry_run => {
+                    let lines = dry_run_lines(&command, self.config.framing);
+                    // Recorded first so the dry-run lines use up the command's value hint.
+                    self.action_tx.send(Action::CommandSent(command))?;
+                    for line in lines {
+                        self.action_tx
+                            .send(Action::IncomingMessage(DeviceMessage::Text(line)))?;
                     }
-                    Err(_) => {
-                        self.serial_tx = None;
+                }
+                Ok(command) => match &self.serial_tx {
+                    Some(tx) => match tx.send(Outbound::Line(command.clone())) {
+                        Ok(_) => {
+                            self.action_tx.send(Action::CommandSent(command))?;
+                        }
+                        Err(_) => {
+                            self.serial_tx = None;
+                            self.action_tx.send(Action::ConnectionFailed(
+                                "Serial writer is unavailable.".into(),
+                            ))?;
+                        }
+                    },
+                    None => {
                         self.action_tx.send(Action::ConnectionFailed(
-                            "Serial writer is unavailable.".into(),
+                            "Serial connection is not ready.".into(),
                         ))?;
                     }
                 },
-                None => {
+                Err(err) => {
+                    // Kept in the history so the line can be fixed up and resent.
+                    self.action_tx.send(Action::CommandSent(command))?;
+                    self.action_tx
+                        .send(Action::IncomingMessage(DeviceMessage::Text(format!(
+                            "Error: {err}"
+                        ))))?;
+                }
+            },
+            Action::SendKeystrokes(bytes) if self.dry_run => {
+                self.action_tx
+                    .send(Action::IncomingMessage(DeviceMessage::Text(format!(
+                        "Dry run: keystrokes {} (not sent)",
+                        format_hex(&bytes)
+                    ))))?;
+            }
+            Action::SendKeystrokes(bytes) => {
+                if let Some(tx) = &self.serial_tx
+                    && tx.send(Outbound::Keystrokes(bytes)).is_err()
+                {
+                    self.serial_tx = None;
                     self.action_tx.send(Action::ConnectionFailed(
-                        "Serial connection is not ready.".into(),
+                        "Serial writer is unavailable.".into(),
                     ))?;
                 }
-            },
+            }
             Action::CommandSent(_) => {}
+            Action::FrameSent(_) => {}
+            Action::DeviceInfo(_) => {}
             Action::IncomingMessage(_) => {}
-            Action::Error(_) => {}
+            Action::TaggedResponse(..) => {}
+            Action::BridgeChanged(baud) => self.bridged = baud.is_some(),
+            Action::DumpProgress(_) => {}
+            Action::Heartbeat => {}
+            // The screens show the error themselves; the app only logs it.
+            Action::Error(message) => {
+                if self.last_error.as_ref() != Some(&message) {
+                    warn!(%message, "reported error");
+                    self.last_error = Some(message);
+                }
+            }
             Action::ToggleHelp => {
                 if let Some(context) = self.help_context_for_mode() {
                     if self.help_overlay == Some(context) {
@@ -274,9 +473,9 @@ impl App {
         Ok(())
     }
 
-    fn drain_pending_actions(&mut self, tui: &mut Tui) -> Result<()> {
+    async fn drain_pending_actions(&mut self, tui: &mut Tui) -> Result<()> {
         while let Ok(action) = self.action_rx.try_recv() {
-            self.handle_action(tui, action)?;
+            self.handle_action(tui, action).await?;
         }
         Ok(())
     }
@@ -288,33 +487,34 @@ impl App {
     }
 
     fn render(&mut self, tui: &mut Tui) -> Result<()> {
-        let help_overlay = self.help_overlay;
-        tui.draw(|frame| {
-            for component in self.components.iter_mut() {
-                if let Err(err) = component.draw(frame, frame.area()) {
-                    let _ = self
-                        .action_tx
-                        .send(Action::Error(format!("Failed to draw: {:?}", err)));
-                }
-            }
-
-            if let Some(context) = help_overlay {
-                let popup_area = centered_rect(80, 60, frame.area());
-                frame.render_widget(Clear, popup_area);
-                let popup = Paragraph::new(context.body())
-                    .wrap(Wrap { trim: true })
-                    .alignment(Alignment::Left)
-                    .block(
-                        Block::default()
-                            .title(context.title())
-                            .borders(Borders::ALL),
-                    );
-                frame.render_widget(popup, popup_area);
-            }
-        })?;
+        tui.draw(|frame| self.draw(frame))?;
         Ok(())
     }
 
+    /// Draw every component, and the help over them when it is open.
+    fn draw(&mut self, frame: &mut Frame) {
+        let palette = self.config.theme.palette();
+        frame.render_widget(Block::default().style(palette.base), frame.area());
+        for component in self.components.iter_mut() {
+            if let Err(err) = component.draw(frame, frame.area()) {
+                let _ = self
+                    .action_tx
+                    .send(Action::Error(format!("Failed to draw: {:?}", err)));
+            }
+        }
+
+        if let Some(context) = self.help_overlay {
+            let popup_area = centered_rect(80, 60, frame.area());
+            frame.render_widget(Clear, popup_area);
+            let popup = Paragraph::new(context.body(&self.config.keymap, &palette))
+                .wrap(Wrap { trim: true })
+                .alignment(Alignment::Left)
+                .style(palette.base)
+                .block(palette.block().title(context.title()));
+            frame.render_widget(popup, popup_area);
+        }
+    }
+
     fn help_context_for_mode(&self) -> Option<HelpContext> {
         match self.mode {
             Mode::Preconnect => Some(HelpContext::Preconnect),
@@ -323,6 +523,14 @@ impl App {
         }
     }
 
+    /// Expand aliases in a typed command. Lines typed while bridged go to the UART untouched.
+    fn expand_aliases(&self, command: &str) -> Result<String, AliasError> {
+        if self.bridged {
+            return Ok(command.to_owned());
+        }
+        self.aliases.expand(command)
+    }
+
     fn is_ctrl_key(key: &KeyEvent, chr: char) -> bool {
         matches!(
             (key.code, key.modifiers),
@@ -339,8 +547,10 @@ impl HelpContext {
         }
     }
 
-    fn body(self) -> Vec<Line<'static>> {
-        match self {
+    /// The prose for the context, then the key bindings as `keymap` has them.
+    fn body(self, keymap: &Keymap, palette: &Palette) -> Vec<Line<'static>> {
+        let key = |action| keymap.label(action);
+        let intro = match self {
             HelpContext::Preconnect => vec![
                 Line::from("Hello World! Welcome to SiTerm!")
                     .add_modifier(Modifier::ITALIC)
@@ -354,35 +564,261 @@ impl HelpContext {
                     "To get started, select your device in the left hand side of the menu, and the baud rate on to be used for UART communication",
                 ),
                 Line::default(),
-                Line::from(
-                    "You can use the arrow keys to navigate, enter to select, and the r key to refresh available serial ports.",
-                ),
+                Line::from(format!(
+                    "You can use the arrow keys to navigate, enter to select, and the {} key to refresh available serial ports.",
+                    key(KeyAction::RefreshPorts)
+                )),
             ],
-            HelpContext::Connected => vec![
+            HelpContext::Connected => [vec![
                 Line::default(),
                 Line::from(Span::styled("Commands:", Modifier::BOLD)),
                 Line::from("Commands follow the following format with some exceptions:"),
                 Line::default(),
                 Line::from(vec![
-                    Span::styled("protocol ", Style::default().fg(Color::Cyan)),
-                    Span::styled("action ", Style::default().fg(Color::LightCyan)),
-                    Span::styled("payload", Style::default().fg(Color::LightBlue)),
+                    Span::styled("protocol ", palette.gradient[0]),
+                    Span::styled("action ", palette.gradient[1]),
+                    Span::styled("payload", palette.gradient[2]),
                 ]),
                 Line::default(),
+            ],
+            COMMAND_DICTIONARY
+                .iter()
+                .map(|command| Line::from(format!("  {command}")))
+                .collect(),
+            vec![
+                Line::default(),
                 Line::from(
                     "For a full list of currently available and future commands visit: https://github.com/SimonGorbot/SiTerm.",
                 ),
                 Line::default(),
                 Line::from(Span::styled("Views:", Modifier::BOLD)),
                 Line::from("There are 3 avaible views for incoming messages:"),
-                Line::from("1. UTF-8 Encoding, enabled with ctrl+u (default)"),
-                Line::from("2. Binary Encoding, enabled with ctrl+b"),
-                Line::from("3. Hex Encoding, enabled with ctrl+h"),
-            ],
-        }
+                Line::from(format!(
+                    "1. UTF-8 Encoding, enabled with {} (default)",
+                    key(KeyAction::Utf8View)
+                )),
+                Line::from(format!(
+                    "2. Binary Encoding, enabled with {}",
+                    key(KeyAction::BinaryView)
+                )),
+                Line::from(format!(
+                    "3. Hex Encoding, enabled with {}",
+                    key(KeyAction::HexView)
+                )),
+                Line::from(format!(
+                    "{} switches the hex and binary views between spaced, compact, comma and c-array layouts (or start with --byte-style).",
+                    key(KeyAction::ByteStyle)
+                )),
+                Line::default(),
+                Line::from(Span::styled("Scripts:", Modifier::BOLD)),
+                Line::from(format!(
+                    "Press {} to save the command history as a script. Start SiTerm with --script <file> to replay one after connecting.",
+                    key(KeyAction::ExportHistory)
+                )),
+                Line::default(),
+                Line::from(Span::styled("Inspector:", Modifier::BOLD)),
+                Line::from(format!(
+                    "Press {} to inspect the bytes of the latest message. Use ←/→ to pick a byte, ↑/↓ to switch messages and Esc to close.",
+                    key(KeyAction::Inspect)
+                )),
+                Line::from(""),
+                Line::from(Span::styled("Scrollback:", Modifier::BOLD)),
+                Line::from(format!(
+                    "PageUp/PageDown scroll through older messages; scrolling back locks the view and scrolling down to the newest message follows new data again. {} locks or unlocks the view where it is and End returns to the newest message.",
+                    key(KeyAction::ScrollLock)
+                )),
+                Line::from(""),
+                Line::from(Span::styled("Search:", Modifier::BOLD)),
+                Line::from(format!(
+                    "{} starts a search; what you type is highlighted in every message as you type it, and all messages stay in view. A query in lower case ignores case. Enter keeps the highlight, Esc clears it.",
+                    key(KeyAction::Search)
+                )),
+                Line::from(""),
+                Line::from(Span::styled("Reconnect:", Modifier::BOLD)),
+                Line::from(format!(
+                    "{} closes the port and opens it again at the same baud rate, keeping the messages and command history.",
+                    key(KeyAction::Reconnect)
+                )),
+                Line::from(format!(
+                    "{} closes the port and returns to the preconnect screen; commands already queued are sent first, and the messages and history are still there when you connect again.",
+                    key(KeyAction::Disconnect)
+                )),
+                Line::from(""),
+                Line::from(Span::styled("Clearing:", Modifier::BOLD)),
+                Line::from(format!(
+                    "{} clears the device messages and {} clears the command history. Exported scripts are not touched.",
+                    key(KeyAction::ClearMessages),
+                    key(KeyAction::ClearHistory)
+                )),
+                Line::from(""),
+                Line::from(Span::styled("Repeat:", Modifier::BOLD)),
+                Line::from(format!(
+                    "Press {} to send the last command again. {} starts or stops auto-repeat at the --repeat-interval-ms pace.",
+                    key(KeyAction::RepeatLast),
+                    key(KeyAction::ToggleAutoRepeat)
+                )),
+                Line::from(""),
+                Line::from(Span::styled("Dry run:", Modifier::BOLD)),
+                Line::from(format!(
+                    "Press {} (or start with --dry-run) to show the command bytes and framed bytes of each command instead of sending it.",
+                    key(KeyAction::ToggleDryRun)
+                )),
+                Line::from(""),
+                Line::from(Span::styled("Verbose:", Modifier::BOLD)),
+                Line::from(format!(
+                    "Press {} (or start with --verbose) to follow each sent command with the command its bytes decode back to, e.g. `→ i2c read 0x48 0x00 2`.",
+                    key(KeyAction::ToggleVerbose)
+                )),
+                Line::from(
+                    "Start with --show-frames to follow each read with where its frames start and end, e.g. `[frame 0: 0..7 len=3]`, and how many bytes are still waiting to complete a frame.",
+                ),
+                Line::from(""),
+                Line::from(Span::styled("Message timing:", Modifier::BOLD)),
+                Line::from(format!(
+                    "Press {} to start each message with the time since the one before it, e.g. `+12ms`, to spot irregular response intervals.",
+                    key(KeyAction::ToggleDeltas)
+                )),
+                Line::from(""),
+                Line::from(Span::styled("Layout:", Modifier::BOLD)),
+                Line::from(format!(
+                    "Terminals shorter than {COMPACT_BELOW_ROWS} rows get the compact layout: one status line, the command line and the messages, with the history pane hidden (Up/Down still recall). Press {} to switch layouts by hand.",
+                    key(KeyAction::ToggleCompact)
+                )),
+                Line::from(""),
+                Line::from(Span::styled("Favorites:", Modifier::BOLD)),
+                Line::from(format!(
+                    "Put `F1 = command` lines (F1 to F8) in {} under the config directory; the function key then sends its command. Set favorites are listed under the messages.",
+                    FAVORITES_FILE
+                )),
+                Line::from(""),
+                Line::from(Span::styled("Labels:", Modifier::BOLD)),
+                Line::from(format!(
+                    "Rename the prompt and panes with `name = text` lines (prompt, session, command, history, messages) in {} under the config directory; quote text that ends in a space, e.g. `prompt = \"$ \"`. `cat = off` sends the cat away.",
+                    LABELS_FILE
+                )),
+                Line::from(""),
+                Line::from(Span::styled("Aliases:", Modifier::BOLD)),
+                Line::from(format!(
+                    "Define `name = command` lines in {} under the config directory; $1..$9 take the words typed after the alias.",
+                    ALIASES_FILE
+                )),
+                Line::from(""),
+                Line::from(Span::styled("Value hints:", Modifier::BOLD)),
+                Line::from(
+                    "End a command with `as <type>` (u8, i8, u16be, u16le, i16be, i16le, celsius) to show the response decoded next to the raw bytes.",
+                ),
+                Line::from(""),
+                Line::from(Span::styled("UART bridge:", Modifier::BOLD)),
+                Line::from(
+                    "`uart bridge <baud>` pipes the device's UART (GP0 TX, GP1 RX) to SiTerm. Each line you send goes out with CRLF and received bytes are shown raw. Send ~. to return to commands.",
+                ),
+                Line::from(format!(
+                    "While bridged, {} starts keystroke mode: every key goes out as you type it (Enter as CRLF, Backspace as set by --backspace) and replies appear live. Esc stops it.",
+                    key(KeyAction::KeystrokeMode)
+                )),
+                Line::from(
+                    "When most bridged bytes look like line noise (NULs and bytes that aren't UTF-8), the header asks whether the baud rate is wrong. Esc hides that until the next bridge.",
+                ),
+                Line::from(""),
+                Line::from(Span::styled("Register dump:", Modifier::BOLD)),
+                Line::from(
+                    "`i2c dump <address> <start> <length>` reads up to 256 registers as a series of i2c reads and shows them as one message. Ranges past 0xff continue from 0x00.",
+                ),
+                Line::from(
+                    "`i2c readm <address> <register>...` reads one byte from each of up to 32 scattered registers in one command; the reply is shown as `register = value` for each.",
+                ),
+                Line::from(""),
+                Line::from(Span::styled("SPI:", Modifier::BOLD)),
+                Line::from(
+                    "`spi read <cs> <register> <length>` and `spi write <cs> <register> <bytes...>` run one transaction on SPI0 (SCK GP18, MOSI GP19, MISO GP20). CS 0 is GP17 and CS 1 is GP21.",
+                ),
+                Line::from(
+                    "I2C addresses are 7-bit (0x00 to 0x7f). An 8-bit address from a datasheet, such as 0x90, is shifted right by one: 0x48.",
+                ),
+                Line::from(
+                    "Write data can be bytes separated by spaces (`0xAA 0 255`) or a C array literal (`{0xAA, 0x00, 0xFF}`), for both i2c and spi.",
+                ),
+                Line::from(
+                    "The bus starts in mode 0 at 1 MHz; `spi config <mode0-mode3> <kHz>` changes both, from 2 to 62500 kHz.",
+                ),
+                Line::from(""),
+                Line::from(Span::styled("Latency:", Modifier::BOLD)),
+                Line::from(
+                    "`ping [count]` sends count echoes (10 by default) one at a time and reports min/avg/max/stddev round-trip time. A reply missing after a second counts as lost.",
+                ),
+                Line::from(""),
+                Line::from(Span::styled("Temperature:", Modifier::BOLD)),
+                Line::from(
+                    "Send `temp` to read the board's internal temperature sensor; the reply is shown in °C.",
+                ),
+                Line::from(""),
+                Line::from(Span::styled("Heartbeats:", Modifier::BOLD)),
+                Line::from(
+                    "`heartbeat on <ms>` has the device send a heartbeat whenever the link has been idle that long, keeping the link indicator live; `heartbeat off` stops them.",
+                ),
+                Line::from(""),
+                Line::from(Span::styled("Settings:", Modifier::BOLD)),
+                Line::from(
+                    "Send `config` to read back the SPI mode and clock and the heartbeat interval the device is running with.",
+                ),
+                Line::from(
+                    "Send `config reset` to put them all back to their defaults without reconnecting; the reply lists the settings now in effect.",
+                ),
+                Line::from(
+                    "With `--tag-responses`, send `format text` or `format bytes` to have the device mark its replies, and the view switches to UTF-8 or hex to suit them; `format auto` stops marking them.",
+                ),
+                Line::from(""),
+                Line::from(Span::styled("Finding a board:", Modifier::BOLD)),
+                Line::from(
+                    "Send `identify` to flash the device's status LED for 5 seconds, then it goes back to showing the link state.",
+                ),
+                Line::from(
+                    "Send `info` to see what the board reports about itself: its label, the longest i2c read, the fastest UART baud, and its settings. Each session asks for it on connecting to size dumps and baud choices.",
+                ),
+                Line::from(""),
+                Line::from(Span::styled("Link health:", Modifier::BOLD)),
+                Line::from(
+                    "Send `stats` to see how many USB overflows the device has hit this session; a rising count usually means commands are written faster than it can read them, so try pacing writes with `--write-delay-ms`.",
+                ),
+                Line::from(
+                    "After flashing, send `selftest` to step the status LED through its colours, scan both i2c buses with reads only, and check the temperature sensor reads a plausible value. The reply passes or fails each one.",
+                ),
+                Line::from(
+                    "Send `resetreason` after the board restarts unexpectedly to see why it last reset: power-on or brown-out, the RUN pin, a debugger, or the watchdog.",
+                ),
+            ]]
+            .concat(),
+        };
+        [intro, key_bindings(keymap, palette)].concat()
     }
 }
 
+/// One line per single-key action with the key `keymap` gives it, so the help never disagrees
+/// with what the keys do.
+fn key_bindings(keymap: &Keymap, palette: &Palette) -> Vec<Line<'static>> {
+    let labels = KeyAction::ALL.map(|action| (keymap.label(action), action.description()));
+    let width = labels.iter().map(|(key, _)| key.len()).max().unwrap_or(0);
+    [
+        vec![
+            Line::default(),
+            Line::from(Span::styled("Key bindings:", Modifier::BOLD)),
+            Line::from(format!(
+                "Rebind these keys with `action = key` lines (e.g. `hex_view = ctrl+t`) in {KEYMAP_FILE} under the config directory.",
+            )),
+        ],
+        labels
+            .into_iter()
+            .map(|(key, description)| {
+                Line::from(vec![
+                    Span::styled(format!("  {key:<width$}  "), palette.accent),
+                    Span::raw(description),
+                ])
+            })
+            .collect(),
+    ]
+    .concat()
+}
+
 fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
     let vertical = Layout::default()
         .direction(Direction::Vertical)
@@ -406,146 +842,483 @@ fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
 impl App {
     // TODO: Implement timeouts for all steps in connection process.
     fn spawn_connection_task(&mut self, port: String, baud_rate: u32) {
-        let (serial_tx, serial_rx) = mpsc::unbounded_channel::<String>();
+        let (serial_tx, serial_rx) = queue::channel();
         self.serial_tx = Some(serial_tx);
         let action_tx = self.action_tx.clone();
-        tokio::spawn(async move {
-            match App::establish_serial_stream(&port, baud_rate).await {
-                Ok(serial_stream) => {
-                    let _ = action_tx.send(Action::ConnectionEstablished {
-                        port: port.clone(),
-                        baud_rate,
-                    });
-                    let _ = action_tx.send(Action::ShowMain);
-                    App::run_serial_session(serial_stream, serial_rx, action_tx.clone()).await;
-                }
-                Err(message) => {
-                    let _ = action_tx.send(Action::ConnectionFailed(message));
-                }
+        let simulate = self.config.simulate;
+        let options = SessionOptions::from_config(&self.config);
+        self.session_counters = Arc::default();
+        self.session_started = None;
+        let counters = Arc::clone(&self.session_counters);
+        self.session_task = Some(tokio::spawn(async move {
+            if simulate {
+                App::connect(
+                    Simulator, port, baud_rate, serial_rx, action_tx, options, counters,
+                )
+                .await;
+            } else if tcp_address(&port).is_some() {
+                App::connect(
+                    Tcp, port, baud_rate, serial_rx, action_tx, options, counters,
+                )
+                .await;
+            } else {
+                App::connect(
+                    Serial, port, baud_rate, serial_rx, action_tx, options, counters,
+                )
+                .await;
             }
-        });
+        }));
     }
 
-    async fn establish_serial_stream(port: &str, baud_rate: u32) -> Result<SerialStream, String> {
-        let serial_port_builder = tokio_serial::new(port, baud_rate)
-            .data_bits(tokio_serial::DataBits::Eight)
-            .stop_bits(tokio_serial::StopBits::One)
-            .parity(tokio_serial::Parity::None)
-            .timeout(std::time::Duration::from_millis(1000));
-
-        let mut serial_port = serial_port_builder
-            .open_native_async()
-            .map_err(|e| format!("Failed to open serial port {port}.\nError: {e}"))?;
-
-        serial_port
-            .clear(tokio_serial::ClearBuffer::All)
-            .map_err(|e| format!("Failed to clear serial port buffer.\nError {e}"))?;
+    /// Open `port` over `transport`, handshake and, if the device accepts, run the session on it,
+    /// counting its traffic into `counters`.
+    async fn connect<T: Transport>(
+        transport: T,
+        port: String,
+        baud_rate: u32,
+        serial_rx: QueueReceiver,
+        action_tx: mpsc::UnboundedSender<Action>,
+        options: SessionOptions,
+        counters: Arc<SessionCounters>,
+    ) {
+        let stream = match transport.open(&port, baud_rate).await {
+            Ok(stream) => {
+                let line = T::line(&stream);
+                App::handshake(stream, options)
+                    .await
+                    .map(|stream| (stream, line))
+            }
+            Err(message) => Err(message),
+        };
+        match stream {
+            Ok((stream, line)) => {
+                let _ = action_tx.send(Action::ConnectionEstablished(ConnectionInfo {
+                    port: port.clone(),
+                    baud_rate,
+                    line,
+                    framing: options.framing,
+                    tagged: options.tagged,
+                    mode: SessionMode::Commands,
+                    label: None,
+                }));
+                let _ = action_tx.send(Action::ShowMain);
+                App::run_serial_session(stream, serial_rx, action_tx.clone(), options, counters)
+                    .instrument(info_span!(
+                        "serial_session",
+                        %port,
+                        baud_rate,
+                        framing = options.framing.name(),
+                        tagged = options.tagged
+                    ))
+                    .await;
+            }
+            Err(message) => {
+                let _ = action_tx.send(Action::ConnectionFailed(message));
+            }
+        }
+    }
 
-        serial_port
-            .write_all((HANDSHAKE_COMMAND.to_owned() + HANDSHAKE_DELIMITER).as_bytes())
+    async fn handshake<S: AsyncRead + AsyncWrite + Unpin>(
+        mut stream: S,
+        options: SessionOptions,
+    ) -> Result<S, String> {
+        let SessionOptions {
+            framing, tagged, ..
+        } = options;
+        stream
+            .write_all(
+                (handshake::command(framing, tagged).to_owned() + HANDSHAKE_DELIMITER).as_bytes(),
+            )
             .await
             .map_err(|e| {
                 format!("Failed to write handshake command using serial port.\nError {e}")
             })?;
 
-        let mut handshake_buffer = [0u8; HANDSHAKE_RESPONSE.len()];
-        let read_result = timeout(
-            HANDSHAKE_TIMEOUT,
-            serial_port.read_exact(&mut handshake_buffer),
-        )
-        .await;
+        let (reply, handshake_bytes) =
+            await_handshake_reply(&mut stream, framing, tagged, options.handshake_timeout).await?;
 
-        let handshake_bytes = match read_result {
-            Err(_) => {
-                return Err("Timed out waiting for handshake response.".into());
+        match reply {
+            HandshakeReply::Accepted => {}
+            HandshakeReply::Incompatible => {
+                return Err(format!(
+                    "Firmware does not support host protocol v{PROTOCOL_VERSION_MAJOR}.\nUpdate the firmware or use a matching SiTerm release."
+                ));
             }
-            Ok(Err(e)) => {
-                return Err(format!("Handshake read failed: {e}"));
+            HandshakeReply::Pending | HandshakeReply::Invalid => {
+                return Err(format!(
+                    "Invalid handshake response received.\n Response received: {}",
+                    String::from_utf8_lossy(&handshake_bytes)
+                ));
             }
-            Ok(Ok(_)) => handshake_buffer,
-        };
-
-        let response_as_string = str::from_utf8(&handshake_bytes)
-            .map_err(|e| format!("Handshake conversion to str failed: {e}"))?;
-
-        if response_as_string != HANDSHAKE_RESPONSE {
-            return Err(format!(
-                "Invalid handshake response received.\n Response received: {response_as_string}"
-            ));
         }
 
-        Ok(serial_port)
+        Ok(stream)
     }
 
-    async fn run_serial_session(
-        serial_stream: SerialStream,
-        serial_rx: mpsc::UnboundedReceiver<String>,
+    async fn run_serial_session<S>(
+        serial_stream: S,
+        serial_rx: QueueReceiver,
         action_tx: mpsc::UnboundedSender<Action>,
-    ) {
+        options: SessionOptions,
+        counters: Arc<SessionCounters>,
+    ) where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let SessionOptions {
+            framing,
+            tagged,
+            pacing,
+            stall_timeout,
+            max_frame,
+            show_frames,
+            ..
+        } = options;
         let (reader_half, writer_half) = tokio::io::split(serial_stream);
+        // Set by the reader when the device confirms a UART bridge, cleared by the writer when it
+        // sends the escape. While set, both directions carry raw bytes instead of frames.
+        let bridged = Arc::new(AtomicBool::new(false));
+        // Set by the writer while an `i2c dump` or `ping` runs, so the reader hands it the
+        // responses.
+        let collecting = Arc::new(AtomicBool::new(false));
+        let (response_tx, response_rx) = mpsc::unbounded_channel::<Vec<u8>>();
+        // The reader tells the writer when the device NAKs the last command.
+        let (nak_tx, nak_rx) = mpsc::unbounded_channel::<()>();
+        // Set by the reader between the device's busy and ready frames; the writer holds commands
+        // meanwhile.
+        let busy = Arc::new(watch::Sender::new(false));
+        // Set by the writer when a command goes out, cleared by the reader when the device answers
+        // it. The writer holds the next command meanwhile, so a NAK always refers to the last frame.
+        let awaiting = Arc::new(watch::Sender::new(false));
 
         let writer_action_tx = action_tx.clone();
-        let writer_task = tokio::spawn(async move {
-            let mut writer_half = writer_half;
-            let mut command_rx = serial_rx;
-            while let Some(command) = command_rx.recv().await {
-                let trimmed = command.trim();
-                if trimmed.is_empty() {
-                    continue;
+        let writer_bridged = Arc::clone(&bridged);
+        let writer_collecting = Arc::clone(&collecting);
+        let writer_busy = Arc::clone(&busy);
+        let writer_awaiting = Arc::clone(&awaiting);
+        let writer_counters = Arc::clone(&counters);
+        let writer_task = tokio::spawn(
+            async move {
+                let mut writer_half = writer_half;
+                let mut command_rx = serial_rx;
+                let mut response_rx = response_rx;
+                let mut nak_rx = nak_rx;
+                let mut retransmit = Retransmit::default();
+                let mut answers = writer_awaiting.subscribe();
+                // Learn the device's limits before taking commands. Firmware without `info`
+                // answers with an error and the defaults stand.
+                writer_collecting.store(true, Ordering::Release);
+                let device_info = match query_device_info(
+                    &mut writer_half,
+                    &mut response_rx,
+                    &writer_action_tx,
+                    &writer_busy,
+                    &writer_counters,
+                    framing,
+                    pacing,
+                )
+                .await
+                {
+                    Ok(info) => info,
+                    Err(e) => {
+                        let _ = writer_action_tx
+                            .send(Action::ConnectionFailed(link_error("write", &e)));
+                        return writer_half;
+                    }
+                };
+                writer_collecting.store(false, Ordering::Release);
+                if let Some(info) = device_info {
+                    let _ = writer_action_tx.send(Action::DeviceInfo(info));
                 }
+                loop {
+                    // `_handled` marks the queued item as dealt with at the end of the iteration.
+                    let (command, _handled) = tokio::select! {
+                        command = command_rx.recv(), if !*writer_awaiting.borrow() => match command {
+                            Some((Outbound::Line(command), handled)) => (command, handled),
+                            Some((Outbound::Keystrokes(bytes), _handled)) => {
+                                if !writer_bridged.load(Ordering::Acquire) {
+                                    debug!(len = bytes.len(), "dropped keystrokes, bridge closed");
+                                    continue;
+                                }
+                                trace!(bytes = ?bytes, "sent keystrokes");
+                                if let Err(e) = write_paced(&mut writer_half, &bytes, pacing).await {
+                                    let _ = writer_action_tx
+                                        .send(Action::ConnectionFailed(link_error("write", &e)));
+                                    break;
+                                }
+                                let _ = writer_action_tx.send(Action::FrameSent(bytes.len()));
+                                continue;
+                            }
+                            None => break,
+                        },
+                        Some(()) = nak_rx.recv() => {
+                            match retransmit.on_nak() {
+                                Ok((frame, note)) => {
+                                    wait_until_ready(&writer_busy, MAX_BUSY_WAIT, &writer_action_tx).await;
+                                    debug!(frame_len = frame.len(), "resending NAKed command");
+                                    let _ = writer_action_tx.send(Action::IncomingMessage(note));
+                                    if let Err(e) = write_paced(&mut writer_half, frame, pacing).await {
+                                        let _ = writer_action_tx
+                                            .send(Action::ConnectionFailed(link_error("write", &e)));
+                                        break;
+                                    }
+                                    writer_counters.frame_sent();
+                                    let _ = writer_action_tx.send(Action::FrameSent(frame.len()));
+                                }
+                                Err(message) => {
+                                    writer_awaiting.send_replace(false);
+                                    let _ = writer_action_tx.send(Action::IncomingMessage(message));
+                                }
+                            }
+                            continue;
+                        }
+                        answered = timeout(ANSWER_TIMEOUT, async {
+                            answers.wait_for(|awaiting| !awaiting).await.is_ok()
+                        }), if *writer_awaiting.borrow() =>
+                        {
+                            if answered.is_err() {
+                                debug!("no answer to the last command, sending the next");
+                                writer_awaiting.send_replace(false);
+                            }
+                            continue;
+                        }
+                    };
+                    if writer_bridged.load(Ordering::Acquire) {
+                        retransmit.forget();
+                        let (bytes, closes_bridge) = bridge_bytes(&command);
+                        if closes_bridge {
+                            // Frames resume as soon as the device sees the escape.
+                            writer_bridged.store(false, Ordering::Release);
+                        }
+                        debug!(len = bytes.len(), "sent bridged bytes");
+                        trace!(bytes = ?bytes, "sent bridged bytes");
+                        if let Err(e) = write_paced(&mut writer_half, &bytes, pacing).await {
+                            let _ = writer_action_tx
+                                .send(Action::ConnectionFailed(link_error("write", &e)));
+                            break;
+                        }
+                        let _ = writer_action_tx.send(Action::FrameSent(bytes.len()));
+                        continue;
+                    }
 
-                match encode_command(trimmed) {
-                    Ok(payload) => match encode_transport_frame(&payload) {
-                        Ok(frame) => {
-                            if let Err(e) = writer_half.write_all(&frame).await {
-                                let _ = writer_action_tx.send(Action::ConnectionFailed(format!(
-                                    "Serial write failed: {e}"
-                                )));
+                    let trimmed = command.trim();
+                    match prepare_command(trimmed, framing) {
+                        None => {}
+                        Some(Outgoing::Rejected(message)) => {
+                            debug!(command = trimmed, message, "rejected command");
+                            let _ = writer_action_tx
+                                .send(Action::IncomingMessage(DeviceMessage::Text(message)));
+                        }
+                        Some(Outgoing::Command { payload, frame }) => {
+                            let chunked = is_chunked(&payload);
+                            wait_until_ready(&writer_busy, MAX_BUSY_WAIT, &writer_action_tx).await;
+                            debug!(
+                                command = trimmed,
+                                payload_len = payload.len(),
+                                frame_len = frame.len(),
+                                "sent command"
+                            );
+                            trace!(frame = ?frame, "sent frame");
+                            if let Err(e) = write_paced(&mut writer_half, &frame, pacing).await {
+                                let _ = writer_action_tx
+                                    .send(Action::ConnectionFailed(link_error("write", &e)));
                                 break;
                             }
+                            writer_counters.frame_sent();
+                            writer_awaiting.send_replace(true);
+                            let _ = writer_action_tx.send(Action::FrameSent(frame.len()));
+                            retransmit.sent(frame, chunked);
                         }
-                        Err(err) => {
-                            let message = format!(
-                                "Error: Failed to frame command `{trimmed}`: {}",
-                                format_transport_error(err)
-                            );
+                        Some(Outgoing::Dump(request)) => {
+                            retransmit.forget();
+                            // Responses to reads abandoned by an earlier dump belong to nobody.
+                            while response_rx.try_recv().is_ok() {}
+                            writer_collecting.store(true, Ordering::Release);
+                            let outcome = read_dump(
+                                &mut writer_half,
+                                &mut response_rx,
+                                &writer_action_tx,
+                                &writer_busy,
+                                &writer_counters,
+                                request,
+                                device_info.as_ref(),
+                                framing,
+                                pacing,
+                            )
+                            .await;
+                            writer_collecting.store(false, Ordering::Release);
+                            let _ = writer_action_tx.send(Action::DumpProgress(None));
+                            match outcome {
+                                Ok(Ok(data)) => {
+                                    let summary = format!(
+                                        "i2c dump of {:#04x} from register {:#04x}: {} bytes",
+                                        request.address,
+                                        request.start,
+                                        data.len()
+                                    );
+                                    let _ = writer_action_tx.send(Action::IncomingMessage(
+                                        DeviceMessage::Text(summary),
+                                    ));
+                                    let _ = writer_action_tx
+                                        .send(Action::IncomingMessage(DeviceMessage::Bytes(data)));
+                                }
+                                Ok(Err(reason)) => {
+                                    let message = format!("Error: i2c dump failed: {reason}");
+                                    let _ = writer_action_tx.send(Action::IncomingMessage(
+                                        DeviceMessage::Text(message),
+                                    ));
+                                }
+                                Err(e) => {
+                                    let _ = writer_action_tx
+                                        .send(Action::ConnectionFailed(link_error("write", &e)));
+                                    break;
+                                }
+                            }
+                        }
+                        Some(Outgoing::Ping(count)) => {
+                            retransmit.forget();
+                            while response_rx.try_recv().is_ok() {}
+                            writer_collecting.store(true, Ordering::Release);
+                            let outcome = measure_latency(
+                                &mut writer_half,
+                                &mut response_rx,
+                                &writer_action_tx,
+                                &writer_busy,
+                                &writer_counters,
+                                count,
+                                framing,
+                                pacing,
+                            )
+                            .await;
+                            writer_collecting.store(false, Ordering::Release);
+                            let message = match outcome {
+                                Ok(Ok(stats)) => stats.summary(),
+                                Ok(Err(reason)) => format!("Error: ping failed: {reason}"),
+                                Err(e) => {
+                                    let _ = writer_action_tx
+                                        .send(Action::ConnectionFailed(link_error("write", &e)));
+                                    break;
+                                }
+                            };
                             let _ = writer_action_tx
                                 .send(Action::IncomingMessage(DeviceMessage::Text(message)));
                         }
-                    },
-                    Err(error) => {
-                        let message = format!(
-                            "Error: Failed to encode command `{trimmed}`: {}",
-                            format_encode_error(error)
-                        );
-                        let _ = writer_action_tx
-                            .send(Action::IncomingMessage(DeviceMessage::Text(message)));
                     }
                 }
+                // Everything queued has been written; push out whatever is still buffered.
+                if let Err(e) = writer_half.shutdown().await {
+                    debug!(error = %e, "failed to flush serial writes");
+                }
+                writer_half
             }
-        });
+            .in_current_span(),
+        );
 
         let mut reader = BufReader::new(reader_half);
-        let mut pending = Vec::new();
+        let mut inbound = Inbound::new(framing, tagged)
+            .max_payload(max_frame.unwrap_or_else(|| default_max_payload(tagged)))
+            .trace_frames(show_frames);
         let mut read_buffer = [0u8; 512];
+        let mut writer_task = writer_task;
+        let mut writer_result = None;
         'reader: loop {
-            match reader.read(&mut read_buffer).await {
+            let stall_deadline = inbound.stall_deadline(stall_timeout);
+            let read = tokio::select! {
+                // The writer stops once its queue is closed, which ends the session.
+                result = &mut writer_task => {
+                    writer_result = Some(result);
+                    break;
+                }
+                () = sleep_until_deadline(stall_deadline) => {
+                    let now = Instant::now().into_std();
+                    if let Some(dropped) = inbound.flush_stalled(now, stall_timeout) {
+                        warn!(dropped, "partial frame stalled");
+                        counters.error();
+                        let _ = action_tx.send(Action::IncomingMessage(stalled_warning(dropped)));
+                    }
+                    continue;
+                }
+                read = reader.read(&mut read_buffer) => read,
+            };
+            match read {
                 Ok(0) => {
                     let _ = action_tx
                         .send(Action::ConnectionFailed("Serial connection closed.".into()));
                     break;
                 }
+                Ok(n) if bridged.load(Ordering::Acquire) => {
+                    debug!(len = n, "received bridged bytes");
+                    trace!(bytes = ?&read_buffer[..n], "received bridged bytes");
+                    let _ = action_tx.send(Action::IncomingMessage(DeviceMessage::Bytes(
+                        read_buffer[..n].to_vec(),
+                    )));
+                }
                 Ok(n) => {
-                    pending.extend_from_slice(&read_buffer[..n]);
+                    inbound.push(&read_buffer[..n]);
                     loop {
-                        match try_decode_transport_frame(&pending) {
-                            Ok(Some((payload, consumed))) => {
-                                pending.drain(..consumed);
-                                let _ = action_tx
-                                    .send(Action::IncomingMessage(DeviceMessage::Bytes(payload)));
+                        match inbound.next_frame() {
+                            Ok(Some(Received {
+                                source,
+                                format,
+                                payload,
+                                frame_len,
+                                skipped,
+                            })) => {
+                                debug!(
+                                    payload_len = payload.len(),
+                                    frame_len, skipped, "received frame"
+                                );
+                                trace!(payload = ?payload, "received frame");
+                                counters.frame_received();
+                                if payload.starts_with(ERROR_PREFIX) {
+                                    counters.error();
+                                }
+                                if skipped > 0 {
+                                    counters.error();
+                                    let _ = action_tx
+                                        .send(Action::IncomingMessage(skipped_warning(skipped)));
+                                }
+                                // `prepare_command` refuses echoes of these, so no response carries them.
+                                if payload == HEARTBEAT {
+                                    let _ = action_tx.send(Action::Heartbeat);
+                                    continue;
+                                }
+                                if payload == BUSY || payload == READY {
+                                    debug!(busy = payload == BUSY, "device flow control");
+                                    busy.send_replace(payload == BUSY);
+                                    continue;
+                                }
+                                if collecting.load(Ordering::Acquire) {
+                                    let _ = response_tx.send(payload);
+                                    continue;
+                                }
+                                if payload == NAK {
+                                    let _ = nak_tx.send(());
+                                    continue;
+                                }
+                                awaiting.send_replace(false);
+                                if let Some(baud) = parse_opened_response(&payload) {
+                                    // Everything after this frame is raw UART data.
+                                    bridged.store(true, Ordering::Release);
+                                    let _ = action_tx.send(Action::BridgeChanged(Some(baud)));
+                                    let _ =
+                                        action_tx.send(payload_to_action(source, format, payload));
+                                    let rest = inbound.take_pending();
+                                    if !rest.is_empty() {
+                                        let _ = action_tx.send(Action::IncomingMessage(
+                                            DeviceMessage::Bytes(rest),
+                                        ));
+                                    }
+                                    break;
+                                }
+                                if payload == BRIDGE_CLOSED {
+                                    let _ = action_tx.send(Action::BridgeChanged(None));
+                                }
+                                let _ = action_tx.send(payload_to_action(source, format, payload));
                             }
                             Ok(None) => break,
                             Err(err) => {
+                                warn!(error = ?err, pending_len = inbound.pending().len(), "failed to decode frame");
+                                trace!(pending = ?inbound.pending(), "undecodable bytes");
                                 let _ = action_tx.send(Action::ConnectionFailed(format!(
                                     "Failed to decode frame: {}",
                                     format_transport_error(err)
@@ -554,45 +1327,1186 @@ impl App {
                             }
                         }
                     }
+                    if let Some(frames) = inbound.take_trace() {
+                        let _ =
+                            action_tx.send(Action::IncomingMessage(DeviceMessage::Text(frames)));
+                    }
                 }
                 Err(e) => {
-                    let _ = action_tx
-                        .send(Action::ConnectionFailed(format!("Serial read failed: {e}")));
+                    let _ = action_tx.send(Action::ConnectionFailed(link_error("read", &e)));
                     break;
                 }
             }
         }
 
-        let _ = writer_task.await;
+        let writer_result = match writer_result {
+            Some(result) => result,
+            None => writer_task.await,
+        };
+        if let Ok(writer_half) = writer_result {
+            drop(reader.into_inner().unsplit(writer_half));
+            debug!("closed serial port");
+        }
+    }
+
+    /// Close the current session and go back to the preconnect screen with its summary. Closing
+    /// the queue lets the writer send what is already queued, then it shuts the port and the reader
+    /// stops with it. The terminal keeps its messages and history for the next connection.
+    async fn disconnect(&mut self) -> Result<()> {
+        let started = self.session_started.take();
+        if let Some(dropped) = self.close_session().await {
+            warn!(dropped, "disconnected before queued commands were sent");
+        }
+        self.drop_closed_session_failures();
+        self.bridged = false;
+        self.mode = Mode::Preconnect;
+        self.action_tx.send(Action::ShowPreconnect)?;
+        if let Some(started) = started {
+            let summary = self.session_counters.snapshot().summary(started.elapsed());
+            self.action_tx
+                .send(Action::Disconnected(format!("Disconnected. {summary}")))?;
+        }
+        Ok(())
+    }
+
+    /// Drop the failures a closed session reported on its way out, such as its port closing,
+    /// keeping every other queued action in order. Only call it once the session task has ended,
+    /// so nothing it sends can arrive later.
+    fn drop_closed_session_failures(&mut self) {
+        let mut kept = Vec::new();
+        while let Ok(action) = self.action_rx.try_recv() {
+            match action {
+                Action::ConnectionFailed(message) => {
+                    debug!(message, "ignored the end of a closed session");
+                }
+                action => kept.push(action),
+            }
+        }
+        for action in kept {
+            let _ = self.action_tx.send(action);
+        }
+    }
+
+    /// Close the queue to the serial writer and give the session up to [`SHUTDOWN_FLUSH_TIMEOUT`]
+    /// to write what is queued and close the port. Returns how many queued commands were dropped
+    /// when it ran out of time.
+    async fn close_session(&mut self) -> Option<usize> {
+        let unsent = self.serial_tx.take().map(QueueSender::close);
+        let mut task = self.session_task.take()?;
+        if timeout(SHUTDOWN_FLUSH_TIMEOUT, &mut task).await.is_ok() {
+            return None;
+        }
+        task.abort();
+        // Wait for the abort to land, so the session sends nothing after this returns.
+        let _ = task.await;
+        Some(unsent.map_or(0, |unsent| unsent.count()))
+    }
+}
+
+/// Connection settings captured from the config when a connection starts.
+#[derive(Clone, Copy, Debug)]
+struct SessionOptions {
+    framing: Framing,
+    tagged: bool,
+    pacing: WritePacing,
+    handshake_timeout: Duration,
+    stall_timeout: Duration,
+    max_frame: Option<usize>,
+    show_frames: bool,
+}
+
+impl SessionOptions {
+    fn from_config(config: &Config) -> Self {
+        Self {
+            framing: config.framing,
+            tagged: config.tagged_responses,
+            pacing: config.write_pacing,
+            handshake_timeout: config.handshake_timeout,
+            stall_timeout: config.stall_timeout,
+            max_frame: config.max_frame,
+            show_frames: config.show_frames,
+        }
     }
 }
 
-fn format_encode_error(error: EncodeError) -> String {
-    match error {
-        EncodeError::Empty => "command is empty".into(),
-        EncodeError::UnknownMethod => "unknown method".into(),
-        EncodeError::UnknownOperation => "unknown operation".into(),
-        EncodeError::UnsupportedOperation { method, operation } => format!(
-            "unsupported operation {:?} for method {:?}",
-            operation, method
-        ),
-        EncodeError::MissingOperation => "missing operation keyword".into(),
-        EncodeError::MissingArgument { index } => {
-            format!("missing argument at position {}", index + 1)
+/// Sleep until `deadline`, or forever when there is none.
+async fn sleep_until_deadline(deadline: Option<std::time::Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(Instant::from_std(deadline)).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Hold off while the device says it is busy, for at most `limit`. A device that stays busy that
+/// long is taken to have lost its ready frame: the flag is cleared with a note and writing resumes,
+/// so a missing frame can't stall the session.
+async fn wait_until_ready(
+    busy: &watch::Sender<bool>,
+    limit: Duration,
+    action_tx: &mpsc::UnboundedSender<Action>,
+) {
+    let mut ready = busy.subscribe();
+    if timeout(limit, ready.wait_for(|busy| !busy)).await.is_err() {
+        busy.send_replace(false);
+        let note = format!(
+            "Device still busy after {} ms; sending anyway.",
+            limit.as_millis()
+        );
+        let _ = action_tx.send(Action::IncomingMessage(DeviceMessage::Text(note)));
+    }
+}
+
+/// Write `bytes`, split up and spaced out as `pacing` asks.
+async fn write_paced<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    bytes: &[u8],
+    pacing: WritePacing,
+) -> std::io::Result<()> {
+    write_chunked(writer, bytes, pacing, tokio::time::sleep).await
+}
+
+/// Write `bytes` in `pacing.chunk_size` pieces, calling `pause` between them. An unpaced write
+/// goes out in one call.
+async fn write_chunked<W, P, F>(
+    writer: &mut W,
+    bytes: &[u8],
+    pacing: WritePacing,
+    mut pause: P,
+) -> std::io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+    P: FnMut(Duration) -> F,
+    F: Future<Output = ()>,
+{
+    if !pacing.is_paced() {
+        return writer.write_all(bytes).await;
+    }
+    for (idx, chunk) in bytes.chunks(pacing.chunk_size.max(1)).enumerate() {
+        if idx > 0 {
+            pause(pacing.delay).await;
+        }
+        writer.write_all(chunk).await?;
+        // Push each chunk out now, or a buffered writer would undo the spacing.
+        writer.flush().await?;
+    }
+    Ok(())
+}
+
+/// Send the reads that make up an `i2c dump` one at a time, waiting for each response, and return
+/// the joined data. The outer error is a failed serial write; the inner one explains why the dump
+/// stopped early.
+#[allow(clippy::too_many_arguments)]
+async fn read_dump<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    responses: &mut mpsc::UnboundedReceiver<Vec<u8>>,
+    action_tx: &mpsc::UnboundedSender<Action>,
+    busy: &watch::Sender<bool>,
+    counters: &SessionCounters,
+    request: DumpRequest,
+    info: Option<&DeviceInfo>,
+    framing: Framing,
+    pacing: WritePacing,
+) -> std::io::Result<Result<Vec<u8>, String>> {
+    let chunks = plan_chunks(request.start, request.length, chunk_len(info));
+    let total = usize::from(request.length);
+    let mut data = Vec::with_capacity(total);
+    for chunk in &chunks {
+        let frame = match encode_transport_frame(&request.read_command(*chunk), framing) {
+            Ok(frame) => frame,
+            Err(err) => return Ok(Err(format_transport_error(err))),
+        };
+        debug!(
+            register = chunk.register,
+            length = chunk.length,
+            "sent dump read"
+        );
+        wait_until_ready(busy, MAX_BUSY_WAIT, action_tx).await;
+        write_paced(writer, &frame, pacing).await?;
+        counters.frame_sent();
+
+        let response = match timeout(DUMP_READ_TIMEOUT, responses.recv()).await {
+            Ok(Some(response)) => response,
+            Ok(None) => return Ok(Err("the serial reader stopped".into())),
+            Err(_) => {
+                return Ok(Err(format!(
+                    "no response reading register {:#04x}",
+                    chunk.register
+                )));
+            }
+        };
+        if response.starts_with(ERROR_PREFIX) {
+            return Ok(Err(format!(
+                "register {:#04x}: {}",
+                chunk.register,
+                String::from_utf8_lossy(&response)
+            )));
+        }
+        data.extend_from_slice(&response);
+        if chunks.len() > 1 {
+            let _ = action_tx.send(Action::DumpProgress(Some((data.len(), total))));
         }
-        EncodeError::UnexpectedArgument { index } => {
-            format!("unexpected argument starting at position {}", index + 1)
+    }
+    Ok(Ok(data))
+}
+
+/// Ask the device for its [`DeviceInfo`], or `None` when it doesn't answer with one in time.
+#[allow(clippy::too_many_arguments)]
+async fn query_device_info<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    responses: &mut mpsc::UnboundedReceiver<Vec<u8>>,
+    action_tx: &mpsc::UnboundedSender<Action>,
+    busy: &watch::Sender<bool>,
+    counters: &SessionCounters,
+    framing: Framing,
+    pacing: WritePacing,
+) -> std::io::Result<Option<DeviceInfo>> {
+    let Ok(frame) = encode_command("info")
+        .map_err(format_encode_error)
+        .and_then(|payload| {
+            encode_transport_frame(&payload, framing).map_err(format_transport_error)
+        })
+    else {
+        return Ok(None);
+    };
+    wait_until_ready(busy, MAX_BUSY_WAIT, action_tx).await;
+    write_paced(writer, &frame, pacing).await?;
+    counters.frame_sent();
+    let info = match timeout(INFO_TIMEOUT, responses.recv()).await {
+        Ok(Some(response)) if !response.starts_with(ERROR_PREFIX) => DeviceInfo::decode(&response),
+        _ => None,
+    };
+    debug!(?info, "device info");
+    Ok(info)
+}
+
+/// Send `count` pings one at a time and time each reply. The outer error is a failed serial write;
+/// the inner one explains why the run stopped early.
+#[allow(clippy::too_many_arguments)]
+async fn measure_latency<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    responses: &mut mpsc::UnboundedReceiver<Vec<u8>>,
+    action_tx: &mpsc::UnboundedSender<Action>,
+    busy: &watch::Sender<bool>,
+    counters: &SessionCounters,
+    count: u16,
+    framing: Framing,
+    pacing: WritePacing,
+) -> std::io::Result<Result<LatencyStats, String>> {
+    let mut stats = LatencyStats::default();
+    for seq in 0..count {
+        let frame = match ping_command(seq)
+            .map_err(format_encode_error)
+            .and_then(|payload| {
+                encode_transport_frame(&payload, framing).map_err(format_transport_error)
+            }) {
+            Ok(frame) => frame,
+            Err(reason) => return Ok(Err(reason)),
+        };
+        let pong = pong_payload(seq);
+        wait_until_ready(busy, MAX_BUSY_WAIT, action_tx).await;
+        let started = Instant::now();
+        write_paced(writer, &frame, pacing).await?;
+        counters.frame_sent();
+        loop {
+            match timeout_at(started + PING_TIMEOUT, responses.recv()).await {
+                Ok(Some(response)) if response == pong => {
+                    let round_trip = started.elapsed();
+                    counters.round_trip(round_trip);
+                    stats.record(round_trip);
+                    break;
+                }
+                // A late reply to a ping already counted as lost.
+                Ok(Some(_)) => {}
+                Ok(None) => return Ok(Err("the serial reader stopped".into())),
+                Err(_) => {
+                    debug!(seq, "ping lost");
+                    stats.record_lost();
+                    break;
+                }
+            }
         }
-        EncodeError::InvalidArgument { index } => {
-            format!("invalid argument at position {}", index + 1)
+    }
+    Ok(Ok(stats))
+}
+
+/// Wait up to `wait` for the firmware's handshake reply.
+async fn await_handshake_reply<R: AsyncRead + Unpin>(
+    serial_port: &mut R,
+    framing: Framing,
+    tagged: bool,
+    wait: Duration,
+) -> Result<(HandshakeReply, Vec<u8>), String> {
+    match timeout(wait, read_handshake_reply(serial_port, framing, tagged)).await {
+        Err(_) => Err("Timed out waiting for handshake response.".into()),
+        Ok(Err(e)) => Err(format!("Handshake read failed: {e}")),
+        Ok(Ok(result)) => Ok(result),
+    }
+}
+
+/// Read the firmware's handshake reply a byte at a time until it matches a known reply.
+///
+/// Some bootloaders and firmware print a banner before they are ready for the handshake, so bytes
+/// that can't start a reply are skipped as chatter, up to [`MAX_HANDSHAKE_CHATTER`] of them. Past
+/// that the device is taken not to be speaking SiTerm, and everything read is returned as invalid.
+async fn read_handshake_reply<R: AsyncRead + Unpin>(
+    serial_port: &mut R,
+    framing: Framing,
+    tagged: bool,
+) -> std::io::Result<(HandshakeReply, Vec<u8>)> {
+    let mut received = Vec::with_capacity(handshake::MAX_REPLY_LEN);
+    // Start of the bytes that may still be the reply; everything before is chatter.
+    let mut start = 0;
+    loop {
+        match handshake::check_reply(&received[start..], framing, tagged) {
+            HandshakeReply::Pending => received.push(serial_port.read_u8().await?),
+            HandshakeReply::Invalid if start < MAX_HANDSHAKE_CHATTER => start += 1,
+            HandshakeReply::Invalid => return Ok((HandshakeReply::Invalid, received)),
+            outcome => {
+                if start > 0 {
+                    debug!(
+                        skipped = start,
+                        chatter = %String::from_utf8_lossy(&received[..start]),
+                        "skipped output ahead of the handshake reply"
+                    );
+                }
+                return Ok((outcome, received.split_off(start)));
+            }
         }
-        EncodeError::OutputTooSmall => "output buffer is too small".into(),
     }
 }
 
-fn format_transport_error(error: TransportCodecError) -> String {
-    match error {
-        TransportCodecError::Encode(err) => format!("encode error: {err}"),
-        TransportCodecError::Decode(err) => format!("decode error: {err}"),
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncBufReadExt;
+
+    use protocol::Method;
+    use ratatui::{
+        Terminal,
+        backend::TestBackend,
+        buffer::{Buffer, Cell},
+        style::Color,
+    };
+
+    use super::*;
+    use crate::theme::Theme;
+
+    #[test]
+    fn help_lists_every_bound_action_with_its_key() {
+        let keymap = Keymap::parse("hex_view = ctrl+t\nquit = none").unwrap();
+        let palette = Theme::default().palette();
+        for context in [HelpContext::Preconnect, HelpContext::Connected] {
+            let lines: Vec<String> = context
+                .body(&keymap, &palette)
+                .iter()
+                .map(ToString::to_string)
+                .collect();
+            for action in KeyAction::ALL {
+                let key = keymap.label(action);
+                let listed = lines.iter().any(|line| {
+                    line.trim_start().starts_with(&format!("{key} "))
+                        && line.ends_with(action.description())
+                });
+                assert!(listed, "{context:?} help is missing {action:?} on {key}");
+            }
+            assert!(
+                lines
+                    .iter()
+                    .any(|line| line.contains("Ctrl+t") && line.ends_with("Hex view"))
+            );
+            assert!(!lines.iter().any(|line| line.contains("Ctrl+x")));
+        }
+    }
+
+    /// A device that answers the handshake only after `delay`.
+    fn delayed_reply(delay: Duration) -> tokio::io::DuplexStream {
+        let (host, mut device) = tokio::io::duplex(64);
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            let reply =
+                handshake::response(Framing::Postcard, false).to_owned() + HANDSHAKE_DELIMITER;
+            let _ = device.write_all(reply.as_bytes()).await;
+            // Keep the device end open until the host is done reading.
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        });
+        host
+    }
+
+    #[tokio::test]
+    async fn short_handshake_timeout_gives_up_on_slow_device() {
+        let mut port = delayed_reply(Duration::from_millis(300));
+        let result = await_handshake_reply(
+            &mut port,
+            Framing::Postcard,
+            false,
+            Duration::from_millis(50),
+        )
+        .await;
+        assert_eq!(
+            result.unwrap_err(),
+            "Timed out waiting for handshake response."
+        );
+    }
+
+    #[tokio::test]
+    async fn longer_handshake_timeout_waits_for_slow_device() {
+        let mut port = delayed_reply(Duration::from_millis(300));
+        let (reply, _) =
+            await_handshake_reply(&mut port, Framing::Postcard, false, Duration::from_secs(2))
+                .await
+                .unwrap();
+        assert_eq!(reply, HandshakeReply::Accepted);
+    }
+
+    #[tokio::test]
+    async fn boot_chatter_before_the_handshake_reply_is_skipped() {
+        let (mut host, mut device) = tokio::io::duplex(256);
+        let reply = handshake::response(Framing::Postcard, false);
+        let output = format!("RP2040 boot v0.3\r\nSiTerm starting\r\n{reply}{HANDSHAKE_DELIMITER}");
+        device.write_all(output.as_bytes()).await.unwrap();
+        let (outcome, bytes) =
+            await_handshake_reply(&mut host, Framing::Postcard, false, Duration::from_secs(2))
+                .await
+                .unwrap();
+        assert_eq!(outcome, HandshakeReply::Accepted);
+        assert_eq!(bytes, reply.as_bytes());
+    }
+
+    #[tokio::test]
+    async fn endless_chatter_is_not_a_handshake() {
+        let (mut host, mut device) = tokio::io::duplex(4096);
+        device
+            .write_all(&[b'#'; MAX_HANDSHAKE_CHATTER + 1])
+            .await
+            .unwrap();
+        let (outcome, bytes) =
+            await_handshake_reply(&mut host, Framing::Postcard, false, Duration::from_secs(2))
+                .await
+                .unwrap();
+        assert_eq!(outcome, HandshakeReply::Invalid);
+        assert_eq!(bytes.len(), MAX_HANDSHAKE_CHATTER + 1);
+    }
+
+    #[tokio::test]
+    async fn paced_write_pauses_between_chunks() {
+        let pacing = WritePacing {
+            chunk_size: 4,
+            delay: Duration::from_millis(5),
+        };
+        let bytes: Vec<u8> = (0..10).collect();
+        let mut written = Vec::new();
+        let mut pauses = Vec::new();
+        write_chunked(&mut written, &bytes, pacing, |delay| {
+            pauses.push(delay);
+            std::future::ready(())
+        })
+        .await
+        .unwrap();
+        assert_eq!(written, bytes);
+        // Three chunks of 4, 4 and 2 bytes need two pauses.
+        assert_eq!(pauses, [pacing.delay; 2]);
+    }
+
+    #[tokio::test]
+    async fn unpaced_write_never_pauses() {
+        let mut written = Vec::new();
+        let mut pauses = 0;
+        write_chunked(&mut written, &[1, 2, 3], WritePacing::default(), |_| {
+            pauses += 1;
+            std::future::ready(())
+        })
+        .await
+        .unwrap();
+        assert_eq!(written, [1, 2, 3]);
+        assert_eq!(pauses, 0);
+    }
+
+    /// A transport whose device accepts the handshake and then sends every byte straight back.
+    struct Loopback;
+
+    impl Transport for Loopback {
+        type Stream = tokio::io::DuplexStream;
+
+        async fn open(&self, _port: &str, _baud_rate: u32) -> Result<Self::Stream, String> {
+            let (host, device) = tokio::io::duplex(256);
+            tokio::spawn(async move {
+                let (device_rx, mut device_tx) = tokio::io::split(device);
+                let mut device_rx = BufReader::new(device_rx);
+                let mut line = Vec::new();
+                device_rx.read_until(b'\n', &mut line).await.unwrap();
+                let reply = handshake::response(Framing::Postcard, false);
+                device_tx.write_all(reply.as_bytes()).await.unwrap();
+                let _ = tokio::io::copy(&mut device_rx, &mut device_tx).await;
+            });
+            Ok(host)
+        }
+    }
+
+    #[tokio::test]
+    async fn session_runs_over_any_transport() {
+        let (action_tx, mut action_rx) = mpsc::unbounded_channel();
+        let (serial_tx, serial_rx) = queue::channel();
+        let options = SessionOptions::from_config(&Config::default());
+        let counters = Arc::new(SessionCounters::default());
+        tokio::spawn(App::connect(
+            Loopback,
+            "loop".into(),
+            115_200,
+            serial_rx,
+            action_tx,
+            options,
+            Arc::clone(&counters),
+        ));
+        serial_tx.send(Outbound::Line("echo hi".into())).unwrap();
+
+        let expected = protocol::host::encode_command("echo hi").unwrap();
+        let mut established = false;
+        loop {
+            let action = timeout(Duration::from_secs(2), action_rx.recv())
+                .await
+                .expect("no response over the loopback")
+                .unwrap();
+            match action {
+                Action::ConnectionEstablished(_) => established = true,
+                Action::IncomingMessage(DeviceMessage::Bytes(bytes)) => {
+                    assert_eq!(bytes, expected);
+                    break;
+                }
+                Action::ConnectionFailed(message) => panic!("{message}"),
+                _ => {}
+            }
+        }
+        assert!(established);
+        let totals = counters.snapshot();
+        // The session opens with `info`, which the loopback echoes as well.
+        assert_eq!((totals.frames_sent, totals.frames_received), (2, 2));
+        assert_eq!(totals.errors, 0);
+    }
+
+    /// A device that answers the handshake and then records everything it is sent, answering each
+    /// command until the host closes the port (`read_commands`), or waits forever without reading.
+    struct Recorder {
+        read_commands: bool,
+        received: std::sync::Mutex<Option<tokio::sync::oneshot::Sender<Vec<u8>>>>,
+    }
+
+    impl Transport for Recorder {
+        type Stream = tokio::io::DuplexStream;
+
+        async fn open(&self, _port: &str, _baud_rate: u32) -> Result<Self::Stream, String> {
+            let (host, device) = tokio::io::duplex(64);
+            let read_commands = self.read_commands;
+            let received = self.received.lock().unwrap().take();
+            tokio::spawn(async move {
+                let (device_rx, mut device_tx) = tokio::io::split(device);
+                let mut device_rx = BufReader::new(device_rx);
+                let mut line = Vec::new();
+                device_rx.read_until(b'\n', &mut line).await.unwrap();
+                let reply = handshake::response(Framing::Postcard, false);
+                device_tx.write_all(reply.as_bytes()).await.unwrap();
+                if !read_commands {
+                    std::future::pending::<()>().await;
+                }
+                let mut inbound = Inbound::new(Framing::Postcard, false);
+                let mut bytes = Vec::new();
+                let mut buffer = [0u8; 64];
+                loop {
+                    while inbound.next_frame().unwrap().is_some() {
+                        let frame = encode_transport_frame(b"ok", Framing::Postcard).unwrap();
+                        device_tx.write_all(&frame).await.unwrap();
+                    }
+                    match device_rx.read(&mut buffer).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => {
+                            bytes.extend_from_slice(&buffer[..n]);
+                            inbound.push(&buffer[..n]);
+                        }
+                    }
+                }
+                if let Some(received) = received {
+                    let _ = received.send(bytes);
+                }
+            });
+            Ok(host)
+        }
+    }
+
+    #[tokio::test]
+    async fn queued_commands_are_sent_before_the_port_closes() {
+        let (received_tx, received_rx) = tokio::sync::oneshot::channel();
+        let recorder = Recorder {
+            read_commands: true,
+            received: std::sync::Mutex::new(Some(received_tx)),
+        };
+        let (action_tx, _action_rx) = mpsc::unbounded_channel();
+        let (serial_tx, serial_rx) = queue::channel();
+        let commands = ["echo one", "echo two", "temp"];
+        for command in commands {
+            serial_tx.send(Outbound::Line(command.into())).unwrap();
+        }
+        let unsent = serial_tx.close();
+
+        let options = SessionOptions::from_config(&Config::default());
+        let session = App::connect(
+            recorder,
+            "rec".into(),
+            115_200,
+            serial_rx,
+            action_tx,
+            options,
+            Arc::default(),
+        );
+        timeout(Duration::from_secs(2), session)
+            .await
+            .expect("session did not end after its queue closed");
+        assert_eq!(unsent.count(), 0);
+
+        let expected: Vec<u8> = std::iter::once("info")
+            .chain(commands)
+            .flat_map(|command| {
+                let payload = protocol::host::encode_command(command).unwrap();
+                encode_transport_frame(&payload, Framing::Postcard).unwrap()
+            })
+            .collect();
+        assert_eq!(received_rx.await.unwrap(), expected);
+    }
+
+    #[tokio::test]
+    async fn quitting_reports_commands_a_stalled_port_never_took() {
+        let mut app = App::new(4.0, 60.0).unwrap();
+        let recorder = Recorder {
+            read_commands: false,
+            received: std::sync::Mutex::new(None),
+        };
+        let (serial_tx, serial_rx) = queue::channel();
+        let options = SessionOptions::from_config(&Config::default());
+        app.session_task = Some(tokio::spawn(App::connect(
+            recorder,
+            "stalled".into(),
+            115_200,
+            serial_rx,
+            app.action_tx.clone(),
+            options,
+            Arc::default(),
+        )));
+        loop {
+            match app.action_rx.recv().await.unwrap() {
+                Action::ConnectionEstablished(_) => break,
+                Action::ConnectionFailed(message) => panic!("{message}"),
+                _ => {}
+            }
+        }
+        // Far more than the 64-byte link holds, so the writer blocks on the first one or two.
+        let long_echo = format!("echo {}", "x".repeat(100));
+        for _ in 0..5 {
+            serial_tx.send(Outbound::Line(long_echo.clone())).unwrap();
+        }
+        app.serial_tx = Some(serial_tx);
+
+        let dropped = app
+            .close_session()
+            .await
+            .expect("the stalled session closed");
+        assert!((1..=5).contains(&dropped), "{dropped}");
+        assert!(app.serial_tx.is_none() && app.session_task.is_none());
+    }
+
+    #[tokio::test]
+    async fn disconnecting_closes_the_session_and_returns_to_preconnect() {
+        let mut app = App::new(4.0, 60.0).unwrap();
+        app.config.simulate = true;
+        app.mode = Mode::Main;
+        app.spawn_connection_task(SIMULATED_PORT.into(), 115_200);
+        loop {
+            let action = timeout(Duration::from_secs(2), app.action_rx.recv())
+                .await
+                .expect("the simulator never reported its info")
+                .unwrap();
+            // The session opens with `info`, so its answer means the link is up and idle.
+            if matches!(action, Action::DeviceInfo(_)) {
+                break;
+            }
+        }
+
+        app.session_started = Some(Instant::now());
+        app.session_counters.frame_sent();
+        // What a closing port reports, which must not reach the preconnect screen.
+        app.action_tx
+            .send(Action::ConnectionFailed("Serial connection closed.".into()))
+            .unwrap();
+
+        app.disconnect().await.unwrap();
+        assert_eq!(app.mode, Mode::Preconnect);
+        assert!(app.serial_tx.is_none() && app.session_task.is_none());
+        let mut actions = Vec::new();
+        while let Ok(action) = app.action_rx.try_recv() {
+            actions.push(action);
+        }
+        assert!(
+            !actions
+                .iter()
+                .any(|action| matches!(action, Action::ConnectionFailed(_))),
+            "{actions:?}"
+        );
+        let Some(Action::Disconnected(summary)) = actions.last() else {
+            panic!("{actions:?}");
+        };
+        assert!(
+            summary.starts_with("Disconnected. Session: ") && summary.contains("2 frames sent"),
+            "{summary}"
+        );
+    }
+
+    #[tokio::test]
+    async fn ping_reports_round_trip_stats() {
+        let (action_tx, mut action_rx) = mpsc::unbounded_channel();
+        let (serial_tx, serial_rx) = queue::channel();
+        let options = SessionOptions::from_config(&Config::default());
+        tokio::spawn(App::connect(
+            Simulator,
+            SIMULATED_PORT.into(),
+            115_200,
+            serial_rx,
+            action_tx,
+            options,
+            Arc::default(),
+        ));
+        serial_tx.send(Outbound::Line("ping 3".into())).unwrap();
+
+        loop {
+            let action = timeout(Duration::from_secs(5), action_rx.recv())
+                .await
+                .expect("no ping report")
+                .unwrap();
+            match action {
+                Action::IncomingMessage(DeviceMessage::Text(text)) => {
+                    assert!(
+                        text.starts_with(
+                            "ping: 3 sent, 3 received, 0 lost; rtt min/avg/max/stddev = "
+                        ),
+                        "{text}"
+                    );
+                    break;
+                }
+                Action::IncomingMessage(DeviceMessage::Bytes(bytes)) => {
+                    panic!("pong leaked to the terminal: {bytes:?}")
+                }
+                Action::ConnectionFailed(message) => panic!("{message}"),
+                _ => {}
+            }
+        }
+    }
+
+    /// Whether `payload` is the `info` query every session opens with.
+    fn is_info_query(payload: &[u8]) -> bool {
+        payload.first() == Some(&(Method::Info as u8))
+    }
+
+    /// What firmware without `info` answers the session's opening query with.
+    const UNKNOWN_COMMAND: &[u8] = b"ERR: UnknownCommand";
+
+    /// A transport whose device NAKs the first command frame and echoes the payload of the rest.
+    struct NakOnce;
+
+    impl Transport for NakOnce {
+        type Stream = tokio::io::DuplexStream;
+
+        async fn open(&self, _port: &str, _baud_rate: u32) -> Result<Self::Stream, String> {
+            let (host, device) = tokio::io::duplex(256);
+            tokio::spawn(async move {
+                let (device_rx, mut device_tx) = tokio::io::split(device);
+                let mut device_rx = BufReader::new(device_rx);
+                let mut line = Vec::new();
+                device_rx.read_until(b'\n', &mut line).await.unwrap();
+                let reply = handshake::response(Framing::Postcard, false);
+                device_tx.write_all(reply.as_bytes()).await.unwrap();
+
+                let mut inbound = Inbound::new(Framing::Postcard, false);
+                let mut buffer = [0u8; 64];
+                let mut naked = false;
+                loop {
+                    while let Some(received) = inbound.next_frame().unwrap() {
+                        let reply = if is_info_query(&received.payload) {
+                            UNKNOWN_COMMAND.to_vec()
+                        } else if naked {
+                            received.payload[2..].to_vec()
+                        } else {
+                            naked = true;
+                            NAK.to_vec()
+                        };
+                        let frame = encode_transport_frame(&reply, Framing::Postcard).unwrap();
+                        device_tx.write_all(&frame).await.unwrap();
+                    }
+                    match device_rx.read(&mut buffer).await {
+                        Ok(0) | Err(_) => return,
+                        Ok(n) => inbound.push(&buffer[..n]),
+                    }
+                }
+            });
+            Ok(host)
+        }
+    }
+
+    #[tokio::test]
+    async fn nak_resends_the_command() {
+        let (action_tx, mut action_rx) = mpsc::unbounded_channel();
+        let (serial_tx, serial_rx) = queue::channel();
+        let options = SessionOptions::from_config(&Config::default());
+        tokio::spawn(App::connect(
+            NakOnce,
+            "nak".into(),
+            115_200,
+            serial_rx,
+            action_tx,
+            options,
+            Arc::default(),
+        ));
+        serial_tx.send(Outbound::Line("echo hi".into())).unwrap();
+
+        let mut frames_sent = 0;
+        let mut notes = Vec::new();
+        loop {
+            let action = timeout(Duration::from_secs(2), action_rx.recv())
+                .await
+                .expect("no response after the NAK")
+                .unwrap();
+            match action {
+                Action::FrameSent(_) => frames_sent += 1,
+                Action::IncomingMessage(DeviceMessage::Text(note)) => notes.push(note),
+                Action::IncomingMessage(DeviceMessage::Bytes(bytes)) => {
+                    assert_eq!(bytes, b"hi");
+                    break;
+                }
+                Action::ConnectionFailed(message) => panic!("{message}"),
+                _ => {}
+            }
+        }
+        assert_eq!(frames_sent, 2);
+        assert_eq!(
+            notes,
+            ["Warning: device reported a corrupted command, resending (1/3)"]
+        );
+    }
+
+    #[tokio::test]
+    async fn a_nak_resends_the_command_it_refers_to() {
+        let (action_tx, mut action_rx) = mpsc::unbounded_channel();
+        let (serial_tx, serial_rx) = queue::channel();
+        let options = SessionOptions::from_config(&Config::default());
+        tokio::spawn(App::connect(
+            NakOnce,
+            "nak".into(),
+            115_200,
+            serial_rx,
+            action_tx,
+            options,
+            Arc::default(),
+        ));
+        // Both queued at once: the second must wait for the first to be answered, or the NAK of
+        // the first would resend the second.
+        serial_tx.send(Outbound::Line("echo one".into())).unwrap();
+        serial_tx.send(Outbound::Line("echo two".into())).unwrap();
+
+        let mut frames_sent = 0;
+        let mut echoes = Vec::new();
+        while echoes.len() < 2 {
+            let action = timeout(Duration::from_secs(2), action_rx.recv())
+                .await
+                .expect("the session stalled")
+                .unwrap();
+            match action {
+                Action::FrameSent(_) => frames_sent += 1,
+                Action::IncomingMessage(DeviceMessage::Bytes(bytes)) => {
+                    echoes.push(String::from_utf8(bytes).unwrap())
+                }
+                Action::ConnectionFailed(message) => panic!("{message}"),
+                _ => {}
+            }
+        }
+        assert_eq!(echoes, ["one", "two"]);
+        assert_eq!(frames_sent, 3);
+    }
+
+    /// A transport whose device takes a while over the first command, reporting busy until it
+    /// has answered, and echoes the payload of every command.
+    struct SlowFirstCommand;
+
+    impl Transport for SlowFirstCommand {
+        type Stream = tokio::io::DuplexStream;
+
+        async fn open(&self, _port: &str, _baud_rate: u32) -> Result<Self::Stream, String> {
+            let (host, device) = tokio::io::duplex(256);
+            tokio::spawn(async move {
+                let (device_rx, mut device_tx) = tokio::io::split(device);
+                let mut device_rx = BufReader::new(device_rx);
+                let mut line = Vec::new();
+                device_rx.read_until(b'\n', &mut line).await.unwrap();
+                let reply = handshake::response(Framing::Postcard, false);
+                device_tx.write_all(reply.as_bytes()).await.unwrap();
+
+                let mut inbound = Inbound::new(Framing::Postcard, false);
+                let mut buffer = [0u8; 64];
+                let mut first = true;
+                loop {
+                    while let Some(received) = inbound.next_frame().unwrap() {
+                        let echo = &received.payload[2..];
+                        let replies = if is_info_query(&received.payload) {
+                            vec![UNKNOWN_COMMAND]
+                        } else if std::mem::take(&mut first) {
+                            let frame = encode_transport_frame(BUSY, Framing::Postcard).unwrap();
+                            device_tx.write_all(&frame).await.unwrap();
+                            tokio::time::sleep(Duration::from_millis(100)).await;
+                            vec![echo, READY]
+                        } else {
+                            vec![echo]
+                        };
+                        for reply in replies {
+                            let frame = encode_transport_frame(reply, Framing::Postcard).unwrap();
+                            device_tx.write_all(&frame).await.unwrap();
+                        }
+                    }
+                    match device_rx.read(&mut buffer).await {
+                        Ok(0) | Err(_) => return,
+                        Ok(n) => inbound.push(&buffer[..n]),
+                    }
+                }
+            });
+            Ok(host)
+        }
+    }
+
+    #[tokio::test]
+    async fn commands_wait_while_the_device_is_busy() {
+        let (action_tx, mut action_rx) = mpsc::unbounded_channel();
+        let (serial_tx, serial_rx) = queue::channel();
+        let options = SessionOptions::from_config(&Config::default());
+        tokio::spawn(App::connect(
+            SlowFirstCommand,
+            "slow".into(),
+            115_200,
+            serial_rx,
+            action_tx,
+            options,
+            Arc::default(),
+        ));
+        serial_tx.send(Outbound::Line("echo one".into())).unwrap();
+
+        let mut events = Vec::new();
+        while events.len() < 4 {
+            let action = timeout(Duration::from_secs(2), action_rx.recv())
+                .await
+                .expect("the session stalled")
+                .unwrap();
+            match action {
+                Action::FrameSent(_) => {
+                    events.push("sent".to_owned());
+                    if events.len() == 1 {
+                        // Long enough for the busy frame to arrive, well before the reply does.
+                        tokio::time::sleep(Duration::from_millis(30)).await;
+                        serial_tx.send(Outbound::Line("echo two".into())).unwrap();
+                    }
+                }
+                Action::IncomingMessage(DeviceMessage::Bytes(bytes)) => {
+                    events.push(String::from_utf8(bytes).unwrap())
+                }
+                Action::IncomingMessage(DeviceMessage::Text(text)) => panic!("{text}"),
+                Action::ConnectionFailed(message) => panic!("{message}"),
+                _ => {}
+            }
+        }
+        // Without flow control the second command would go out while the first was running.
+        assert_eq!(events, ["sent", "one", "sent", "two"]);
+    }
+
+    /// A transport whose device answers the handshake, then sends the start of a frame and
+    /// nothing more.
+    struct CutOffFrame;
+
+    impl Transport for CutOffFrame {
+        type Stream = tokio::io::DuplexStream;
+
+        async fn open(&self, _port: &str, _baud_rate: u32) -> Result<Self::Stream, String> {
+            let (host, device) = tokio::io::duplex(256);
+            tokio::spawn(async move {
+                let (device_rx, mut device_tx) = tokio::io::split(device);
+                let mut device_rx = BufReader::new(device_rx);
+                let mut line = Vec::new();
+                device_rx.read_until(b'\n', &mut line).await.unwrap();
+                let reply = handshake::response(Framing::Postcard, false);
+                device_tx.write_all(reply.as_bytes()).await.unwrap();
+                let frame = encode_transport_frame(b"lost", Framing::Postcard).unwrap();
+                device_tx.write_all(&frame[..4]).await.unwrap();
+                // Hold the link open without another byte, unread so the opening `info` query
+                // can't end it.
+                std::future::pending::<()>().await;
+            });
+            Ok(host)
+        }
+    }
+
+    #[tokio::test]
+    async fn a_stalled_partial_frame_is_reported_and_dropped() {
+        let (action_tx, mut action_rx) = mpsc::unbounded_channel();
+        let (_serial_tx, serial_rx) = queue::channel();
+        let options = SessionOptions {
+            stall_timeout: Duration::from_millis(50),
+            ..SessionOptions::from_config(&Config::default())
+        };
+        tokio::spawn(App::connect(
+            CutOffFrame,
+            "cut".into(),
+            115_200,
+            serial_rx,
+            action_tx,
+            options,
+            Arc::default(),
+        ));
+
+        loop {
+            let action = timeout(Duration::from_secs(2), action_rx.recv())
+                .await
+                .expect("the stall was never reported")
+                .unwrap();
+            match action {
+                Action::IncomingMessage(message) => {
+                    assert_eq!(message, stalled_warning(4));
+                    break;
+                }
+                Action::ConnectionFailed(message) => panic!("{message}"),
+                _ => {}
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn a_lost_ready_only_holds_commands_so_long() {
+        let (action_tx, mut action_rx) = mpsc::unbounded_channel();
+        let busy = watch::Sender::new(true);
+        wait_until_ready(&busy, Duration::from_millis(20), &action_tx).await;
+        assert!(!*busy.borrow());
+        assert_eq!(
+            action_rx.try_recv().unwrap(),
+            Action::IncomingMessage(DeviceMessage::Text(
+                "Device still busy after 20 ms; sending anyway.".into()
+            ))
+        );
+
+        // Once ready, nothing waits and nothing is said.
+        wait_until_ready(&busy, Duration::from_millis(20), &action_tx).await;
+        assert!(action_rx.try_recv().is_err());
+    }
+
+    fn update_components(app: &mut App, action: Action) {
+        for component in app.components.iter_mut() {
+            component.update(action.clone()).unwrap();
+        }
+    }
+
+    fn draw_app(app: &mut App) -> Buffer {
+        let mut terminal = Terminal::new(TestBackend::new(100, 40)).unwrap();
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+        terminal.backend().buffer().clone()
+    }
+
+    /// Each screen the app shows, with the help over the preconnect screen and the session, all
+    /// drawn with `theme`.
+    fn draw_every_screen(theme: Theme) -> Vec<(&'static str, Buffer)> {
+        let mut app = App::new(4.0, 60.0).unwrap().config(Config {
+            theme,
+            ..Config::default()
+        });
+        for component in app.components.iter_mut() {
+            component
+                .register_config_handler(app.config.clone())
+                .unwrap();
+        }
+        let mut screens = Vec::new();
+        update_components(&mut app, Action::PortsUpdated(vec!["/dev/ttyACM0".into()]));
+        screens.push(("preconnect", draw_app(&mut app)));
+        app.help_overlay = Some(HelpContext::Preconnect);
+        screens.push(("preconnect help", draw_app(&mut app)));
+        app.help_overlay = None;
+
+        update_components(
+            &mut app,
+            Action::Connect {
+                port: "/dev/ttyACM0".into(),
+                baud_rate: 115_200,
+            },
+        );
+        update_components(&mut app, Action::ShowConnecting);
+        screens.push(("connecting", draw_app(&mut app)));
+        update_components(&mut app, Action::ShowError("Handshake failed.".into()));
+        screens.push(("error", draw_app(&mut app)));
+
+        update_components(&mut app, Action::ShowMain);
+        for text in ["hello", "Warning: slow", "Error: bad"] {
+            update_components(
+                &mut app,
+                Action::IncomingMessage(DeviceMessage::Text(text.into())),
+            );
+        }
+        screens.push(("session", draw_app(&mut app)));
+        app.help_overlay = Some(HelpContext::Connected);
+        screens.push(("session help", draw_app(&mut app)));
+        screens
+    }
+
+    fn screen<'a>(screens: &'a [(&str, Buffer)], name: &str) -> &'a Buffer {
+        &screens
+            .iter()
+            .find(|(screen, _)| *screen == name)
+            .unwrap()
+            .1
+    }
+
+    /// The first cell of the first place `text` is drawn.
+    fn cell_of<'a>(buffer: &'a Buffer, text: &str) -> &'a Cell {
+        let area = buffer.area;
+        for y in area.top()..area.bottom() {
+            for x in area.left()..area.right() {
+                let drawn: String = (x..area.right()).map(|x| buffer[(x, y)].symbol()).collect();
+                if drawn.starts_with(text) {
+                    return &buffer[(x, y)];
+                }
+            }
+        }
+        panic!("{text:?} isn't drawn");
+    }
+
+    #[test]
+    fn high_contrast_theme_draws_every_pane() {
+        let dim = [Color::DarkGray, Color::Blue, Color::LightBlue, Color::Cyan];
+        let screens = draw_every_screen(Theme::HighContrast);
+        for (name, buffer) in &screens {
+            let cells = buffer.content();
+            assert!(cells.iter().any(|cell| cell.symbol() == "┏"), "{name}");
+            assert!(cells.iter().all(|cell| !dim.contains(&cell.fg)), "{name}");
+        }
+        let preconnect = screen(&screens, "preconnect");
+        assert_eq!(cell_of(preconnect, "➤").bg, Color::White);
+        assert_eq!(cell_of(preconnect, "/____/").fg, Color::White);
+        assert_eq!(cell_of(preconnect, "じ").fg, Color::White);
+    }
+
+    #[test]
+    fn default_theme_keeps_its_colours() {
+        let screens = draw_every_screen(Theme::Default);
+        let preconnect = screen(&screens, "preconnect");
+        assert_eq!(cell_of(preconnect, "➤").bg, Color::LightBlue);
+        assert_eq!(cell_of(preconnect, "/____/").fg, Color::Blue);
+        assert_eq!(cell_of(preconnect, "じ").fg, Color::Blue);
+        assert_eq!(
+            cell_of(screen(&screens, "connecting"), "Connecting to device").fg,
+            Color::LightCyan
+        );
+        assert_eq!(
+            cell_of(screen(&screens, "session help"), "protocol").fg,
+            Color::Cyan
+        );
+        for (name, buffer) in &screens {
+            let cells = buffer.content();
+            assert!(cells.iter().any(|cell| cell.symbol() == "┌"), "{name}");
+            assert!(cells.iter().all(|cell| cell.symbol() != "┏"), "{name}");
+        }
     }
 }
@@ -1,6 +1,14 @@
+use std::path::PathBuf;
+
 use clap::Parser;
+use protocol::{HANDSHAKE_TIMEOUT, duration_millis, transport::Framing};
 
-use crate::config::{get_config_dir, get_data_dir};
+use crate::{
+    components::terminal::ByteStyle,
+    config::{DEFAULT_STALL_TIMEOUT, DEFAULT_WRITE_CHUNK, get_config_dir, get_data_dir},
+    logging::{DEFAULT_LOG_RETAIN, DEFAULT_LOG_ROTATE_BYTES},
+    theme::Theme,
+};
 
 #[derive(Parser, Debug)]
 #[command(author, version = version(), about)]
@@ -12,6 +20,159 @@ pub struct Cli {
     /// Frame rate, i.e. number of frames per second
     #[arg(short, long, value_name = "FLOAT", default_value_t = 60.0)]
     pub frame_rate: f64,
+
+    /// Script of commands to send once connected, one per line (`#` starts a comment)
+    #[arg(long, value_name = "FILE")]
+    pub script: Option<PathBuf>,
+
+    /// Interval between auto-repeated commands in milliseconds (minimum 100, and never shorter than
+    /// one tick)
+    #[arg(long, value_name = "MS", default_value_t = 1000)]
+    pub repeat_interval_ms: u64,
+
+    /// Write a debug log (including protocol frames) for this run to the data directory's sessions folder
+    #[arg(long)]
+    pub log: bool,
+
+    /// Size in KiB a log file reaches before the run moves on to a new one
+    #[arg(long, value_name = "KIB", default_value_t = DEFAULT_LOG_ROTATE_BYTES / 1024)]
+    pub log_rotate_kb: u64,
+
+    /// Log files kept across runs; the oldest are deleted beyond this
+    #[arg(long, value_name = "COUNT", default_value_t = DEFAULT_LOG_RETAIN)]
+    pub log_keep: usize,
+
+    /// Frame layout to negotiate with the device: postcard, or length for `[len][payload][crc]`
+    #[arg(long, value_name = "FRAMING", default_value = "postcard", value_parser = parse_framing)]
+    pub framing: Framing,
+
+    /// Start sessions in dry-run mode: show the bytes each command encodes to instead of sending it
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Follow each sent command with how it decodes, e.g. `→ i2c read 0x48 0x00 2`
+    #[arg(long)]
+    pub verbose: bool,
+
+    /// Note where each received frame starts and ends in the byte stream, and any bytes left undecoded, e.g. `[frame 0: 0..7 len=3]`
+    #[arg(long)]
+    pub show_frames: bool,
+
+    /// Hide this prefix from the start of echo replies in the UTF-8 view, e.g. "rp2040: " (hex and binary still show it)
+    #[arg(long, value_name = "TEXT")]
+    pub strip_echo_prefix: Option<String>,
+
+    /// Ask the device to tag each response with the method that produced it, shown as `[i2c]`
+    #[arg(long)]
+    pub tag_responses: bool,
+
+    /// Pause between write chunks in milliseconds, for devices that drop bytes sent back to back
+    #[arg(long, value_name = "MS", default_value_t = 0)]
+    pub write_delay_ms: u64,
+
+    /// Bytes per write chunk when --write-delay-ms is set
+    #[arg(long, value_name = "BYTES", default_value_t = DEFAULT_WRITE_CHUNK)]
+    pub write_chunk: usize,
+
+    #[arg(
+        long,
+        value_name = "MS",
+        default_value_t = duration_millis(HANDSHAKE_TIMEOUT),
+        value_parser = parse_handshake_timeout,
+        help = format!(
+            "Time to wait for the device's handshake reply in milliseconds (minimum {}, the device's)",
+            duration_millis(HANDSHAKE_TIMEOUT)
+        )
+    )]
+    pub handshake_timeout: u64,
+
+    /// Time a partially received frame may go without new bytes before it is reported and dropped, in milliseconds
+    #[arg(
+        long,
+        value_name = "MS",
+        default_value_t = DEFAULT_STALL_TIMEOUT.as_millis() as u64,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub stall_timeout_ms: u64,
+
+    /// Longest payload a received frame may claim before its header is skipped as corrupt, in bytes [default: the device's longest response]
+    #[arg(long, value_name = "BYTES", value_parser = clap::value_parser!(u16).range(1..))]
+    pub max_frame: Option<u16>,
+
+    /// Offer an in-process simulated device instead of serial ports, for trying SiTerm without hardware
+    #[arg(long)]
+    pub simulate: bool,
+
+    /// Also offer a serial port exposed over TCP (e.g. by ser2net) at HOST:PORT on the port list
+    #[arg(long, value_name = "HOST:PORT", value_parser = parse_tcp_address)]
+    pub tcp: Option<String>,
+
+    /// Layout of the hex and binary views: spaced, compact, comma or c-array
+    #[arg(long, value_name = "STYLE", default_value = "spaced", value_parser = parse_byte_style)]
+    pub byte_style: ByteStyle,
+
+    /// Byte Backspace sends in keystroke mode: del (0x7f) or bs (0x08)
+    #[arg(long, value_name = "KEY", default_value = "del", value_parser = parse_backspace)]
+    pub backspace: u8,
+
+    /// Colours of the interface: default or high-contrast
+    #[arg(long, value_name = "THEME", default_value = "default", value_parser = parse_theme)]
+    pub theme: Theme,
+}
+
+fn parse_framing(name: &str) -> Result<Framing, String> {
+    Framing::from_name(name).ok_or_else(|| {
+        format!(
+            "expected one of: {}",
+            Framing::ALL.map(|framing| framing.name()).join(", ")
+        )
+    })
+}
+
+/// A handshake timeout in milliseconds, no shorter than the device's own, or the host would give
+/// up before the device's timeout error arrives.
+fn parse_handshake_timeout(ms: &str) -> Result<u64, String> {
+    let ms: u64 = ms.parse().map_err(|err| format!("{err}"))?;
+    let minimum = duration_millis(HANDSHAKE_TIMEOUT);
+    if ms < minimum {
+        return Err(format!("must be at least {minimum}, the device's timeout"));
+    }
+    Ok(ms)
+}
+
+fn parse_byte_style(name: &str) -> Result<ByteStyle, String> {
+    ByteStyle::from_name(name).ok_or_else(|| {
+        format!(
+            "expected one of: {}",
+            ByteStyle::ALL.map(|style| style.name()).join(", ")
+        )
+    })
+}
+
+fn parse_theme(name: &str) -> Result<Theme, String> {
+    Theme::from_name(name).ok_or_else(|| {
+        format!(
+            "expected one of: {}",
+            Theme::ALL.map(|theme| theme.name()).join(", ")
+        )
+    })
+}
+
+fn parse_backspace(name: &str) -> Result<u8, String> {
+    match name {
+        "del" => Ok(0x7F),
+        "bs" => Ok(0x08),
+        _ => Err("expected one of: del, bs".into()),
+    }
+}
+
+fn parse_tcp_address(address: &str) -> Result<String, String> {
+    match address.rsplit_once(':') {
+        Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {
+            Ok(address.to_owned())
+        }
+        _ => Err("expected HOST:PORT, e.g. lab-server:4001".into()),
+    }
 }
 
 const VERSION_MESSAGE: &str = concat!(
@@ -2,23 +2,29 @@ use color_eyre::Result;
 use ratatui::{
     Frame,
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Paragraph},
+    widgets::Paragraph,
 };
 use tokio::sync::mpsc::UnboundedSender;
 
 use super::Component;
-use crate::{action::Action, config::Config};
+use crate::{
+    action::Action,
+    config::Config,
+    keymap::{KeyAction, Keymap},
+    theme::Palette,
+};
 
 #[derive(Default)]
 pub struct ConnectingScreen {
     action_tx: Option<UnboundedSender<Action>>,
     #[allow(dead_code)]
     config: Option<Config>,
+    keymap: Keymap,
     is_active: bool,
     port: Option<String>,
     baud_rate: Option<u32>,
+    palette: Palette,
 }
 
 impl ConnectingScreen {
@@ -41,12 +47,14 @@ impl Component for ConnectingScreen {
     }
 
     fn register_config_handler(&mut self, config: Config) -> Result<()> {
+        self.keymap = config.keymap.clone();
+        self.palette = config.theme.palette();
         self.config = Some(config);
         Ok(())
     }
 
     fn handle_key_event(&mut self, key: crossterm::event::KeyEvent) -> Result<Option<Action>> {
-        use crossterm::event::{KeyCode, KeyModifiers};
+        use crossterm::event::KeyCode;
 
         if !self.is_active {
             return Ok(None);
@@ -56,7 +64,7 @@ impl Component for ConnectingScreen {
             (KeyCode::Esc, _) => {
                 self.send(Action::ShowPreconnect)?;
             }
-            (KeyCode::Char('q'), KeyModifiers::NONE) => {
+            _ if self.keymap.action(&key) == Some(KeyAction::Quit) => {
                 self.send(Action::Quit)?;
             }
             _ => {}
@@ -73,7 +81,7 @@ impl Component for ConnectingScreen {
                 self.port = Some(port);
                 self.baud_rate = Some(baud_rate);
             }
-            Action::ConnectionEstablished { .. }
+            Action::ConnectionEstablished(_)
             | Action::ConnectionFailed(_)
             | Action::ShowPreconnect
             | Action::ShowMain
@@ -95,12 +103,7 @@ impl Component for ConnectingScreen {
             .constraints([Constraint::Percentage(40), Constraint::Percentage(60)].as_ref())
             .split(area);
 
-        let title = Span::styled(
-            "Connecting to device…",
-            Style::default()
-                .fg(Color::LightCyan)
-                .add_modifier(Modifier::BOLD),
-        );
+        let title = Span::styled("Connecting to device…", self.palette.heading);
         let mut lines = vec![
             Line::from("Testing serial connection and verifying SiTerm firmware."),
             Line::from("Press Esc to cancel and return to selection."),
@@ -112,13 +115,13 @@ impl Component for ConnectingScreen {
         }
 
         frame.render_widget(
-            Paragraph::new(lines).block(Block::default().title(title).borders(Borders::ALL)),
+            Paragraph::new(lines).block(self.palette.block().title(title)),
             layout[0],
         );
 
         frame.render_widget(
             Paragraph::new("Waiting for handshake response…")
-                .block(Block::default().title("Status").borders(Borders::ALL)),
+                .block(self.palette.block().title("Status")),
             layout[1],
         );
 
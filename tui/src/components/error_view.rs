@@ -2,22 +2,29 @@ use color_eyre::Result;
 use ratatui::{
     Frame,
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Modifier, Style},
+    style::Modifier,
     text::{Line, Span},
-    widgets::{Block, Borders, Paragraph},
+    widgets::Paragraph,
 };
 use tokio::sync::mpsc::UnboundedSender;
 
 use super::Component;
-use crate::{action::Action, config::Config};
+use crate::{
+    action::Action,
+    config::Config,
+    keymap::{KeyAction, Keymap},
+    theme::Palette,
+};
 
 #[derive(Default)]
 pub struct ErrorScreen {
     action_tx: Option<UnboundedSender<Action>>,
     #[allow(dead_code)]
     config: Option<Config>,
+    keymap: Keymap,
     is_active: bool,
     message: Option<String>,
+    palette: Palette,
 }
 
 impl ErrorScreen {
@@ -40,6 +47,8 @@ impl Component for ErrorScreen {
     }
 
     fn register_config_handler(&mut self, config: Config) -> Result<()> {
+        self.keymap = config.keymap.clone();
+        self.palette = config.theme.palette();
         self.config = Some(config);
         Ok(())
     }
@@ -55,7 +64,7 @@ impl Component for ErrorScreen {
             (KeyCode::Enter, _) | (KeyCode::Esc, _) | (KeyCode::Char('r'), KeyModifiers::NONE) => {
                 self.send(Action::ShowPreconnect)?;
             }
-            (KeyCode::Char('q'), KeyModifiers::NONE) => {
+            _ if self.keymap.action(&key) == Some(KeyAction::Quit) => {
                 self.send(Action::Quit)?;
             }
             _ => {}
@@ -90,7 +99,7 @@ impl Component for ErrorScreen {
 
         let title = Span::styled(
             "Connection Error",
-            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            self.palette.error.add_modifier(Modifier::BOLD),
         );
 
         let body = self
@@ -99,7 +108,7 @@ impl Component for ErrorScreen {
             .unwrap_or_else(|| "Unknown error.".into());
 
         frame.render_widget(
-            Paragraph::new(body).block(Block::default().title(title).borders(Borders::ALL)),
+            Paragraph::new(body).block(self.palette.block().title(title)),
             layout[0],
         );
 
@@ -110,8 +119,7 @@ impl Component for ErrorScreen {
             Line::from("Press Enter to return to the preconnect screen, or q to quit."),
         ];
         frame.render_widget(
-            Paragraph::new(instructions)
-                .block(Block::default().title("Next steps").borders(Borders::ALL)),
+            Paragraph::new(instructions).block(self.palette.block().title("Next steps")),
             layout[1],
         );
 
@@ -1,17 +1,49 @@
 use std::cmp::min;
 
 use color_eyre::Result;
+use protocol::device_info::DeviceInfo;
 use ratatui::{
     Frame,
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Modifier, Style},
+    style::{Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    widgets::{List, ListItem, ListState, Paragraph},
 };
 use tokio::sync::mpsc::UnboundedSender;
 
 use super::Component;
-use crate::{action::Action, config::Config};
+use crate::{
+    action::Action,
+    config::Config,
+    keymap::{KeyAction, Keymap},
+    last_connection::LastConnection,
+    theme::Palette,
+};
+
+/// Rates offered when the device hasn't reported its UART capabilities.
+const FALLBACK_BAUD_RATES: &[u32] = &[9_600, 19_200, 38_400, 57_600, 115_200];
+/// Rates offered to a device that has, trimmed to its maximum.
+const STANDARD_BAUD_RATES: &[u32] = &[
+    9_600, 19_200, 38_400, 57_600, 115_200, 230_400, 460_800, 921_600,
+];
+
+/// Baud rates to offer for a device, falling back to [`FALLBACK_BAUD_RATES`] when its
+/// capabilities are unknown or rule out every standard rate.
+fn baud_options(info: Option<&DeviceInfo>) -> Vec<u32> {
+    let Some(uart) = info.and_then(|info| info.uart) else {
+        return FALLBACK_BAUD_RATES.to_vec();
+    };
+    let supported: Vec<u32> = STANDARD_BAUD_RATES
+        .iter()
+        .copied()
+        .filter(|&baud| uart.supports_baud(baud))
+        .collect();
+    if supported.is_empty() {
+        FALLBACK_BAUD_RATES.to_vec()
+    } else {
+        supported
+    }
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Focus {
@@ -30,6 +62,16 @@ pub struct PreconnectScreen {
     port_index: usize,
     baud_index: usize,
     status_message: Option<String>,
+    keymap: Keymap,
+    /// Keymap conflicts and the favorites the keymap shadows, shown in place of the status line
+    /// until the first key press.
+    keymap_warnings: Vec<String>,
+    /// Port and baud rate of the last successful connection.
+    remembered: Option<LastConnection>,
+    /// Whether the next port list should select [`Self::remembered`], as it does at startup and
+    /// on coming back from a connection.
+    restore_pending: bool,
+    palette: Palette,
 }
 
 impl Default for PreconnectScreen {
@@ -40,10 +82,17 @@ impl Default for PreconnectScreen {
             focus: Focus::Ports,
             is_active: true,
             ports: Vec::new(),
-            baud_rates: vec![9_600, 19_200, 38_400, 57_600, 115_200],
+            // The device can't be asked before a port is opened, so start from the fallback and
+            // trim once a session reports its [`DeviceInfo`].
+            baud_rates: baud_options(None),
             port_index: 0,
             baud_index: 0,
             status_message: None,
+            keymap: Keymap::default(),
+            keymap_warnings: Vec::new(),
+            remembered: None,
+            restore_pending: true,
+            palette: Palette::default(),
         }
     }
 }
@@ -99,6 +148,46 @@ impl PreconnectScreen {
         };
     }
 
+    /// Select the remembered port and baud rate. A port that has gone away leaves the first one
+    /// selected and says so; a rate that isn't offered leaves the rate as it was.
+    fn restore_selection(&mut self) {
+        self.restore_pending = false;
+        let Some(remembered) = &self.remembered else {
+            return;
+        };
+        if let Some(index) = self
+            .baud_rates
+            .iter()
+            .position(|&baud| baud == remembered.baud_rate)
+        {
+            self.baud_index = index;
+        }
+        if self.ports.is_empty() {
+            return;
+        }
+        match self.ports.iter().position(|port| *port == remembered.port) {
+            Some(index) => self.port_index = index,
+            None => {
+                self.port_index = 0;
+                self.status_message = Some(format!(
+                    "Last used port {} is gone. Select a port and press Enter to connect.",
+                    remembered.port
+                ));
+            }
+        }
+    }
+
+    /// Offer the rates `info` supports, keeping the selected one when it is still offered.
+    fn offer_bauds_for(&mut self, info: &DeviceInfo) {
+        let selected = self.baud_rates[self.baud_index];
+        self.baud_rates = baud_options(Some(info));
+        self.baud_index = self
+            .baud_rates
+            .iter()
+            .position(|&baud| baud == selected)
+            .unwrap_or(0);
+    }
+
     fn attempt_connect(&mut self) -> Result<Option<Action>> {
         if self.ports.is_empty() {
             self.status_message = Some("No serial ports detected. Press r to refresh.".into());
@@ -117,6 +206,12 @@ impl Component for PreconnectScreen {
     }
 
     fn register_config_handler(&mut self, config: Config) -> Result<()> {
+        self.keymap = config.keymap.clone();
+        self.keymap_warnings = config.keymap.warnings().to_vec();
+        self.keymap_warnings
+            .extend(config.favorites.shadowed_by(&config.keymap));
+        self.remembered = config.last_connection.clone();
+        self.palette = config.theme.palette();
         self.config = Some(config);
         Ok(())
     }
@@ -133,17 +228,21 @@ impl Component for PreconnectScreen {
             return Ok(None);
         }
 
-        match (key.code, key.modifiers) {
-            (KeyCode::Char('q'), KeyModifiers::NONE) => {
+        self.keymap_warnings.clear();
+        match self.keymap.action(&key) {
+            Some(KeyAction::Quit) => {
                 self.send(Action::Quit)?;
+                return Ok(None);
             }
-            (KeyCode::Char('h'), KeyModifiers::NONE) => {
-                return Ok(Some(Action::ToggleHelp));
-            }
-            (KeyCode::Char('r'), KeyModifiers::NONE) => {
+            Some(KeyAction::Help) => return Ok(Some(Action::ToggleHelp)),
+            Some(KeyAction::RefreshPorts) => {
                 self.status_message = Some("Refreshing ports...".into());
                 self.send(Action::RefreshPorts)?;
+                return Ok(None);
             }
+            _ => {}
+        }
+        match (key.code, key.modifiers) {
             (KeyCode::Tab, _)
             | (KeyCode::Left, KeyModifiers::NONE)
             | (KeyCode::Right, KeyModifiers::NONE) => {
@@ -168,6 +267,10 @@ impl Component for PreconnectScreen {
             Action::ShowPreconnect => {
                 self.is_active = true;
                 self.status_message = None;
+                self.restore_pending = true;
+                if !self.ports.is_empty() {
+                    self.restore_selection();
+                }
             }
             Action::ShowConnecting | Action::ShowMain | Action::ShowError(_) => {
                 self.is_active = false;
@@ -184,11 +287,24 @@ impl Component for PreconnectScreen {
                     );
                 } else {
                     self.status_message = Some(" Select a port and press Enter to connect.".into());
+                    if self.restore_pending {
+                        self.restore_selection();
+                    }
                 }
             }
-            Action::ConnectionFailed(message) => {
+            Action::ConnectionEstablished(info) => {
+                self.remembered = Some(LastConnection {
+                    port: info.port,
+                    baud_rate: info.baud_rate,
+                });
+            }
+            Action::DeviceInfo(info) => self.offer_bauds_for(&info),
+            Action::ConnectionFailed(message)
+            | Action::PortListFailed(message)
+            | Action::Disconnected(message) => {
                 self.status_message = Some(message);
             }
+            Action::Error(message) => self.status_message = Some(format!("Error: {message}")),
             _ => {}
         }
         Ok(None)
@@ -212,13 +328,14 @@ impl Component for PreconnectScreen {
             )
             .split(area);
 
+        let [top, upper, middle, bottom] = self.palette.gradient;
         let welcome_text = vec![
             Line::from("Welcome to ").style(Modifier::ITALIC),
-            Line::from(r"   _____ _ ______                  ").style(Color::Cyan),
-            Line::from(r"  / ___/(_)_  __/__  _________ ___ ").style(Color::Cyan),
-            Line::from(r"  \__ \/ / / / / _ \/ ___/ __ `__ \").style(Color::LightCyan),
-            Line::from(r" ___/ / / / / /  __/ /  / / / / / /").style(Color::LightBlue),
-            Line::from(r"/____/_/ /_/  \___/_/  /_/ /_/ /_/").style(Color::Blue),
+            Line::from(r"   _____ _ ______                  ").style(top),
+            Line::from(r"  / ___/(_)_  __/__  _________ ___ ").style(top),
+            Line::from(r"  \__ \/ / / / / _ \/ ___/ __ `__ \").style(upper),
+            Line::from(r" ___/ / / / / /  __/ /  / / / / / /").style(middle),
+            Line::from(r"/____/_/ /_/  \___/_/  /_/ /_/ /_/").style(bottom),
         ];
         frame.render_widget(Paragraph::new(welcome_text), layout[0]);
 
@@ -229,8 +346,7 @@ impl Component for PreconnectScreen {
             ),
         ];
         frame.render_widget(
-            Paragraph::new(instruction_lines)
-                .block(Block::default().title("Instructions").borders(Borders::ALL)),
+            Paragraph::new(instruction_lines).block(self.palette.block().title("Instructions")),
             layout[1],
         );
 
@@ -247,29 +363,26 @@ impl Component for PreconnectScreen {
         #[rustfmt::skip]
         let cat_ascii = vec![
             Line::from(""),
-            Line::from(vec![Span::styled(" ╱|、    ", Style::default().fg(Color::Cyan))]).right_aligned(),
-            Line::from(vec![Span::styled("(˚ˎ。7   ", Style::default().fg(Color::LightCyan))]).right_aligned(),
-            Line::from(vec![Span::styled("|、˜〵   ", Style::default().fg(Color::LightBlue))]).right_aligned(),
-            Line::from(vec![Span::styled("じしˍ,)ノ", Style::default().fg(Color::Blue))]).right_aligned(),
+            Line::from(vec![Span::styled(" ╱|、    ", top)]).right_aligned(),
+            Line::from(vec![Span::styled("(˚ˎ。7   ", upper)]).right_aligned(),
+            Line::from(vec![Span::styled("|、˜〵   ", middle)]).right_aligned(),
+            Line::from(vec![Span::styled("じしˍ,)ノ", bottom)]).right_aligned(),
         ];
 
         frame.render_widget(
-            Paragraph::new(cat_ascii).block(Block::bordered().title("Your new friend")),
+            Paragraph::new(cat_ascii).block(self.palette.block().title("Your new friend")),
             cat_spot[1],
         );
 
-        let highlight_style = Style::default()
-            .fg(Color::Black)
-            .bg(Color::LightBlue)
-            .add_modifier(Modifier::BOLD);
+        let highlight_style = self.palette.selection;
 
         let port_block_style = if self.focus == Focus::Ports {
-            Style::default().fg(Color::Blue)
+            self.palette.focus
         } else {
             Style::default()
         };
         let baud_block_style = if self.focus == Focus::Baud {
-            Style::default().fg(Color::Blue)
+            self.palette.focus
         } else {
             Style::default()
         };
@@ -289,9 +402,9 @@ impl Component for PreconnectScreen {
         frame.render_stateful_widget(
             List::new(port_items)
                 .block(
-                    Block::default()
-                        .title(Span::styled("Serial Ports", port_block_style))
-                        .borders(Borders::ALL),
+                    self.palette
+                        .block()
+                        .title(Span::styled("Serial Ports", port_block_style)),
                 )
                 .highlight_style(highlight_style)
                 .highlight_symbol("➤ "),
@@ -309,9 +422,9 @@ impl Component for PreconnectScreen {
         frame.render_stateful_widget(
             List::new(baud_items)
                 .block(
-                    Block::default()
-                        .title(Span::styled("Baud Rates", baud_block_style))
-                        .borders(Borders::ALL),
+                    self.palette
+                        .block()
+                        .title(Span::styled("Baud Rates", baud_block_style)),
                 )
                 .highlight_style(highlight_style)
                 .highlight_symbol("➤ "),
@@ -319,12 +432,146 @@ impl Component for PreconnectScreen {
             &mut baud_state,
         );
 
-        let status = self
-            .status_message
-            .clone()
-            .unwrap_or_else(|| "Ready.".into());
-        frame.render_widget(Paragraph::new(status), layout[3]);
+        if self.keymap_warnings.is_empty() {
+            let status = self
+                .status_message
+                .clone()
+                .unwrap_or_else(|| "Ready.".into());
+            frame.render_widget(Paragraph::new(status), layout[3]);
+        } else {
+            frame.render_widget(
+                Paragraph::new(self.keymap_warnings.join(" ")).style(self.palette.warning),
+                layout[3],
+            );
+        }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use protocol::{
+        MAX_I2C_READ_LEN,
+        device_info::{DeviceConfig, DeviceLabel, UartCapabilities},
+    };
+
+    use super::*;
+
+    #[test]
+    fn unknown_devices_get_fallback_rates() {
+        assert_eq!(baud_options(None), FALLBACK_BAUD_RATES);
+        assert_eq!(
+            baud_options(Some(&DeviceInfo {
+                max_i2c_read: MAX_I2C_READ_LEN,
+                config: DeviceConfig::DEFAULT,
+                uart: None,
+                label: DeviceLabel::default(),
+            })),
+            FALLBACK_BAUD_RATES
+        );
+    }
+
+    fn screen_remembering(port: &str, baud_rate: u32) -> PreconnectScreen {
+        let mut screen = PreconnectScreen::new();
+        screen
+            .register_config_handler(Config {
+                last_connection: Some(LastConnection {
+                    port: port.into(),
+                    baud_rate,
+                }),
+                ..Config::default()
+            })
+            .unwrap();
+        screen
+    }
+
+    fn ports(names: &[&str]) -> Action {
+        Action::PortsUpdated(names.iter().map(|&name| name.to_owned()).collect())
+    }
+
+    #[test]
+    fn remembered_selection_is_restored() {
+        let mut screen = screen_remembering("/dev/ttyACM1", 57_600);
+        screen
+            .update(ports(&["/dev/ttyACM0", "/dev/ttyACM1"]))
+            .unwrap();
+        assert_eq!(
+            screen.attempt_connect().unwrap(),
+            Some(Action::Connect {
+                port: "/dev/ttyACM1".into(),
+                baud_rate: 57_600
+            })
+        );
+
+        // A refresh keeps whatever was picked since; coming back from a session restores it again.
+        screen.select_previous_port();
+        screen
+            .update(ports(&["/dev/ttyACM0", "/dev/ttyACM1"]))
+            .unwrap();
+        assert_eq!(screen.port_index, 0);
+        screen.update(Action::ShowPreconnect).unwrap();
+        assert_eq!(screen.port_index, 1);
+    }
+
+    #[test]
+    fn missing_remembered_port_falls_back_to_the_first() {
+        let mut screen = screen_remembering("/dev/ttyUSB3", 115_200);
+        screen
+            .update(ports(&["/dev/ttyACM0", "/dev/ttyACM1"]))
+            .unwrap();
+        assert_eq!(screen.port_index, 0);
+        assert_eq!(
+            screen.status_message.as_deref(),
+            Some("Last used port /dev/ttyUSB3 is gone. Select a port and press Enter to connect.")
+        );
+    }
+
+    #[test]
+    fn reported_max_baud_trims_the_options() {
+        let info = DeviceInfo {
+            max_i2c_read: MAX_I2C_READ_LEN,
+            config: DeviceConfig::DEFAULT,
+            uart: Some(UartCapabilities {
+                max_baud: 460_800,
+                parities: 0,
+            }),
+            label: DeviceLabel::default(),
+        };
+        assert_eq!(baud_options(Some(&info)).last(), Some(&460_800));
+
+        let slow = DeviceInfo {
+            max_i2c_read: MAX_I2C_READ_LEN,
+            config: DeviceConfig::DEFAULT,
+            uart: Some(UartCapabilities {
+                max_baud: 1_200,
+                parities: 0,
+            }),
+            label: DeviceLabel::default(),
+        };
+        assert_eq!(baud_options(Some(&slow)), FALLBACK_BAUD_RATES);
+    }
+
+    #[test]
+    fn reported_info_trims_the_offered_rates() {
+        let mut screen = PreconnectScreen::new();
+        screen.baud_index = screen
+            .baud_rates
+            .iter()
+            .position(|&baud| baud == 115_200)
+            .unwrap();
+        screen
+            .update(Action::DeviceInfo(DeviceInfo {
+                max_i2c_read: MAX_I2C_READ_LEN,
+                config: DeviceConfig::DEFAULT,
+                uart: Some(UartCapabilities {
+                    max_baud: 230_400,
+                    parities: 0,
+                }),
+                label: DeviceLabel::default(),
+            }))
+            .unwrap();
+        assert_eq!(screen.baud_rates.last(), Some(&230_400));
+        assert_eq!(screen.baud_rates[screen.baud_index], 115_200);
+    }
+}
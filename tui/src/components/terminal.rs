@@ -1,33 +1,80 @@
-use std::{collections::VecDeque, fmt::Write};
+use std::{
+    collections::VecDeque,
+    fmt::Write,
+    rc::Rc,
+    time::{Duration, Instant},
+};
 
 use color_eyre::Result;
+use protocol::{
+    Method,
+    host::hint::{ValueHint, default_hint, split_value_hint},
+    response::{ERROR_PREFIX, ResponseFormat},
+};
 use ratatui::{
     Frame,
-    layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Modifier, Style},
+    layout::{Constraint, Direction, Layout, Rect, Size},
+    style::{Modifier, Style},
     text::{Line, Span, Text},
-    widgets::{Block, Borders, Clear, List, ListItem, Paragraph},
+    widgets::{Clear, List, ListItem, Paragraph},
 };
 use tokio::sync::mpsc::UnboundedSender;
 use unicode_width::UnicodeWidthChar;
 
 use super::Component;
+
 use crate::{
     action::{Action, DeviceMessage},
-    config::Config,
+    config::{Config, DEFAULT_BACKSPACE, DEFAULT_REPEAT_INTERVAL},
+    favorites::Favorites,
+    keymap::{KeyAction, Keymap},
+    labels::Labels,
+    pipeline::{BRIDGE_EXIT_LINE, BRIDGE_LINE_ENDING, interpret_command, is_blank_line},
+    script,
+    theme::Palette,
+    transport::{ConnectionInfo, SessionMode},
 };
 
+mod baud_check;
+mod history;
+mod inspector;
+mod length;
+mod liveness;
+mod repeat;
+mod scrollback;
+mod search;
+
+use baud_check::BaudCheck;
+use history::{CommandOutcome, HistoryEntry};
+use inspector::ByteInspector;
+use length::{CommandLength, length_note};
+use liveness::Liveness;
+use repeat::AutoRepeat;
+use scrollback::Scrollback;
+use search::{find_matches, highlight_matches};
+
 const HISTORY_LIMIT: usize = 20;
 const MESSAGE_LIMIT: usize = 200;
+/// How long the "sent" note stays on the Command Input border.
+const SENT_NOTE_DURATION: Duration = Duration::from_millis(750);
+/// Columns taken by the time since the previous message, e.g. `  +12ms`.
+const DELTA_WIDTH: usize = 7;
+/// Terminals shorter than this many rows get the compact layout unless one was picked by hand.
+/// The full layout needs 23 rows before the message pane reaches its minimum height.
+pub const COMPACT_BELOW_ROWS: u16 = 24;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 enum InputMode {
+    #[default]
     Normal,
     Editing,
+    /// Typing the query the message pane highlights.
+    Searching,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 enum MessageEncoding {
+    #[default]
     Utf8,
     Hex,
     Binary,
@@ -43,63 +90,159 @@ impl MessageEncoding {
     }
 }
 
+/// How the hex and binary views lay bytes out, so they can be pasted where they're needed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ByteStyle {
+    /// `0xAA 0xBB`
+    #[default]
+    Spaced,
+    /// `AABB`, for tools that take a bare hex string.
+    Compact,
+    /// `0xAA, 0xBB`
+    Comma,
+    /// `{ 0xAA, 0xBB }`, ready for a C array initializer.
+    CArray,
+}
+
+impl ByteStyle {
+    pub const ALL: [ByteStyle; 4] = [
+        ByteStyle::Spaced,
+        ByteStyle::Compact,
+        ByteStyle::Comma,
+        ByteStyle::CArray,
+    ];
+
+    /// Name used on the command line and in the view label.
+    pub fn name(self) -> &'static str {
+        match self {
+            ByteStyle::Spaced => "spaced",
+            ByteStyle::Compact => "compact",
+            ByteStyle::Comma => "comma",
+            ByteStyle::CArray => "c-array",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|style| style.name().eq_ignore_ascii_case(name))
+    }
+
+    fn next(self) -> Self {
+        let index = Self::ALL
+            .iter()
+            .position(|&style| style == self)
+            .unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
 #[derive(Debug, Clone)]
 struct MessageLine {
     content: DeviceMessage,
     style: Style,
+    /// Type the user asked to see this response decoded as.
+    hint: Option<ValueHint>,
+    /// Method the device said produced this response.
+    source: Option<Method>,
+    /// Leading bytes left out of the UTF-8 view, the echo prefix on an echo reply.
+    hidden_prefix: usize,
+    /// When the message reached the pane.
+    arrived: Instant,
 }
 
 impl MessageLine {
     fn new(content: DeviceMessage, style: Style) -> Self {
-        Self { content, style }
+        Self {
+            content,
+            style,
+            hint: None,
+            source: None,
+            hidden_prefix: 0,
+            arrived: Instant::now(),
+        }
+    }
+
+    fn with_hint(mut self, hint: Option<ValueHint>) -> Self {
+        self.hint = hint;
+        self
+    }
+
+    fn with_source(mut self, source: Option<Method>) -> Self {
+        self.source = source;
+        self
+    }
+
+    fn with_hidden_prefix(mut self, len: usize) -> Self {
+        self.hidden_prefix = len;
+        self
     }
 }
 
+#[derive(Default)]
 pub struct TerminalScreen {
     action_tx: Option<UnboundedSender<Action>>,
-    #[allow(dead_code)]
     config: Option<Config>,
+    keymap: Keymap,
     is_active: bool,
     input_mode: InputMode,
     command_buffer: String,
-    command_history: VecDeque<String>,
+    command_history: VecDeque<HistoryEntry>,
     incoming_messages: VecDeque<MessageLine>,
-    connection_label: Option<String>,
+    /// What the current session runs over.
+    connection: Option<ConnectionInfo>,
+    /// A reconnect was asked for and its session isn't up yet.
+    reconnecting: bool,
     cursor_index: usize,
     history_position: Option<usize>,
     draft_buffer: Option<String>,
     message_encoding: MessageEncoding,
-}
-
-impl Default for InputMode {
-    fn default() -> Self {
-        InputMode::Normal
-    }
-}
-
-impl Default for MessageEncoding {
-    fn default() -> Self {
-        MessageEncoding::Utf8
-    }
-}
-
-impl Default for TerminalScreen {
-    fn default() -> Self {
-        Self {
-            action_tx: None,
-            config: None,
-            is_active: false,
-            input_mode: InputMode::default(),
-            command_buffer: String::new(),
-            command_history: VecDeque::new(),
-            incoming_messages: VecDeque::new(),
-            connection_label: None,
-            cursor_index: 0,
-            history_position: None,
-            draft_buffer: None,
-            message_encoding: MessageEncoding::default(),
-        }
-    }
+    byte_style: ByteStyle,
+    inspector: Option<ByteInspector>,
+    scrollback: Scrollback,
+    liveness: Liveness,
+    /// Rendering hint of the last command sent, applied to the next device response.
+    pending_hint: Option<ValueHint>,
+    /// The last command sent was an echo, so the next device response is its reply.
+    pending_echo: bool,
+    /// Whether the newest history entry still waits for a message that settles its outcome.
+    awaiting_outcome: bool,
+    auto_repeat: Option<AutoRepeat>,
+    /// Baud rate of the open UART bridge. Responses are raw UART data while set.
+    bridge_baud: Option<u32>,
+    /// Whether the bridged bytes look like the bridge runs at the wrong baud rate.
+    baud_check: BaudCheck,
+    /// Whether keys go straight to the bridged UART instead of into the command line.
+    keystroke_mode: bool,
+    /// Whether bytes received in keystroke mode still extend the newest message.
+    keystroke_line_open: bool,
+    /// Short confirmation shown in the session header until the next key press.
+    notice: Option<&'static str>,
+    /// Error reported by the app, shown in the session header until the next key press.
+    error_note: Option<String>,
+    /// Bytes read and total of the `i2c dump` in progress.
+    dump_progress: Option<(usize, usize)>,
+    /// Length of the frame just written and when the note about it expires.
+    sent_note: Option<(usize, Instant)>,
+    /// Commands are encoded and shown instead of sent.
+    dry_run: bool,
+    /// Each sent command is followed by the command its bytes decode back to.
+    verbose: bool,
+    /// Each message starts with the time since the one before it.
+    show_deltas: bool,
+    /// Encoded length of the command line, kept between frames.
+    command_length: CommandLength,
+    /// Layout picked with the toggle key, replacing the choice made from the terminal height.
+    compact_override: Option<bool>,
+    /// Whether the last drawn frame used the compact layout.
+    compact: bool,
+    /// Last known terminal size, so the message pane can be resized when the layout changes.
+    screen_size: Option<(u16, u16)>,
+    favorites: Favorites,
+    labels: Labels,
+    palette: Palette,
+    /// Text highlighted in the message pane; empty when not searching.
+    search_query: String,
 }
 
 impl TerminalScreen {
@@ -115,29 +258,362 @@ impl TerminalScreen {
     }
 
     fn push_history(&mut self, command: String) {
-        if command.is_empty() {
+        if is_blank_line(&command) {
             return;
         }
         if self.command_history.len() >= HISTORY_LIMIT {
             self.command_history.pop_front();
         }
-        self.command_history.push_back(command);
+        self.command_history.push_back(HistoryEntry::new(command));
+    }
+
+    fn last_command(&self) -> Option<&String> {
+        self.command_history.back().map(|entry| &entry.command)
+    }
+
+    /// Send the most recent command again.
+    fn repeat_last_command(&mut self) -> Result<()> {
+        match self.last_command().cloned() {
+            Some(command) => self.send(Action::SendCommand(command)),
+            None => {
+                self.push_text("Error: No command to repeat yet.".into());
+                Ok(())
+            }
+        }
+    }
+
+    /// Start auto-repeating the most recent command, or stop if already running.
+    fn toggle_auto_repeat(&mut self) {
+        if self.auto_repeat.take().is_some() {
+            return;
+        }
+        let Some(command) = self.last_command().cloned() else {
+            self.push_text("Error: No command to repeat yet.".into());
+            return;
+        };
+        let interval = self
+            .config
+            .as_ref()
+            .map(|config| config.repeat_interval)
+            .unwrap_or(DEFAULT_REPEAT_INTERVAL);
+        self.auto_repeat = Some(AutoRepeat::new(command, interval, Instant::now()));
     }
 
     fn push_message(&mut self, message: MessageLine) {
         if self.incoming_messages.len() >= MESSAGE_LIMIT {
             self.incoming_messages.pop_front();
+            // Keep the inspector pointed at the same message as the buffer shifts.
+            if let Some(inspector) = &mut self.inspector {
+                match inspector.message_index.checked_sub(1) {
+                    Some(index) => inspector.message_index = index,
+                    None => self.inspector = None,
+                }
+            }
         }
         self.incoming_messages.push_back(message);
+        self.scrollback.message_added(self.incoming_messages.len());
+    }
+
+    /// Track the message pane height for a terminal of the given size.
+    fn resize_message_pane(&mut self, width: u16, height: u16) {
+        self.screen_size = Some((width, height));
+        self.compact = use_compact_layout(height, self.compact_override);
+        let rows = message_viewport_rows(Rect::new(0, 0, width, height), self.compact);
+        self.scrollback.resize(rows, self.incoming_messages.len());
+    }
+
+    fn message_bytes(&self, index: usize) -> Option<&[u8]> {
+        match self.incoming_messages.get(index).map(|msg| &msg.content) {
+            Some(DeviceMessage::Bytes(bytes)) if !bytes.is_empty() => Some(bytes),
+            _ => None,
+        }
+    }
+
+    /// Open the byte inspector on the most recent message that carries bytes.
+    fn open_inspector(&mut self) {
+        let latest = (0..self.incoming_messages.len())
+            .rev()
+            .find(|&index| self.message_bytes(index).is_some());
+        self.inspector = latest.map(ByteInspector::new);
+    }
+
+    /// Step the inspector to the nearest older (`older == true`) or newer byte message.
+    fn step_inspector(&mut self, older: bool) {
+        let Some(current) = self.inspector.map(|inspector| inspector.message_index) else {
+            return;
+        };
+        let next = if older {
+            (0..current)
+                .rev()
+                .find(|&index| self.message_bytes(index).is_some())
+        } else {
+            (current + 1..self.incoming_messages.len())
+                .find(|&index| self.message_bytes(index).is_some())
+        };
+        let Some(index) = next else {
+            return;
+        };
+        let len = self.message_bytes(index).map_or(0, <[u8]>::len);
+        if let Some(inspector) = &mut self.inspector {
+            inspector.message_index = index;
+            inspector.clamp_cursor(len);
+        }
+    }
+
+    fn handle_inspector_key(&mut self, key: crossterm::event::KeyEvent) -> Result<Option<Action>> {
+        use crossterm::event::{KeyCode, KeyModifiers};
+
+        let len = self
+            .inspector
+            .and_then(|inspector| self.message_bytes(inspector.message_index))
+            .map_or(0, <[u8]>::len);
+
+        match self.keymap.action(&key) {
+            Some(KeyAction::Inspect) => {
+                self.inspector = None;
+                self.send(Action::ClearScreen)?;
+                return Ok(None);
+            }
+            Some(KeyAction::Quit) => {
+                self.send(Action::Quit)?;
+                return Ok(None);
+            }
+            _ => {}
+        }
+        match (key.code, key.modifiers) {
+            (KeyCode::Esc, _) => {
+                self.inspector = None;
+                self.send(Action::ClearScreen)?;
+            }
+            (KeyCode::Left, KeyModifiers::NONE) => {
+                if let Some(inspector) = &mut self.inspector {
+                    inspector.move_left();
+                }
+            }
+            (KeyCode::Right, KeyModifiers::NONE) => {
+                if let Some(inspector) = &mut self.inspector {
+                    inspector.move_right(len);
+                }
+            }
+            (KeyCode::Up, KeyModifiers::NONE) => self.step_inspector(true),
+            (KeyCode::Down, KeyModifiers::NONE) => self.step_inspector(false),
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    /// Empty the message pane, along with the inspector and scroll position that pointed into it.
+    /// A locked view stays locked.
+    fn clear_messages(&mut self) {
+        self.incoming_messages.clear();
+        self.inspector = None;
+        self.scrollback.clamp(0);
+        self.notice = Some("Messages cleared");
+    }
+
+    /// Forget the commands sent this session. Scripts already exported are left alone.
+    fn clear_history(&mut self) {
+        self.command_history.clear();
+        self.awaiting_outcome = false;
+        self.reset_history_navigation();
+        self.notice = Some("History cleared");
+    }
+
+    /// Start or stop following each sent command with how it decodes.
+    fn toggle_verbose(&mut self) {
+        self.verbose = !self.verbose;
+        self.notice = Some(if self.verbose {
+            "Verbose: sent commands show how they decode"
+        } else {
+            "Verbose off"
+        });
+    }
+
+    /// Start or stop showing the time between messages.
+    fn toggle_deltas(&mut self) {
+        self.show_deltas = !self.show_deltas;
+        self.notice = Some(if self.show_deltas {
+            "Showing time since the previous message"
+        } else {
+            "Message timing off"
+        });
+    }
+
+    /// Switch between the compact and full layouts, overriding the automatic choice.
+    fn toggle_compact(&mut self) {
+        self.compact = !self.compact;
+        self.compact_override = Some(self.compact);
+        self.notice = Some(if self.compact {
+            "Compact layout"
+        } else {
+            "Full layout"
+        });
+        if let Some((width, height)) = self.screen_size {
+            self.resize_message_pane(width, height);
+        }
+    }
+
+    /// Save the command history as a replayable script and report where it went.
+    fn export_history(&mut self) {
+        let text = if self.command_history.is_empty() {
+            "Error: No commands to export yet.".to_string()
+        } else {
+            match script::export_to_data_dir(
+                self.command_history
+                    .iter()
+                    .map(|entry| entry.command.as_str()),
+            ) {
+                Ok(path) => format!(
+                    "Exported {} commands to {} (replay with --script)",
+                    self.command_history.len(),
+                    path.display()
+                ),
+                Err(err) => format!("Error: Failed to export history: {err}"),
+            }
+        };
+        self.push_text(text);
+    }
+
+    /// Show a message from the connection, attributed to `source` when the device tagged it.
+    fn receive_message(&mut self, message: DeviceMessage, source: Option<Method>) {
+        self.liveness.touch(Instant::now());
+        let style = self.style_for_message(&message);
+        // Text messages are host-side errors, so the command never got a device response.
+        let hint = self.pending_hint.take().filter(
+            |_| matches!(&message, DeviceMessage::Bytes(bytes) if !bytes.starts_with(ERROR_PREFIX)),
+        );
+        let echo_reply = std::mem::take(&mut self.pending_echo) || source == Some(Method::Echo);
+        let hidden_prefix = match (&message, self.echo_prefix()) {
+            (DeviceMessage::Bytes(bytes), Some(prefix)) if echo_reply => {
+                echo_prefix_len(bytes, prefix)
+            }
+            _ => 0,
+        };
+        if self.awaiting_outcome
+            && let Some(outcome) = CommandOutcome::from_message(&message)
+            && let Some(entry) = self.command_history.back_mut()
+        {
+            entry.outcome = outcome;
+            self.awaiting_outcome = false;
+        }
+        if let DeviceMessage::Bytes(bytes) = &message
+            && self.bridge_baud.is_some()
+        {
+            self.baud_check.observe(bytes);
+        }
+        if let DeviceMessage::Bytes(bytes) = &message
+            && self.keystroke_mode
+        {
+            self.append_keystroke_echo(bytes);
+            return;
+        }
+        self.keystroke_line_open = false;
+        self.push_message(
+            MessageLine::new(message, style)
+                .with_hint(hint)
+                .with_source(source)
+                .with_hidden_prefix(hidden_prefix),
+        );
+    }
+
+    fn echo_prefix(&self) -> Option<&str> {
+        self.config.as_ref()?.echo_prefix.as_deref()
+    }
+
+    /// Show UART bytes received in keystroke mode as they arrive, continuing the newest message
+    /// until a line ends the way a serial terminal would.
+    fn append_keystroke_echo(&mut self, bytes: &[u8]) {
+        match self.incoming_messages.back_mut() {
+            Some(MessageLine {
+                content: DeviceMessage::Bytes(line),
+                ..
+            }) if self.keystroke_line_open => line.extend_from_slice(bytes),
+            _ => self.push_message(MessageLine::new(
+                DeviceMessage::Bytes(bytes.to_vec()),
+                Style::default(),
+            )),
+        }
+        self.keystroke_line_open = !bytes.ends_with(b"\n");
+    }
+
+    /// Close the session and open the same port again. The transcript and history stay, and a
+    /// marker in the transcript shows where the new session starts.
+    fn reconnect(&mut self) -> Option<Action> {
+        let connection = self.connection.as_ref()?;
+        self.reconnecting = true;
+        Some(Action::Connect {
+            port: connection.port.clone(),
+            baud_rate: connection.baud_rate,
+        })
+    }
+
+    /// Send keys straight to the UART. Only a bridge carries raw bytes, so the mode needs one.
+    fn start_keystroke_mode(&mut self) {
+        if self.bridge_baud.is_none() {
+            self.notice = Some("Keystroke mode needs an open UART bridge");
+            return;
+        }
+        self.keystroke_mode = true;
+        self.keystroke_line_open = false;
+        self.input_mode = InputMode::Editing;
+        self.auto_repeat = None;
+    }
+
+    fn stop_keystroke_mode(&mut self) {
+        if self.keystroke_mode {
+            self.keystroke_mode = false;
+            self.keystroke_line_open = false;
+            self.input_mode = InputMode::Normal;
+        }
+    }
+
+    /// Bytes a key sends in keystroke mode. Esc leaves the mode instead.
+    fn handle_keystroke(&mut self, key: crossterm::event::KeyEvent) -> Result<Option<Action>> {
+        use crossterm::event::{KeyCode, KeyModifiers};
+
+        let backspace = self
+            .config
+            .as_ref()
+            .map_or(DEFAULT_BACKSPACE, |config| config.backspace);
+        let bytes = match (key.code, key.modifiers) {
+            (KeyCode::Esc, _) => {
+                self.stop_keystroke_mode();
+                return Ok(None);
+            }
+            (KeyCode::Enter, _) => BRIDGE_LINE_ENDING.to_vec(),
+            (KeyCode::Backspace, _) | (KeyCode::Char('h'), KeyModifiers::CONTROL) => {
+                vec![backspace]
+            }
+            (KeyCode::Tab, _) => vec![b'\t'],
+            (KeyCode::Char(c), modifiers)
+                if modifiers.is_empty() || modifiers == KeyModifiers::SHIFT =>
+            {
+                c.to_string().into_bytes()
+            }
+            // Ctrl+a..Ctrl+z as their control codes, for REPLs that use them.
+            (KeyCode::Char(c), KeyModifiers::CONTROL) if c.is_ascii_alphabetic() => {
+                vec![c.to_ascii_lowercase() as u8 & 0x1F]
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(Action::SendKeystrokes(bytes)))
+    }
+
+    /// Show a host-side note in the message pane.
+    fn push_text(&mut self, text: String) {
+        let message = DeviceMessage::Text(text);
+        let style = self.style_for_message(&message);
+        self.push_message(MessageLine::new(message, style));
     }
 
-    fn style_for_message(message: &DeviceMessage) -> Style {
+    fn style_for_message(&self, message: &DeviceMessage) -> Style {
         match message {
             DeviceMessage::Text(text)
                 if text.starts_with("Error:") || text.starts_with("Failed to encode command") =>
             {
-                Style::default().fg(Color::Red)
+                self.palette.error
             }
+            DeviceMessage::Text(text) if text.starts_with("Warning:") => self.palette.warning,
             _ => Style::default(),
         }
     }
@@ -152,15 +628,98 @@ impl TerminalScreen {
         Ok(())
     }
 
-    fn render_message_text(&self, message: &DeviceMessage) -> String {
-        match message {
-            DeviceMessage::Text(text) => text.clone(),
-            DeviceMessage::Bytes(bytes) => format_bytes(bytes, self.message_encoding),
+    /// Switch to the view a response's tag asks for: UTF-8 for text, hex for bytes. Auto leaves the
+    /// view as the user set it.
+    fn follow_response_format(&mut self, format: ResponseFormat) -> Result<()> {
+        match format {
+            ResponseFormat::Auto => Ok(()),
+            ResponseFormat::Text => self.change_message_encoding(MessageEncoding::Utf8),
+            ResponseFormat::Bytes if self.message_encoding == MessageEncoding::Utf8 => {
+                self.change_message_encoding(MessageEncoding::Hex)
+            }
+            ResponseFormat::Bytes => Ok(()),
+        }
+    }
+
+    /// Move the hex and binary views on to the next [`ByteStyle`].
+    fn cycle_byte_style(&mut self) -> Result<()> {
+        self.byte_style = self.byte_style.next();
+        self.send(Action::ClearScreen)?;
+        self.send(Action::Render)
+    }
+
+    /// The view named in the title, with the byte style when it applies and isn't the default.
+    fn view_label(&self) -> String {
+        let encoding = self.message_encoding.label();
+        if self.message_encoding == MessageEncoding::Utf8 || self.byte_style == ByteStyle::default()
+        {
+            encoding.to_owned()
+        } else {
+            format!("{encoding}, {}", self.byte_style.name())
+        }
+    }
+
+    /// Newest-first list of sent commands with their outcome markers.
+    fn draw_history(&self, frame: &mut Frame, area: Rect) {
+        let history_items: Vec<ListItem> = self
+            .command_history
+            .iter()
+            .rev()
+            .map(|entry| {
+                ListItem::new(Line::from(vec![
+                    entry.outcome.marker(&self.palette),
+                    Span::raw(entry.command.clone()),
+                ]))
+            })
+            .collect();
+        frame.render_widget(
+            List::new(history_items)
+                .block(self.palette.block().title(self.labels.history.as_str())),
+            area,
+        );
+    }
+
+    fn render_message_text(&self, message: &MessageLine) -> String {
+        let text = self.render_message_content(message);
+        match message.source {
+            Some(method) => format!("[{}] {text}", method.name()),
+            None => text,
+        }
+    }
+
+    /// Time between message `index` arriving and the one before it.
+    fn delta_before(&self, index: usize) -> Option<Duration> {
+        let previous = self.incoming_messages.get(index.checked_sub(1)?)?;
+        let message = self.incoming_messages.get(index)?;
+        Some(message.arrived.duration_since(previous.arrived))
+    }
+
+    fn render_message_content(&self, message: &MessageLine) -> String {
+        match (&message.content, message.hint) {
+            (DeviceMessage::Text(text), _) => text.clone(),
+            (DeviceMessage::Bytes(bytes), None)
+                if self.message_encoding == MessageEncoding::Utf8 =>
+            {
+                format_bytes(
+                    &bytes[message.hidden_prefix..],
+                    self.message_encoding,
+                    self.byte_style,
+                )
+            }
+            (DeviceMessage::Bytes(bytes), None) => {
+                format_bytes(bytes, self.message_encoding, self.byte_style)
+            }
+            (DeviceMessage::Bytes(bytes), Some(hint)) => format!(
+                "{}  → {}",
+                format_bytes(bytes, self.message_encoding, self.byte_style),
+                hint.render(bytes)
+            ),
         }
     }
 
     fn enter_edit_mode(&mut self) {
         self.input_mode = InputMode::Editing;
+        self.auto_repeat = None;
         self.cursor_index = self.command_buffer.len();
         self.reset_history_navigation();
     }
@@ -207,7 +766,7 @@ impl TerminalScreen {
 
         if let Some(entry) = self.command_history.iter().rev().nth(next_offset) {
             self.history_position = Some(next_offset);
-            self.command_buffer = entry.clone();
+            self.command_buffer = entry.command.clone();
             self.cursor_index = self.command_buffer.len();
         }
     }
@@ -230,7 +789,7 @@ impl TerminalScreen {
                 let new_offset = offset.saturating_sub(1);
                 if let Some(entry) = self.command_history.iter().rev().nth(new_offset) {
                     self.history_position = Some(new_offset);
-                    self.command_buffer = entry.clone();
+                    self.command_buffer = entry.command.clone();
                     self.cursor_index = self.command_buffer.len();
                 } else {
                     self.history_position = None;
@@ -240,7 +799,35 @@ impl TerminalScreen {
         }
     }
 
+    /// `cursor_index` is a byte offset into `command_buffer` and must sit on a char boundary, or
+    /// slicing and inserting at it panics. Every edit keeps it there; this catches one that doesn't.
+    fn debug_assert_cursor(&self) {
+        debug_assert!(
+            self.command_buffer.is_char_boundary(self.cursor_index),
+            "cursor {} is not on a char boundary of {:?}",
+            self.cursor_index,
+            self.command_buffer
+        );
+    }
+
+    /// Cursor position snapped back onto a char boundary, so a release build degrades to a
+    /// misplaced cursor instead of panicking.
+    fn cursor_boundary(&self) -> usize {
+        self.command_buffer.floor_char_boundary(self.cursor_index)
+    }
+
     fn handle_editing_key(&mut self, key: crossterm::event::KeyEvent) -> Result<Option<Action>> {
+        if self.keystroke_mode {
+            return self.handle_keystroke(key);
+        }
+        self.debug_assert_cursor();
+        self.cursor_index = self.cursor_boundary();
+        let action = self.apply_editing_key(key);
+        self.debug_assert_cursor();
+        action
+    }
+
+    fn apply_editing_key(&mut self, key: crossterm::event::KeyEvent) -> Result<Option<Action>> {
         use crossterm::event::{KeyCode, KeyModifiers};
 
         match (key.code, key.modifiers) {
@@ -253,21 +840,21 @@ impl TerminalScreen {
                 self.command_buffer.clear();
                 self.cursor_index = 0;
                 self.input_mode = InputMode::Normal;
-                let should_send = !command.trim().is_empty();
-                if should_send {
+                if !is_blank_line(&command) {
                     self.reset_history_navigation();
                     return Ok(Some(Action::SendCommand(command)));
                 }
                 self.reset_history_navigation();
             }
-            (KeyCode::Backspace, _) => {
-                if self.cursor_index > 0 {
-                    self.move_cursor_left();
-                    if let Some(ch) = self.command_buffer[self.cursor_index..].chars().next() {
-                        let len = ch.len_utf8();
-                        self.command_buffer
-                            .drain(self.cursor_index..self.cursor_index + len);
-                    }
+            // Many terminals send Ctrl+h for Backspace.
+            (KeyCode::Backspace, _) | (KeyCode::Char('h'), KeyModifiers::CONTROL)
+                if self.cursor_index > 0 =>
+            {
+                self.move_cursor_left();
+                if let Some(ch) = self.command_buffer[self.cursor_index..].chars().next() {
+                    let len = ch.len_utf8();
+                    self.command_buffer
+                        .drain(self.cursor_index..self.cursor_index + len);
                 }
             }
             (KeyCode::Char(c), modifiers)
@@ -286,12 +873,12 @@ impl TerminalScreen {
             (KeyCode::Home, _) => self.cursor_index = 0,
             (KeyCode::End, _) => self.cursor_index = self.command_buffer.len(),
             (KeyCode::Delete, _) => {
-                if self.cursor_index < self.command_buffer.len() {
-                    if let Some(ch) = self.command_buffer[self.cursor_index..].chars().next() {
-                        let len = ch.len_utf8();
-                        self.command_buffer
-                            .drain(self.cursor_index..self.cursor_index + len);
-                    }
+                if self.cursor_index < self.command_buffer.len()
+                    && let Some(ch) = self.command_buffer[self.cursor_index..].chars().next()
+                {
+                    let len = ch.len_utf8();
+                    self.command_buffer
+                        .drain(self.cursor_index..self.cursor_index + len);
                 }
             }
             (KeyCode::Up, KeyModifiers::NONE) => {
@@ -308,24 +895,59 @@ impl TerminalScreen {
     fn handle_normal_key(&mut self, key: crossterm::event::KeyEvent) -> Result<Option<Action>> {
         use crossterm::event::{KeyCode, KeyModifiers};
 
-        match (key.code, key.modifiers) {
-            (KeyCode::Char('h'), KeyModifiers::NONE) => {
-                return Ok(Some(Action::ToggleHelp));
-            }
-            (KeyCode::Char('e'), KeyModifiers::NONE) => {
-                self.enter_edit_mode();
+        if self.inspector.is_some() {
+            return self.handle_inspector_key(key);
+        }
+
+        if let Some(action) = self.keymap.action(&key) {
+            match action {
+                KeyAction::Help => return Ok(Some(Action::ToggleHelp)),
+                KeyAction::Edit => self.enter_edit_mode(),
+                KeyAction::Inspect => self.open_inspector(),
+                KeyAction::ExportHistory => self.export_history(),
+                KeyAction::RepeatLast => self.repeat_last_command()?,
+                KeyAction::ToggleAutoRepeat => self.toggle_auto_repeat(),
+                KeyAction::ToggleDryRun => return Ok(Some(Action::ToggleDryRun)),
+                KeyAction::ClearMessages => self.clear_messages(),
+                KeyAction::ClearHistory => self.clear_history(),
+                KeyAction::Utf8View => self.change_message_encoding(MessageEncoding::Utf8)?,
+                KeyAction::HexView => self.change_message_encoding(MessageEncoding::Hex)?,
+                KeyAction::BinaryView => self.change_message_encoding(MessageEncoding::Binary)?,
+                KeyAction::ByteStyle => self.cycle_byte_style()?,
+                KeyAction::KeystrokeMode => self.start_keystroke_mode(),
+                KeyAction::ScrollLock => self.scrollback.toggle_lock(),
+                KeyAction::Search => self.input_mode = InputMode::Searching,
+                KeyAction::Reconnect => return Ok(self.reconnect()),
+                KeyAction::Disconnect => return Ok(Some(Action::Disconnect)),
+                KeyAction::ToggleVerbose => self.toggle_verbose(),
+                KeyAction::ToggleDeltas => self.toggle_deltas(),
+                KeyAction::ToggleCompact => self.toggle_compact(),
+                KeyAction::Quit => self.send(Action::Quit)?,
+                KeyAction::RefreshPorts => {}
             }
-            (KeyCode::Char('u'), KeyModifiers::CONTROL) => {
-                self.change_message_encoding(MessageEncoding::Utf8)?;
+            return Ok(None);
+        }
+
+        match (key.code, key.modifiers) {
+            (KeyCode::PageUp, _) => {
+                let page = self.scrollback.page();
+                self.scrollback
+                    .scroll_back(page, self.incoming_messages.len());
             }
-            (KeyCode::Char('h'), KeyModifiers::CONTROL) => {
-                self.change_message_encoding(MessageEncoding::Hex)?;
+            (KeyCode::PageDown, _) => {
+                let page = self.scrollback.page();
+                self.scrollback.scroll_forward(page);
             }
-            (KeyCode::Char('b'), KeyModifiers::CONTROL) => {
-                self.change_message_encoding(MessageEncoding::Binary)?;
+            (KeyCode::End, _) => self.scrollback.pin(),
+            (KeyCode::Esc, _) => {
+                self.search_query.clear();
+                self.baud_check.dismiss();
             }
-            (KeyCode::Char('q'), KeyModifiers::NONE) => {
-                self.send(Action::Quit)?;
+            (KeyCode::F(number), KeyModifiers::NONE) => {
+                return Ok(self
+                    .favorites
+                    .command(number)
+                    .map(|command| Action::SendCommand(command.to_owned())));
             }
             (KeyCode::Char('c'), KeyModifiers::CONTROL)
             | (KeyCode::Char('d'), KeyModifiers::CONTROL) => {
@@ -337,6 +959,42 @@ impl TerminalScreen {
     }
 }
 
+impl TerminalScreen {
+    /// Edit the search query. The highlight follows each key; Enter keeps it and Esc clears it.
+    fn handle_search_key(&mut self, key: crossterm::event::KeyEvent) -> Result<Option<Action>> {
+        use crossterm::event::{KeyCode, KeyModifiers};
+
+        match (key.code, key.modifiers) {
+            (KeyCode::Esc, _) => {
+                self.search_query.clear();
+                self.input_mode = InputMode::Normal;
+            }
+            (KeyCode::Enter, _) => self.input_mode = InputMode::Normal,
+            (KeyCode::Backspace, _) | (KeyCode::Char('h'), KeyModifiers::CONTROL) => {
+                self.search_query.pop();
+            }
+            (KeyCode::Char('u'), KeyModifiers::CONTROL) => self.search_query.clear(),
+            (KeyCode::Char(c), modifiers)
+                if modifiers.is_empty() || modifiers == KeyModifiers::SHIFT =>
+            {
+                self.search_query.push(c);
+            }
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    /// Number of messages the search query appears in, as currently rendered.
+    fn search_match_count(&self) -> usize {
+        self.incoming_messages
+            .iter()
+            .filter(|msg| {
+                !find_matches(&self.render_message_text(msg), &self.search_query).is_empty()
+            })
+            .count()
+    }
+}
+
 impl Component for TerminalScreen {
     fn register_action_handler(&mut self, tx: UnboundedSender<Action>) -> Result<()> {
         self.action_tx = Some(tx);
@@ -344,18 +1002,34 @@ impl Component for TerminalScreen {
     }
 
     fn register_config_handler(&mut self, config: Config) -> Result<()> {
+        self.dry_run = config.dry_run;
+        self.verbose = config.verbose;
+        self.byte_style = config.byte_style;
+        self.keymap = config.keymap.clone();
+        self.favorites = config.favorites.clone();
+        self.labels = config.labels.clone();
+        self.palette = config.theme.palette();
         self.config = Some(config);
         Ok(())
     }
 
+    fn init(&mut self, area: Size) -> Result<()> {
+        self.resize_message_pane(area.width, area.height);
+        Ok(())
+    }
+
     fn handle_key_event(&mut self, key: crossterm::event::KeyEvent) -> Result<Option<Action>> {
         if !self.is_active {
             return Ok(None);
         }
 
+        self.notice = None;
+        self.error_note = None;
+        self.sent_note = None;
         match self.input_mode {
             InputMode::Normal => self.handle_normal_key(key),
             InputMode::Editing => self.handle_editing_key(key),
+            InputMode::Searching => self.handle_search_key(key),
         }
     }
 
@@ -369,23 +1043,94 @@ impl Component for TerminalScreen {
             }
             Action::ShowPreconnect | Action::ShowConnecting | Action::ShowError(_) => {
                 self.is_active = false;
+                self.inspector = None;
+                self.auto_repeat = None;
+                self.awaiting_outcome = false;
+                self.bridge_baud = None;
+                self.baud_check.reset();
+                self.stop_keystroke_mode();
+                self.dump_progress = None;
+                self.sent_note = None;
+                self.liveness.reset();
                 self.input_mode = InputMode::Normal;
                 self.cursor_index = self.command_buffer.len();
                 self.reset_history_navigation();
             }
             Action::CommandSent(command) => {
+                // Bridged lines go to the UART verbatim, so they carry no hint.
+                self.pending_hint = if self.bridge_baud.is_some() {
+                    None
+                } else {
+                    split_value_hint(&command)
+                        .ok()
+                        .and_then(|(rest, hint)| hint.or_else(|| default_hint(rest)))
+                };
+                self.pending_echo = self.bridge_baud.is_none() && is_echo_command(&command);
+                // Bridged lines have no response of their own to settle them.
+                self.awaiting_outcome = self.bridge_baud.is_none();
+                if self.verbose
+                    && self.bridge_baud.is_none()
+                    && let Some(interpretation) = interpret_command(&command)
+                {
+                    self.push_text(interpretation);
+                }
                 self.push_history(command);
                 self.command_buffer.clear();
                 self.cursor_index = 0;
                 self.reset_history_navigation();
             }
-            Action::IncomingMessage(message) => {
-                let style = Self::style_for_message(&message);
-                self.push_message(MessageLine::new(message, style));
+            Action::IncomingMessage(message) => self.receive_message(message, None),
+            Action::TaggedResponse(method, format, bytes) => {
+                self.follow_response_format(format)?;
+                self.receive_message(DeviceMessage::Bytes(bytes), Some(method))
+            }
+            Action::BridgeChanged(baud) => {
+                // A repeat started for the command session would otherwise go raw to the UART.
+                self.auto_repeat = None;
+                self.bridge_baud = baud;
+                if let Some(connection) = &mut self.connection {
+                    connection.mode = baud.map_or(SessionMode::Commands, SessionMode::Bridged);
+                }
+                self.baud_check.reset();
+                self.pending_hint = None;
+                if baud.is_none() {
+                    self.stop_keystroke_mode();
+                }
+            }
+            Action::DumpProgress(progress) => self.dump_progress = progress,
+            Action::Error(message) => self.error_note = Some(message),
+            Action::Heartbeat => self.liveness.touch(Instant::now()),
+            Action::ToggleDryRun => self.dry_run = !self.dry_run,
+            Action::FrameSent(len) => {
+                self.sent_note = Some((len, Instant::now() + SENT_NOTE_DURATION));
+            }
+            Action::ConnectionEstablished(info) => {
+                self.connection = Some(info);
+                if std::mem::take(&mut self.reconnecting) {
+                    self.push_text("— reconnected —".into());
+                }
+                self.liveness.touch(Instant::now());
+            }
+            Action::DeviceInfo(info) => {
+                if let Some(connection) = &mut self.connection {
+                    let label = info.label.as_str();
+                    connection.label = (!label.is_empty()).then(|| label.to_owned());
+                }
             }
-            Action::ConnectionEstablished { port, baud_rate } => {
-                self.connection_label = Some(format!("{port} @ {baud_rate} baud"));
+            Action::ConnectionFailed(_) => self.reconnecting = false,
+            Action::Tick => {
+                let now = Instant::now();
+                self.liveness.refresh(now);
+                self.sent_note = self.sent_note.filter(|&(_, until)| now < until);
+                if let Some(command) = self
+                    .auto_repeat
+                    .as_mut()
+                    .and_then(|repeat| repeat.poll(now))
+                {
+                    return Ok(Some(Action::SendCommand(command.to_string())));
+                }
             }
+            Action::Resize(width, height) => self.resize_message_pane(width, height),
             _ => {}
         }
         Ok(None)
@@ -396,109 +1141,249 @@ impl Component for TerminalScreen {
             return Ok(());
         }
 
-        let layout = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints(
-                [
-                    Constraint::Length(4),
-                    Constraint::Length(3),
-                    Constraint::Length(6),
-                    Constraint::Min(10),
-                ]
-                .as_ref(),
-            )
-            .split(area);
+        self.compact = use_compact_layout(area.height, self.compact_override);
+        let layout = split_layout(area, self.compact);
 
-        let connection_line = self
-            .connection_label
-            .clone()
-            .unwrap_or_else(|| "Not connected".into());
+        let connection_line = match &self.connection {
+            Some(connection) => connection.summary(),
+            None => "Not connected".into(),
+        };
         let mode_label = match self.input_mode {
+            _ if self.keystroke_mode => "Keystroke",
             InputMode::Normal => "Normal",
             InputMode::Editing => "Editing",
+            InputMode::Searching => "Searching",
         };
-        let instruction = vec![
+        let mut instruction = vec![
+            Line::from(vec![
+                self.liveness.span(Instant::now(), &self.palette),
+                Span::raw(format!(
+                    " Connected: {connection_line} • Mode: {mode_label} • View: {}",
+                    self.view_label()
+                )),
+                Span::styled(
+                    self.auto_repeat
+                        .as_ref()
+                        .map(|repeat| {
+                            format!(" • Repeat: every {} ms", repeat.interval().as_millis())
+                        })
+                        .unwrap_or_default(),
+                    self.palette.mode,
+                ),
+                Span::styled(
+                    self.bridge_baud
+                        .map(|baud| {
+                            format!(" • UART bridge @ {baud} baud ({BRIDGE_EXIT_LINE} to exit)")
+                        })
+                        .unwrap_or_default(),
+                    self.palette.accent,
+                ),
+                Span::styled(
+                    if self.keystroke_mode {
+                        " • KEYSTROKE MODE: keys go straight to the UART (Esc to stop)"
+                    } else {
+                        ""
+                    },
+                    self.palette.mode.add_modifier(Modifier::BOLD),
+                ),
+                Span::styled(
+                    if self.dry_run {
+                        format!(
+                            " • DRY RUN: commands are not sent ({} to toggle)",
+                            self.keymap.label(KeyAction::ToggleDryRun)
+                        )
+                    } else {
+                        String::new()
+                    },
+                    self.palette.error.add_modifier(Modifier::BOLD),
+                ),
+                Span::styled(
+                    self.dump_progress
+                        .map(|(read, total)| format!(" • Dumping {read}/{total} bytes"))
+                        .unwrap_or_default(),
+                    self.palette.warning,
+                ),
+                Span::styled(
+                    self.notice
+                        .map(|notice| format!(" • {notice}"))
+                        .unwrap_or_default(),
+                    self.palette.success,
+                ),
+            ]),
             Line::from(format!(
-                "Connected: {connection_line} • Mode: {mode_label} • View: {}",
-                self.message_encoding.label()
+                "Press {} to edit the command, Enter to send, Esc to cancel editing, {} to quit.",
+                self.keymap.label(KeyAction::Edit),
+                self.keymap.label(KeyAction::Quit)
+            )),
+            Line::from(format!(
+                "{} UTF-8, {} Hex, {} Binary to change message view.",
+                self.keymap.label(KeyAction::Utf8View),
+                self.keymap.label(KeyAction::HexView),
+                self.keymap.label(KeyAction::BinaryView)
             )),
-            Line::from(
-                "Press e to edit the command, Enter to send, Esc to cancel editing, q to quit.",
-            ),
-            Line::from("Ctrl+u UTF-8, Ctrl+h Hex, Ctrl+b Binary to change message view."),
         ];
-        frame.render_widget(
-            Paragraph::new(instruction)
-                .block(Block::default().title("Session").borders(Borders::ALL)),
-            layout[0],
-        );
+        // Notes take the place of the key hints, or end the status line when compact. A reported
+        // error goes above the baud hint, which outlasts it.
+        if self.baud_check.suspected() {
+            let hint = Span::styled(
+                "Possible baud mismatch? The bridged bytes look like line noise (Esc to dismiss)",
+                self.palette.warning,
+            );
+            if self.compact {
+                instruction[0].push_span(Span::raw(" • "));
+                instruction[0].push_span(hint);
+            } else {
+                instruction.insert(1, Line::from(hint));
+            }
+        }
+        if let Some(message) = &self.error_note {
+            let error = Span::styled(
+                format!("Error: {message}"),
+                self.palette.error.add_modifier(Modifier::BOLD),
+            );
+            if self.compact {
+                instruction[0].push_span(Span::raw(" • "));
+                instruction[0].push_span(error);
+            } else {
+                instruction.insert(1, Line::from(error));
+            }
+        }
+        if self.compact {
+            frame.render_widget(Paragraph::new(instruction.swap_remove(0)), layout[0]);
+        } else {
+            frame.render_widget(
+                Paragraph::new(instruction)
+                    .block(self.palette.block().title(self.labels.session.as_str())),
+                layout[0],
+            );
+        }
 
-        let command_line = if self.input_mode == InputMode::Editing {
-            let cursor_index = self.cursor_index.min(self.command_buffer.len());
-            let (left, right) = self.command_buffer.split_at(cursor_index);
+        let sent_note = Span::styled(
+            self.sent_note
+                .map(|(len, _)| format!(" • sent ({len} bytes)"))
+                .unwrap_or_default(),
+            self.palette.success,
+        );
+        let length_note = match self
+            .command_length
+            .measure(&self.command_buffer)
+            .and_then(length_note)
+        {
+            Some((note, true)) => {
+                Span::styled(note, self.palette.error.add_modifier(Modifier::BOLD))
+            }
+            Some((note, false)) => Span::styled(note, self.palette.warning),
+            None => Span::raw(""),
+        };
+        let mut command_line = if self.keystroke_mode {
             Line::from(vec![
-                Span::styled("Command> ", Style::default().fg(Color::Cyan)),
-                Span::raw(left.to_string()),
-                Span::styled("┃", Style::default().fg(Color::Yellow)),
-                Span::raw(right.to_string()),
+                Span::styled("Keys> ", self.palette.mode),
+                Span::styled("each key is sent as you type it", self.palette.muted),
+            ])
+        } else if self.input_mode == InputMode::Searching {
+            Line::from(vec![
+                Span::styled("Search> ", self.palette.accent),
+                Span::raw(self.search_query.clone()),
+                Span::styled("┃", self.palette.warning),
+                Span::styled(
+                    "  Enter keeps the highlight, Esc clears it",
+                    self.palette.muted,
+                ),
+            ])
+        } else if self.input_mode == InputMode::Editing {
+            let (left, right) = self.command_buffer.split_at(self.cursor_boundary());
+            Line::from(vec![
+                Span::styled(self.labels.prompt.as_str(), self.palette.accent),
+                Span::raw(left.to_string()),
+                Span::styled("┃", self.palette.warning),
+                Span::raw(right.to_string()),
             ])
         } else {
             Line::from(vec![
-                Span::styled("Command> ", Style::default().fg(Color::Cyan)),
+                Span::styled(self.labels.prompt.as_str(), self.palette.accent),
                 Span::raw(self.command_buffer.clone()),
             ])
         };
-        frame.render_widget(
-            Paragraph::new(Text::from(command_line)).block(
-                Block::default()
-                    .title("Command Input")
-                    .borders(Borders::ALL),
-            ),
-            layout[1],
-        );
-
-        let history_items: Vec<ListItem> = self
-            .command_history
-            .iter()
-            .rev()
-            .map(|entry| ListItem::new(entry.clone()))
-            .collect();
-        frame.render_widget(
-            List::new(history_items).block(
-                Block::default()
-                    .title("Command History")
-                    .borders(Borders::ALL),
-            ),
-            layout[2],
-        );
-
-        let bottom_cat = Span::styled(
-            " ᓚᘏᗢ ",
-            Style::default()
-                .fg(Color::Cyan)
-                .add_modifier(Modifier::BOLD),
-        );
+        if self.compact {
+            command_line.push_span(sent_note);
+            command_line.push_span(length_note);
+            frame.render_widget(Paragraph::new(command_line), layout[1]);
+        } else {
+            frame.render_widget(
+                Paragraph::new(Text::from(command_line)).block(self.palette.block().title(
+                    Line::from(vec![
+                        Span::raw(self.labels.command.as_str()),
+                        sent_note,
+                        length_note,
+                    ]),
+                )),
+                layout[1],
+            );
+            self.draw_history(frame, layout[2]);
+        }
 
-        let message_block = Block::default()
-            .title(format!(
-                "Device Messages ({})",
-                self.message_encoding.label()
-            ))
-            .title_bottom(bottom_cat)
-            .borders(Borders::ALL);
+        let scroll_label = if self.scrollback.is_following() {
+            " [FOLLOW]".to_owned()
+        } else if self.scrollback.is_pinned() {
+            " [LOCKED]".to_owned()
+        } else {
+            format!(
+                " [LOCKED] {} newer hidden, End to follow",
+                self.scrollback.offset()
+            )
+        };
+        let search_label = if self.search_query.is_empty() {
+            String::new()
+        } else {
+            format!(
+                " • \"{}\" in {} messages",
+                self.search_query,
+                self.search_match_count()
+            )
+        };
+        let mut message_block = self.palette.block().title(format!(
+            "{} ({}){scroll_label}{search_label}",
+            self.labels.messages,
+            self.view_label()
+        ));
+        if self.labels.cat {
+            message_block = message_block.title_bottom(Span::styled(
+                " ᓚᘏᗢ ",
+                self.palette.accent.add_modifier(Modifier::BOLD),
+            ));
+        }
+        let favorites = self.favorites.bar_labels();
+        if !favorites.is_empty() {
+            message_block = message_block.title_bottom(Span::styled(
+                format!(" {} ", favorites.join(" │ ")),
+                self.palette.warning,
+            ));
+        }
 
         let message_area = message_block.inner(layout[3]);
         let available_width = message_area.width as usize;
+        self.scrollback
+            .resize(message_area.height as usize, self.incoming_messages.len());
 
         let mut message_items: Vec<ListItem> = self
             .incoming_messages
             .iter()
+            .enumerate()
             .rev()
-            .map(|msg| {
-                let formatted = self.render_message_text(&msg.content);
+            .skip(self.scrollback.offset())
+            .map(|(index, msg)| {
+                let mut formatted = self.render_message_text(msg);
+                if self.show_deltas {
+                    formatted = format!("{} {formatted}", format_delta(self.delta_before(index)));
+                }
                 let rendered = pad_to_width(&formatted, available_width);
 
-                ListItem::new(Line::from(vec![Span::styled(rendered, msg.style)]))
+                ListItem::new(Line::from(highlight_matches(
+                    &rendered,
+                    &self.search_query,
+                    msg.style,
+                    self.palette.search_match,
+                )))
             })
             .collect();
 
@@ -509,10 +1394,73 @@ impl Component for TerminalScreen {
         frame.render_widget(Clear, layout[3]);
         frame.render_widget(List::new(message_items).block(message_block), layout[3]);
 
+        if let Some(inspector) = self.inspector
+            && let Some(bytes) = self.message_bytes(inspector.message_index)
+        {
+            inspector.draw(
+                frame,
+                layout[3],
+                bytes,
+                self.incoming_messages.len(),
+                &self.palette,
+            );
+        }
+
         Ok(())
     }
 }
 
+/// Whether a terminal `height` rows tall gets the compact layout, unless one was picked by hand.
+fn use_compact_layout(height: u16, picked: Option<bool>) -> bool {
+    picked.unwrap_or(height < COMPACT_BELOW_ROWS)
+}
+
+/// Session, command input, history and message areas. The compact layout gives the session and
+/// command one unbordered row each and leaves the history area empty.
+fn split_layout(area: Rect, compact: bool) -> Rc<[Rect]> {
+    let constraints = if compact {
+        [
+            Constraint::Length(1),
+            Constraint::Length(1),
+            Constraint::Length(0),
+            Constraint::Min(3),
+        ]
+    } else {
+        [
+            Constraint::Length(4),
+            Constraint::Length(3),
+            Constraint::Length(6),
+            Constraint::Min(10),
+        ]
+    };
+    Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(constraints)
+        .split(area)
+}
+
+/// Number of message rows visible inside the bordered message block for a screen area.
+fn message_viewport_rows(area: Rect, compact: bool) -> usize {
+    split_layout(area, compact)[3].height.saturating_sub(2) as usize
+}
+
+fn is_echo_command(command: &str) -> bool {
+    command
+        .split_whitespace()
+        .next()
+        .is_some_and(|method| Method::try_from(method) == Ok(Method::Echo))
+}
+
+/// How many leading bytes of an echo reply to hide: all of `prefix` when the reply starts with it,
+/// otherwise none, so a reply without it is shown whole.
+fn echo_prefix_len(reply: &[u8], prefix: &str) -> usize {
+    if reply.starts_with(prefix.as_bytes()) {
+        prefix.len()
+    } else {
+        0
+    }
+}
+
 fn pad_to_width(text: &str, width: usize) -> String {
     if width == 0 {
         return String::new();
@@ -532,17 +1480,29 @@ fn pad_to_width(text: &str, width: usize) -> String {
     }
 
     if current_width < width {
-        rendered.extend(std::iter::repeat(' ').take(width - current_width));
+        rendered.extend(std::iter::repeat_n(' ', width - current_width));
     }
 
     rendered
 }
 
-fn format_bytes(bytes: &[u8], encoding: MessageEncoding) -> String {
+/// Time since the previous message, right-aligned in [`DELTA_WIDTH`] columns: milliseconds up to
+/// ten seconds, whole seconds after that. The first message has no previous one and gets blanks.
+fn format_delta(delta: Option<Duration>) -> String {
+    let label = match delta {
+        None => String::new(),
+        Some(delta) if delta < Duration::from_secs(10) => format!("+{}ms", delta.as_millis()),
+        Some(delta) if delta < Duration::from_secs(100_000) => format!("+{}s", delta.as_secs()),
+        Some(_) => "+long".to_owned(),
+    };
+    format!("{label:>DELTA_WIDTH$}")
+}
+
+fn format_bytes(bytes: &[u8], encoding: MessageEncoding, style: ByteStyle) -> String {
     match encoding {
         MessageEncoding::Utf8 => format_utf8(bytes),
-        MessageEncoding::Hex => format_hex(bytes),
-        MessageEncoding::Binary => format_binary(bytes),
+        MessageEncoding::Hex => format_numbers(bytes, false, style),
+        MessageEncoding::Binary => format_numbers(bytes, true, style),
     }
 }
 
@@ -568,34 +1528,1045 @@ fn format_utf8(bytes: &[u8]) -> String {
     output
 }
 
-fn format_hex(bytes: &[u8]) -> String {
+pub(crate) fn format_hex(bytes: &[u8]) -> String {
+    format_numbers(bytes, false, ByteStyle::Spaced)
+}
+
+/// Write each byte as two hex digits, or eight binary ones, laid out in `style`.
+fn format_numbers(bytes: &[u8], binary: bool, style: ByteStyle) -> String {
     if bytes.is_empty() {
         return "<empty>".into();
     }
 
-    let mut output = String::with_capacity(bytes.len() * 4 + bytes.len().saturating_sub(1));
+    let (open, separator, close, prefixed) = match style {
+        ByteStyle::Spaced => ("", " ", "", true),
+        ByteStyle::Compact => ("", "", "", false),
+        ByteStyle::Comma => ("", ", ", "", true),
+        ByteStyle::CArray => ("{ ", ", ", " }", true),
+    };
+    let mut output = String::with_capacity(open.len() + bytes.len() * 12 + close.len());
+    output.push_str(open);
     for (idx, byte) in bytes.iter().enumerate() {
         if idx > 0 {
-            output.push(' ');
+            output.push_str(separator);
         }
-        output.push_str("0x");
-        let _ = write!(&mut output, "{:02X}", byte);
+        let _ = match (binary, prefixed) {
+            (false, true) => write!(&mut output, "0x{:02X}", byte),
+            (false, false) => write!(&mut output, "{:02X}", byte),
+            (true, true) => write!(&mut output, "0b{:08b}", byte),
+            (true, false) => write!(&mut output, "{:08b}", byte),
+        };
     }
+    output.push_str(close);
     output
 }
 
-fn format_binary(bytes: &[u8]) -> String {
-    if bytes.is_empty() {
-        return "<empty>".into();
+#[cfg(test)]
+mod tests {
+    use protocol::transport::Framing;
+
+    use super::*;
+
+    fn screen_with_messages(count: usize) -> TerminalScreen {
+        let mut screen = TerminalScreen::new();
+        for idx in 0..count {
+            screen.push_message(MessageLine::new(
+                DeviceMessage::Text(format!("message {idx}")),
+                Style::default(),
+            ));
+        }
+        screen
     }
 
-    let mut output = String::with_capacity(bytes.len() * 11);
-    for (idx, byte) in bytes.iter().enumerate() {
-        if idx > 0 {
-            output.push(' ');
+    #[test]
+    fn resize_clamps_scrolled_view() {
+        let mut screen = screen_with_messages(60);
+        screen.update(Action::Resize(80, 20)).unwrap();
+        screen.scrollback.scroll_back(usize::MAX / 2, 60);
+        let short_rows = message_viewport_rows(Rect::new(0, 0, 80, 20), true);
+        assert_eq!(screen.scrollback.offset(), 60 - short_rows);
+
+        // A taller pane pulls the view forward to the oldest message that still fills it.
+        screen.update(Action::Resize(80, 40)).unwrap();
+        let rows = message_viewport_rows(Rect::new(0, 0, 80, 40), false);
+        assert!(rows > short_rows);
+        assert_eq!(screen.scrollback.offset(), 60 - rows);
+
+        // A shorter one can scroll further back, so the view keeps its place.
+        screen.update(Action::Resize(80, 20)).unwrap();
+        assert_eq!(screen.scrollback.offset(), 60 - rows);
+
+        screen.update(Action::Resize(80, 70)).unwrap();
+        let rows = message_viewport_rows(Rect::new(0, 0, 80, 70), false);
+        assert_eq!(screen.scrollback.offset(), 60 - rows);
+    }
+
+    #[test]
+    fn short_terminals_get_the_compact_layout_unless_overridden() {
+        assert!(use_compact_layout(COMPACT_BELOW_ROWS - 1, None));
+        assert!(!use_compact_layout(COMPACT_BELOW_ROWS, None));
+        assert!(!use_compact_layout(10, Some(false)));
+        assert!(use_compact_layout(60, Some(true)));
+
+        let area = Rect::new(0, 0, 80, 16);
+        let layout = split_layout(area, true);
+        assert_eq!(layout[1].height, 1, "the command line stays visible");
+        assert_eq!(layout[2].height, 0, "history is hidden");
+        assert_eq!(message_viewport_rows(area, true), 12);
+        assert!(message_viewport_rows(area, true) > message_viewport_rows(area, false));
+
+        let mut screen = screen_with_messages(60);
+        screen.update(Action::Resize(80, 16)).unwrap();
+        assert!(screen.compact);
+        screen.toggle_compact();
+        assert!(!screen.compact);
+        screen.update(Action::Resize(80, 12)).unwrap();
+        assert!(!screen.compact, "a layout picked by hand survives resizes");
+    }
+
+    #[test]
+    fn value_hint_applies_to_next_response_only() {
+        let mut screen = TerminalScreen::new();
+        screen
+            .update(Action::CommandSent("i2c read 0x48 0x00 2 as i16be".into()))
+            .unwrap();
+        screen
+            .update(Action::IncomingMessage(DeviceMessage::Bytes(vec![
+                0xFF, 0x38,
+            ])))
+            .unwrap();
+        screen
+            .update(Action::IncomingMessage(DeviceMessage::Bytes(vec![0x01])))
+            .unwrap();
+
+        let rendered: Vec<String> = screen
+            .incoming_messages
+            .iter()
+            .map(|msg| screen.render_message_text(msg))
+            .collect();
+        assert!(rendered[0].ends_with("→ i16be: -200"), "{}", rendered[0]);
+        assert!(!rendered[1].contains('→'));
+    }
+
+    #[test]
+    fn read_multi_responses_show_each_register() {
+        let mut screen = TerminalScreen::new();
+        screen
+            .update(Action::CommandSent("i2c readm 0x48 0x00 0x05".into()))
+            .unwrap();
+        screen
+            .update(Action::IncomingMessage(DeviceMessage::Bytes(vec![
+                0x00, 0x12, 0x05, 0x34,
+            ])))
+            .unwrap();
+
+        let rendered = screen.render_message_text(&screen.incoming_messages[0]);
+        assert!(
+            rendered.ends_with("→ 0x00 = 0x12, 0x05 = 0x34"),
+            "{rendered}"
+        );
+    }
+
+    #[test]
+    fn clearing_messages_resets_derived_state() {
+        let mut screen = screen_with_messages(30);
+        screen.scrollback.resize(10, 30);
+        screen.scrollback.scroll_back(5, 30);
+        screen.push_message(MessageLine::new(
+            DeviceMessage::Bytes(vec![0x01]),
+            Style::default(),
+        ));
+        screen.open_inspector();
+        screen.push_history("echo hi".into());
+
+        screen.clear_messages();
+        assert!(screen.incoming_messages.is_empty());
+        assert!(screen.inspector.is_none());
+        assert!(screen.scrollback.is_pinned());
+        assert_eq!(screen.command_history.len(), 1);
+        assert_eq!(screen.notice, Some("Messages cleared"));
+    }
+
+    #[test]
+    fn clearing_history_leaves_messages() {
+        let mut screen = screen_with_messages(3);
+        screen.push_history("echo one".into());
+        screen.push_history("echo two".into());
+        screen.recall_older_command();
+
+        screen.clear_history();
+        assert!(screen.command_history.is_empty());
+        assert_eq!(screen.history_position, None);
+        assert_eq!(screen.draft_buffer, None);
+        assert_eq!(screen.incoming_messages.len(), 3);
+        assert_eq!(screen.notice, Some("History cleared"));
+    }
+
+    #[test]
+    fn notice_is_dropped_on_next_key() {
+        use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+        let mut screen = screen_with_messages(1);
+        screen.is_active = true;
+        screen
+            .handle_key_event(KeyEvent::new(KeyCode::Char('l'), KeyModifiers::CONTROL))
+            .unwrap();
+        assert_eq!(screen.notice, Some("Messages cleared"));
+        screen
+            .handle_key_event(KeyEvent::new(KeyCode::End, KeyModifiers::NONE))
+            .unwrap();
+        assert_eq!(screen.notice, None);
+    }
+
+    #[test]
+    fn reported_errors_show_in_the_header_until_the_next_key() {
+        use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+        let mut screen = screen_with_messages(1);
+        screen.update(Action::ShowMain).unwrap();
+        screen
+            .update(Action::Error("Failed to draw: broken pipe".into()))
+            .unwrap();
+        assert!(rendered_text(&mut screen).contains("Error: Failed to draw: broken pipe"));
+
+        screen
+            .handle_key_event(KeyEvent::new(KeyCode::End, KeyModifiers::NONE))
+            .unwrap();
+        assert!(!rendered_text(&mut screen).contains("Failed to draw"));
+    }
+
+    #[test]
+    fn sent_note_is_dropped_on_next_key() {
+        use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+        let mut screen = TerminalScreen::new();
+        screen.is_active = true;
+        screen.update(Action::FrameSent(9)).unwrap();
+        assert_eq!(screen.sent_note.map(|(len, _)| len), Some(9));
+        screen
+            .handle_key_event(KeyEvent::new(KeyCode::End, KeyModifiers::NONE))
+            .unwrap();
+        assert_eq!(screen.sent_note, None);
+    }
+
+    #[test]
+    fn sent_note_expires_on_tick() {
+        let mut screen = TerminalScreen::new();
+        screen.update(Action::FrameSent(9)).unwrap();
+        screen.update(Action::Tick).unwrap();
+        assert!(screen.sent_note.is_some());
+
+        screen.sent_note = Some((9, Instant::now()));
+        screen.update(Action::Tick).unwrap();
+        assert_eq!(screen.sent_note, None);
+    }
+
+    #[test]
+    fn dump_progress_clears_when_leaving_the_session() {
+        let mut screen = TerminalScreen::new();
+        screen
+            .update(Action::DumpProgress(Some((128, 256))))
+            .unwrap();
+        assert_eq!(screen.dump_progress, Some((128, 256)));
+        screen.update(Action::ShowPreconnect).unwrap();
+        assert_eq!(screen.dump_progress, None);
+    }
+
+    #[test]
+    fn line_noise_on_the_bridge_suggests_a_baud_mismatch_until_dismissed() {
+        use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+        let mut screen = TerminalScreen::new();
+        screen.update(Action::ShowMain).unwrap();
+        let noise = [0xFF, 0xF8, 0xE0, 0xFE].repeat(64);
+        screen
+            .update(Action::IncomingMessage(DeviceMessage::Bytes(noise.clone())))
+            .unwrap();
+        assert!(!rendered_text(&mut screen).contains("Possible baud mismatch?"));
+
+        screen.update(Action::BridgeChanged(Some(9_600))).unwrap();
+        screen
+            .update(Action::IncomingMessage(DeviceMessage::Bytes(noise.clone())))
+            .unwrap();
+        assert!(rendered_text(&mut screen).contains("Possible baud mismatch?"));
+
+        screen
+            .handle_key_event(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE))
+            .unwrap();
+        screen
+            .update(Action::IncomingMessage(DeviceMessage::Bytes(noise.clone())))
+            .unwrap();
+        assert!(!rendered_text(&mut screen).contains("Possible baud mismatch?"));
+
+        // Reopening the bridge, e.g. at another rate, checks again.
+        screen.update(Action::BridgeChanged(Some(115_200))).unwrap();
+        screen
+            .update(Action::IncomingMessage(DeviceMessage::Bytes(noise)))
+            .unwrap();
+        assert!(screen.baud_check.suspected());
+    }
+
+    #[test]
+    fn bridged_lines_are_never_hinted() {
+        let mut screen = TerminalScreen::new();
+        screen.update(Action::BridgeChanged(Some(115_200))).unwrap();
+        screen.update(Action::CommandSent("temp".into())).unwrap();
+        assert_eq!(screen.pending_hint, None);
+
+        screen.update(Action::BridgeChanged(None)).unwrap();
+        screen.update(Action::CommandSent("temp".into())).unwrap();
+        assert_eq!(screen.pending_hint, Some(ValueHint::Celsius));
+    }
+
+    #[test]
+    fn keystroke_mode_sends_each_key_while_bridged() {
+        use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        let mut screen = TerminalScreen::new();
+        screen
+            .register_config_handler(Config {
+                backspace: 0x08,
+                ..Config::default()
+            })
+            .unwrap();
+        screen.is_active = true;
+        screen.handle_key_event(key(KeyCode::Char('K'))).unwrap();
+        assert!(!screen.keystroke_mode);
+        assert_eq!(
+            screen.notice,
+            Some("Keystroke mode needs an open UART bridge")
+        );
+
+        screen.update(Action::BridgeChanged(Some(115_200))).unwrap();
+        screen.handle_key_event(key(KeyCode::Char('K'))).unwrap();
+        assert!(screen.keystroke_mode);
+        for (code, bytes) in [
+            (KeyCode::Char('x'), b"x".as_slice()),
+            (KeyCode::Enter, b"\r\n"),
+            (KeyCode::Backspace, &[0x08]),
+        ] {
+            assert_eq!(
+                screen.handle_key_event(key(code)).unwrap(),
+                Some(Action::SendKeystrokes(bytes.to_vec()))
+            );
         }
-        output.push_str("0b");
-        let _ = write!(&mut output, "{:08b}", byte);
+        assert!(screen.command_buffer.is_empty());
+
+        for chunk in [b"> 1".as_slice(), b"+1\r\n", b"2"] {
+            screen
+                .update(Action::IncomingMessage(DeviceMessage::Bytes(
+                    chunk.to_vec(),
+                )))
+                .unwrap();
+        }
+        let received: Vec<_> = screen
+            .incoming_messages
+            .iter()
+            .map(|line| line.content.clone())
+            .collect();
+        assert_eq!(
+            received,
+            [
+                DeviceMessage::Bytes(b"> 1+1\r\n".to_vec()),
+                DeviceMessage::Bytes(b"2".to_vec())
+            ]
+        );
+
+        screen.update(Action::BridgeChanged(None)).unwrap();
+        assert!(!screen.keystroke_mode);
+        assert_eq!(screen.input_mode, InputMode::Normal);
+    }
+
+    #[test]
+    fn temp_responses_render_in_celsius() {
+        let mut screen = TerminalScreen::new();
+        screen.update(Action::CommandSent("temp".into())).unwrap();
+        screen
+            .update(Action::IncomingMessage(DeviceMessage::Bytes(vec![
+                0x01, 0x11,
+            ])))
+            .unwrap();
+        let rendered = screen.render_message_text(&screen.incoming_messages[0]);
+        assert!(rendered.ends_with("→ 27.3 °C"), "{rendered}");
+    }
+
+    fn editing_screen() -> TerminalScreen {
+        let mut screen = TerminalScreen::new();
+        screen.is_active = true;
+        screen.enter_edit_mode();
+        screen
+    }
+
+    fn press(screen: &mut TerminalScreen, code: crossterm::event::KeyCode) {
+        let key = crossterm::event::KeyEvent::new(code, crossterm::event::KeyModifiers::NONE);
+        screen.handle_key_event(key).unwrap();
+    }
+
+    fn type_text(screen: &mut TerminalScreen, text: &str) {
+        for ch in text.chars() {
+            press(screen, crossterm::event::KeyCode::Char(ch));
+        }
+    }
+
+    #[test]
+    fn blank_lines_are_neither_sent_nor_recorded() {
+        use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+        for blank in ["", "   ", "\t\t"] {
+            let mut screen = editing_screen();
+            type_text(&mut screen, blank);
+            let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+            assert_eq!(screen.handle_key_event(enter).unwrap(), None, "{blank:?}");
+            assert!(screen.command_buffer.is_empty());
+
+            screen.update(Action::CommandSent(blank.into())).unwrap();
+            assert!(screen.command_history.is_empty(), "{blank:?}");
+        }
+    }
+
+    #[test]
+    fn function_keys_send_their_favorite() {
+        use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+        let mut screen = TerminalScreen::new();
+        let config = Config {
+            favorites: Favorites::parse("F2 = temp").unwrap(),
+            ..Config::default()
+        };
+        screen.register_config_handler(config).unwrap();
+        screen.is_active = true;
+        let key = |number| KeyEvent::new(KeyCode::F(number), KeyModifiers::NONE);
+        assert_eq!(
+            screen.handle_key_event(key(2)).unwrap(),
+            Some(Action::SendCommand("temp".into()))
+        );
+        assert_eq!(screen.handle_key_event(key(1)).unwrap(), None);
+        assert!(screen.command_history.is_empty());
+    }
+
+    #[test]
+    fn ctrl_h_is_backspace_while_editing() {
+        use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+        let mut screen = editing_screen();
+        type_text(&mut screen, "temp");
+        screen
+            .handle_key_event(KeyEvent::new(KeyCode::Char('h'), KeyModifiers::CONTROL))
+            .unwrap();
+        assert_eq!(screen.command_buffer, "tem");
+        assert_eq!(screen.message_encoding, MessageEncoding::Utf8);
+    }
+
+    #[test]
+    fn editing_around_multibyte_characters_keeps_cursor_on_boundaries() {
+        use crossterm::event::KeyCode;
+
+        let mut screen = editing_screen();
+        type_text(&mut screen, "a😀é");
+        assert_eq!(screen.cursor_index, screen.command_buffer.len());
+
+        press(&mut screen, KeyCode::Left);
+        press(&mut screen, KeyCode::Left);
+        assert_eq!(screen.cursor_index, 1);
+        type_text(&mut screen, "ß");
+        assert_eq!(screen.command_buffer, "aß😀é");
+
+        press(&mut screen, KeyCode::Delete);
+        assert_eq!(screen.command_buffer, "aßé");
+        press(&mut screen, KeyCode::Backspace);
+        assert_eq!(screen.command_buffer, "aé");
+        press(&mut screen, KeyCode::Right);
+        press(&mut screen, KeyCode::Right);
+        press(&mut screen, KeyCode::Backspace);
+        assert_eq!(screen.command_buffer, "a");
+
+        press(&mut screen, KeyCode::Home);
+        type_text(&mut screen, "日");
+        press(&mut screen, KeyCode::End);
+        press(&mut screen, KeyCode::Delete);
+        assert_eq!(screen.command_buffer, "日a");
+        assert_eq!(screen.cursor_index, screen.command_buffer.len());
+    }
+
+    #[test]
+    fn history_entries_record_their_outcome() {
+        let mut screen = TerminalScreen::new();
+        screen
+            .update(Action::CommandSent("echo hi".into()))
+            .unwrap();
+        screen
+            .update(Action::IncomingMessage(DeviceMessage::Text(
+                "Warning: skipped 1 byte".into(),
+            )))
+            .unwrap();
+        screen
+            .update(Action::IncomingMessage(DeviceMessage::Bytes(
+                b"hi".to_vec(),
+            )))
+            .unwrap();
+        screen
+            .update(Action::CommandSent("i2c read 9 0x48 0 1".into()))
+            .unwrap();
+        screen
+            .update(Action::IncomingMessage(DeviceMessage::Bytes(
+                b"ERR: InvalidBus".to_vec(),
+            )))
+            .unwrap();
+        // Only the first settling message counts.
+        screen
+            .update(Action::IncomingMessage(DeviceMessage::Bytes(vec![0x01])))
+            .unwrap();
+
+        let outcomes: Vec<CommandOutcome> = screen
+            .command_history
+            .iter()
+            .map(|entry| entry.outcome)
+            .collect();
+        assert_eq!(
+            outcomes,
+            [CommandOutcome::Succeeded, CommandOutcome::DeviceError]
+        );
+
+        screen.recall_older_command();
+        assert_eq!(screen.command_buffer, "i2c read 9 0x48 0 1");
+    }
+
+    #[test]
+    fn recalled_multibyte_history_can_be_edited() {
+        use crossterm::event::KeyCode;
+
+        let mut screen = editing_screen();
+        screen.push_history("echo 😀😀".into());
+        type_text(&mut screen, "é");
+        press(&mut screen, KeyCode::Up);
+        assert_eq!(screen.cursor_index, screen.command_buffer.len());
+        press(&mut screen, KeyCode::Left);
+        type_text(&mut screen, "!");
+        assert_eq!(screen.command_buffer, "echo 😀!😀");
+        press(&mut screen, KeyCode::Down);
+        assert_eq!(screen.command_buffer, "é");
+        press(&mut screen, KeyCode::Backspace);
+        assert!(screen.command_buffer.is_empty());
+    }
+
+    #[test]
+    fn cursor_boundary_snaps_back_to_a_char_start() {
+        let mut screen = editing_screen();
+        screen.command_buffer = "😀".into();
+        screen.cursor_index = 2;
+        assert_eq!(screen.cursor_boundary(), 0);
+        screen.cursor_index = 9;
+        assert_eq!(screen.cursor_boundary(), 4);
+    }
+
+    #[test]
+    fn dry_run_follows_config_and_toggle() {
+        use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+        let mut screen = TerminalScreen::new();
+        let config = Config {
+            dry_run: true,
+            ..Config::default()
+        };
+        screen.register_config_handler(config).unwrap();
+        screen.is_active = true;
+        assert!(screen.dry_run);
+        let key = KeyEvent::new(KeyCode::Char('D'), KeyModifiers::SHIFT);
+        assert_eq!(
+            screen.handle_key_event(key).unwrap(),
+            Some(Action::ToggleDryRun)
+        );
+        screen.update(Action::ToggleDryRun).unwrap();
+        assert!(!screen.dry_run);
+    }
+
+    #[test]
+    fn remapped_keys_trigger_their_action() {
+        use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+        let mut screen = TerminalScreen::new();
+        let config = Config {
+            keymap: Keymap::parse("hex_view = ctrl+t\nhelp = ?").unwrap(),
+            ..Config::default()
+        };
+        screen.register_config_handler(config).unwrap();
+        screen.is_active = true;
+
+        let ctrl = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+        screen.handle_key_event(ctrl('x')).unwrap();
+        assert_eq!(screen.message_encoding, MessageEncoding::Utf8);
+        screen.handle_key_event(ctrl('t')).unwrap();
+        assert_eq!(screen.message_encoding, MessageEncoding::Hex);
+
+        let plain = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        assert_eq!(screen.handle_key_event(plain('h')).unwrap(), None);
+        assert_eq!(
+            screen.handle_key_event(plain('?')).unwrap(),
+            Some(Action::ToggleHelp)
+        );
+    }
+
+    #[test]
+    fn byte_styles_lay_out_hex_and_binary() {
+        use ByteStyle::*;
+        use MessageEncoding::{Binary, Hex};
+
+        let cases: [(&[u8], MessageEncoding, ByteStyle, &str); 12] = [
+            (&[0xAA, 0x0B, 0xCC], Hex, Spaced, "0xAA 0x0B 0xCC"),
+            (&[0xAA, 0x0B, 0xCC], Hex, Compact, "AA0BCC"),
+            (&[0xAA, 0x0B, 0xCC], Hex, Comma, "0xAA, 0x0B, 0xCC"),
+            (&[0xAA, 0x0B, 0xCC], Hex, CArray, "{ 0xAA, 0x0B, 0xCC }"),
+            (&[0x05], Hex, CArray, "{ 0x05 }"),
+            (&[0x05], Binary, Spaced, "0b00000101"),
+            (&[0x05, 0xF0], Binary, Compact, "0000010111110000"),
+            (&[0x05, 0xF0], Binary, Comma, "0b00000101, 0b11110000"),
+            (&[0x05, 0xF0], Binary, CArray, "{ 0b00000101, 0b11110000 }"),
+            (&[], Hex, CArray, "<empty>"),
+            (&[], Binary, Compact, "<empty>"),
+            (b"hi", MessageEncoding::Utf8, CArray, "hi"),
+        ];
+        for (bytes, encoding, style, expected) in cases {
+            assert_eq!(
+                format_bytes(bytes, encoding, style),
+                expected,
+                "{encoding:?} {style:?}"
+            );
+        }
+        assert_eq!(
+            format_hex(&[0xAA, 0x0B]),
+            format_bytes(&[0xAA, 0x0B], Hex, ByteStyle::default())
+        );
+    }
+
+    #[test]
+    fn byte_style_cycles_and_keeps_the_hint() {
+        let mut screen = TerminalScreen::new();
+        screen.message_encoding = MessageEncoding::Hex;
+        screen
+            .update(Action::CommandSent("i2c read 0x48 0x00 2 as u16be".into()))
+            .unwrap();
+        screen
+            .update(Action::IncomingMessage(DeviceMessage::Bytes(vec![
+                0x01, 0x02,
+            ])))
+            .unwrap();
+        screen.cycle_byte_style().unwrap();
+        screen.cycle_byte_style().unwrap();
+        screen.cycle_byte_style().unwrap();
+        assert_eq!(screen.byte_style, ByteStyle::CArray);
+        assert_eq!(screen.view_label(), "Hex, c-array");
+        assert_eq!(
+            screen.render_message_text(&screen.incoming_messages[0]),
+            "{ 0x01, 0x02 }  → u16be: 258"
+        );
+        screen.cycle_byte_style().unwrap();
+        assert_eq!(screen.byte_style, ByteStyle::Spaced);
+        assert_eq!(screen.view_label(), "Hex");
+    }
+
+    #[test]
+    fn tagged_responses_are_prefixed_with_their_method() {
+        let mut screen = TerminalScreen::new();
+        screen.message_encoding = MessageEncoding::Hex;
+        screen
+            .update(Action::TaggedResponse(
+                Method::I2c,
+                ResponseFormat::Auto,
+                vec![0x12, 0x34],
+            ))
+            .unwrap();
+        screen
+            .update(Action::IncomingMessage(DeviceMessage::Bytes(vec![0x12])))
+            .unwrap();
+        let rendered: Vec<_> = screen
+            .incoming_messages
+            .iter()
+            .map(|msg| screen.render_message_text(msg))
+            .collect();
+        assert_eq!(rendered, ["[i2c] 0x12 0x34", "0x12"]);
+    }
+
+    #[test]
+    fn the_view_follows_the_format_the_device_tags() {
+        let mut screen = TerminalScreen::new();
+        let reply = |format| Action::TaggedResponse(Method::Echo, format, b"hi".to_vec());
+        screen.update(reply(ResponseFormat::Bytes)).unwrap();
+        assert_eq!(screen.message_encoding, MessageEncoding::Hex);
+
+        // Binary is already a byte view, so it's kept.
+        screen.message_encoding = MessageEncoding::Binary;
+        screen.update(reply(ResponseFormat::Bytes)).unwrap();
+        assert_eq!(screen.message_encoding, MessageEncoding::Binary);
+        screen.update(reply(ResponseFormat::Auto)).unwrap();
+        assert_eq!(screen.message_encoding, MessageEncoding::Binary);
+
+        screen.update(reply(ResponseFormat::Text)).unwrap();
+        assert_eq!(screen.message_encoding, MessageEncoding::Utf8);
+    }
+
+    #[test]
+    fn echo_prefix_is_hidden_only_when_asked_for() {
+        assert_eq!(echo_prefix_len(b"rp2040: hi", "rp2040: "), 8);
+        assert_eq!(echo_prefix_len(b"hi", "rp2040: "), 0);
+        assert_eq!(echo_prefix_len(b"rp2040", "rp2040: "), 0);
+
+        let reply = || Action::IncomingMessage(DeviceMessage::Bytes(b"rp2040: hi".to_vec()));
+        let mut screen = TerminalScreen::new();
+        screen
+            .register_config_handler(Config {
+                echo_prefix: Some("rp2040: ".into()),
+                ..Config::default()
+            })
+            .unwrap();
+        screen
+            .update(Action::CommandSent("echo hi".into()))
+            .unwrap();
+        screen.update(reply()).unwrap();
+        // Only echo replies lose the prefix.
+        screen
+            .update(Action::CommandSent("i2c write 0x48 0x00 0x01".into()))
+            .unwrap();
+        screen.update(reply()).unwrap();
+        let rendered: Vec<_> = screen
+            .incoming_messages
+            .iter()
+            .map(|msg| screen.render_message_text(msg))
+            .collect();
+        assert_eq!(rendered, ["hi", "rp2040: hi"]);
+
+        // The bytes themselves are untouched.
+        screen.message_encoding = MessageEncoding::Hex;
+        let first = &screen.incoming_messages[0];
+        assert_eq!(first.content, DeviceMessage::Bytes(b"rp2040: hi".to_vec()));
+        assert!(screen.render_message_text(first).starts_with("0x72 0x70"));
+
+        let mut plain = TerminalScreen::new();
+        plain.update(Action::CommandSent("echo hi".into())).unwrap();
+        plain.update(reply()).unwrap();
+        assert_eq!(
+            plain.render_message_text(&plain.incoming_messages[0]),
+            "rp2040: hi"
+        );
+    }
+
+    #[test]
+    fn deltas_are_measured_from_the_previous_message() {
+        let mut screen = screen_with_messages(4);
+        let start = Instant::now();
+        for (msg, offset_ms) in screen.incoming_messages.iter_mut().zip([0, 12, 12, 10_500]) {
+            msg.arrived = start + Duration::from_millis(offset_ms);
+        }
+        let labels: Vec<_> = (0..4)
+            .map(|index| format_delta(screen.delta_before(index)))
+            .collect();
+        assert_eq!(labels, ["       ", "  +12ms", "   +0ms", "   +10s"]);
+        assert!(labels.iter().all(|label| label.len() == DELTA_WIDTH));
+        assert_eq!(format_delta(Some(Duration::from_millis(9_999))), "+9999ms");
+        assert_eq!(format_delta(Some(Duration::from_secs(200_000))), "  +long");
+    }
+
+    #[test]
+    fn verbose_shows_the_decoded_command_and_keeps_the_hint() {
+        let mut screen = TerminalScreen::new();
+        screen.toggle_verbose();
+        screen
+            .update(Action::CommandSent("i2c r 72 0 2 as i16be".into()))
+            .unwrap();
+        screen
+            .update(Action::IncomingMessage(DeviceMessage::Bytes(vec![
+                0xFF, 0x38,
+            ])))
+            .unwrap();
+        let rendered: Vec<_> = screen
+            .incoming_messages
+            .iter()
+            .map(|msg| screen.render_message_text(msg))
+            .collect();
+        assert_eq!(rendered[0], "→ i2c read 0x48 0x00 2");
+        assert_eq!(screen.incoming_messages[1].hint, Some(ValueHint::I16Be));
+
+        screen.toggle_verbose();
+        screen.update(Action::CommandSent("temp".into())).unwrap();
+        assert_eq!(screen.incoming_messages.len(), 2);
+    }
+
+    #[test]
+    fn value_hint_skips_error_responses() {
+        let mut screen = TerminalScreen::new();
+        screen
+            .update(Action::CommandSent("i2c read 0x48 0x00 2 as u8".into()))
+            .unwrap();
+        screen
+            .update(Action::IncomingMessage(DeviceMessage::Bytes(
+                b"ERR: ExecutionFailed".to_vec(),
+            )))
+            .unwrap();
+        assert_eq!(screen.incoming_messages[0].hint, None);
+    }
+
+    #[test]
+    fn auto_repeat_stops_when_leaving_the_session() {
+        let mut screen = TerminalScreen::new();
+        screen
+            .update(Action::CommandSent("i2c read 0x48 0x00 2".into()))
+            .unwrap();
+        screen.toggle_auto_repeat();
+        assert!(screen.auto_repeat.is_some());
+
+        screen.update(Action::ShowPreconnect).unwrap();
+        assert!(screen.auto_repeat.is_none());
+    }
+
+    #[test]
+    fn auto_repeat_stops_on_a_bridge_or_mode_change() {
+        let mut screen = TerminalScreen::new();
+        screen
+            .update(Action::CommandSent("i2c read 0x48 0x00 2".into()))
+            .unwrap();
+
+        screen.toggle_auto_repeat();
+        screen.update(Action::BridgeChanged(Some(9_600))).unwrap();
+        assert!(screen.auto_repeat.is_none());
+
+        screen.toggle_auto_repeat();
+        screen.start_keystroke_mode();
+        assert!(screen.auto_repeat.is_none());
+
+        screen.toggle_auto_repeat();
+        screen.enter_edit_mode();
+        assert!(screen.auto_repeat.is_none());
+    }
+
+    #[test]
+    fn auto_repeat_needs_a_previous_command() {
+        let mut screen = TerminalScreen::new();
+        screen.toggle_auto_repeat();
+        assert!(screen.auto_repeat.is_none());
+        assert_eq!(screen.incoming_messages.len(), 1);
+    }
+
+    #[test]
+    fn scroll_lock_holds_the_view_as_messages_arrive() {
+        use crossterm::event::KeyCode;
+
+        let mut screen = screen_with_messages(30);
+        screen.is_active = true;
+        screen.scrollback.resize(10, 30);
+        let message = || MessageLine::new(DeviceMessage::Text("new".into()), Style::default());
+
+        press(&mut screen, KeyCode::Char('l'));
+        screen.push_message(message());
+        screen.push_message(message());
+        assert_eq!(screen.scrollback.offset(), 2);
+
+        // Clearing empties the pane but keeps the lock.
+        screen.clear_messages();
+        assert!(!screen.scrollback.is_following());
+
+        press(&mut screen, KeyCode::Char('l'));
+        screen.push_message(message());
+        assert!(screen.scrollback.is_pinned());
+
+        for _ in 0..30 {
+            screen.push_message(message());
+        }
+        press(&mut screen, KeyCode::PageUp);
+        assert!(!screen.scrollback.is_following());
+        press(&mut screen, KeyCode::PageDown);
+        assert!(screen.scrollback.is_following());
+        screen.push_message(message());
+        assert!(screen.scrollback.is_pinned());
+    }
+
+    #[test]
+    fn history_and_messages_survive_a_reconnect() {
+        use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+        let mut screen = screen_with_messages(3);
+        let established = || {
+            Action::ConnectionEstablished(ConnectionInfo {
+                port: "/dev/ttyACM0".into(),
+                baud_rate: 115_200,
+                line: None,
+                framing: Framing::Postcard,
+                tagged: false,
+                mode: SessionMode::Commands,
+                label: None,
+            })
+        };
+        screen.update(established()).unwrap();
+        screen.update(Action::ShowMain).unwrap();
+        screen.push_history("echo hi".into());
+        type_text(&mut screen, "e");
+        type_text(&mut screen, "temp");
+        press(&mut screen, KeyCode::Esc);
+
+        let action = screen
+            .handle_key_event(KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL))
+            .unwrap();
+        assert_eq!(
+            action,
+            Some(Action::Connect {
+                port: "/dev/ttyACM0".into(),
+                baud_rate: 115_200,
+            })
+        );
+        screen.update(Action::ShowConnecting).unwrap();
+        screen.update(established()).unwrap();
+        screen.update(Action::ShowMain).unwrap();
+
+        assert!(screen.is_active);
+        assert_eq!(screen.last_command().map(String::as_str), Some("echo hi"));
+        assert_eq!(screen.command_buffer, "temp");
+        assert_eq!(screen.incoming_messages.len(), 4);
+        assert_eq!(
+            screen.incoming_messages.back().map(|msg| &msg.content),
+            Some(&DeviceMessage::Text("— reconnected —".into()))
+        );
+
+        // Only a reconnect gets a marker.
+        screen.update(established()).unwrap();
+        assert_eq!(screen.incoming_messages.len(), 4);
+    }
+
+    #[test]
+    fn the_header_follows_the_bridge_and_the_device_label() {
+        use protocol::{
+            MAX_I2C_READ_LEN,
+            device_info::{DeviceConfig, DeviceInfo, DeviceLabel},
+        };
+
+        let mut screen = TerminalScreen::new();
+        screen
+            .update(Action::ConnectionEstablished(ConnectionInfo {
+                port: "sim".into(),
+                baud_rate: 115_200,
+                line: None,
+                framing: Framing::Postcard,
+                tagged: false,
+                mode: SessionMode::Commands,
+                label: None,
+            }))
+            .unwrap();
+        screen
+            .update(Action::DeviceInfo(DeviceInfo {
+                max_i2c_read: MAX_I2C_READ_LEN,
+                config: DeviceConfig::DEFAULT,
+                uart: None,
+                label: DeviceLabel::new("bench board").unwrap(),
+            }))
+            .unwrap();
+        screen.update(Action::BridgeChanged(Some(9_600))).unwrap();
+        assert_eq!(
+            screen.connection.as_ref().unwrap().summary(),
+            "sim @ 115200 baud, postcard framing, UART bridge @ 9600 baud, bench board"
+        );
+        screen.update(Action::BridgeChanged(None)).unwrap();
+        assert_eq!(
+            screen.connection.as_ref().unwrap().summary(),
+            "sim @ 115200 baud, postcard framing, commands, bench board"
+        );
+    }
+
+    #[test]
+    fn disconnecting_returns_to_preconnect_keeping_the_history() {
+        use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+        let mut screen = screen_with_messages(3);
+        screen.update(Action::ShowMain).unwrap();
+        screen.push_history("echo hi".into());
+        screen.push_history("temp".into());
+
+        let action = screen
+            .handle_key_event(KeyEvent::new(KeyCode::Char('w'), KeyModifiers::CONTROL))
+            .unwrap();
+        assert_eq!(action, Some(Action::Disconnect));
+        screen.update(Action::ShowPreconnect).unwrap();
+        assert!(!screen.is_active);
+
+        screen.update(Action::ShowMain).unwrap();
+        assert_eq!(screen.last_command().map(String::as_str), Some("temp"));
+        assert_eq!(screen.incoming_messages.len(), 3);
+    }
+
+    #[test]
+    fn resize_keeps_pinned_view_pinned() {
+        let mut screen = screen_with_messages(60);
+        screen.update(Action::Resize(80, 40)).unwrap();
+        screen.update(Action::Resize(80, 15)).unwrap();
+        screen.update(Action::Resize(120, 50)).unwrap();
+        assert!(screen.scrollback.is_pinned());
+    }
+
+    #[test]
+    fn search_highlights_as_it_is_typed_and_esc_clears_it() {
+        use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+        let mut screen = screen_with_messages(12);
+        screen.update(Action::ShowMain).unwrap();
+        for code in [KeyCode::Char('/'), KeyCode::Char('1')] {
+            screen
+                .handle_key_event(KeyEvent::new(code, KeyModifiers::NONE))
+                .unwrap();
+        }
+        assert_eq!(screen.input_mode, InputMode::Searching);
+        assert_eq!(screen.search_match_count(), 3);
+
+        screen
+            .handle_key_event(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE))
+            .unwrap();
+        assert_eq!(screen.input_mode, InputMode::Normal);
+        assert_eq!(screen.search_query, "1");
+
+        screen
+            .handle_key_event(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE))
+            .unwrap();
+        assert!(screen.search_query.is_empty());
+    }
+
+    fn rendered_text(screen: &mut TerminalScreen) -> String {
+        use ratatui::{Terminal, backend::TestBackend};
+
+        let mut terminal = Terminal::new(TestBackend::new(100, 40)).unwrap();
+        terminal
+            .draw(|frame| screen.draw(frame, frame.area()).unwrap())
+            .unwrap();
+        terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect()
+    }
+
+    #[test]
+    fn labels_from_the_config_replace_the_defaults() {
+        let mut screen = screen_with_messages(1);
+        screen.update(Action::ShowMain).unwrap();
+        let defaults = rendered_text(&mut screen);
+        for label in [
+            "Session",
+            "Command> ",
+            "Command History",
+            "Device Messages",
+            "ᓚᘏᗢ",
+        ] {
+            assert!(defaults.contains(label), "{label} missing");
+        }
+
+        let config = Config {
+            labels: Labels::parse("prompt = \"$ \"\nmessages = Output\ncat = off").unwrap(),
+            ..Config::default()
+        };
+        screen.register_config_handler(config).unwrap();
+        let overridden = rendered_text(&mut screen);
+        assert!(overridden.contains("$ "));
+        assert!(overridden.contains("Output (UTF-8)"));
+        assert!(!overridden.contains("Command> "));
+        assert!(!overridden.contains("Device Messages"));
+        assert!(!overridden.contains("ᓚᘏᗢ"));
+        assert!(overridden.contains("Command History"));
     }
-    output
 }
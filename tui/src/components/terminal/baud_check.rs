@@ -0,0 +1,122 @@
+//! Spotting a UART bridge opened at the wrong baud rate.
+//!
+//! Sampling a UART at the wrong rate turns each character into framing noise: sampling too fast
+//! reads the idle line as set high bits (`0xFF`, `0xF8`, `0xE0`, ...) and too slow reads a run of
+//! zeros. The check counts only those bytes, NULs and bytes that aren't part of valid UTF-8, so a
+//! reply made of small binary values or of non-ASCII text never counts against it. It judges the
+//! latest [`WINDOW`] bytes, and only once that many have arrived.
+
+/// Bridged bytes judged together.
+pub(super) const WINDOW: usize = 128;
+/// Share of noise bytes, in percent, at which a window looks garbled.
+const GARBLED_PERCENT: usize = 75;
+
+/// Whether `bytes` hold at least [`WINDOW`] bytes and look like a wrong baud rate.
+pub(super) fn looks_garbled(bytes: &[u8]) -> bool {
+    if bytes.len() < WINDOW {
+        return false;
+    }
+    let noise: usize = bytes
+        .utf8_chunks()
+        .map(|chunk| {
+            chunk.valid().bytes().filter(|&byte| byte == 0).count() + chunk.invalid().len()
+        })
+        .sum();
+    noise * 100 >= bytes.len() * GARBLED_PERCENT
+}
+
+/// Recent bridged bytes and whether the hint is up.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(super) struct BaudCheck {
+    recent: Vec<u8>,
+    suspected: bool,
+    dismissed: bool,
+}
+
+impl BaudCheck {
+    /// Judge the latest bridged bytes along with `bytes`.
+    pub fn observe(&mut self, bytes: &[u8]) {
+        if self.dismissed {
+            return;
+        }
+        self.recent.extend_from_slice(bytes);
+        let excess = self.recent.len().saturating_sub(WINDOW);
+        self.recent.drain(..excess);
+        self.suspected = looks_garbled(&self.recent);
+    }
+
+    /// Hide the hint until the bridge is opened again.
+    pub fn dismiss(&mut self) {
+        self.dismissed = true;
+        self.suspected = false;
+        self.recent.clear();
+    }
+
+    /// Start over, e.g. when a bridge opens at a new rate or closes.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Whether to show the possible-mismatch hint.
+    pub fn suspected(&self) -> bool {
+        self.suspected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `pattern` repeated out to `len` bytes.
+    fn stream(pattern: &[u8], len: usize) -> Vec<u8> {
+        pattern.iter().copied().cycle().take(len).collect()
+    }
+
+    #[test]
+    fn wrong_baud_noise_looks_garbled() {
+        for garbled in [
+            stream(&[0xFF, 0xF8, 0xE0, 0xFE, 0x80, 0xFC], 256),
+            stream(&[0x00], WINDOW),
+            // Noise with the odd character that happens to decode.
+            stream(&[0xF0, 0x00, 0xE6, 0x78, 0x86, 0x9E, 0xFF, 0x00], 200),
+        ] {
+            assert!(looks_garbled(&garbled), "{garbled:02x?}");
+        }
+    }
+
+    #[test]
+    fn text_and_binary_replies_look_ok() {
+        for ok in [
+            stream(b"temp=23.5\r\n", 256),
+            stream("23.1 °C ᓚᘏᗢ\r\n".as_bytes(), 256),
+            // A register dump: small values and the odd 0xFF.
+            stream(&[0x01, 0x1F, 0x42, 0x00, 0x7F, 0xFF, 0x10, 0x03], 256),
+            // Too little to judge.
+            stream(&[0xFF], WINDOW - 1),
+        ] {
+            assert!(!looks_garbled(&ok), "{ok:02x?}");
+        }
+    }
+
+    #[test]
+    fn the_hint_follows_the_latest_bytes_until_dismissed() {
+        let mut check = BaudCheck::default();
+        check.observe(&stream(&[0xFE, 0xF8], WINDOW / 2));
+        assert!(!check.suspected());
+        check.observe(&stream(&[0xFE, 0xF8], WINDOW / 2));
+        assert!(check.suspected());
+
+        check.observe(&stream(b"login: ", WINDOW));
+        assert!(!check.suspected());
+
+        check.observe(&stream(&[0xFF], WINDOW));
+        assert!(check.suspected());
+        check.dismiss();
+        check.observe(&stream(&[0xFF], WINDOW));
+        assert!(!check.suspected());
+
+        check.reset();
+        check.observe(&stream(&[0xFF], WINDOW));
+        assert!(check.suspected());
+    }
+}
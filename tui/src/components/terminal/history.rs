@@ -0,0 +1,103 @@
+//! Command history entries and how their last send went.
+//!
+//! A command's outcome is taken from the first message that settles it after it was sent, the same
+//! way the value hint is applied: a device response is a success or a device error, and a host-side
+//! `Error:` note means it never got as far as the device.
+
+use protocol::response::ERROR_PREFIX;
+use ratatui::text::Span;
+
+use crate::{action::DeviceMessage, theme::Palette};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(super) enum CommandOutcome {
+    /// Nothing has settled it yet, or it was bridged UART text with no response of its own.
+    #[default]
+    Pending,
+    /// The device answered without an error.
+    Succeeded,
+    /// The host couldn't encode, frame or finish it.
+    EncodeError,
+    /// The device answered with an `ERR:` response.
+    DeviceError,
+}
+
+impl CommandOutcome {
+    /// The outcome `message` settles, or `None` if it says nothing about the last command.
+    pub fn from_message(message: &DeviceMessage) -> Option<Self> {
+        match message {
+            DeviceMessage::Bytes(bytes) if bytes.starts_with(ERROR_PREFIX) => {
+                Some(CommandOutcome::DeviceError)
+            }
+            DeviceMessage::Bytes(_) => Some(CommandOutcome::Succeeded),
+            DeviceMessage::Text(text) if text.starts_with("Error:") => {
+                Some(CommandOutcome::EncodeError)
+            }
+            DeviceMessage::Text(_) => None,
+        }
+    }
+
+    /// Marker drawn before the entry in the history list. Each outcome has its own glyph so the
+    /// list reads without colour.
+    pub fn marker(&self, palette: &Palette) -> Span<'static> {
+        match self {
+            CommandOutcome::Pending => Span::raw("  "),
+            CommandOutcome::Succeeded => Span::styled("✓ ", palette.muted),
+            CommandOutcome::EncodeError => Span::styled("✗ ", palette.error),
+            CommandOutcome::DeviceError => Span::styled("! ", palette.warning),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct HistoryEntry {
+    pub command: String,
+    pub outcome: CommandOutcome,
+}
+
+impl HistoryEntry {
+    pub fn new(command: String) -> Self {
+        Self {
+            command,
+            outcome: CommandOutcome::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_map_to_outcomes_and_markers() {
+        let cases = [
+            (
+                DeviceMessage::Bytes(vec![0x01]),
+                Some(CommandOutcome::Succeeded),
+                "✓ ",
+            ),
+            (
+                DeviceMessage::Bytes(b"ERR: InvalidBus".to_vec()),
+                Some(CommandOutcome::DeviceError),
+                "! ",
+            ),
+            (
+                DeviceMessage::Text("Error: Failed to encode command `x`: unknown method".into()),
+                Some(CommandOutcome::EncodeError),
+                "✗ ",
+            ),
+        ];
+        for (message, outcome, marker) in cases {
+            assert_eq!(CommandOutcome::from_message(&message), outcome);
+            assert_eq!(outcome.unwrap().marker(&Palette::DEFAULT).content, marker);
+        }
+        assert_eq!(
+            CommandOutcome::from_message(&DeviceMessage::Text("Warning: skipped 2 bytes".into())),
+            None
+        );
+        assert_eq!(
+            CommandOutcome::Pending.marker(&Palette::DEFAULT).content,
+            "  "
+        );
+    }
+}
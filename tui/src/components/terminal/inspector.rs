@@ -0,0 +1,177 @@
+//! Byte-level inspector for a single received message.
+//!
+//! The inspector is a host-only view over a stored `DeviceMessage::Bytes`: it lists every byte in
+//! hex, decimal, binary and ASCII, and decodes a few multi-byte interpretations starting at the
+//! byte under the cursor.
+
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Clear, Paragraph},
+};
+
+use crate::theme::Palette;
+
+/// Tracks which message is being inspected and which byte the cursor is on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct ByteInspector {
+    /// Index into the terminal's message buffer (oldest first).
+    pub message_index: usize,
+    /// Byte offset within the inspected message.
+    pub cursor: usize,
+}
+
+impl ByteInspector {
+    pub fn new(message_index: usize) -> Self {
+        Self {
+            message_index,
+            cursor: 0,
+        }
+    }
+
+    pub fn move_left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn move_right(&mut self, len: usize) {
+        if self.cursor + 1 < len {
+            self.cursor += 1;
+        }
+    }
+
+    /// Keep the cursor inside the message after switching to a shorter one.
+    pub fn clamp_cursor(&mut self, len: usize) {
+        self.cursor = self.cursor.min(len.saturating_sub(1));
+    }
+
+    pub fn draw(
+        &self,
+        frame: &mut Frame,
+        area: Rect,
+        bytes: &[u8],
+        message_count: usize,
+        palette: &Palette,
+    ) {
+        let title = format!(
+            "Byte Inspector • message {}/{} • byte {}/{} (←/→ byte, ↑/↓ message, Esc close)",
+            self.message_index + 1,
+            message_count,
+            self.cursor + 1,
+            bytes.len()
+        );
+        let block = palette.block().title(title);
+        let inner = block.inner(area);
+
+        let interpretations = interpretations(bytes, self.cursor);
+        // Header row, a spacer and the interpretations take fixed room; the rest lists bytes.
+        let reserved = 2 + interpretations.len();
+        let visible_rows = (inner.height as usize).saturating_sub(reserved).max(1);
+        let first_row = self.cursor.saturating_sub(visible_rows - 1);
+
+        let mut lines = vec![Line::styled(
+            BYTE_ROW_HEADER,
+            Style::default().add_modifier(Modifier::BOLD),
+        )];
+        for (index, byte) in bytes.iter().enumerate().skip(first_row).take(visible_rows) {
+            let row = format_byte_row(index, *byte);
+            if index == self.cursor {
+                lines.push(Line::styled(row, palette.cursor));
+            } else {
+                lines.push(Line::from(row));
+            }
+        }
+        lines.push(Line::default());
+        for (label, value) in interpretations {
+            lines.push(Line::from(format!("{label:<8} {value}")));
+        }
+
+        frame.render_widget(Clear, area);
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+}
+
+const BYTE_ROW_HEADER: &str = "  idx  hex  dec  binary    ascii";
+
+/// Render one byte as `idx  hex  dec  binary  ascii`, with non-printable bytes shown as `.`.
+pub(super) fn format_byte_row(index: usize, byte: u8) -> String {
+    let ascii = if byte.is_ascii_graphic() || byte == b' ' {
+        byte as char
+    } else {
+        '.'
+    };
+    format!("{index:>5}  0x{byte:02X} {byte:>4}  {byte:08b}  {ascii}")
+}
+
+/// Decode the bytes starting at `offset` as the common signed/multi-byte types.
+/// Interpretations that would read past the end of the message are omitted.
+pub(super) fn interpretations(bytes: &[u8], offset: usize) -> Vec<(&'static str, String)> {
+    let Some(tail) = bytes.get(offset..) else {
+        return Vec::new();
+    };
+
+    let mut values = Vec::new();
+    if let Some(&byte) = tail.first() {
+        values.push(("i8", (byte as i8).to_string()));
+    }
+    if let Some(pair) = tail.first_chunk::<2>() {
+        values.push(("u16 BE", u16::from_be_bytes(*pair).to_string()));
+        values.push(("u16 LE", u16::from_le_bytes(*pair).to_string()));
+        values.push(("i16 BE", i16::from_be_bytes(*pair).to_string()));
+        values.push(("i16 LE", i16::from_le_bytes(*pair).to_string()));
+    }
+    if let Some(quad) = tail.first_chunk::<4>() {
+        values.push(("u32 BE", u32::from_be_bytes(*quad).to_string()));
+        values.push(("u32 LE", u32::from_le_bytes(*quad).to_string()));
+    }
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn byte_row_shows_every_base() {
+        assert_eq!(format_byte_row(0, b'A'), "    0  0x41   65  01000001  A");
+        assert_eq!(format_byte_row(12, 0xFF), "   12  0xFF  255  11111111  .");
+        assert_eq!(format_byte_row(3, b'\n'), "    3  0x0A   10  00001010  .");
+    }
+
+    #[test]
+    fn interpretations_decode_from_cursor() {
+        let bytes = [0x00, 0xFF, 0x80, 0x01, 0x02];
+        let values = interpretations(&bytes, 1);
+        let lookup = |label| {
+            values
+                .iter()
+                .find(|(name, _)| *name == label)
+                .map(|(_, value)| value.as_str())
+        };
+        assert_eq!(lookup("i8"), Some("-1"));
+        assert_eq!(lookup("u16 BE"), Some("65408"));
+        assert_eq!(lookup("u16 LE"), Some("33023"));
+        assert_eq!(lookup("i16 BE"), Some("-128"));
+        assert_eq!(lookup("u32 BE"), Some("4286578946"));
+    }
+
+    #[test]
+    fn interpretations_skip_types_past_the_end() {
+        let values = interpretations(&[0x12, 0x34], 1);
+        assert_eq!(values, vec![("i8", "52".to_string())]);
+        assert!(interpretations(&[0x12], 4).is_empty());
+    }
+
+    #[test]
+    fn cursor_stays_within_message() {
+        let mut inspector = ByteInspector::new(0);
+        inspector.move_left();
+        assert_eq!(inspector.cursor, 0);
+        inspector.move_right(2);
+        inspector.move_right(2);
+        assert_eq!(inspector.cursor, 1);
+        inspector.clamp_cursor(1);
+        assert_eq!(inspector.cursor, 0);
+    }
+}
@@ -0,0 +1,92 @@
+//! Encoded length of the command being typed, against what the device accepts.
+//!
+//! The firmware rejects a command longer than it can run whole, so a long paste is flagged on the
+//! Command Input border while it is still being edited instead of failing once sent. Anything over
+//! a frame is sent as chunks, so the limit is the reassembled one, or less for a command such as
+//! `echo` that has to come back in one response (see [`max_command_len`]).
+//! The line is only encoded again when it changes, not on every frame drawn.
+
+use protocol::host::{encode_command, max_command_len};
+
+/// Share of the limit, in percent, from which the border shows the length against it.
+const SHOW_LENGTH_FROM_PERCENT: usize = 75;
+
+/// Remembers the encoded length and limit of the last line measured.
+#[derive(Debug, Default)]
+pub(super) struct CommandLength {
+    line: String,
+    encoded: Option<(usize, usize)>,
+}
+
+impl CommandLength {
+    /// Encoded length of `line` and the longest command of its kind the device runs, or `None`
+    /// while it doesn't encode to a command.
+    pub fn measure(&mut self, line: &str) -> Option<(usize, usize)> {
+        if self.line != line {
+            self.line.clear();
+            self.line.push_str(line);
+            self.encoded = encode_command(line)
+                .ok()
+                .map(|payload| (payload.len(), max_command_len(&payload)));
+        }
+        self.encoded
+    }
+}
+
+/// Border note for a command of `len` encoded bytes against its `limit`, and whether it is over.
+/// Nothing while well under it.
+pub(super) fn length_note((len, limit): (usize, usize)) -> Option<(String, bool)> {
+    if len > limit {
+        Some((format!(" • too long: {len}/{limit} bytes"), true))
+    } else if len * 100 >= limit * SHOW_LENGTH_FROM_PERCENT {
+        Some((format!(" • {len}/{limit} bytes"), false))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use protocol::{MAX_ECHO_LEN, chunk::MAX_CHUNKED_COMMAND_LEN};
+
+    use super::*;
+
+    #[test]
+    fn warns_only_past_the_device_limit() {
+        assert_eq!(length_note((383, 512)), None);
+        assert_eq!(
+            length_note((384, 512)),
+            Some((" • 384/512 bytes".into(), false))
+        );
+        assert_eq!(
+            length_note((512, 512)),
+            Some((" • 512/512 bytes".into(), false))
+        );
+        assert_eq!(
+            length_note((513, 512)),
+            Some((" • too long: 513/512 bytes".into(), true))
+        );
+    }
+
+    #[test]
+    fn measures_the_encoded_command_against_its_kind() {
+        let mut length = CommandLength::default();
+        // Method, operation, then the echoed bytes.
+        assert_eq!(
+            length.measure("echo hello"),
+            Some((2 + 5, 2 + MAX_ECHO_LEN))
+        );
+        let pasted = format!("echo {}", "x".repeat(MAX_CHUNKED_COMMAND_LEN));
+        assert_eq!(
+            length.measure(&pasted),
+            Some((2 + MAX_CHUNKED_COMMAND_LEN, 2 + MAX_ECHO_LEN))
+        );
+        assert_eq!(
+            length
+                .measure("i2c write 0x50 0x00 0x01")
+                .map(|(_, limit)| limit),
+            Some(MAX_CHUNKED_COMMAND_LEN)
+        );
+        assert_eq!(length.measure("i2c read"), None);
+    }
+}
@@ -0,0 +1,143 @@
+//! Link liveness shown next to the session header.
+//!
+//! Health is derived from how long ago the device last sent anything. The indicator pairs a
+//! distinct glyph with each colour so the state reads without relying on colour alone.
+
+use std::time::{Duration, Instant};
+
+use ratatui::{style::Style, text::Span};
+
+use crate::theme::Palette;
+
+/// Silence longer than this marks the link as idle.
+pub const LINK_IDLE_AFTER: Duration = Duration::from_secs(5);
+/// Silence longer than this marks the link as stale.
+pub const LINK_STALE_AFTER: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum LinkHealth {
+    Live,
+    Idle,
+    Stale,
+}
+
+impl LinkHealth {
+    pub fn from_age(age: Duration) -> Self {
+        if age >= LINK_STALE_AFTER {
+            LinkHealth::Stale
+        } else if age >= LINK_IDLE_AFTER {
+            LinkHealth::Idle
+        } else {
+            LinkHealth::Live
+        }
+    }
+
+    fn symbol(&self) -> &'static str {
+        match self {
+            LinkHealth::Live => "●",
+            LinkHealth::Idle => "◐",
+            LinkHealth::Stale => "✖",
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            LinkHealth::Live => "live",
+            LinkHealth::Idle => "idle",
+            LinkHealth::Stale => "stale",
+        }
+    }
+
+    fn style(&self, palette: &Palette) -> Style {
+        match self {
+            LinkHealth::Live => palette.success,
+            LinkHealth::Idle => palette.warning,
+            LinkHealth::Stale => palette.error,
+        }
+    }
+}
+
+/// Time of the last received data and the health it currently maps to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(super) struct Liveness {
+    last_activity: Option<Instant>,
+    health: Option<LinkHealth>,
+}
+
+impl Liveness {
+    /// Record traffic from the device.
+    pub fn touch(&mut self, now: Instant) {
+        self.last_activity = Some(now);
+        self.health = Some(LinkHealth::Live);
+    }
+
+    /// Forget the link, e.g. after leaving the session screen.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Re-evaluate health against the current time. Called on every tick.
+    pub fn refresh(&mut self, now: Instant) {
+        self.health = self
+            .last_activity
+            .map(|last| LinkHealth::from_age(now.saturating_duration_since(last)));
+    }
+
+    pub fn span(&self, now: Instant, palette: &Palette) -> Span<'static> {
+        match (self.health, self.last_activity) {
+            (Some(health), Some(last)) => {
+                let age = now.saturating_duration_since(last).as_secs();
+                Span::styled(
+                    format!("{} {} ({age}s)", health.symbol(), health.label()),
+                    health.style(palette),
+                )
+            }
+            _ => Span::styled("○ no link", palette.muted),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn health_follows_age_thresholds() {
+        assert_eq!(LinkHealth::from_age(Duration::ZERO), LinkHealth::Live);
+        assert_eq!(
+            LinkHealth::from_age(LINK_IDLE_AFTER - Duration::from_millis(1)),
+            LinkHealth::Live
+        );
+        assert_eq!(LinkHealth::from_age(LINK_IDLE_AFTER), LinkHealth::Idle);
+        assert_eq!(
+            LinkHealth::from_age(LINK_STALE_AFTER - Duration::from_millis(1)),
+            LinkHealth::Idle
+        );
+        assert_eq!(LinkHealth::from_age(LINK_STALE_AFTER), LinkHealth::Stale);
+    }
+
+    #[test]
+    fn refresh_degrades_until_touched() {
+        let start = Instant::now();
+        let mut liveness = Liveness::default();
+        liveness.refresh(start);
+        assert_eq!(liveness.health, None);
+
+        liveness.touch(start);
+        liveness.refresh(start + LINK_STALE_AFTER);
+        assert_eq!(liveness.health, Some(LinkHealth::Stale));
+
+        liveness.touch(start + LINK_STALE_AFTER);
+        assert_eq!(liveness.health, Some(LinkHealth::Live));
+    }
+
+    #[test]
+    fn states_use_distinct_symbols() {
+        let states = [LinkHealth::Live, LinkHealth::Idle, LinkHealth::Stale];
+        for (idx, a) in states.iter().enumerate() {
+            for b in &states[idx + 1..] {
+                assert_ne!(a.symbol(), b.symbol());
+            }
+        }
+    }
+}
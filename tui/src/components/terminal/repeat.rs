@@ -0,0 +1,76 @@
+//! Auto-repeat of the most recent command.
+//!
+//! Repeats are driven from `Action::Tick`, so at most one command goes out per tick whatever the
+//! interval, and a late tick never triggers a catch-up burst.
+
+use std::time::{Duration, Instant};
+
+/// Shortest interval auto-repeat will use, regardless of configuration.
+pub const MIN_REPEAT_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct AutoRepeat {
+    command: String,
+    interval: Duration,
+    next_due: Instant,
+}
+
+impl AutoRepeat {
+    /// Start repeating `command`; the first repeat is one interval from `now`.
+    pub fn new(command: String, interval: Duration, now: Instant) -> Self {
+        let interval = interval.max(MIN_REPEAT_INTERVAL);
+        Self {
+            command,
+            interval,
+            next_due: now + interval,
+        }
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Return the command if a repeat is due, scheduling the next one an interval from `now`.
+    pub fn poll(&mut self, now: Instant) -> Option<&str> {
+        if now < self.next_due {
+            return None;
+        }
+        self.next_due = now + self.interval;
+        Some(&self.command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fires_once_per_interval() {
+        let start = Instant::now();
+        let interval = Duration::from_millis(500);
+        let mut repeat = AutoRepeat::new("i2c read 0x48 0x00 2".into(), interval, start);
+
+        assert_eq!(repeat.poll(start), None);
+        assert_eq!(repeat.poll(start + interval), Some("i2c read 0x48 0x00 2"));
+        assert_eq!(repeat.poll(start + interval), None);
+        assert!(repeat.poll(start + interval * 2).is_some());
+    }
+
+    #[test]
+    fn late_poll_does_not_burst() {
+        let start = Instant::now();
+        let interval = Duration::from_millis(200);
+        let mut repeat = AutoRepeat::new("echo hi".into(), interval, start);
+
+        let late = start + interval * 10;
+        assert!(repeat.poll(late).is_some());
+        assert_eq!(repeat.poll(late), None);
+        assert!(repeat.poll(late + interval).is_some());
+    }
+
+    #[test]
+    fn interval_is_clamped_to_minimum() {
+        let repeat = AutoRepeat::new("echo hi".into(), Duration::ZERO, Instant::now());
+        assert_eq!(repeat.interval(), MIN_REPEAT_INTERVAL);
+    }
+}
@@ -0,0 +1,178 @@
+//! Scroll position for the message pane.
+//!
+//! Messages render newest first, so `offset` counts how many of the newest messages are scrolled
+//! out of view. The view either follows incoming data, staying on the newest message, or is locked
+//! on the messages it shows while new ones pile up above. Scrolling back locks it, and scrolling all
+//! the way forward again follows.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(super) struct Scrollback {
+    offset: usize,
+    viewport: usize,
+    locked: bool,
+}
+
+impl Scrollback {
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn is_pinned(&self) -> bool {
+        self.offset == 0
+    }
+
+    /// Whether new messages will move the view.
+    pub fn is_following(&self) -> bool {
+        !self.locked
+    }
+
+    /// Scroll towards older messages.
+    pub fn scroll_back(&mut self, rows: usize, total: usize) {
+        self.offset = (self.offset + rows).min(self.max_offset(total));
+        if self.offset > 0 {
+            self.locked = true;
+        }
+    }
+
+    /// Scroll towards newer messages. Reaching the newest message follows new data again.
+    pub fn scroll_forward(&mut self, rows: usize) {
+        self.offset = self.offset.saturating_sub(rows);
+        if self.offset == 0 {
+            self.locked = false;
+        }
+    }
+
+    /// Return to the newest message and follow new data.
+    pub fn pin(&mut self) {
+        self.offset = 0;
+        self.locked = false;
+    }
+
+    /// Lock the view where it is, or return to following if it was locked.
+    pub fn toggle_lock(&mut self) {
+        if self.locked {
+            self.pin();
+        } else {
+            self.locked = true;
+        }
+    }
+
+    /// Rows to move for a page scroll, keeping one row of context.
+    pub fn page(&self) -> usize {
+        self.viewport.saturating_sub(1).max(1)
+    }
+
+    /// Record a new viewport height and pull the offset back into range for it.
+    pub fn resize(&mut self, viewport: usize, total: usize) {
+        self.viewport = viewport;
+        self.clamp(total);
+    }
+
+    /// Keep a locked view on the same messages when a new one arrives at the top.
+    pub fn message_added(&mut self, total: usize) {
+        if self.locked {
+            self.offset += 1;
+        }
+        self.clamp(total);
+    }
+
+    pub fn clamp(&mut self, total: usize) {
+        self.offset = self.offset.min(self.max_offset(total));
+    }
+
+    fn max_offset(&self, total: usize) -> usize {
+        total.saturating_sub(self.viewport.max(1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shrink_then_grow_keeps_offset_in_range() {
+        let total = 50;
+        let mut scroll = Scrollback::default();
+        scroll.resize(10, total);
+        scroll.scroll_back(100, total);
+        assert_eq!(scroll.offset(), 40);
+
+        scroll.resize(4, total);
+        assert_eq!(scroll.offset(), 40);
+
+        scroll.resize(30, total);
+        assert_eq!(scroll.offset(), 20);
+
+        scroll.resize(80, total);
+        assert_eq!(scroll.offset(), 0);
+        assert!(scroll.is_pinned());
+    }
+
+    #[test]
+    fn pinned_view_stays_pinned_across_resizes() {
+        let total = 25;
+        let mut scroll = Scrollback::default();
+        scroll.resize(10, total);
+        scroll.resize(2, total);
+        scroll.resize(40, total);
+        scroll.message_added(total + 1);
+        assert!(scroll.is_pinned());
+    }
+
+    #[test]
+    fn unpinned_view_tracks_new_messages() {
+        let mut scroll = Scrollback::default();
+        scroll.resize(5, 20);
+        scroll.scroll_back(3, 20);
+        scroll.message_added(21);
+        assert_eq!(scroll.offset(), 4);
+        scroll.scroll_back(100, 21);
+        scroll.message_added(21);
+        assert_eq!(scroll.offset(), 16);
+        scroll.scroll_forward(100);
+        assert!(scroll.is_pinned());
+    }
+
+    #[test]
+    fn lock_holds_the_view_until_scrolled_to_the_bottom() {
+        let mut scroll = Scrollback::default();
+        scroll.resize(5, 20);
+        assert!(scroll.is_following());
+        scroll.message_added(21);
+        assert!(scroll.is_pinned());
+
+        scroll.toggle_lock();
+        assert!(!scroll.is_following());
+        scroll.message_added(22);
+        scroll.message_added(23);
+        assert_eq!(scroll.offset(), 2);
+
+        scroll.scroll_forward(1);
+        assert!(!scroll.is_following());
+        scroll.message_added(24);
+        assert_eq!(scroll.offset(), 2);
+
+        scroll.scroll_forward(2);
+        assert!(scroll.is_following());
+        scroll.message_added(25);
+        assert!(scroll.is_pinned());
+    }
+
+    #[test]
+    fn scrolling_back_locks_and_toggling_follows() {
+        let mut scroll = Scrollback::default();
+        scroll.resize(5, 20);
+        scroll.scroll_back(3, 20);
+        assert!(!scroll.is_following());
+
+        scroll.toggle_lock();
+        assert!(scroll.is_following());
+        assert!(scroll.is_pinned());
+
+        // Nothing to scroll back to yet, so the view keeps following.
+        let mut scroll = Scrollback::default();
+        scroll.resize(5, 3);
+        scroll.scroll_back(3, 3);
+        assert!(scroll.is_following());
+    }
+}
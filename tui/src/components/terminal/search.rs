@@ -0,0 +1,121 @@
+//! Highlighting what the message search finds.
+//!
+//! Every line stays in view; each occurrence of the query in it is drawn in the palette's
+//! search-match style over the line's own style. A query without capitals ignores the case of
+//! ASCII letters and one with a capital matches exactly, like smart case in an editor. Folding
+//! only ASCII letters keeps byte offsets the same in the line and its folded copy, so a match
+//! never starts or ends inside a multi-byte character.
+
+use std::{borrow::Cow, ops::Range};
+
+use ratatui::{style::Style, text::Span};
+
+/// Byte ranges of the occurrences of `query` in `text`, left to right and not overlapping.
+pub(super) fn find_matches(text: &str, query: &str) -> Vec<Range<usize>> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let (haystack, needle) = if query.chars().any(char::is_uppercase) {
+        (Cow::Borrowed(text), Cow::Borrowed(query))
+    } else {
+        (
+            Cow::Owned(text.to_ascii_lowercase()),
+            Cow::Owned(query.to_ascii_lowercase()),
+        )
+    };
+    haystack
+        .match_indices(needle.as_ref())
+        .map(|(start, found)| start..start + found.len())
+        .collect()
+}
+
+/// `text` split into spans around the occurrences of `query`, the matches in `base` patched with
+/// `matched` and the rest in `base`.
+pub(super) fn highlight_matches(
+    text: &str,
+    query: &str,
+    base: Style,
+    matched: Style,
+) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    let mut end = 0;
+    for range in find_matches(text, query) {
+        if range.start > end {
+            spans.push(Span::styled(text[end..range.start].to_owned(), base));
+        }
+        end = range.end;
+        spans.push(Span::styled(text[range].to_owned(), base.patch(matched)));
+    }
+    if end < text.len() || spans.is_empty() {
+        spans.push(Span::styled(text[end..].to_owned(), base));
+    }
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::style::{Color, Modifier};
+
+    use super::*;
+
+    const MATCHED: Style = Style::new().add_modifier(Modifier::REVERSED);
+
+    fn split(text: &str, query: &str) -> Vec<(String, bool)> {
+        highlight_matches(text, query, Style::new().fg(Color::Red), MATCHED)
+            .into_iter()
+            .map(|span| {
+                assert_eq!(span.style.fg, Some(Color::Red));
+                let reversed = span.style.add_modifier.contains(Modifier::REVERSED);
+                (span.content.into_owned(), reversed)
+            })
+            .collect()
+    }
+
+    fn owned(spans: &[(&str, bool)]) -> Vec<(String, bool)> {
+        spans
+            .iter()
+            .map(|&(text, matched)| (text.to_owned(), matched))
+            .collect()
+    }
+
+    #[test]
+    fn every_match_in_a_line_is_split_out() {
+        assert_eq!(
+            split("ack 0x48 ack 0x50 ack", "ack"),
+            owned(&[
+                ("ack", true),
+                (" 0x48 ", false),
+                ("ack", true),
+                (" 0x50 ", false),
+                ("ack", true),
+            ])
+        );
+        assert_eq!(split("aaaa", "aa"), owned(&[("aa", true), ("aa", true)]));
+        assert_eq!(split("no match", "xyz"), owned(&[("no match", false)]));
+        assert_eq!(split("anything", ""), owned(&[("anything", false)]));
+        assert_eq!(split("", "x"), owned(&[("", false)]));
+    }
+
+    #[test]
+    fn multi_byte_characters_stay_whole() {
+        assert_eq!(
+            split("23.1 °C then 24.0 °C", "°C"),
+            owned(&[
+                ("23.1 ", false),
+                ("°C", true),
+                (" then 24.0 ", false),
+                ("°C", true),
+            ])
+        );
+        assert_eq!(
+            split("ᓚᘏᗢ Straße ᓚᘏᗢ", "straße"),
+            owned(&[("ᓚᘏᗢ ", false), ("Straße", true), (" ᓚᘏᗢ", false)])
+        );
+    }
+
+    #[test]
+    fn capitals_in_the_query_make_it_match_case() {
+        assert_eq!(find_matches("Error: error", "error"), vec![0..5, 7..12]);
+        assert_eq!(find_matches("Error: error", "Error"), vec![0..5]);
+    }
+}
@@ -7,32 +7,168 @@
 //! modules currently display. As we add real CLI options (e.g. default port,
 //! preferred baud rate, theme), wire them into `Config::from_cli`.
 
-use std::{env, path::PathBuf};
+use std::{env, fs, io, path::PathBuf, time::Duration};
 
-#[derive(Clone, Debug, Default)]
+use color_eyre::{Result, eyre::eyre};
+use protocol::{HANDSHAKE_TIMEOUT, transport::Framing};
+
+use crate::{
+    cli::Cli, components::terminal::ByteStyle, favorites::Favorites, keymap::Keymap,
+    labels::Labels, last_connection::LastConnection, theme::Theme,
+};
+
+/// Interval between auto-repeated commands when none is configured.
+pub const DEFAULT_REPEAT_INTERVAL: Duration = Duration::from_millis(1000);
+/// Bytes per write when pacing is on and no chunk size is configured.
+pub const DEFAULT_WRITE_CHUNK: usize = 16;
+/// How long a partial frame may go without new bytes before it is reported and dropped.
+pub const DEFAULT_STALL_TIMEOUT: Duration = Duration::from_millis(2000);
+/// What Backspace sends in keystroke mode unless configured otherwise: DEL, as most terminals do.
+pub const DEFAULT_BACKSPACE: u8 = 0x7F;
+
+/// Throttling for serial writes, for receivers that drop bytes sent back to back.
+///
+/// With a delay set, each write is split into `chunk_size` pieces with the delay between them.
+/// Smaller chunks are gentler on the receiver, but every chunk costs a write call and a delay, so
+/// a 64-byte frame sent 8 bytes at a time with 5 ms pauses takes at least 35 ms to go out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WritePacing {
+    pub chunk_size: usize,
+    pub delay: Duration,
+}
+
+impl Default for WritePacing {
+    fn default() -> Self {
+        Self {
+            chunk_size: DEFAULT_WRITE_CHUNK,
+            delay: Duration::ZERO,
+        }
+    }
+}
+
+impl WritePacing {
+    /// Whether writes are split up at all.
+    pub fn is_paced(&self) -> bool {
+        !self.delay.is_zero()
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Config {
-    // Example future fields:
-    // pub default_port: Option<String>,
-    // pub default_baud: Option<u32>,
-    // pub theme: Theme,
+    /// How often auto-repeat re-sends the last command.
+    pub repeat_interval: Duration,
+    /// Frame layout requested in the handshake and used for the whole session.
+    pub framing: Framing,
+    /// Whether sessions start in dry-run mode, where commands are encoded but never sent.
+    pub dry_run: bool,
+    /// Whether each sent command is followed by the command its bytes decode back to.
+    pub verbose: bool,
+    /// Whether each read is followed by the boundaries of the frames decoded from it.
+    pub show_frames: bool,
+    /// Prefix the device puts on echo replies, hidden from them in the UTF-8 view.
+    pub echo_prefix: Option<String>,
+    /// Whether responses arrive tagged with the method that produced them.
+    pub tagged_responses: bool,
+    /// Throttling applied to every serial write, bridged data included.
+    pub write_pacing: WritePacing,
+    /// How long to wait for the handshake reply; never shorter than the device's own timeout.
+    pub handshake_timeout: Duration,
+    /// How long a partial frame may sit without new bytes before it is reported and dropped.
+    pub stall_timeout: Duration,
+    /// Longest payload a received frame may claim; `None` for the device's longest response.
+    pub max_frame: Option<usize>,
+    /// Whether the preconnect screen offers the simulated device instead of serial ports.
+    pub simulate: bool,
+    /// `HOST:PORT` of a networked serial server to list alongside the local ports.
+    pub tcp: Option<String>,
+    /// Layout of bytes in the hex and binary views.
+    pub byte_style: ByteStyle,
+    /// Byte sent for Backspace in keystroke mode.
+    pub backspace: u8,
+    /// Keys for the single-key commands, loaded from the config directory.
+    pub keymap: Keymap,
+    /// Commands on F1 to F8, loaded from the config directory.
+    pub favorites: Favorites,
+    /// Prompt and pane titles, loaded from the config directory.
+    pub labels: Labels,
+    /// Port and baud rate the preconnect screen starts on, from the last successful connection.
+    pub last_connection: Option<LastConnection>,
+    /// Colours of every screen.
+    pub theme: Theme,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            repeat_interval: DEFAULT_REPEAT_INTERVAL,
+            framing: Framing::default(),
+            dry_run: false,
+            verbose: false,
+            show_frames: false,
+            echo_prefix: None,
+            tagged_responses: false,
+            write_pacing: WritePacing::default(),
+            handshake_timeout: HANDSHAKE_TIMEOUT,
+            stall_timeout: DEFAULT_STALL_TIMEOUT,
+            max_frame: None,
+            simulate: false,
+            tcp: None,
+            byte_style: ByteStyle::default(),
+            backspace: DEFAULT_BACKSPACE,
+            keymap: Keymap::default(),
+            favorites: Favorites::default(),
+            labels: Labels::default(),
+            last_connection: None,
+            theme: Theme::default(),
+        }
+    }
 }
 
 impl Config {
     /// Construct a placeholder configuration.
-    ///
-    /// ```ignore
-    /// // Pseudo-code for upcoming CLI integration:
-    /// pub fn from_cli(args: &Cli) -> Self {
-    ///     let mut cfg = Config::default();
-    ///     cfg.default_port = args.port.clone();
-    ///     cfg.default_baud = args.baud_rate;
-    ///     cfg
-    /// }
-    /// ```
     #[allow(unused)]
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Build the configuration from command-line arguments.
+    pub fn from_cli(args: &Cli) -> Self {
+        Self {
+            repeat_interval: repeat_interval(args.repeat_interval_ms, args.tick_rate),
+            framing: args.framing,
+            dry_run: args.dry_run,
+            verbose: args.verbose,
+            show_frames: args.show_frames,
+            echo_prefix: args
+                .strip_echo_prefix
+                .clone()
+                .filter(|prefix| !prefix.is_empty()),
+            tagged_responses: args.tag_responses,
+            write_pacing: WritePacing {
+                chunk_size: args.write_chunk.max(1),
+                delay: Duration::from_millis(args.write_delay_ms),
+            },
+            handshake_timeout: Duration::from_millis(args.handshake_timeout),
+            stall_timeout: Duration::from_millis(args.stall_timeout_ms),
+            max_frame: args.max_frame.map(usize::from),
+            simulate: args.simulate,
+            tcp: args.tcp.clone(),
+            byte_style: args.byte_style,
+            backspace: args.backspace,
+            keymap: Keymap::default(),
+            favorites: Favorites::default(),
+            labels: Labels::default(),
+            last_connection: None,
+            theme: args.theme,
+        }
+    }
+}
+
+/// Auto-repeat interval for `ms`, raised to one tick: repeats are polled on `Action::Tick`, so a
+/// shorter interval would be shown but not kept to.
+fn repeat_interval(ms: u64, tick_rate: f64) -> Duration {
+    let tick = Duration::try_from_secs_f64(tick_rate.recip()).unwrap_or_default();
+    Duration::from_millis(ms).max(tick)
 }
 
 /// Return the directory used for local data (logs, session caches, etc.).
@@ -49,6 +185,17 @@ pub fn get_config_dir() -> PathBuf {
         .unwrap_or_else(default_config_dir)
 }
 
+/// Read the file `name` from the config directory and `parse` it. A missing file means the
+/// defaults; a parse error is reported against the file's path.
+pub fn load_file<T: Default>(name: &str, parse: fn(&str) -> Result<T, String>) -> Result<T> {
+    let path = get_config_dir().join(name);
+    match fs::read_to_string(&path) {
+        Ok(text) => parse(&text).map_err(|message| eyre!("{}: {message}", path.display())),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(T::default()),
+        Err(err) => Err(err.into()),
+    }
+}
+
 fn project_directory() -> Option<directories::ProjectDirs> {
     directories::ProjectDirs::from("com", "kdheepak", env!("CARGO_PKG_NAME"))
 }
@@ -64,3 +211,42 @@ fn default_config_dir() -> PathBuf {
         .unwrap_or_else(|_| PathBuf::from("."))
         .join(".config")
 }
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    #[test]
+    fn handshake_timeout_never_drops_below_the_device_default() {
+        let err = Cli::try_parse_from(["siterm", "--handshake-timeout", "500"]).unwrap_err();
+        assert!(err.to_string().contains("must be at least 3000"), "{err}");
+        let cli = Cli::parse_from(["siterm"]);
+        assert_eq!(Config::from_cli(&cli).handshake_timeout, HANDSHAKE_TIMEOUT);
+        let cli = Cli::parse_from(["siterm", "--handshake-timeout", "10000"]);
+        assert_eq!(
+            Config::from_cli(&cli).handshake_timeout,
+            Duration::from_secs(10)
+        );
+    }
+
+    #[test]
+    fn repeat_interval_is_never_shorter_than_a_tick() {
+        let cli = Cli::parse_from(["siterm", "--repeat-interval-ms", "100"]);
+        assert_eq!(
+            Config::from_cli(&cli).repeat_interval,
+            Duration::from_millis(250)
+        );
+        let cli = Cli::parse_from(["siterm", "--repeat-interval-ms", "100", "--tick-rate", "10"]);
+        assert_eq!(
+            Config::from_cli(&cli).repeat_interval,
+            Duration::from_millis(100)
+        );
+        let cli = Cli::parse_from(["siterm", "--repeat-interval-ms", "400"]);
+        assert_eq!(
+            Config::from_cli(&cli).repeat_interval,
+            Duration::from_millis(400)
+        );
+    }
+}
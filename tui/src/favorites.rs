@@ -0,0 +1,158 @@
+//! Favorite commands sent with a function key.
+//!
+//! Favorites are read from [`FAVORITES_FILE`] in the config directory, one `Fn = command` per line
+//! for F1 to F8, with blank lines and `#` comments ignored:
+//!
+//! ```text
+//! F1 = temp
+//! F2 = i2c read 0x48 0x00 2 as i16be
+//! ```
+//!
+//! Pressing the key in normal mode sends the command as if it had been typed, aliases included.
+//! The set favorites are listed along the bottom of the message pane.
+
+use color_eyre::Result;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use protocol::host::strip_comment;
+
+use crate::{config, keymap::Keymap};
+
+pub const FAVORITES_FILE: &str = "favorites.siterm";
+/// Function keys that can hold a favorite, F1 up to this one.
+pub const FAVORITE_KEYS: u8 = 8;
+/// Characters of a command shown in the bar before it is cut short.
+const BAR_COMMAND_WIDTH: usize = 16;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Favorites {
+    commands: [Option<String>; FAVORITE_KEYS as usize],
+}
+
+impl Favorites {
+    /// Parse favorite definitions. The error names the offending line.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut favorites = Self::default();
+        for (idx, line) in text.lines().enumerate() {
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            let number = idx + 1;
+            let Some((key, command)) = line.split_once('=') else {
+                return Err(format!("line {number}: expected `F1 = command`"));
+            };
+            let (key, command) = (key.trim(), command.trim());
+            let Some(slot) = parse_key(key) else {
+                return Err(format!(
+                    "line {number}: favorites go on F1 to F{FAVORITE_KEYS}, got `{key}`"
+                ));
+            };
+            if command.is_empty() {
+                return Err(format!("line {number}: {key} has no command"));
+            }
+            let entry = &mut favorites.commands[usize::from(slot - 1)];
+            if entry.is_some() {
+                return Err(format!("line {number}: {key} is already set"));
+            }
+            *entry = Some(command.to_owned());
+        }
+        Ok(favorites)
+    }
+
+    /// Read the favorites file from the config directory. A missing file means no favorites.
+    pub fn load() -> Result<Self> {
+        config::load_file(FAVORITES_FILE, Self::parse)
+    }
+
+    /// One warning per set favorite whose key `keymap` gives to an action, which runs instead.
+    pub fn shadowed_by(&self, keymap: &Keymap) -> Vec<String> {
+        (1..=FAVORITE_KEYS)
+            .filter(|&key| self.command(key).is_some())
+            .filter_map(|key| {
+                let action = keymap.action(&KeyEvent::new(KeyCode::F(key), KeyModifiers::NONE))?;
+                Some(format!(
+                    "Warning: F{key} is bound to {}; its favorite is never sent",
+                    action.name()
+                ))
+            })
+            .collect()
+    }
+
+    /// The command on function key `key`, if one is set.
+    pub fn command(&self, key: u8) -> Option<&str> {
+        let slot = usize::from(key.checked_sub(1)?);
+        self.commands.get(slot)?.as_deref()
+    }
+
+    /// One label per set favorite for the bar, e.g. `F2 i2c read 0x48 0…`.
+    pub fn bar_labels(&self) -> Vec<String> {
+        (1..=FAVORITE_KEYS)
+            .filter_map(|key| {
+                let command = self.command(key)?;
+                let label = if command.chars().count() > BAR_COMMAND_WIDTH {
+                    let cut: String = command.chars().take(BAR_COMMAND_WIDTH - 1).collect();
+                    format!("{}…", cut.trim_end())
+                } else {
+                    command.to_owned()
+                };
+                Some(format!("F{key} {label}"))
+            })
+            .collect()
+    }
+}
+
+/// `F1` to `F8`, in any case, as a key number.
+fn parse_key(key: &str) -> Option<u8> {
+    let number = key.strip_prefix(['F', 'f'])?.parse().ok()?;
+    (1..=FAVORITE_KEYS).contains(&number).then_some(number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn favorites_under_a_bound_key_are_reported() {
+        let favorites = Favorites::parse("F2 = temp\nF3 = version").unwrap();
+        let keymap = Keymap::parse("help = f2\ninspect = f4").unwrap();
+        assert_eq!(
+            favorites.shadowed_by(&keymap),
+            ["Warning: F2 is bound to help; its favorite is never sent"]
+        );
+        assert!(favorites.shadowed_by(&Keymap::default()).is_empty());
+    }
+
+    #[test]
+    fn favorites_are_read_per_function_key() {
+        let favorites =
+            Favorites::parse("# quick sends\nF1 = temp\n\nf3 = i2c read 0x48 0x00 2 # sensor\n")
+                .unwrap();
+        assert_eq!(favorites.command(1), Some("temp"));
+        assert_eq!(favorites.command(2), None);
+        assert_eq!(favorites.command(3), Some("i2c read 0x48 0x00 2"));
+        assert_eq!(favorites.command(0), None);
+        assert_eq!(favorites.command(9), None);
+        assert_eq!(favorites.bar_labels(), ["F1 temp", "F3 i2c read 0x48 0…"]);
+    }
+
+    #[test]
+    fn bad_lines_are_reported_with_their_number() {
+        assert_eq!(
+            Favorites::parse("F1 temp"),
+            Err("line 1: expected `F1 = command`".into())
+        );
+        assert_eq!(
+            Favorites::parse("F1 = temp\nF9 = temp"),
+            Err("line 2: favorites go on F1 to F8, got `F9`".into())
+        );
+        assert_eq!(
+            Favorites::parse("F2 =   # nothing"),
+            Err("line 1: F2 has no command".into())
+        );
+        assert_eq!(
+            Favorites::parse("F1 = temp\nF1 = echo hi"),
+            Err("line 2: F1 is already set".into())
+        );
+        assert_eq!(Favorites::parse(""), Ok(Favorites::default()));
+    }
+}
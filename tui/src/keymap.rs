@@ -0,0 +1,529 @@
+//! Key bindings for the single-key commands.
+//!
+//! Bindings are read from [`KEYMAP_FILE`] in the config directory, one `action = key` per line,
+//! with blank lines and `#` comments ignored. Actions not named keep their default key, and
+//! `action = none` leaves one unbound:
+//!
+//! ```text
+//! # Hex view on Ctrl+t
+//! hex_view = ctrl+t
+//! export_history = none
+//! ```
+//!
+//! Keys are a single character or a key name (`enter`, `esc`, `tab`, `space`, `pageup`, `f5`, ...)
+//! after any of `ctrl+`, `alt+` and `shift+`. Navigation and editing keys (arrows, Enter, Esc,
+//! Backspace, PageUp/PageDown) and Ctrl+c/Ctrl+d to quit are fixed. Many terminals send Ctrl+h for
+//! Backspace, so no default uses it and the command editor always treats it as Backspace.
+//!
+//! A key can only trigger one action. When two claim the same key, a binding from the file beats a
+//! default and an earlier line beats a later one; the loser is left unbound and reported as a
+//! warning on the preconnect screen.
+
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    fmt,
+};
+
+use color_eyre::Result;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use protocol::host::strip_comment;
+
+use crate::config;
+
+pub const KEYMAP_FILE: &str = "keymap.siterm";
+/// Key text that leaves an action unbound.
+const UNBOUND: &str = "none";
+
+/// Something a single key press can do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyAction {
+    Quit,
+    Help,
+    RefreshPorts,
+    Edit,
+    Inspect,
+    ExportHistory,
+    RepeatLast,
+    ToggleAutoRepeat,
+    ToggleDryRun,
+    ClearMessages,
+    ClearHistory,
+    Utf8View,
+    HexView,
+    BinaryView,
+    ByteStyle,
+    KeystrokeMode,
+    ScrollLock,
+    Search,
+    Reconnect,
+    Disconnect,
+    ToggleVerbose,
+    ToggleDeltas,
+    ToggleCompact,
+}
+
+impl KeyAction {
+    pub const ALL: [KeyAction; 23] = [
+        KeyAction::Quit,
+        KeyAction::Help,
+        KeyAction::RefreshPorts,
+        KeyAction::Edit,
+        KeyAction::Inspect,
+        KeyAction::ExportHistory,
+        KeyAction::RepeatLast,
+        KeyAction::ToggleAutoRepeat,
+        KeyAction::ToggleDryRun,
+        KeyAction::ClearMessages,
+        KeyAction::ClearHistory,
+        KeyAction::Utf8View,
+        KeyAction::HexView,
+        KeyAction::BinaryView,
+        KeyAction::ByteStyle,
+        KeyAction::KeystrokeMode,
+        KeyAction::ScrollLock,
+        KeyAction::Search,
+        KeyAction::Reconnect,
+        KeyAction::Disconnect,
+        KeyAction::ToggleVerbose,
+        KeyAction::ToggleDeltas,
+        KeyAction::ToggleCompact,
+    ];
+
+    /// Name used in the keymap file.
+    pub fn name(self) -> &'static str {
+        match self {
+            KeyAction::Quit => "quit",
+            KeyAction::Help => "help",
+            KeyAction::RefreshPorts => "refresh_ports",
+            KeyAction::Edit => "edit",
+            KeyAction::Inspect => "inspect",
+            KeyAction::ExportHistory => "export_history",
+            KeyAction::RepeatLast => "repeat_last",
+            KeyAction::ToggleAutoRepeat => "auto_repeat",
+            KeyAction::ToggleDryRun => "dry_run",
+            KeyAction::ClearMessages => "clear_messages",
+            KeyAction::ClearHistory => "clear_history",
+            KeyAction::Utf8View => "utf8_view",
+            KeyAction::HexView => "hex_view",
+            KeyAction::BinaryView => "binary_view",
+            KeyAction::ByteStyle => "byte_style",
+            KeyAction::KeystrokeMode => "keystroke_mode",
+            KeyAction::ScrollLock => "scroll_lock",
+            KeyAction::Search => "search",
+            KeyAction::Reconnect => "reconnect",
+            KeyAction::Disconnect => "disconnect",
+            KeyAction::ToggleVerbose => "verbose",
+            KeyAction::ToggleDeltas => "deltas",
+            KeyAction::ToggleCompact => "compact",
+        }
+    }
+
+    /// What the action does, as listed in the help overlay's key bindings.
+    pub fn description(self) -> &'static str {
+        match self {
+            KeyAction::Quit => "Quit SiTerm",
+            KeyAction::Help => "Show or hide this help",
+            KeyAction::RefreshPorts => "Refresh the serial ports",
+            KeyAction::Edit => "Edit a command",
+            KeyAction::Inspect => "Inspect the bytes of the latest message",
+            KeyAction::ExportHistory => "Save the command history as a script",
+            KeyAction::RepeatLast => "Send the last command again",
+            KeyAction::ToggleAutoRepeat => "Start or stop auto-repeat",
+            KeyAction::ToggleDryRun => "Toggle dry run",
+            KeyAction::ClearMessages => "Clear the device messages",
+            KeyAction::ClearHistory => "Clear the command history",
+            KeyAction::Utf8View => "UTF-8 view",
+            KeyAction::HexView => "Hex view",
+            KeyAction::BinaryView => "Binary view",
+            KeyAction::ByteStyle => "Next hex and binary layout",
+            KeyAction::KeystrokeMode => "Keystroke mode while bridged",
+            KeyAction::ScrollLock => "Lock or unlock the scrollback",
+            KeyAction::Search => "Search the messages",
+            KeyAction::Reconnect => "Reconnect to the port",
+            KeyAction::Disconnect => "Disconnect",
+            KeyAction::ToggleVerbose => "Toggle verbose",
+            KeyAction::ToggleDeltas => "Toggle message timing",
+            KeyAction::ToggleCompact => "Toggle the compact layout",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|action| action.name().eq_ignore_ascii_case(name))
+    }
+
+    fn default_key(self) -> KeyBinding {
+        match self {
+            KeyAction::Quit => KeyBinding::plain('q'),
+            KeyAction::Help => KeyBinding::plain('h'),
+            KeyAction::RefreshPorts => KeyBinding::plain('r'),
+            KeyAction::Edit => KeyBinding::plain('e'),
+            KeyAction::Inspect => KeyBinding::plain('i'),
+            KeyAction::ExportHistory => KeyBinding::plain('s'),
+            KeyAction::RepeatLast => KeyBinding::plain('.'),
+            KeyAction::ToggleAutoRepeat => KeyBinding::plain('R'),
+            KeyAction::ToggleDryRun => KeyBinding::plain('D'),
+            KeyAction::ClearMessages => KeyBinding::ctrl('l'),
+            KeyAction::ClearHistory => KeyBinding::ctrl('k'),
+            KeyAction::Utf8View => KeyBinding::ctrl('u'),
+            KeyAction::HexView => KeyBinding::ctrl('x'),
+            KeyAction::BinaryView => KeyBinding::ctrl('b'),
+            KeyAction::ByteStyle => KeyBinding::plain('B'),
+            KeyAction::KeystrokeMode => KeyBinding::plain('K'),
+            KeyAction::ScrollLock => KeyBinding::plain('l'),
+            KeyAction::Search => KeyBinding::plain('/'),
+            KeyAction::Reconnect => KeyBinding::ctrl('r'),
+            KeyAction::Disconnect => KeyBinding::ctrl('w'),
+            KeyAction::ToggleVerbose => KeyBinding::plain('V'),
+            KeyAction::ToggleDeltas => KeyBinding::plain('t'),
+            KeyAction::ToggleCompact => KeyBinding::plain('c'),
+        }
+    }
+}
+
+/// A key with its modifiers. Shift is folded into the character, so `R` and `shift+r` are the
+/// same binding however the terminal reports them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyBinding {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        match code {
+            KeyCode::Char(c) => Self {
+                code: KeyCode::Char(if modifiers.contains(KeyModifiers::SHIFT) {
+                    c.to_ascii_uppercase()
+                } else {
+                    c
+                }),
+                modifiers: modifiers.difference(KeyModifiers::SHIFT),
+            },
+            _ => Self { code, modifiers },
+        }
+    }
+
+    const fn plain(c: char) -> Self {
+        Self {
+            code: KeyCode::Char(c),
+            modifiers: KeyModifiers::NONE,
+        }
+    }
+
+    const fn ctrl(c: char) -> Self {
+        Self {
+            code: KeyCode::Char(c),
+            modifiers: KeyModifiers::CONTROL,
+        }
+    }
+
+    pub fn from_event(key: &KeyEvent) -> Self {
+        Self::new(key.code, key.modifiers)
+    }
+
+    /// Parse a key such as `q`, `ctrl+h` or `alt+pagedown`.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        let (prefix, key) = match text.rsplit_once('+') {
+            // A trailing `+` is the plus key itself.
+            Some((prefix, "")) => (prefix.strip_suffix('+').unwrap_or(prefix), "+"),
+            Some((prefix, key)) => (prefix, key),
+            None => ("", text),
+        };
+        let mut modifiers = KeyModifiers::NONE;
+        for modifier in prefix.split('+').filter(|part| !part.is_empty()) {
+            modifiers |= match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(format!("unknown modifier `{modifier}` in `{text}`")),
+            };
+        }
+        let mut chars = key.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(if modifiers.contains(KeyModifiers::CONTROL) {
+                c.to_ascii_lowercase()
+            } else {
+                c
+            }),
+            _ => named_key(key).ok_or_else(|| format!("unknown key `{key}` in `{text}`"))?,
+        };
+        Ok(Self::new(code, modifiers))
+    }
+}
+
+fn named_key(name: &str) -> Option<KeyCode> {
+    let name = name.to_ascii_lowercase();
+    let code = match name.as_str() {
+        "space" => KeyCode::Char(' '),
+        "enter" => KeyCode::Enter,
+        "esc" => KeyCode::Esc,
+        "tab" => KeyCode::Tab,
+        "backspace" => KeyCode::Backspace,
+        "delete" => KeyCode::Delete,
+        "insert" => KeyCode::Insert,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        _ => {
+            let number = name.strip_prefix('f')?.parse().ok()?;
+            return (1..=12).contains(&number).then_some(KeyCode::F(number));
+        }
+    };
+    Some(code)
+}
+
+/// Rendered the way the on-screen hints spell keys, e.g. `q`, `Ctrl+h` or `PageUp`.
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            f.write_str("Ctrl+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            f.write_str("Alt+")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => f.write_str("Space"),
+            KeyCode::Char(c) => write!(f, "{c}"),
+            KeyCode::F(number) => write!(f, "F{number}"),
+            KeyCode::Esc => f.write_str("Esc"),
+            KeyCode::PageUp => f.write_str("PageUp"),
+            KeyCode::PageDown => f.write_str("PageDown"),
+            code => write!(f, "{code:?}"),
+        }
+    }
+}
+
+/// Which key triggers each action.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keymap {
+    keys: HashMap<KeyAction, KeyBinding>,
+    actions: HashMap<KeyBinding, KeyAction>,
+    /// Bindings dropped because another action claimed their key.
+    warnings: Vec<String>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::resolve(Vec::new())
+    }
+}
+
+impl Keymap {
+    /// Parse keymap lines over the defaults. The error names the offending line; conflicting
+    /// bindings are not errors but end up in [`Keymap::warnings`].
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut bindings: Vec<(KeyAction, Option<KeyBinding>)> = Vec::new();
+        for (idx, line) in text.lines().enumerate() {
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            let number = idx + 1;
+            let Some((name, key)) = line.split_once('=') else {
+                return Err(format!("line {number}: expected `action = key`"));
+            };
+            let (name, key) = (name.trim(), key.trim());
+            let Some(action) = KeyAction::from_name(name) else {
+                return Err(format!("line {number}: unknown action `{name}`"));
+            };
+            if bindings.iter().any(|(bound, _)| *bound == action) {
+                return Err(format!("line {number}: `{name}` is already bound"));
+            }
+            let key = if key.eq_ignore_ascii_case(UNBOUND) {
+                None
+            } else {
+                Some(
+                    KeyBinding::parse(key)
+                        .map_err(|message| format!("line {number}: {message}"))?,
+                )
+            };
+            bindings.push((action, key));
+        }
+        Ok(Self::resolve(bindings))
+    }
+
+    /// Read the keymap file from the config directory. A missing file means the defaults.
+    pub fn load() -> Result<Self> {
+        config::load_file(KEYMAP_FILE, Self::parse)
+    }
+
+    /// Give each key to the first action that claims it: the configured bindings in file order,
+    /// then the defaults of every action the file didn't mention.
+    fn resolve(configured: Vec<(KeyAction, Option<KeyBinding>)>) -> Self {
+        let defaults: Vec<_> = KeyAction::ALL
+            .into_iter()
+            .filter(|action| !configured.iter().any(|(bound, _)| bound == action))
+            .map(|action| (action, Some(action.default_key())))
+            .collect();
+        let mut keymap = Self {
+            keys: HashMap::new(),
+            actions: HashMap::new(),
+            warnings: Vec::new(),
+        };
+        for (action, key) in configured.into_iter().chain(defaults) {
+            let Some(key) = key else {
+                continue;
+            };
+            match keymap.actions.entry(key) {
+                Entry::Occupied(owner) => keymap.warnings.push(format!(
+                    "Warning: {key} is bound to both {} and {}; {} is left unbound",
+                    owner.get().name(),
+                    action.name(),
+                    action.name()
+                )),
+                Entry::Vacant(slot) => {
+                    slot.insert(action);
+                    keymap.keys.insert(action, key);
+                }
+            }
+        }
+        keymap
+    }
+
+    /// The action `key` triggers, if any.
+    pub fn action(&self, key: &KeyEvent) -> Option<KeyAction> {
+        self.actions.get(&KeyBinding::from_event(key)).copied()
+    }
+
+    /// The key bound to `action`, if any.
+    pub fn binding(&self, action: KeyAction) -> Option<KeyBinding> {
+        self.keys.get(&action).copied()
+    }
+
+    /// Key shown for `action` in hints, or `-` when it is unbound.
+    pub fn label(&self, action: KeyAction) -> String {
+        self.binding(action)
+            .map_or_else(|| "-".to_owned(), |key| key.to_string())
+    }
+
+    /// Conflicts found while loading, one message each.
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn defaults_match_the_built_in_keys() {
+        let keymap = Keymap::default();
+        assert!(keymap.warnings().is_empty());
+        assert_eq!(
+            keymap.action(&press(KeyCode::Char('x'), KeyModifiers::CONTROL)),
+            Some(KeyAction::HexView)
+        );
+        assert_eq!(
+            keymap.action(&press(KeyCode::Char('h'), KeyModifiers::CONTROL)),
+            None
+        );
+        assert_eq!(
+            keymap.action(&press(KeyCode::Char('R'), KeyModifiers::SHIFT)),
+            Some(KeyAction::ToggleAutoRepeat)
+        );
+        assert_eq!(
+            keymap.action(&press(KeyCode::Char('R'), KeyModifiers::NONE)),
+            Some(KeyAction::ToggleAutoRepeat)
+        );
+        assert_eq!(keymap.label(KeyAction::Utf8View), "Ctrl+u");
+    }
+
+    #[test]
+    fn remapped_keys_replace_the_default() {
+        let keymap = Keymap::parse("# hex\nhex_view = ctrl+t\nhelp = F1\nquit = none").unwrap();
+        assert_eq!(
+            keymap.action(&press(KeyCode::Char('t'), KeyModifiers::CONTROL)),
+            Some(KeyAction::HexView)
+        );
+        assert_eq!(
+            keymap.action(&press(KeyCode::Char('x'), KeyModifiers::CONTROL)),
+            None
+        );
+        assert_eq!(
+            keymap.action(&press(KeyCode::F(1), KeyModifiers::NONE)),
+            Some(KeyAction::Help)
+        );
+        assert_eq!(
+            keymap.action(&press(KeyCode::Char('q'), KeyModifiers::NONE)),
+            None
+        );
+        assert_eq!(keymap.label(KeyAction::HexView), "Ctrl+t");
+        assert_eq!(keymap.label(KeyAction::Quit), "-");
+    }
+
+    #[test]
+    fn conflicts_warn_and_keep_the_configured_binding() {
+        let keymap = Keymap::parse("edit = q\nhelp = ctrl+l\ninspect = ctrl+l").unwrap();
+        assert_eq!(
+            keymap.action(&press(KeyCode::Char('q'), KeyModifiers::NONE)),
+            Some(KeyAction::Edit)
+        );
+        assert_eq!(
+            keymap.action(&press(KeyCode::Char('l'), KeyModifiers::CONTROL)),
+            Some(KeyAction::Help)
+        );
+        assert_eq!(
+            keymap.warnings(),
+            [
+                "Warning: Ctrl+l is bound to both help and inspect; inspect is left unbound",
+                "Warning: q is bound to both edit and quit; quit is left unbound",
+                "Warning: Ctrl+l is bound to both help and clear_messages; clear_messages is left unbound",
+            ]
+        );
+    }
+
+    #[test]
+    fn keys_parse_with_modifiers_and_names() {
+        assert_eq!(
+            KeyBinding::parse("shift+r").unwrap(),
+            KeyBinding::plain('R')
+        );
+        assert_eq!(KeyBinding::parse("Ctrl+H").unwrap(), KeyBinding::ctrl('h'));
+        assert_eq!(KeyBinding::parse("+").unwrap(), KeyBinding::plain('+'));
+        assert_eq!(
+            KeyBinding::parse("alt++").unwrap(),
+            KeyBinding::new(KeyCode::Char('+'), KeyModifiers::ALT)
+        );
+        assert_eq!(
+            KeyBinding::parse("alt+pagedown").unwrap().to_string(),
+            "Alt+PageDown"
+        );
+        assert_eq!(
+            KeyBinding::parse("hyper+x").unwrap_err(),
+            "unknown modifier `hyper` in `hyper+x`"
+        );
+        assert_eq!(
+            KeyBinding::parse("f13").unwrap_err(),
+            "unknown key `f13` in `f13`"
+        );
+    }
+
+    #[test]
+    fn bad_lines_name_their_line() {
+        assert_eq!(
+            Keymap::parse("help = h\n\nteleport = t").unwrap_err(),
+            "line 3: unknown action `teleport`"
+        );
+        assert_eq!(
+            Keymap::parse("help = h\nhelp = F1").unwrap_err(),
+            "line 2: `help` is already bound"
+        );
+        assert_eq!(
+            Keymap::parse("help").unwrap_err(),
+            "line 1: expected `action = key`"
+        );
+    }
+}
@@ -0,0 +1,162 @@
+//! Wording of the connected screen: the command prompt and the pane titles.
+//!
+//! Labels are read from [`LABELS_FILE`] in the config directory, one `name = text` per line, with
+//! blank lines and `#` comments ignored. Labels not named keep their default. Text is trimmed
+//! unless it is in double quotes, so a prompt can end in a space or hold a `#`:
+//!
+//! ```text
+//! prompt = "$ "
+//! messages = Output
+//! cat = off
+//! ```
+//!
+//! The names are `prompt`, `session`, `command`, `history` and `messages`, plus `cat`, which is
+//! `on` or `off` for the cat on the bottom border of the message pane.
+
+use color_eyre::Result;
+
+use crate::config;
+
+pub const LABELS_FILE: &str = "labels.siterm";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Labels {
+    /// Written before the command being typed.
+    pub prompt: String,
+    pub session: String,
+    pub command: String,
+    pub history: String,
+    /// Followed by the view and scroll state, e.g. `(UTF-8) [FOLLOW]`.
+    pub messages: String,
+    /// Whether the cat sits on the bottom border of the message pane.
+    pub cat: bool,
+}
+
+impl Default for Labels {
+    fn default() -> Self {
+        Self {
+            prompt: "Command> ".into(),
+            session: "Session".into(),
+            command: "Command Input".into(),
+            history: "Command History".into(),
+            messages: "Device Messages".into(),
+            cat: true,
+        }
+    }
+}
+
+impl Labels {
+    /// Parse label overrides on top of the defaults. The error names the offending line.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut labels = Self::default();
+        for (idx, line) in text.lines().enumerate() {
+            let line = strip_label_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            let number = idx + 1;
+            let Some((name, value)) = line.split_once('=') else {
+                return Err(format!("line {number}: expected `name = text`"));
+            };
+            let (name, value) = (name.trim(), unquote(value.trim()));
+            let label = match name.to_ascii_lowercase().as_str() {
+                "prompt" => &mut labels.prompt,
+                "session" => &mut labels.session,
+                "command" => &mut labels.command,
+                "history" => &mut labels.history,
+                "messages" => &mut labels.messages,
+                "cat" => {
+                    labels.cat = match value.to_ascii_lowercase().as_str() {
+                        "on" => true,
+                        "off" => false,
+                        _ => return Err(format!("line {number}: cat is `on` or `off`")),
+                    };
+                    continue;
+                }
+                _ => {
+                    return Err(format!(
+                        "line {number}: unknown label `{name}`; expected prompt, session, command, \
+                         history, messages or cat"
+                    ));
+                }
+            };
+            *label = value.to_owned();
+        }
+        Ok(labels)
+    }
+
+    /// Read the labels file from the config directory. A missing file means the defaults.
+    pub fn load() -> Result<Self> {
+        config::load_file(LABELS_FILE, Self::parse)
+    }
+}
+
+/// `line` without a trailing `#` comment. As in commands, a `#` only opens one at the start of the
+/// line or after whitespace, and never inside double quotes, so `messages = "Board #1"` keeps it.
+fn strip_label_comment(line: &str) -> &str {
+    let mut quoted = false;
+    let mut previous_is_space = true;
+    for (idx, ch) in line.char_indices() {
+        match ch {
+            '"' => quoted = !quoted,
+            '#' if !quoted && previous_is_space => return &line[..idx],
+            _ => {}
+        }
+        previous_is_space = ch.is_ascii_whitespace();
+    }
+    line
+}
+
+/// `text` without one pair of surrounding double quotes, if it has them.
+fn unquote(text: &str) -> &str {
+    text.strip_prefix('"')
+        .and_then(|inner| inner.strip_suffix('"'))
+        .unwrap_or(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overrides_replace_only_the_labels_named() {
+        let labels = Labels::parse(
+            "# terse\nprompt = \"$ \"\nMESSAGES = Output # from the board\ncat = off\n",
+        )
+        .unwrap();
+        assert_eq!(labels.prompt, "$ ");
+        assert_eq!(labels.messages, "Output");
+        assert!(!labels.cat);
+        assert_eq!(labels.session, "Session");
+        assert_eq!(labels.history, "Command History");
+
+        assert_eq!(Labels::parse("\n# nothing set\n"), Ok(Labels::default()));
+        assert_eq!(Labels::default().prompt, "Command> ");
+    }
+
+    #[test]
+    fn a_hash_inside_quotes_is_part_of_the_label() {
+        let labels = Labels::parse("messages = \"Board #1\" # the left one").unwrap();
+        assert_eq!(labels.messages, "Board #1");
+    }
+
+    #[test]
+    fn bad_lines_are_reported() {
+        assert_eq!(
+            Labels::parse("prompt = >\ntitle = SiTerm"),
+            Err(
+                "line 2: unknown label `title`; expected prompt, session, command, history, \
+                 messages or cat"
+                    .into()
+            )
+        );
+        assert_eq!(
+            Labels::parse("cat = maybe"),
+            Err("line 1: cat is `on` or `off`".into())
+        );
+        assert_eq!(
+            Labels::parse("prompt"),
+            Err("line 1: expected `name = text`".into())
+        );
+    }
+}
@@ -0,0 +1,92 @@
+//! The port and baud rate of the last successful connection.
+//!
+//! SiTerm writes [`LAST_CONNECTION_FILE`] to the config directory each time a connection is
+//! established, and the preconnect screen starts on the same port and rate so reconnecting is a
+//! single Enter:
+//!
+//! ```text
+//! port = /dev/ttyACM0
+//! baud = 115200
+//! ```
+//!
+//! The file is SiTerm's own bookkeeping, so one that can't be read is treated as missing rather
+//! than stopping startup.
+
+use std::fs;
+
+use color_eyre::Result;
+use tracing::warn;
+
+use crate::config;
+
+pub const LAST_CONNECTION_FILE: &str = "last_connection.siterm";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LastConnection {
+    pub port: String,
+    pub baud_rate: u32,
+}
+
+impl LastConnection {
+    /// Parse the file's contents, or `None` if either setting is missing or garbled.
+    pub fn parse(text: &str) -> Option<Self> {
+        let (mut port, mut baud_rate) = (None, None);
+        for line in text.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            match key.trim() {
+                "port" => port = Some(value.trim().to_owned()).filter(|port| !port.is_empty()),
+                "baud" => baud_rate = value.trim().parse().ok(),
+                _ => {}
+            }
+        }
+        Some(Self {
+            port: port?,
+            baud_rate: baud_rate?,
+        })
+    }
+
+    /// Read the file from the config directory, if there is a usable one.
+    pub fn load() -> Option<Self> {
+        let path = config::get_config_dir().join(LAST_CONNECTION_FILE);
+        let text = fs::read_to_string(&path).ok()?;
+        let last = Self::parse(&text);
+        if last.is_none() {
+            warn!(path = %path.display(), "ignoring unreadable last connection");
+        }
+        last
+    }
+
+    /// Write the file to the config directory, replacing any earlier one.
+    pub fn save(&self) -> Result<()> {
+        let dir = config::get_config_dir();
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join(LAST_CONNECTION_FILE), self.to_text())?;
+        Ok(())
+    }
+
+    fn to_text(&self) -> String {
+        format!("port = {}\nbaud = {}\n", self.port, self.baud_rate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_text_parses_back() {
+        let last = LastConnection {
+            port: "tcp://10.0.0.2:4000".into(),
+            baud_rate: 230_400,
+        };
+        assert_eq!(LastConnection::parse(&last.to_text()), Some(last));
+        assert_eq!(LastConnection::parse("port = /dev/ttyACM0\n"), None);
+        assert_eq!(
+            LastConnection::parse("port = /dev/ttyACM0\nbaud = fast\n"),
+            None
+        );
+        assert_eq!(LastConnection::parse("port =\nbaud = 9600\n"), None);
+    }
+}
@@ -0,0 +1,170 @@
+//! Host-side `ping`, which measures round-trip latency with echo commands.
+//!
+//! `ping [count]` never reaches the firmware as such. The host sends `count` echoes one at a time,
+//! each carrying its own sequence number, and times how long the matching reply takes. A reply that
+//! doesn't arrive within [`PING_TIMEOUT`] counts as lost, and a late reply to an earlier ping is
+//! ignored rather than taken as the answer to the current one.
+
+use std::time::Duration;
+
+use protocol::host::{EncodeError, encode_command, strip_comment};
+
+const PING_KEYWORD: &str = "ping";
+/// Pings sent when no count is given.
+pub const DEFAULT_PING_COUNT: u16 = 10;
+/// Most pings one `ping` sends.
+pub const MAX_PING_COUNT: u16 = 1000;
+/// Longest to wait for each reply before counting it as lost.
+pub const PING_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Parse a `ping` line into its count. Returns `None` for any other command so the caller can
+/// encode it normally.
+pub fn parse_ping(input: &str) -> Option<Result<u16, String>> {
+    let mut words = strip_comment(input).split_whitespace();
+    if !words.next()?.eq_ignore_ascii_case(PING_KEYWORD) {
+        return None;
+    }
+    let count = match (words.next(), words.next()) {
+        (None, _) => Ok(DEFAULT_PING_COUNT),
+        (Some(count), None) => match count.parse::<u16>() {
+            Ok(count @ 1..=MAX_PING_COUNT) => Ok(count),
+            _ => Err(format!(
+                "ping count must be between 1 and {MAX_PING_COUNT}, got `{count}`"
+            )),
+        },
+        (Some(_), Some(_)) => Err("usage: ping [count]".to_owned()),
+    };
+    Some(count)
+}
+
+/// What the device echoes back for ping `seq`.
+pub fn pong_payload(seq: u16) -> Vec<u8> {
+    format!("ping {seq}").into_bytes()
+}
+
+/// The echo command that carries ping `seq`.
+pub fn ping_command(seq: u16) -> Result<Vec<u8>, EncodeError> {
+    encode_command(&format!("echo ping {seq}"))
+}
+
+/// Round-trip times collected by a `ping` run.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LatencyStats {
+    samples: Vec<Duration>,
+    lost: usize,
+}
+
+impl LatencyStats {
+    pub fn record(&mut self, round_trip: Duration) {
+        self.samples.push(round_trip);
+    }
+
+    pub fn record_lost(&mut self) {
+        self.lost += 1;
+    }
+
+    pub fn sent(&self) -> usize {
+        self.samples.len() + self.lost
+    }
+
+    pub fn min(&self) -> Option<Duration> {
+        self.samples.iter().min().copied()
+    }
+
+    pub fn max(&self) -> Option<Duration> {
+        self.samples.iter().max().copied()
+    }
+
+    pub fn mean(&self) -> Option<Duration> {
+        let count = u32::try_from(self.samples.len()).ok().filter(|&n| n > 0)?;
+        Some(self.samples.iter().sum::<Duration>() / count)
+    }
+
+    /// Population standard deviation of the replies received.
+    pub fn std_dev(&self) -> Option<Duration> {
+        let mean = self.mean()?.as_secs_f64();
+        let variance = self
+            .samples
+            .iter()
+            .map(|sample| (sample.as_secs_f64() - mean).powi(2))
+            .sum::<f64>()
+            / self.samples.len() as f64;
+        Some(Duration::from_secs_f64(variance.sqrt()))
+    }
+
+    /// One-line report, e.g.
+    /// `ping: 10 sent, 9 received, 1 lost; rtt min/avg/max/stddev = 1.021/1.340/2.104/0.210 ms`.
+    pub fn summary(&self) -> String {
+        let counts = format!(
+            "ping: {} sent, {} received, {} lost",
+            self.sent(),
+            self.samples.len(),
+            self.lost
+        );
+        match (self.min(), self.mean(), self.max(), self.std_dev()) {
+            (Some(min), Some(mean), Some(max), Some(std_dev)) => format!(
+                "{counts}; rtt min/avg/max/stddev = {:.3}/{:.3}/{:.3}/{:.3} ms",
+                millis(min),
+                millis(mean),
+                millis(max),
+                millis(std_dev)
+            ),
+            _ => counts,
+        }
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_cover_received_and_lost_pings() {
+        let mut stats = LatencyStats::default();
+        for ms in [1, 2, 3, 4] {
+            stats.record(Duration::from_millis(ms));
+        }
+        stats.record_lost();
+
+        assert_eq!(stats.sent(), 5);
+        assert_eq!(stats.lost, 1);
+        assert_eq!(stats.min(), Some(Duration::from_millis(1)));
+        assert_eq!(stats.max(), Some(Duration::from_millis(4)));
+        assert_eq!(stats.mean(), Some(Duration::from_micros(2_500)));
+        let std_dev = stats.std_dev().unwrap().as_secs_f64() * 1000.0;
+        assert!((std_dev - 1.25f64.sqrt()).abs() < 1e-6, "{std_dev}");
+        assert_eq!(
+            stats.summary(),
+            "ping: 5 sent, 4 received, 1 lost; rtt min/avg/max/stddev = 1.000/2.500/4.000/1.118 ms"
+        );
+    }
+
+    #[test]
+    fn all_lost_reports_counts_only() {
+        let mut stats = LatencyStats::default();
+        stats.record_lost();
+        stats.record_lost();
+        assert_eq!(stats.mean(), None);
+        assert_eq!(stats.summary(), "ping: 2 sent, 0 received, 2 lost");
+    }
+
+    #[test]
+    fn ping_counts_are_parsed_and_bounded() {
+        assert_eq!(parse_ping("echo ping"), None);
+        assert_eq!(parse_ping("ping"), Some(Ok(DEFAULT_PING_COUNT)));
+        assert_eq!(parse_ping("PING 25"), Some(Ok(25)));
+        assert!(parse_ping("ping 0").unwrap().is_err());
+        assert!(parse_ping("ping 1001").unwrap().is_err());
+        assert!(parse_ping("ping 1 2").unwrap().is_err());
+        assert_eq!(
+            protocol::decode_command(&ping_command(7).unwrap()).unwrap(),
+            protocol::Command::EchoWrite {
+                payload: &pong_payload(7)
+            }
+        );
+    }
+}
@@ -1,4 +1,13 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
 use color_eyre::Result;
+use time::OffsetDateTime;
 use tracing_error::ErrorLayer;
 use tracing_subscriber::{EnvFilter, fmt, prelude::*};
 
@@ -9,15 +18,42 @@ lazy_static::lazy_static! {
         "{}_LOG_LEVEL",
         env!("CARGO_CRATE_NAME").to_uppercase()
     );
-    pub static ref LOG_FILE: String = format!("{}.log", env!("CARGO_PKG_NAME"));
 }
 
-pub fn init() -> Result<()> {
-    let directory = config::get_data_dir();
-    std::fs::create_dir_all(directory.clone())?;
-    let log_path = directory.join(LOG_FILE.clone());
-    let log_file = std::fs::File::create(log_path)?;
-    let env_filter = EnvFilter::builder().with_default_directive(tracing::Level::INFO.into());
+/// Directory under [`config::get_data_dir`] that holds the session logs.
+pub const SESSIONS_DIR: &str = "sessions";
+/// Size a session log file grows to before the session moves on to a new one.
+pub const DEFAULT_LOG_ROTATE_BYTES: u64 = 10 * 1024 * 1024;
+/// Session log files kept across runs before the oldest is deleted.
+pub const DEFAULT_LOG_RETAIN: usize = 20;
+
+/// When session logs move to a new file and how many files are kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogRotation {
+    pub max_bytes: u64,
+    pub retain: usize,
+}
+
+/// Install the tracing subscriber. Without `to_file` nothing is written to disk; with it, each run
+/// logs to its own timestamped files under [`SESSIONS_DIR`] in [`config::get_data_dir`], rotated
+/// and pruned as `rotation` says.
+///
+/// Serial sessions log a summary of each frame at `debug` and the raw bytes at `trace`, so the
+/// default `debug` level records what was exchanged and `trace` adds the bytes.
+pub fn init(to_file: bool, rotation: LogRotation) -> Result<()> {
+    if !to_file {
+        tracing_subscriber::registry()
+            .with(ErrorLayer::default())
+            .try_init()?;
+        return Ok(());
+    }
+
+    let log_file = SessionLog::create(
+        config::get_data_dir().join(SESSIONS_DIR),
+        &session_stem(SystemTime::now()),
+        rotation,
+    )?;
+    let env_filter = EnvFilter::builder().with_default_directive(tracing::Level::DEBUG.into());
     // If the `RUST_LOG` environment variable is set, use that as the default, otherwise use the
     // value of the `LOG_ENV` environment variable. If the `LOG_ENV` environment variable contains
     // errors, then this will return an error.
@@ -27,7 +63,7 @@ pub fn init() -> Result<()> {
     let file_subscriber = fmt::layer()
         .with_file(true)
         .with_line_number(true)
-        .with_writer(log_file)
+        .with_writer(Mutex::new(log_file))
         .with_target(false)
         .with_ansi(false)
         .with_filter(env_filter);
@@ -37,3 +73,206 @@ pub fn init() -> Result<()> {
         .try_init()?;
     Ok(())
 }
+
+/// Start of this run's log file names, e.g. `component-20261017-104038-4242`: the UTC start time,
+/// then the process id so two runs started in the same second don't share files.
+fn session_stem(now: SystemTime) -> String {
+    let started = OffsetDateTime::from(now);
+    format!(
+        "{}-{:04}{:02}{:02}-{:02}{:02}{:02}-{}",
+        env!("CARGO_PKG_NAME"),
+        started.year(),
+        u8::from(started.month()),
+        started.day(),
+        started.hour(),
+        started.minute(),
+        started.second(),
+        std::process::id()
+    )
+}
+
+/// One run's log. Writes go to `<stem>-<part>.log`, and a write that would take the file past
+/// the rotation size starts the next part first, so an event is never split across files.
+struct SessionLog {
+    directory: PathBuf,
+    stem: String,
+    part: u32,
+    file: File,
+    written: u64,
+    rotation: LogRotation,
+}
+
+impl SessionLog {
+    fn create(directory: PathBuf, stem: &str, rotation: LogRotation) -> io::Result<Self> {
+        fs::create_dir_all(&directory)?;
+        let part = 1;
+        let file = File::create(part_path(&directory, stem, part))?;
+        let log = Self {
+            directory,
+            stem: stem.to_owned(),
+            part,
+            file,
+            written: 0,
+            rotation,
+        };
+        log.prune()?;
+        Ok(log)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        self.part += 1;
+        self.file = File::create(part_path(&self.directory, &self.stem, self.part))?;
+        self.written = 0;
+        self.prune()
+    }
+
+    /// Delete the oldest log files until at most the retained count are left, ordered by start
+    /// time and then part number. The count is shared by every run logging to the directory, so
+    /// a run started earlier may lose files while it is still open; the file this run is writing
+    /// to, and anything newer, is never deleted.
+    fn prune(&self) -> io::Result<()> {
+        let current = (self.stem.as_str(), self.part);
+        let mut logs: Vec<(String, u32, PathBuf)> = fs::read_dir(&self.directory)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter_map(|path| {
+                let (stem, part) = parse_part_name(path.file_name()?.to_str()?)?;
+                Some((stem.to_owned(), part, path))
+            })
+            .collect();
+        logs.sort();
+        let excess = logs.len().saturating_sub(self.rotation.retain.max(1));
+        for (_, _, old) in logs
+            .iter()
+            .filter(|(stem, part, _)| (stem.as_str(), *part) < current)
+            .take(excess)
+        {
+            fs::remove_file(old)?;
+        }
+        Ok(())
+    }
+}
+
+fn part_path(directory: &Path, stem: &str, part: u32) -> PathBuf {
+    directory.join(format!("{stem}-{part:03}.log"))
+}
+
+/// Split a session log file name into its stem and part number, or `None` for any other file.
+fn parse_part_name(name: &str) -> Option<(&str, u32)> {
+    let (stem, part) = name.strip_suffix(".log")?.rsplit_once('-')?;
+    if !stem.starts_with(concat!(env!("CARGO_PKG_NAME"), "-")) {
+        return None;
+    }
+    Some((stem, part.parse().ok()?))
+}
+
+impl Write for SessionLog {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written > 0 && self.written + buf.len() as u64 > self.rotation.max_bytes {
+            self.rotate()?;
+        }
+        self.file.write_all(buf)?;
+        self.written += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log_names(directory: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn a_full_file_rotates_and_old_files_are_pruned() {
+        let directory = std::env::temp_dir().join(format!("siterm-logs-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        let rotation = LogRotation {
+            max_bytes: 64,
+            retain: 2,
+        };
+        let mut log = SessionLog::create(directory.clone(), "component-x", rotation).unwrap();
+        let line = [b'.'; 40];
+
+        log.write_all(&line).unwrap();
+        assert_eq!(log_names(&directory), ["component-x-001.log"]);
+        log.write_all(&line).unwrap();
+        assert_eq!(
+            log_names(&directory),
+            ["component-x-001.log", "component-x-002.log"]
+        );
+        log.write_all(&line).unwrap();
+        assert_eq!(
+            log_names(&directory),
+            ["component-x-002.log", "component-x-003.log"]
+        );
+        assert_eq!(
+            fs::read(directory.join("component-x-002.log"))
+                .unwrap()
+                .len(),
+            40
+        );
+
+        // Past part 999 the names no longer sort by age, so the live file must still survive.
+        log.part = 998;
+        log.write_all(&line).unwrap();
+        log.write_all(&line).unwrap();
+        assert_eq!(
+            log_names(&directory),
+            ["component-x-1000.log", "component-x-999.log"]
+        );
+        log.write_all(&line).unwrap();
+        assert_eq!(
+            log_names(&directory),
+            ["component-x-1000.log", "component-x-1001.log"]
+        );
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn pruning_keeps_newer_runs_and_skips_other_files() {
+        let directory =
+            std::env::temp_dir().join(format!("siterm-logs-shared-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        for name in ["component-a-001.log", "component-c-001.log", "notes.log"] {
+            File::create(directory.join(name)).unwrap();
+        }
+        let rotation = LogRotation {
+            max_bytes: 64,
+            retain: 1,
+        };
+        let _log = SessionLog::create(directory.clone(), "component-b", rotation).unwrap();
+
+        assert_eq!(
+            log_names(&directory),
+            ["component-b-001.log", "component-c-001.log", "notes.log"]
+        );
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn session_names_start_with_the_utc_start_time() {
+        let at = |secs| std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs);
+        let stem = session_stem(at(1_792_233_038));
+        assert!(stem.starts_with("component-20261017-103038-"), "{stem}");
+        let leap_day = session_stem(at(951_782_400));
+        assert!(
+            leap_day.starts_with("component-20000229-000000-"),
+            "{leap_day}"
+        );
+    }
+}
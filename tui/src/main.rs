@@ -2,24 +2,63 @@ use clap::Parser;
 use cli::Cli;
 use color_eyre::Result;
 
-use crate::app::App;
+use crate::{
+    alias::Aliases, app::App, config::Config, favorites::Favorites, keymap::Keymap, labels::Labels,
+    last_connection::LastConnection, logging::LogRotation, tui::TerminalStreams,
+};
 
 mod action;
+mod alias;
 mod app;
 mod cli;
 mod components;
 mod config;
 mod errors;
+mod favorites;
+mod keymap;
+mod labels;
+mod last_connection;
+mod latency;
 mod logging;
+mod pipeline;
+mod queue;
+mod script;
+mod session_summary;
+mod simulator;
+mod theme;
+mod transport;
 mod tui;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     crate::errors::init()?;
-    crate::logging::init()?;
 
     let args = Cli::parse();
-    let mut app = App::new(args.tick_rate, args.frame_rate)?;
+    crate::logging::init(
+        args.log,
+        LogRotation {
+            max_bytes: args.log_rotate_kb.max(1).saturating_mul(1024),
+            retain: args.log_keep,
+        },
+    )?;
+    if let Some(reason) = TerminalStreams::detect().unsupported_reason() {
+        eprintln!("{reason}");
+        std::process::exit(1);
+    }
+    let script = match &args.script {
+        Some(path) => script::load(path)?,
+        None => Vec::new(),
+    };
+    let mut app = App::new(args.tick_rate, args.frame_rate)?
+        .script(script)
+        .aliases(Aliases::load()?)
+        .config(Config {
+            keymap: Keymap::load()?,
+            favorites: Favorites::load()?,
+            labels: Labels::load()?,
+            last_connection: LastConnection::load(),
+            ..Config::from_cli(&args)
+        });
     app.run().await?;
     Ok(())
 }
@@ -0,0 +1,964 @@
+//! The synchronous half of a serial session: command lines to frames, and received bytes to
+//! messages.
+//!
+//! The session tasks in `app` only move bytes between the serial port and these functions, so a
+//! command can be driven to the wire and its response back to a [`DeviceMessage`] without a device.
+
+use std::time::{Duration, Instant};
+
+use protocol::{
+    Command, I2C_BUS_COUNT, MAX_COMMAND_LEN, MAX_I2C_ADDRESS, Method,
+    bridge::BRIDGE_ESCAPE,
+    decode_command,
+    flow::{BUSY, READY},
+    heartbeat::HEARTBEAT,
+    host::{
+        DecodedFrame, EncodeError, TransportCodecError, decode_transport_frame_resyncing,
+        dump::{DumpRequest, chunk_len, parse_dump, plan_chunks},
+        encode_command, encode_transport_frame,
+        hint::ValueHint,
+        max_command_len, split_into_chunks,
+    },
+    response::{MAX_RESPONSE_LEN, ResponseFormat, split_tag},
+    transport::Framing,
+};
+
+use crate::{
+    action::{Action, DeviceMessage},
+    components::terminal::format_hex,
+    latency::{parse_ping, ping_command},
+};
+
+/// Line that ends a UART bridge session; it is sent as the bridge escape instead of as text.
+pub const BRIDGE_EXIT_LINE: &str = "~.";
+/// Appended to each line typed while bridged, as a serial terminal sends on Enter.
+pub const BRIDGE_LINE_ENDING: &[u8] = b"\r\n";
+
+/// What the UI queues for the serial writer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outbound {
+    /// A typed line: a command, or text for the UART while bridged.
+    Line(String),
+    /// Bytes from keystroke mode, written to the bridged UART as they are.
+    Keystrokes(Vec<u8>),
+}
+
+/// What the writer should do with a line typed while commands are framed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outgoing {
+    /// An encoded command and the frame that carries it, or the chunk frames back to back for a
+    /// command too long for one (see `protocol::chunk`).
+    Command { payload: Vec<u8>, frame: Vec<u8> },
+    /// An `i2c dump`, sent as a series of reads.
+    Dump(DumpRequest),
+    /// A latency run of this many echoes.
+    Ping(u16),
+    /// The line can't be sent; show this error instead.
+    Rejected(String),
+}
+
+/// Whether `line` has nothing to send: it is empty or all whitespace. Blank lines are never sent
+/// or added to the history, however they arrive.
+pub fn is_blank_line(line: &str) -> bool {
+    line.trim().is_empty()
+}
+
+/// Encode and frame a command line. Blank lines give `None`.
+pub fn prepare_command(command: &str, framing: Framing) -> Option<Outgoing> {
+    if is_blank_line(command) {
+        return None;
+    }
+    let trimmed = command.trim();
+    let encode_failed = |error| {
+        Outgoing::Rejected(format!(
+            "Error: Failed to encode command `{trimmed}`: {}",
+            format_encode_error(error)
+        ))
+    };
+
+    if let Some(request) = parse_dump(trimmed) {
+        return Some(request.map_or_else(encode_failed, Outgoing::Dump));
+    }
+    if let Some(count) = parse_ping(trimmed) {
+        return Some(count.map_or_else(
+            |reason| {
+                Outgoing::Rejected(format!(
+                    "Error: Failed to encode command `{trimmed}`: {reason}"
+                ))
+            },
+            Outgoing::Ping,
+        ));
+    }
+    let outgoing = match encode_command(trimmed) {
+        Ok(payload) if payload.len() > max_command_len(&payload) => Outgoing::Rejected(format!(
+            "Error: Command `{trimmed}` encodes to {} bytes; the device takes at most {}.",
+            payload.len(),
+            max_command_len(&payload)
+        )),
+        Ok(payload) if echoes_unsolicited_frame(&payload) => Outgoing::Rejected(format!(
+            "Error: Command `{trimmed}` would echo a heartbeat or busy/ready frame, which can't be \
+             told apart from the device's own."
+        )),
+        Ok(payload) => match frame_command(&payload, framing) {
+            Ok(frame) => Outgoing::Command { payload, frame },
+            Err(err) => Outgoing::Rejected(format!(
+                "Error: Failed to frame command `{trimmed}`: {}",
+                format_transport_error(err)
+            )),
+        },
+        Err(error) => encode_failed(error),
+    };
+    Some(outgoing)
+}
+
+/// Frame an encoded command, splitting it into chunks of at most [`MAX_COMMAND_LEN`] bytes when it
+/// doesn't fit one frame. The chunk frames go back to back so they are written as one.
+fn frame_command(payload: &[u8], framing: Framing) -> Result<Vec<u8>, TransportCodecError> {
+    if !is_chunked(payload) {
+        return encode_transport_frame(payload, framing);
+    }
+    let mut frames = Vec::new();
+    for chunk in split_into_chunks(payload, MAX_COMMAND_LEN) {
+        frames.extend(encode_transport_frame(&chunk, framing)?);
+    }
+    Ok(frames)
+}
+
+/// Whether `payload` is an `echo` whose reply would carry the same bytes as one of the device's
+/// unsolicited frames. The session takes those for the device's own and never shows them.
+fn echoes_unsolicited_frame(payload: &[u8]) -> bool {
+    matches!(
+        decode_command(payload),
+        Ok(Command::EchoWrite { payload }) if [HEARTBEAT, BUSY, READY].contains(&payload)
+    )
+}
+
+/// Whether an encoded command is too long for one frame and goes out as a run of chunks.
+pub fn is_chunked(payload: &[u8]) -> bool {
+    payload.len() > MAX_COMMAND_LEN
+}
+
+/// How the firmware will read a command line: the command its encoded bytes decode back to, e.g.
+/// `→ i2c read 0x48 0x00 2` for `i2c r 72 0 2`. Lines that don't encode to a single
+/// command (dumps, pings, errors and blank lines) give `None`.
+pub fn interpret_command(command: &str) -> Option<String> {
+    let Outgoing::Command { payload, .. } = prepare_command(command, Framing::default())? else {
+        return None;
+    };
+    Some(match decode_command(&payload) {
+        Ok(decoded) => format!("→ {decoded}"),
+        Err(err) => format!("→ Error: the encoded bytes don't decode back: {err:?}"),
+    })
+}
+
+/// Bytes to send for a line typed while bridged, and whether they close the bridge.
+pub fn bridge_bytes(line: &str) -> (Vec<u8>, bool) {
+    if line.trim() == BRIDGE_EXIT_LINE {
+        (BRIDGE_ESCAPE.to_vec(), true)
+    } else {
+        ([line.as_bytes(), BRIDGE_LINE_ENDING].concat(), false)
+    }
+}
+
+/// What `command` would put on the wire, as messages for dry-run mode. Nothing is sent.
+pub fn dry_run_lines(command: &str, framing: Framing) -> Vec<String> {
+    let trimmed = command.trim();
+    match prepare_command(command, framing) {
+        None => Vec::new(),
+        Some(Outgoing::Command { payload, frame }) => vec![
+            format!("Dry run: `{trimmed}` (not sent)"),
+            payload_line(&payload),
+            frame_line(&frame, framing),
+        ],
+        Some(Outgoing::Dump(request)) => {
+            let chunks = plan_chunks(request.start, request.length, chunk_len(None));
+            let mut lines = vec![format!(
+                "Dry run: `{trimmed}` is sent as {} i2c read(s)",
+                chunks.len()
+            )];
+            for chunk in chunks {
+                let payload = request.read_command(chunk);
+                lines.push(payload_line(&payload));
+                lines.push(match encode_transport_frame(&payload, framing) {
+                    Ok(frame) => frame_line(&frame, framing),
+                    Err(err) => format!(
+                        "Error: Failed to frame command: {}",
+                        format_transport_error(err)
+                    ),
+                });
+            }
+            lines
+        }
+        Some(Outgoing::Ping(count)) => {
+            let mut lines = vec![format!(
+                "Dry run: `{trimmed}` is sent as {count} echo command(s), the first being"
+            )];
+            match ping_command(0) {
+                Ok(payload) => {
+                    lines.push(payload_line(&payload));
+                    lines.push(match encode_transport_frame(&payload, framing) {
+                        Ok(frame) => frame_line(&frame, framing),
+                        Err(err) => format!(
+                            "Error: Failed to frame command: {}",
+                            format_transport_error(err)
+                        ),
+                    });
+                }
+                Err(error) => lines.push(format!(
+                    "Error: Failed to encode command: {}",
+                    format_encode_error(error)
+                )),
+            }
+            lines
+        }
+        Some(Outgoing::Rejected(message)) => vec![message],
+    }
+}
+
+fn payload_line(payload: &[u8]) -> String {
+    format!(
+        "  command, {} bytes: {}",
+        payload.len(),
+        format_hex(payload)
+    )
+}
+
+fn frame_line(frame: &[u8], framing: Framing) -> String {
+    format!(
+        "  {} frame, {} bytes: {}",
+        framing.name(),
+        frame.len(),
+        format_hex(frame)
+    )
+}
+
+/// A frame read off the serial port, with any response tag split off.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Received {
+    /// Method the device tagged the response with.
+    pub source: Option<Method>,
+    /// How the device's tag says to show the response.
+    pub format: ResponseFormat,
+    pub payload: Vec<u8>,
+    /// Length of the frame on the wire, not counting skipped bytes.
+    pub frame_len: usize,
+    /// Corrupt bytes dropped before the frame while resyncing.
+    pub skipped: usize,
+}
+
+/// Collects serial reads and splits them into frames.
+#[derive(Debug)]
+pub struct Inbound {
+    framing: Framing,
+    tagged: bool,
+    pending: Vec<u8>,
+    /// When bytes last arrived or a frame was taken, to tell a frame still arriving from one that
+    /// never will.
+    progress_at: Option<Instant>,
+    /// Longest payload a frame may claim before its header is taken for corruption rather than
+    /// waited for.
+    max_payload: usize,
+    /// Bytes dropped for claiming too long a frame, reported with the next frame decoded.
+    dropped: usize,
+    /// Where the frames taken since the last [`Inbound::take_trace`] sat, when tracing is on.
+    trace: Option<FrameTrace>,
+}
+
+/// Frame boundaries for `--show-frames`. Offsets count from the front of the buffer as it stood
+/// when the trace was last taken, so undecoded bytes carried over start the next trace at 0.
+#[derive(Debug, Default)]
+struct FrameTrace {
+    /// Frames taken over the whole session, to number them.
+    frames: usize,
+    offset: usize,
+    notes: Vec<String>,
+}
+
+impl FrameTrace {
+    /// Note a decoded frame of `consumed` bytes, the first `skipped` of them corrupt.
+    fn frame(&mut self, skipped: usize, consumed: usize, payload_len: usize) {
+        let start = self.offset + skipped;
+        if skipped > 0 {
+            self.notes
+                .push(format!("[skipped {}..{start}]", self.offset));
+        }
+        let end = self.offset + consumed;
+        self.notes.push(format!(
+            "[frame {}: {start}..{end} len={payload_len}]",
+            self.frames
+        ));
+        self.frames += 1;
+        self.offset = end;
+    }
+
+    /// Note `skipped` corrupt bytes dropped from the front of the buffer with no frame after them
+    /// yet.
+    fn skip(&mut self, skipped: usize) {
+        let end = self.offset + skipped;
+        self.notes.push(format!("[skipped {}..{end}]", self.offset));
+        self.offset = end;
+    }
+
+    /// The notes so far and the `pending` bytes left after them, or `None` if there are neither.
+    fn take(&mut self, pending: usize) -> Option<String> {
+        if pending > 0 {
+            let start = self.offset;
+            self.notes.push(format!(
+                "[pending {start}..{}: {pending} undecoded byte(s)]",
+                start + pending
+            ));
+        }
+        self.offset = 0;
+        let notes = std::mem::take(&mut self.notes);
+        (!notes.is_empty()).then(|| notes.join(" "))
+    }
+}
+
+/// Longest payload the firmware sends on a session: its largest response, plus the tag byte on a
+/// tagged session.
+pub fn default_max_payload(tagged: bool) -> usize {
+    MAX_RESPONSE_LEN + usize::from(tagged)
+}
+
+impl Inbound {
+    pub fn new(framing: Framing, tagged: bool) -> Self {
+        Self {
+            framing,
+            tagged,
+            pending: Vec::new(),
+            progress_at: None,
+            max_payload: default_max_payload(tagged),
+            dropped: 0,
+            trace: None,
+        }
+    }
+
+    /// Record where each decoded frame sits in the stream, for [`Inbound::take_trace`].
+    pub fn trace_frames(mut self, on: bool) -> Self {
+        self.trace = on.then(FrameTrace::default);
+        self
+    }
+
+    /// The boundaries of the frames taken since the last call and of the bytes still undecoded,
+    /// e.g. `[frame 0: 0..7 len=3] [pending 7..9: 2 undecoded byte(s)]`. `None` when tracing is
+    /// off or there is nothing to show.
+    pub fn take_trace(&mut self) -> Option<String> {
+        let pending = self.pending.len();
+        self.trace.as_mut()?.take(pending)
+    }
+
+    /// Treat a header claiming a payload longer than `max` bytes as corruption, instead of one
+    /// longer than [`default_max_payload`].
+    pub fn max_payload(mut self, max: usize) -> Self {
+        self.max_payload = max;
+        self
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) {
+        if !bytes.is_empty() {
+            self.progress_at = Some(Instant::now());
+        }
+        self.pending.extend_from_slice(bytes);
+    }
+
+    /// When the partial frame in the buffer counts as stalled, `timeout` after the last progress.
+    /// `None` while nothing is buffered.
+    pub fn stall_deadline(&self, timeout: Duration) -> Option<Instant> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        self.progress_at.map(|at| at + timeout)
+    }
+
+    /// Drop a partial frame that has made no progress for `timeout` as of `now`, returning how many
+    /// bytes it held. A frame that stops short never completes, and would otherwise hold the
+    /// buffer without a word to the user.
+    pub fn flush_stalled(&mut self, now: Instant, timeout: Duration) -> Option<usize> {
+        let deadline = self.stall_deadline(timeout)?;
+        if now < deadline {
+            return None;
+        }
+        self.progress_at = None;
+        Some(std::mem::take(&mut self.pending).len())
+    }
+
+    /// Bytes read but not yet part of a decoded frame.
+    pub fn pending(&self) -> &[u8] {
+        &self.pending
+    }
+
+    /// Hand over the undecoded bytes, which are raw UART data once a bridge opens.
+    pub fn take_pending(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.pending)
+    }
+
+    /// Decode the next complete frame, if one has arrived.
+    ///
+    /// A header claiming more than the cap can't start a frame the device sent, so its first byte
+    /// is dropped and the search goes on from the next, the way a corrupt frame is skipped. The
+    /// dropped bytes are counted in the next frame's [`Received::skipped`].
+    pub fn next_frame(&mut self) -> Result<Option<Received>, TransportCodecError> {
+        let mut dropped = 0;
+        let decoded = loop {
+            match decode_transport_frame_resyncing(&self.pending[dropped..], self.framing)? {
+                Some(frame) => break Some(frame),
+                // A corrupt length would otherwise hold the buffer until the frame stalls.
+                None if self
+                    .framing
+                    .claimed_payload_len(&self.pending[dropped..])
+                    .is_some_and(|claimed| claimed > self.max_payload) =>
+                {
+                    dropped += 1;
+                }
+                None => break None,
+            }
+        };
+        self.pending.drain(..dropped);
+        self.dropped += dropped;
+        if dropped > 0
+            && let Some(trace) = &mut self.trace
+        {
+            trace.skip(dropped);
+        }
+        let Some(DecodedFrame {
+            payload,
+            consumed,
+            skipped,
+        }) = decoded
+        else {
+            return Ok(None);
+        };
+        self.pending.drain(..consumed);
+        self.progress_at = Some(Instant::now());
+        if let Some(trace) = &mut self.trace {
+            trace.frame(skipped, consumed, payload.len());
+        }
+
+        let (source, format, payload) = match split_tag(&payload) {
+            Some((source, format, rest)) if self.tagged => (source, format, rest.to_vec()),
+            _ => (None, ResponseFormat::Auto, payload),
+        };
+        Ok(Some(Received {
+            source,
+            format,
+            payload,
+            frame_len: consumed - skipped,
+            skipped: skipped + std::mem::take(&mut self.dropped),
+        }))
+    }
+}
+
+/// The action that shows a device response, attributed to its handler when it was tagged.
+pub fn payload_to_action(
+    source: Option<Method>,
+    format: ResponseFormat,
+    payload: Vec<u8>,
+) -> Action {
+    match source {
+        Some(method) => Action::TaggedResponse(method, format, payload),
+        None => Action::IncomingMessage(DeviceMessage::Bytes(payload)),
+    }
+}
+
+/// Note shown when corrupt bytes were dropped to find the next frame.
+pub fn skipped_warning(skipped: usize) -> DeviceMessage {
+    DeviceMessage::Text(format!(
+        "Warning: skipped {skipped} corrupt byte(s) resyncing the stream"
+    ))
+}
+
+/// Note shown when a partial frame stalled and its bytes were dropped.
+pub fn stalled_warning(dropped: usize) -> DeviceMessage {
+    DeviceMessage::Text(format!(
+        "Warning: partial frame stalled, dropped {dropped} buffered byte(s)"
+    ))
+}
+
+/// Times a command is resent after the device NAKs it before the host gives up.
+pub const MAX_RETRANSMITS: u8 = 3;
+
+/// The last command frame written, kept so it can be resent when the device answers with a
+/// [`NAK`](protocol::nak::NAK). The session holds each command until the last one is answered, so a
+/// NAK always means this frame.
+///
+/// A chunked command is not resent. The device answers only the finished command, so a NAK
+/// doesn't say which chunk was corrupted, and resending chunks it already took breaks the run.
+#[derive(Debug, Default)]
+pub struct Retransmit {
+    frame: Option<Vec<u8>>,
+    chunked: bool,
+    attempts: u8,
+}
+
+impl Retransmit {
+    /// Remember a freshly written command frame, or run of chunk frames when `chunked`.
+    pub fn sent(&mut self, frame: Vec<u8>, chunked: bool) {
+        self.frame = Some(frame);
+        self.chunked = chunked;
+        self.attempts = 0;
+    }
+
+    /// Drop the frame once the link carries something else, such as bridged bytes.
+    pub fn forget(&mut self) {
+        self.frame = None;
+    }
+
+    /// Handle a NAK: the frame to resend with a note saying so, or the error to show instead.
+    pub fn on_nak(&mut self) -> Result<(&[u8], DeviceMessage), DeviceMessage> {
+        if self.frame.is_none() {
+            return Err(DeviceMessage::Text(
+                "Error: device reported a corrupted command, but there is nothing to resend".into(),
+            ));
+        }
+        if self.chunked {
+            self.frame = None;
+            return Err(DeviceMessage::Text(
+                "Error: device reported a corrupted chunk of a long command; send the command again"
+                    .into(),
+            ));
+        }
+        if self.attempts >= MAX_RETRANSMITS {
+            self.frame = None;
+            return Err(DeviceMessage::Text(format!(
+                "Error: device still reported the command as corrupted after {MAX_RETRANSMITS} resends"
+            )));
+        }
+        self.attempts += 1;
+        let note = DeviceMessage::Text(format!(
+            "Warning: device reported a corrupted command, resending ({}/{MAX_RETRANSMITS})",
+            self.attempts
+        ));
+        Ok((self.frame.as_deref().unwrap_or_default(), note))
+    }
+}
+
+/// Describe an encode error. Argument positions count words of the command line from 1.
+pub fn format_encode_error(error: EncodeError) -> String {
+    match error {
+        EncodeError::Empty => "command is empty".into(),
+        EncodeError::UnknownMethod => "unknown method".into(),
+        EncodeError::UnknownOperation => "unknown operation".into(),
+        EncodeError::UnsupportedOperation { method, operation } => format!(
+            "unsupported operation {:?} for method {:?}",
+            operation, method
+        ),
+        EncodeError::MissingOperation => "missing operation keyword".into(),
+        EncodeError::MissingArgument { index } => {
+            format!("missing argument at position {}", index + 1)
+        }
+        EncodeError::UnexpectedArgument { index } => {
+            format!("unexpected argument starting at position {}", index + 1)
+        }
+        EncodeError::InvalidArgument { index } => {
+            format!("invalid argument at position {}", index + 1)
+        }
+        EncodeError::OutputTooSmall => "output buffer is too small".into(),
+        EncodeError::InvalidBus => format!(
+            "invalid i2c bus, expected --bus followed by 0 to {}",
+            I2C_BUS_COUNT - 1
+        ),
+        EncodeError::InvalidAddress { index, address } => format!(
+            "i2c address {address:#04x} at position {} is not 7-bit (0x00 to {MAX_I2C_ADDRESS:#04x}); \
+             for an 8-bit address from a datasheet, use {:#04x}",
+            index + 1,
+            address >> 1
+        ),
+        EncodeError::InvalidByteList { index, reason } => {
+            format!("invalid data bytes at position {}: {reason}", index + 1)
+        }
+        EncodeError::UnknownValueHint => format!(
+            "unknown value hint, expected one of: {}",
+            ValueHint::ALL.map(|hint| hint.name()).join(", ")
+        ),
+    }
+}
+
+pub fn format_transport_error(error: TransportCodecError) -> String {
+    match error {
+        TransportCodecError::Encode(err) => format!("encode error: {err}"),
+        TransportCodecError::Decode(err) => format!("decode error: {err}"),
+        TransportCodecError::Checksum => "checksum mismatch".into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use protocol::{
+        MAX_ECHO_LEN, chunk::MAX_CHUNKED_COMMAND_LEN, response::tag_byte,
+        transport::LENGTH_PREFIXED_OVERHEAD,
+    };
+
+    use super::*;
+
+    fn frame_of(command: &str, framing: Framing) -> Vec<u8> {
+        match prepare_command(command, framing) {
+            Some(Outgoing::Command { frame, .. }) => frame,
+            other => panic!("`{command}` was not framed: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn command_round_trips_through_the_wire() {
+        for framing in Framing::ALL {
+            let Some(Outgoing::Command { payload, frame }) =
+                prepare_command("i2c read 0x48 0x00 2", framing)
+            else {
+                panic!("command was not framed");
+            };
+            assert_eq!(payload, encode_command("i2c read 0x48 0x00 2").unwrap());
+
+            let mut inbound = Inbound::new(framing, false);
+            inbound.push(&frame);
+            let received = inbound.next_frame().unwrap().unwrap();
+            assert_eq!(received.payload, payload);
+            assert_eq!(received.frame_len, frame.len());
+            assert_eq!(inbound.next_frame(), Ok(None));
+        }
+        assert_eq!(
+            frame_of("echo hi", Framing::LengthPrefixed).len(),
+            encode_command("echo hi").unwrap().len() + LENGTH_PREFIXED_OVERHEAD
+        );
+    }
+
+    #[test]
+    fn response_split_across_reads_becomes_one_message() {
+        let frame = encode_transport_frame(b"hi", Framing::Postcard).unwrap();
+        let (first, second) = frame.split_at(frame.len() / 2);
+        let mut inbound = Inbound::new(Framing::Postcard, false);
+        inbound.push(first);
+        assert_eq!(inbound.next_frame(), Ok(None));
+        inbound.push(second);
+        let received = inbound.next_frame().unwrap().unwrap();
+        assert_eq!(
+            payload_to_action(received.source, received.format, received.payload),
+            Action::IncomingMessage(DeviceMessage::Bytes(b"hi".to_vec()))
+        );
+    }
+
+    #[test]
+    fn only_a_partial_frame_without_progress_stalls() {
+        let timeout = Duration::from_millis(500);
+        let frame = encode_transport_frame(b"hi", Framing::Postcard).unwrap();
+        let mut inbound = Inbound::new(Framing::Postcard, false);
+        assert_eq!(inbound.stall_deadline(timeout), None);
+
+        inbound.push(&frame);
+        assert!(inbound.next_frame().unwrap().is_some());
+        assert_eq!(inbound.stall_deadline(timeout), None);
+
+        inbound.push(&frame[..3]);
+        let deadline = inbound.stall_deadline(timeout).unwrap();
+        assert_eq!(inbound.next_frame(), Ok(None));
+        assert_eq!(inbound.flush_stalled(deadline - timeout / 2, timeout), None);
+        assert_eq!(inbound.flush_stalled(deadline, timeout), Some(3));
+        assert!(inbound.pending().is_empty());
+        assert_eq!(inbound.flush_stalled(deadline + timeout, timeout), None);
+    }
+
+    #[test]
+    fn headers_claiming_more_than_the_cap_are_skipped_as_corruption() {
+        for framing in Framing::ALL {
+            let limit = encode_transport_frame(&[0xA5; 16], framing).unwrap();
+            let mut inbound = Inbound::new(framing, false).max_payload(16);
+            inbound.push(&limit);
+            assert_eq!(inbound.next_frame().unwrap().unwrap().payload, [0xA5; 16]);
+
+            // Only the header of the longer frame has arrived; it is dropped without waiting, and
+            // the frame after it decodes with the dropped bytes counted as skipped.
+            let oversize = encode_transport_frame(&[0xA5; 17], framing).unwrap();
+            inbound.push(&oversize[..2]);
+            assert_eq!(inbound.next_frame(), Ok(None), "{framing:?}");
+            let next = encode_transport_frame(b"next", framing).unwrap();
+            inbound.push(&next);
+            let received = inbound.next_frame().unwrap().unwrap();
+            assert_eq!(received.payload, b"next", "{framing:?}");
+            assert_eq!(received.skipped, oversize[..2].len(), "{framing:?}");
+        }
+        assert_eq!(default_max_payload(true), MAX_RESPONSE_LEN + 1);
+    }
+
+    #[test]
+    fn traced_frames_are_annotated_with_their_boundaries() {
+        let framing = Framing::LengthPrefixed;
+        let one = encode_transport_frame(b"abc", framing).unwrap();
+        let two = encode_transport_frame(b"hello", framing).unwrap();
+        let mut inbound = Inbound::new(framing, false).trace_frames(true);
+        inbound.push(&[one.as_slice(), &two, &one[..2]].concat());
+        while inbound.next_frame().unwrap().is_some() {}
+        assert_eq!(
+            inbound.take_trace().as_deref(),
+            Some(
+                "[frame 0: 0..7 len=3] [frame 1: 7..16 len=5] [pending 16..18: 2 undecoded byte(s)]"
+            )
+        );
+        assert_eq!(
+            inbound.take_trace().as_deref(),
+            Some("[pending 0..2: 2 undecoded byte(s)]")
+        );
+
+        // The carried-over bytes start the next trace, after a corrupt byte that is skipped.
+        inbound.push(&one[2..]);
+        inbound.push(&[0x00]);
+        inbound.push(&one);
+        while inbound.next_frame().unwrap().is_some() {}
+        assert_eq!(
+            inbound.take_trace().as_deref(),
+            Some("[frame 2: 0..7 len=3] [skipped 7..8] [frame 3: 8..15 len=3]")
+        );
+        assert_eq!(inbound.take_trace(), None);
+
+        let mut untraced = Inbound::new(framing, false);
+        untraced.push(&one);
+        assert!(untraced.next_frame().unwrap().is_some());
+        assert_eq!(untraced.take_trace(), None);
+    }
+
+    #[test]
+    fn tagged_response_keeps_its_method() {
+        let wire = [
+            &[tag_byte(Some(Method::I2c), ResponseFormat::Bytes)],
+            [0x12, 0x34].as_slice(),
+        ]
+        .concat();
+        let frame = encode_transport_frame(&wire, Framing::Postcard).unwrap();
+
+        let mut tagged = Inbound::new(Framing::Postcard, true);
+        tagged.push(&frame);
+        let received = tagged.next_frame().unwrap().unwrap();
+        assert_eq!(
+            payload_to_action(received.source, received.format, received.payload),
+            Action::TaggedResponse(Method::I2c, ResponseFormat::Bytes, vec![0x12, 0x34])
+        );
+
+        // An untagged session leaves the byte in the payload.
+        let mut untagged = Inbound::new(Framing::Postcard, false);
+        untagged.push(&frame);
+        assert_eq!(untagged.next_frame().unwrap().unwrap().payload, wire);
+    }
+
+    #[test]
+    fn blank_and_invalid_lines_are_not_framed() {
+        for blank in ["", "   ", "\t\t", " \t "] {
+            assert!(is_blank_line(blank), "{blank:?}");
+            assert_eq!(prepare_command(blank, Framing::Postcard), None);
+        }
+        assert!(!is_blank_line(" temp "));
+        assert_eq!(
+            prepare_command("i2c read 0x48", Framing::Postcard),
+            Some(Outgoing::Rejected(
+                "Error: Failed to encode command `i2c read 0x48`: missing argument at position 4"
+                    .into()
+            ))
+        );
+        assert_eq!(
+            prepare_command("i2c write 0x50 0x00 0xAA, 0x01", Framing::Postcard),
+            Some(Outgoing::Rejected(
+                "Error: Failed to encode command `i2c write 0x50 0x00 0xAA, 0x01`: invalid data bytes at position 5: mixes layouts; separate bytes with spaces, or write `{0xAA, 0x00}`"
+                    .into()
+            ))
+        );
+        assert_eq!(
+            prepare_command("i2c read 0x90 0x00 2", Framing::Postcard),
+            Some(Outgoing::Rejected(
+                "Error: Failed to encode command `i2c read 0x90 0x00 2`: i2c address 0x90 at position 3 is not 7-bit (0x00 to 0x7f); for an 8-bit address from a datasheet, use 0x48"
+                    .into()
+            ))
+        );
+        assert!(matches!(
+            prepare_command("i2c dump 0x50 0x00 16", Framing::Postcard),
+            Some(Outgoing::Dump(_))
+        ));
+    }
+
+    #[test]
+    fn commands_longer_than_the_device_takes_are_rejected() {
+        // An echo comes back in one response, so it is held to that rather than the chunked limit.
+        let fits = format!("echo {}", "x".repeat(MAX_ECHO_LEN));
+        assert!(matches!(
+            prepare_command(&fits, Framing::Postcard),
+            Some(Outgoing::Command { payload, .. }) if payload.len() == 2 + MAX_ECHO_LEN
+        ));
+        let pasted = format!("echo {}", "x".repeat(MAX_ECHO_LEN + 1));
+        let Some(Outgoing::Rejected(error)) = prepare_command(&pasted, Framing::Postcard) else {
+            panic!("an oversized command was sent");
+        };
+        assert!(
+            error.ends_with("encodes to 259 bytes; the device takes at most 258."),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn echoes_of_unsolicited_frames_are_rejected() {
+        let heartbeat = format!("echo {}", String::from_utf8(HEARTBEAT.to_vec()).unwrap());
+        let Some(Outgoing::Rejected(error)) = prepare_command(&heartbeat, Framing::Postcard) else {
+            panic!("an echo of the heartbeat was sent");
+        };
+        assert!(error.contains("heartbeat or busy/ready frame"), "{error}");
+        for control in [BUSY, READY] {
+            let line = format!("echo {}", String::from_utf8(control.to_vec()).unwrap());
+            assert!(matches!(
+                prepare_command(&line, Framing::Postcard),
+                Some(Outgoing::Rejected(_))
+            ));
+        }
+        // Only the exact payloads are ambiguous.
+        assert!(matches!(
+            prepare_command(&format!("{heartbeat}!"), Framing::Postcard),
+            Some(Outgoing::Command { .. })
+        ));
+    }
+
+    #[test]
+    fn commands_longer_than_a_frame_are_sent_as_chunks() {
+        let data = vec!["0xA5"; 255].join(" ");
+        let line = format!("i2c write 0x50 0x00 {data}");
+        let Some(Outgoing::Command { payload, frame }) = prepare_command(&line, Framing::Postcard)
+        else {
+            panic!("a full-length i2c write was not sent");
+        };
+        assert!(payload.len() > MAX_COMMAND_LEN);
+
+        let mut chunks = protocol::chunk::ChunkAssembler::<MAX_CHUNKED_COMMAND_LEN>::new();
+        let mut rest = frame.as_slice();
+        let mut frames = 0;
+        let reassembled = loop {
+            let decoded = decode_transport_frame_resyncing(rest, Framing::Postcard)
+                .unwrap()
+                .expect("the last chunk never arrived");
+            rest = &rest[decoded.consumed..];
+            frames += 1;
+            if let Some(command) = chunks.push(&decoded.payload).unwrap() {
+                break command.to_vec();
+            }
+        };
+        assert_eq!(frames, 2);
+        assert!(rest.is_empty());
+        assert_eq!(reassembled, payload);
+    }
+
+    #[test]
+    fn interpretation_is_the_command_that_was_encoded() {
+        for (line, shown) in [
+            ("i2c r 72 0 2 as i16be", "→ i2c read 0x48 0x00 2"),
+            (
+                "I2C W --bus 0 0x50 0x10 0xFF 1",
+                "→ i2c write --bus 0 0x50 0x10 0xff 0x01",
+            ),
+            ("spi config 3 500", "→ spi config mode3 500"),
+            (
+                "spi write 1 0x02 {0xAA, 170,}",
+                "→ spi write 1 0x02 0xaa 0xaa",
+            ),
+            ("temp # board sensor", "→ temp"),
+        ] {
+            let interpretation = interpret_command(line).unwrap();
+            assert_eq!(interpretation, shown);
+            let shown_command = interpretation.trim_start_matches("→ ");
+            assert_eq!(
+                encode_command(shown_command).unwrap(),
+                encode_command(line).unwrap()
+            );
+        }
+        for line in ["", "i2c read 0x48", "i2c dump 0x50 0x00 16", "ping 3"] {
+            assert_eq!(interpret_command(line), None, "{line}");
+        }
+    }
+
+    #[test]
+    fn bridge_lines_get_a_line_ending_and_exit_escapes() {
+        assert_eq!(bridge_bytes("AT"), (b"AT\r\n".to_vec(), false));
+        assert_eq!(bridge_bytes(" ~. "), (BRIDGE_ESCAPE.to_vec(), true));
+    }
+
+    #[test]
+    fn dry_run_shows_command_and_framed_bytes() {
+        let lines = dry_run_lines("i2c read 0x48 0x00 2", Framing::Postcard);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[1],
+            "  command, 6 bytes: 0x02 0x01 0x01 0x48 0x00 0x02"
+        );
+        let frame = frame_of("i2c read 0x48 0x00 2", Framing::Postcard);
+        assert_eq!(
+            lines[2],
+            format!(
+                "  postcard frame, {} bytes: {}",
+                frame.len(),
+                format_hex(&frame)
+            )
+        );
+    }
+
+    #[test]
+    fn dry_run_reports_encode_errors() {
+        assert_eq!(
+            dry_run_lines("i2c read 0x48", Framing::Postcard),
+            ["Error: Failed to encode command `i2c read 0x48`: missing argument at position 4"]
+        );
+        assert!(dry_run_lines("   ", Framing::Postcard).is_empty());
+    }
+
+    #[test]
+    fn dry_run_expands_dumps_into_reads() {
+        let lines = dry_run_lines("i2c dump 0x50 0xF0 32", Framing::LengthPrefixed);
+        assert_eq!(
+            lines[0],
+            "Dry run: `i2c dump 0x50 0xF0 32` is sent as 2 i2c read(s)"
+        );
+        assert_eq!(lines.len(), 5);
+        assert!(
+            lines[3].ends_with("0x02 0x01 0x01 0x50 0x00 0x10"),
+            "{}",
+            lines[3]
+        );
+    }
+
+    #[test]
+    fn naks_resend_the_last_frame_a_bounded_number_of_times() {
+        let mut retransmit = Retransmit::default();
+        assert!(retransmit.on_nak().is_err());
+
+        let frame = frame_of("echo hi", Framing::Postcard);
+        retransmit.sent(frame.clone(), false);
+        for attempt in 1..=MAX_RETRANSMITS {
+            let (resent, note) = retransmit.on_nak().unwrap();
+            assert_eq!(resent, frame);
+            let DeviceMessage::Text(note) = note else {
+                panic!("expected a note");
+            };
+            assert!(note.ends_with(&format!("({attempt}/{MAX_RETRANSMITS})")));
+        }
+        assert!(retransmit.on_nak().is_err());
+        // Giving up forgets the frame rather than resending it on a later NAK.
+        assert!(retransmit.on_nak().is_err());
+
+        retransmit.sent(frame.clone(), false);
+        assert!(retransmit.on_nak().is_ok());
+        retransmit.forget();
+        assert!(retransmit.on_nak().is_err());
+    }
+
+    #[test]
+    fn a_nak_for_the_middle_chunk_fails_the_command_instead_of_resending() {
+        let payload = encode_command(&format!("echo {}", "x".repeat(200))).unwrap();
+        let chunks = split_into_chunks(&payload, 80);
+        assert_eq!(chunks.len(), 3);
+        let frames: Vec<Vec<u8>> = chunks
+            .iter()
+            .map(|chunk| encode_transport_frame(chunk, Framing::Postcard).unwrap())
+            .collect();
+
+        // The device takes the first chunk, then NAKs the middle one; the NAK doesn't say which.
+        let mut assembler = protocol::chunk::ChunkAssembler::<MAX_CHUNKED_COMMAND_LEN>::new();
+        assert_eq!(assembler.push(&chunks[0]), Ok(None));
+
+        let mut retransmit = Retransmit::default();
+        retransmit.sent(frames.concat(), true);
+        let Err(DeviceMessage::Text(error)) = retransmit.on_nak() else {
+            panic!("a chunked run was resent");
+        };
+        assert!(error.contains("send the command again"), "{error}");
+        assert!(retransmit.on_nak().is_err());
+    }
+}
@@ -0,0 +1,119 @@
+//! The queue between the UI and a session's serial writer.
+//!
+//! An item counts as unsent from the moment it is queued until the writer is done with it, so a
+//! session closed before its queue drains can say how much never went out instead of losing it
+//! quietly.
+
+use std::sync::{
+    Arc,
+    atomic::{AtomicUsize, Ordering},
+};
+
+use tokio::sync::mpsc;
+
+use crate::pipeline::Outbound;
+
+/// A queue for one session.
+pub fn channel() -> (QueueSender, QueueReceiver) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let unsent = Arc::new(AtomicUsize::new(0));
+    (
+        QueueSender {
+            tx,
+            unsent: Arc::clone(&unsent),
+        },
+        QueueReceiver { rx, unsent },
+    )
+}
+
+#[derive(Debug)]
+pub struct QueueSender {
+    tx: mpsc::UnboundedSender<Outbound>,
+    unsent: Arc<AtomicUsize>,
+}
+
+impl QueueSender {
+    /// Queue `item`. It comes back if the writer has stopped.
+    pub fn send(&self, item: Outbound) -> Result<(), Outbound> {
+        self.unsent.fetch_add(1, Ordering::AcqRel);
+        self.tx.send(item).map_err(|err| {
+            self.unsent.fetch_sub(1, Ordering::AcqRel);
+            err.0
+        })
+    }
+
+    /// Close the queue. The writer still gets everything already queued, then stops; the count
+    /// returned keeps tracking what it hasn't finished.
+    pub fn close(self) -> Unsent {
+        Unsent(self.unsent)
+    }
+}
+
+/// Items of a closed queue the writer hasn't finished yet.
+#[derive(Debug)]
+pub struct Unsent(Arc<AtomicUsize>);
+
+impl Unsent {
+    pub fn count(&self) -> usize {
+        self.0.load(Ordering::Acquire)
+    }
+}
+
+#[derive(Debug)]
+pub struct QueueReceiver {
+    rx: mpsc::UnboundedReceiver<Outbound>,
+    unsent: Arc<AtomicUsize>,
+}
+
+impl QueueReceiver {
+    /// The next item, or `None` once the queue is closed and empty. The item stays counted as
+    /// unsent until the [`Handled`] returned with it is dropped.
+    pub async fn recv(&mut self) -> Option<(Outbound, Handled)> {
+        let item = self.rx.recv().await?;
+        Some((item, Handled(Arc::clone(&self.unsent))))
+    }
+}
+
+/// Marks a queued item as dealt with when dropped, however the writer finishes with it.
+#[derive(Debug)]
+pub struct Handled(Arc<AtomicUsize>);
+
+impl Drop for Handled {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn items_stay_unsent_until_handled() {
+        let (tx, mut rx) = channel();
+        tx.send(Outbound::Line("echo a".into())).unwrap();
+        tx.send(Outbound::Keystrokes(b"b".to_vec())).unwrap();
+        let unsent = tx.close();
+        assert_eq!(unsent.count(), 2);
+
+        let (item, handled) = rx.recv().await.unwrap();
+        assert_eq!(item, Outbound::Line("echo a".into()));
+        assert_eq!(unsent.count(), 2);
+        drop(handled);
+        assert_eq!(unsent.count(), 1);
+
+        let (_, handled) = rx.recv().await.unwrap();
+        drop(handled);
+        assert_eq!(unsent.count(), 0);
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[test]
+    fn sending_to_a_stopped_writer_gives_the_item_back() {
+        let (tx, rx) = channel();
+        drop(rx);
+        let item = Outbound::Line("temp".into());
+        assert_eq!(tx.send(item.clone()), Err(item));
+        assert_eq!(tx.close().count(), 0);
+    }
+}
@@ -0,0 +1,130 @@
+//! Plain-text command scripts.
+//!
+//! A script is one command per line in the same grammar typed into the terminal. Blank lines and
+//! `#` comments are ignored, so exported histories can carry notes and still be replayed with
+//! `--script`.
+
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use color_eyre::Result;
+use protocol::host::strip_comment;
+
+use crate::config;
+
+pub const SCRIPT_EXTENSION: &str = "siterm";
+
+/// Render commands as a replayable script, oldest first.
+/// Line breaks inside an entry are folded into spaces so every entry stays a single command.
+pub fn export<'a>(commands: impl IntoIterator<Item = &'a str>, exported_at: u64) -> String {
+    let mut script = format!("# SiTerm command history\n# exported at {exported_at} (unix time)\n");
+    for command in commands {
+        let line = command.replace(['\r', '\n'], " ");
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        script.push_str(line);
+        script.push('\n');
+    }
+    script
+}
+
+/// Extract the commands from a script, dropping blank lines and comments.
+pub fn parse(script: &str) -> Vec<String> {
+    script
+        .lines()
+        .filter(|line| !strip_comment(line).trim().is_empty())
+        .map(|line| line.trim().to_string())
+        .collect()
+}
+
+/// Read and parse a script file.
+pub fn load(path: &Path) -> Result<Vec<String>> {
+    Ok(parse(&fs::read_to_string(path)?))
+}
+
+/// Write commands to a fresh timestamped script under the data directory and return its path.
+pub fn export_to_data_dir<'a>(commands: impl IntoIterator<Item = &'a str>) -> Result<PathBuf> {
+    let exported_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    let directory = config::get_data_dir().join("scripts");
+    fs::create_dir_all(&directory)?;
+    Ok(write_new(
+        &directory,
+        exported_at,
+        &export(commands, exported_at),
+    )?)
+}
+
+/// Write `script` to `history-<exported_at>.siterm` in `directory`, or to `-2`, `-3` and so on
+/// after the time when an export in the same second already took the name.
+fn write_new(directory: &Path, exported_at: u64, script: &str) -> io::Result<PathBuf> {
+    for attempt in 1u32.. {
+        let name = match attempt {
+            1 => format!("history-{exported_at}.{SCRIPT_EXTENSION}"),
+            n => format!("history-{exported_at}-{n}.{SCRIPT_EXTENSION}"),
+        };
+        let path = directory.join(name);
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                file.write_all(script.as_bytes())?;
+                return Ok(path);
+            }
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(err) => return Err(err),
+        }
+    }
+    unreachable!("ran out of names for the script")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn export_then_parse_round_trips() {
+        let history = [
+            "echo hello world",
+            "i2c read 0x40 0x11 4",
+            "i2c write 0x50 0x20 0xAA 0xBB # with note",
+        ];
+        let script = export(history, 1_700_000_000);
+        assert!(script.starts_with("# SiTerm command history\n"));
+        assert_eq!(parse(&script), history);
+    }
+
+    #[test]
+    fn export_folds_line_breaks_and_skips_blank_entries() {
+        let script = export(["echo a\r\nb", "   ", "echo c"], 0);
+        assert_eq!(parse(&script), ["echo a  b", "echo c"]);
+    }
+
+    #[test]
+    fn exports_in_the_same_second_get_their_own_files() {
+        let directory = std::env::temp_dir().join(format!("siterm-scripts-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+
+        let first = write_new(&directory, 1_700_000_000, "echo one\n").unwrap();
+        let second = write_new(&directory, 1_700_000_000, "echo two\n").unwrap();
+        assert_eq!(first.file_name().unwrap(), "history-1700000000.siterm");
+        assert_eq!(second.file_name().unwrap(), "history-1700000000-2.siterm");
+        assert_eq!(fs::read_to_string(&first).unwrap(), "echo one\n");
+        assert_eq!(fs::read_to_string(&second).unwrap(), "echo two\n");
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn parse_ignores_comments_and_blank_lines() {
+        let script = "# header\n\n  echo one  \n\t# indented note\necho two # trailing\n";
+        assert_eq!(parse(script), ["echo one", "echo two # trailing"]);
+    }
+}
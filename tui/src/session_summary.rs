@@ -0,0 +1,149 @@
+//! What a connection did, summed up once it ends.
+//!
+//! The session's reader and writer tasks count frames, errors and `ping` round trips into a shared
+//! [`SessionCounters`] as they go. When the connection drops, the app takes a [`SessionTotals`]
+//! snapshot and shows its [`summary`](SessionTotals::summary) under the reason on the error screen,
+//! so runs can be compared at a glance.
+
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// Running totals for one connection, shared between its tasks.
+#[derive(Debug, Default)]
+pub struct SessionCounters {
+    frames_sent: AtomicU64,
+    frames_received: AtomicU64,
+    errors: AtomicU64,
+    round_trips: AtomicU64,
+    round_trip_micros: AtomicU64,
+}
+
+impl SessionCounters {
+    pub fn frame_sent(&self) {
+        self.frames_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn frame_received(&self) {
+        self.frames_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// An error response from the device, or bytes dropped from the stream.
+    pub fn error(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A `ping` reply that came back after `round_trip`.
+    pub fn round_trip(&self, round_trip: Duration) {
+        let micros = u64::try_from(round_trip.as_micros()).unwrap_or(u64::MAX);
+        self.round_trips.fetch_add(1, Ordering::Relaxed);
+        self.round_trip_micros.fetch_add(micros, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> SessionTotals {
+        SessionTotals {
+            frames_sent: self.frames_sent.load(Ordering::Relaxed),
+            frames_received: self.frames_received.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            round_trips: self.round_trips.load(Ordering::Relaxed),
+            round_trip_total: Duration::from_micros(self.round_trip_micros.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// A connection's totals at one moment.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SessionTotals {
+    pub frames_sent: u64,
+    pub frames_received: u64,
+    pub errors: u64,
+    pub round_trips: u64,
+    pub round_trip_total: Duration,
+}
+
+impl SessionTotals {
+    /// Mean `ping` round trip, if any were measured.
+    pub fn mean_latency(&self) -> Option<Duration> {
+        let count = u32::try_from(self.round_trips).ok().filter(|&n| n > 0)?;
+        Some(self.round_trip_total / count)
+    }
+
+    /// One line for a session that lasted `duration`.
+    pub fn summary(&self, duration: Duration) -> String {
+        let mut summary = format!("Session: {}", format_session_length(duration));
+        if self.frames_sent == 0 && self.frames_received == 0 {
+            summary.push_str(", no frames sent or received");
+        } else {
+            summary.push_str(&format!(
+                ", {} frames sent, {} received",
+                self.frames_sent, self.frames_received
+            ));
+        }
+        match self.errors {
+            0 => summary.push_str(", no errors"),
+            1 => summary.push_str(", 1 error"),
+            errors => summary.push_str(&format!(", {errors} errors")),
+        }
+        if let Some(mean) = self.mean_latency() {
+            summary.push_str(&format!(
+                ", average latency {:.1} ms",
+                mean.as_secs_f64() * 1000.0
+            ));
+        }
+        summary
+    }
+}
+
+/// Whole seconds, with minutes and hours once there are any: `45s`, `3m 07s`, `1h 02m 09s`.
+fn format_session_length(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    match (hours, minutes) {
+        (0, 0) => format!("{seconds}s"),
+        (0, _) => format!("{minutes}m {seconds:02}s"),
+        _ => format!("{hours}h {minutes:02}m {seconds:02}s"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarises_the_counters() {
+        let counters = SessionCounters::default();
+        for _ in 0..12 {
+            counters.frame_sent();
+        }
+        for _ in 0..11 {
+            counters.frame_received();
+        }
+        counters.error();
+        counters.round_trip(Duration::from_micros(3_000));
+        counters.round_trip(Duration::from_micros(3_400));
+
+        assert_eq!(
+            counters.snapshot().summary(Duration::from_secs(125)),
+            "Session: 2m 05s, 12 frames sent, 11 received, 1 error, average latency 3.2 ms"
+        );
+    }
+
+    #[test]
+    fn a_session_without_traffic_says_so() {
+        let totals = SessionCounters::default().snapshot();
+        assert_eq!(totals.mean_latency(), None);
+        assert_eq!(
+            totals.summary(Duration::from_millis(4_900)),
+            "Session: 4s, no frames sent or received, no errors"
+        );
+        let errors = SessionTotals {
+            errors: 3,
+            ..totals
+        };
+        assert_eq!(
+            errors.summary(Duration::from_secs(3_729)),
+            "Session: 1h 02m 09s, no frames sent or received, 3 errors"
+        );
+    }
+}
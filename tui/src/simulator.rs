@@ -0,0 +1,480 @@
+//! In-process stand-in for the firmware, for running SiTerm without hardware (`--simulate`).
+//!
+//! The simulator answers the same handshake and speaks the same frames as the device, so the
+//! whole encode, decode and render loop runs as it would against a board. Only a few commands are
+//! implemented: echo, i2c and spi reads (which return a counting pattern starting at the register,
+//! so each register of an `i2c readm` reads back as itself), i2c and spi writes, spi config and
+//! the temperature sensor. Commands sent as chunks are put back together first, and a response
+//! longer than the firmware's response buffer fails the command as it would on the device. A frame
+//! whose CRC doesn't match is answered with a NAK, so the host's resend path runs too.
+//!
+//! Heartbeat commands are confirmed but no heartbeats are sent. `config` reads back the SPI and
+//! heartbeat settings the session has made and `config reset` puts them back to the defaults.
+//! `identify` is confirmed with no LED to flash, `stats` reports a link that never fails,
+//! `selftest` reports a passing board with nothing on either I2C bus, `resetreason` reports a
+//! power-on reset, `info` reports a device with no UART, and `format` sets the format tagged
+//! responses carry. Anything else is answered with the error a device without that handler would
+//! send.
+
+use std::{fmt::Write as _, io};
+
+use protocol::{
+    Command, HANDSHAKE_DELIMITER, HANDSHAKE_INCOMPATIBLE, I2C_BUS_COUNT, MAX_I2C_READ_LEN, Method,
+    ProtocolError,
+    chunk::{self, CHUNK_ERROR_CODE, ChunkAssembler, MAX_CHUNKED_COMMAND_LEN},
+    decode_command,
+    device_info::{DEVICE_INFO_MAX_LEN, DeviceConfig, DeviceInfo, DeviceLabel},
+    handshake::{self, HandshakeRequest},
+    heartbeat::{self, HeartbeatSchedule},
+    host::{TransportCodecError, encode_transport_frame, try_decode_transport_frame},
+    identify, nak,
+    reset_reason::ResetReason,
+    response::{self as response_format, ERROR_PREFIX, MAX_RESPONSE_LEN, ResponseFormat, tag_byte},
+    self_test::{AdcCheck, BusScan, SelfTestReport},
+    spi,
+    stats::Stats,
+    temperature::DeciCelsius,
+    transport::Framing,
+};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+use tracing::debug;
+
+use crate::transport::Transport;
+
+/// Port name offered by the preconnect screen in simulation mode.
+pub const SIMULATED_PORT: &str = "simulator";
+/// What the simulated temperature sensor always reads, 23.1 °C.
+const SIMULATED_TEMPERATURE: DeciCelsius = DeciCelsius(231);
+/// Bytes buffered in each direction of the in-memory link.
+const LINK_BUFFER: usize = 4096;
+
+/// Transport that reaches a fresh simulated device, whatever port is asked for.
+pub struct Simulator;
+
+impl Transport for Simulator {
+    type Stream = DuplexStream;
+
+    async fn open(&self, _port: &str, _baud_rate: u32) -> Result<DuplexStream, String> {
+        Ok(connect())
+    }
+}
+
+/// Start a simulated device and return the host end of its link.
+pub fn connect() -> DuplexStream {
+    let (host, device) = tokio::io::duplex(LINK_BUFFER);
+    tokio::spawn(async move {
+        if let Err(err) = run(device).await {
+            debug!(error = %err, "simulator stopped");
+        }
+    });
+    host
+}
+
+async fn run(mut link: DuplexStream) -> io::Result<()> {
+    let Some((framing, tagged)) = handshake(&mut link).await? else {
+        return Ok(());
+    };
+
+    let mut settings = DeviceConfig::DEFAULT;
+    let mut format = ResponseFormat::Auto;
+    let mut chunks = ChunkAssembler::<MAX_CHUNKED_COMMAND_LEN>::new();
+    let mut pending = Vec::new();
+    let mut read_buffer = [0u8; 256];
+    loop {
+        let n = link.read(&mut read_buffer).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        pending.extend_from_slice(&read_buffer[..n]);
+        loop {
+            // Like the firmware, a bad frame clears the buffer rather than resyncing.
+            let (source, response) = match try_decode_transport_frame(&pending, framing) {
+                Ok(Some((payload, consumed))) => {
+                    pending.drain(..consumed);
+                    if !chunk::is_chunk(&payload) {
+                        respond(&payload, &mut settings, &mut format)
+                    } else {
+                        match chunks.push(&payload) {
+                            Ok(Some(command)) => respond(command, &mut settings, &mut format),
+                            Ok(None) => continue,
+                            Err(_) => (None, error(CHUNK_ERROR_CODE)),
+                        }
+                    }
+                }
+                Ok(None) => break,
+                Err(TransportCodecError::Checksum) => {
+                    pending.clear();
+                    (None, nak::NAK.to_vec())
+                }
+                Err(_) => {
+                    pending.clear();
+                    (None, error("InvalidChecksum"))
+                }
+            };
+            let payload = if tagged {
+                [&[tag_byte(source, format)], response.as_slice()].concat()
+            } else {
+                response
+            };
+            if let Ok(frame) = encode_transport_frame(&payload, framing) {
+                link.write_all(&frame).await?;
+            }
+        }
+    }
+}
+
+/// Wait for a compatible handshake line and accept it. Returns the negotiated framing and
+/// tagging, or `None` if the host hung up first.
+async fn handshake(link: &mut DuplexStream) -> io::Result<Option<(Framing, bool)>> {
+    let delimiter = HANDSHAKE_DELIMITER.as_bytes();
+    let mut line = Vec::new();
+    loop {
+        let byte = match link.read_u8().await {
+            Ok(byte) => byte,
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        line.push(byte);
+        if !line.ends_with(delimiter) {
+            continue;
+        }
+        line.truncate(line.len() - delimiter.len());
+        match handshake::check_request(&line) {
+            HandshakeRequest::Compatible { framing, tagged } => {
+                link.write_all(handshake::response(framing, tagged).as_bytes())
+                    .await?;
+                return Ok(Some((framing, tagged)));
+            }
+            HandshakeRequest::Incompatible { .. } => {
+                link.write_all(HANDSHAKE_INCOMPATIBLE.as_bytes()).await?;
+            }
+            HandshakeRequest::Unrecognised => {}
+        }
+        line.clear();
+    }
+}
+
+/// Run one command against the session's `settings` and response `format` and return its
+/// response with the method to tag it with.
+fn respond(
+    payload: &[u8],
+    settings: &mut DeviceConfig,
+    format: &mut ResponseFormat,
+) -> (Option<Method>, Vec<u8>) {
+    let command = match decode_command(payload) {
+        Ok(command) => command,
+        // Mirrors the firmware's mapping of decode failures onto error codes.
+        Err(ProtocolError::UnknownBus(_)) => return (None, error("InvalidBus")),
+        Err(ProtocolError::InvalidAddress(_)) => return (None, error("InvalidAddress")),
+        Err(ProtocolError::UnknownChipSelect(_)) => return (None, error("InvalidChipSelect")),
+        Err(ProtocolError::Empty | ProtocolError::MalformedPayload { .. }) => {
+            return (None, error("InvalidChecksum"));
+        }
+        Err(_) => return (None, error("UnknownCommand")),
+    };
+    let response = match command {
+        Command::EchoWrite { payload } => payload.to_vec(),
+        Command::I2cRead {
+            register, length, ..
+        }
+        | Command::SpiRead {
+            register, length, ..
+        } => (0..length).map(|i| register.wrapping_add(i)).collect(),
+        Command::I2cReadMulti { registers, .. } => registers
+            .iter()
+            .flat_map(|&register| [register, register])
+            .collect(),
+        Command::I2cWrite {
+            address,
+            register,
+            payload,
+            ..
+        } => {
+            let mut response = String::new();
+            let _ = write!(
+                response,
+                "OK [{address:#04X}, {register:#04X}, {}]",
+                payload.len()
+            );
+            response.into_bytes()
+        }
+        Command::SpiWrite {
+            cs,
+            register,
+            payload,
+        } => {
+            let mut response = String::new();
+            let _ = write!(response, "OK [CS{cs}, {register:#04X}, {}]", payload.len());
+            response.into_bytes()
+        }
+        Command::SpiConfig { mode, freq_khz } => match spi::config_error(mode, freq_khz) {
+            Some(problem) => error(&format!("ExecutionFailed: spi error: {problem}")),
+            None => {
+                settings.spi_mode = mode;
+                settings.spi_freq_khz = freq_khz;
+                let mut response = String::new();
+                let _ = spi::confirmation(&mut response, mode, freq_khz);
+                response.into_bytes()
+            }
+        },
+        Command::Temperature => SIMULATED_TEMPERATURE.to_be_bytes().to_vec(),
+        Command::UartBridge { .. } => error("UnknownCommand"),
+        Command::Heartbeat {
+            interval_ms,
+            enable,
+        } => {
+            let mut schedule = HeartbeatSchedule::new();
+            schedule.configure(enable, interval_ms, 0);
+            settings.heartbeat_ms = schedule.interval_ms();
+            let mut response = String::new();
+            let _ = heartbeat::confirmation(&mut response, schedule.interval_ms());
+            response.into_bytes()
+        }
+        Command::GetConfig => settings.encode().to_vec(),
+        Command::ResetConfig => {
+            *settings = DeviceConfig::DEFAULT;
+            settings.encode().to_vec()
+        }
+        Command::Info => {
+            let info = DeviceInfo {
+                max_i2c_read: MAX_I2C_READ_LEN,
+                config: *settings,
+                uart: None,
+                label: DeviceLabel::new("SiTerm simulator").unwrap_or_default(),
+            };
+            let mut encoded = [0; DEVICE_INFO_MAX_LEN];
+            let len = info.encode(&mut encoded);
+            encoded[..len].to_vec()
+        }
+        Command::Identify => {
+            let mut response = String::new();
+            let _ = identify::confirmation(&mut response);
+            response.into_bytes()
+        }
+        Command::Stats => Stats::new().to_string().into_bytes(),
+        Command::SelfTest => SelfTestReport {
+            led_colours: 0,
+            i2c: [BusScan::Finished { found: 0 }; I2C_BUS_COUNT as usize],
+            adc: AdcCheck::Reading(SIMULATED_TEMPERATURE),
+        }
+        .to_string()
+        .into_bytes(),
+        Command::ResetReason => ResetReason::PowerOn.to_string().into_bytes(),
+        Command::SetResponseFormat { format: requested } => {
+            *format = requested;
+            let mut response = String::new();
+            let _ = response_format::confirmation(&mut response, requested);
+            response.into_bytes()
+        }
+    };
+    if response.len() > MAX_RESPONSE_LEN {
+        // The firmware's response buffer holds no more, so it fails the command instead.
+        return (Some(command.method()), error("ExecutionFailed"));
+    }
+    (Some(command.method()), response)
+}
+
+fn error(code: &str) -> Vec<u8> {
+    [ERROR_PREFIX, code.as_bytes()].concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use protocol::{handshake::HandshakeReply, host::encode_command};
+
+    use super::*;
+    use crate::pipeline::{Inbound, Outgoing, Received, prepare_command};
+
+    async fn open(framing: Framing, tagged: bool) -> DuplexStream {
+        let mut link = connect();
+        let command = handshake::command(framing, tagged).to_owned() + HANDSHAKE_DELIMITER;
+        link.write_all(command.as_bytes()).await.unwrap();
+        let mut reply = Vec::new();
+        loop {
+            match handshake::check_reply(&reply, framing, tagged) {
+                HandshakeReply::Pending => reply.push(link.read_u8().await.unwrap()),
+                HandshakeReply::Accepted => return link,
+                other => panic!("handshake failed: {other:?}"),
+            }
+        }
+    }
+
+    async fn exchange(link: &mut DuplexStream, inbound: &mut Inbound, command: &str) -> Vec<u8> {
+        receive(link, inbound, command).await.payload
+    }
+
+    async fn receive(link: &mut DuplexStream, inbound: &mut Inbound, command: &str) -> Received {
+        let Some(Outgoing::Command { frame, .. }) = prepare_command(command, Framing::Postcard)
+        else {
+            panic!("`{command}` was not framed");
+        };
+        link.write_all(&frame).await.unwrap();
+        let mut buffer = [0u8; 256];
+        loop {
+            if let Some(received) = inbound.next_frame().unwrap() {
+                return received;
+            }
+            let n = link.read(&mut buffer).await.unwrap();
+            inbound.push(&buffer[..n]);
+        }
+    }
+
+    #[tokio::test]
+    async fn simulator_handshakes_and_echoes() {
+        let mut link = open(Framing::Postcard, false).await;
+        let mut inbound = Inbound::new(Framing::Postcard, false);
+        assert_eq!(exchange(&mut link, &mut inbound, "echo hi").await, b"hi");
+        assert_eq!(
+            exchange(&mut link, &mut inbound, "i2c read 0x48 0xFE 3").await,
+            [0xFE, 0xFF, 0x00]
+        );
+        assert_eq!(
+            exchange(&mut link, &mut inbound, "uart bridge 9600").await,
+            b"ERR: UnknownCommand"
+        );
+    }
+
+    #[test]
+    fn responses_are_capped_where_the_firmware_caps_them() {
+        let mut settings = DeviceConfig::DEFAULT;
+        let mut format = ResponseFormat::Auto;
+        for (len, expected) in [
+            (MAX_RESPONSE_LEN, vec![b'x'; MAX_RESPONSE_LEN]),
+            (MAX_RESPONSE_LEN + 1, error("ExecutionFailed")),
+        ] {
+            let command = encode_command(&format!("echo {}", "x".repeat(len))).unwrap();
+            let (_, response) = respond(&command, &mut settings, &mut format);
+            assert_eq!(response, expected, "{len}");
+        }
+    }
+
+    #[tokio::test]
+    async fn a_corrupted_frame_is_naked_like_on_the_device() {
+        let mut link = open(Framing::Postcard, false).await;
+        let mut inbound = Inbound::new(Framing::Postcard, false);
+        let mut frame =
+            encode_transport_frame(&encode_command("echo hi").unwrap(), Framing::Postcard).unwrap();
+        // Flip a payload bit; the length and layout still line up.
+        frame[3] ^= 0x20;
+        link.write_all(&frame).await.unwrap();
+        let mut buffer = [0u8; 256];
+        let nak = loop {
+            if let Some(received) = inbound.next_frame().unwrap() {
+                break received.payload;
+            }
+            let n = link.read(&mut buffer).await.unwrap();
+            inbound.push(&buffer[..n]);
+        };
+        assert_eq!(nak, nak::NAK);
+        assert_eq!(exchange(&mut link, &mut inbound, "echo hi").await, b"hi");
+    }
+
+    #[tokio::test]
+    async fn simulator_reassembles_chunked_commands() {
+        let mut link = open(Framing::Postcard, false).await;
+        let mut inbound = Inbound::new(Framing::Postcard, false);
+        let data = vec!["0x5A"; 255].join(" ");
+        assert_eq!(
+            exchange(
+                &mut link,
+                &mut inbound,
+                &format!("i2c write 0x50 0x00 {data}")
+            )
+            .await,
+            b"OK [0x50, 0x00, 255]"
+        );
+
+        // A chunk with no start is refused, and the link carries on.
+        let stray = [chunk::CHUNK_MARKER, chunk::CHUNK_END, 1, 0, 4, 0x01];
+        link.write_all(&encode_transport_frame(&stray, Framing::Postcard).unwrap())
+            .await
+            .unwrap();
+        let mut buffer = [0u8; 64];
+        let refused = loop {
+            if let Some(received) = inbound.next_frame().unwrap() {
+                break received.payload;
+            }
+            let n = link.read(&mut buffer).await.unwrap();
+            inbound.push(&buffer[..n]);
+        };
+        assert_eq!(refused, b"ERR: InvalidChunk");
+        assert_eq!(exchange(&mut link, &mut inbound, "echo hi").await, b"hi");
+    }
+
+    #[tokio::test]
+    async fn simulator_honours_negotiated_framing_and_tags() {
+        let mut link = open(Framing::LengthPrefixed, true).await;
+        let Some(Outgoing::Command { frame, .. }) =
+            prepare_command("temp", Framing::LengthPrefixed)
+        else {
+            panic!("temp was not framed");
+        };
+        link.write_all(&frame).await.unwrap();
+
+        let mut inbound = Inbound::new(Framing::LengthPrefixed, true);
+        let mut buffer = [0u8; 64];
+        let received = loop {
+            if let Some(received) = inbound.next_frame().unwrap() {
+                break received;
+            }
+            let n = link.read(&mut buffer).await.unwrap();
+            inbound.push(&buffer[..n]);
+        };
+        assert_eq!(received.source, Some(Method::Temp));
+        assert_eq!(received.payload, SIMULATED_TEMPERATURE.to_be_bytes());
+    }
+
+    #[tokio::test]
+    async fn config_reset_restores_the_defaults_and_keeps_the_session() {
+        let mut link = open(Framing::Postcard, false).await;
+        let mut inbound = Inbound::new(Framing::Postcard, false);
+        exchange(&mut link, &mut inbound, "spi config mode3 4000").await;
+        exchange(&mut link, &mut inbound, "heartbeat on 1000").await;
+        let changed = DeviceConfig {
+            spi_mode: 3,
+            spi_freq_khz: 4000,
+            heartbeat_ms: Some(1000),
+        };
+        assert_eq!(
+            exchange(&mut link, &mut inbound, "config").await,
+            changed.encode()
+        );
+
+        assert_eq!(
+            exchange(&mut link, &mut inbound, "config reset").await,
+            DeviceConfig::DEFAULT.encode()
+        );
+        assert_eq!(
+            exchange(&mut link, &mut inbound, "config").await,
+            DeviceConfig::DEFAULT.encode()
+        );
+        assert_eq!(exchange(&mut link, &mut inbound, "echo hi").await, b"hi");
+    }
+
+    #[tokio::test]
+    async fn tags_carry_the_format_the_host_set() {
+        let mut link = open(Framing::Postcard, true).await;
+        let mut inbound = Inbound::new(Framing::Postcard, true);
+        let echoed = receive(&mut link, &mut inbound, "echo hi").await;
+        assert_eq!(echoed.format, ResponseFormat::Auto);
+
+        let confirmed = receive(&mut link, &mut inbound, "format text").await;
+        assert_eq!(
+            (confirmed.source, confirmed.format, confirmed.payload),
+            (
+                Some(Method::Format),
+                ResponseFormat::Text,
+                b"responses as text".to_vec()
+            )
+        );
+        let echoed = receive(&mut link, &mut inbound, "echo hi").await;
+        assert_eq!(
+            (echoed.format, echoed.payload),
+            (ResponseFormat::Text, b"hi".to_vec())
+        );
+
+        receive(&mut link, &mut inbound, "format bytes").await;
+        let read = receive(&mut link, &mut inbound, "i2c read 0x48 0x00 1").await;
+        assert_eq!(read.format, ResponseFormat::Bytes);
+    }
+}
@@ -0,0 +1,193 @@
+//! Colours for the whole interface, picked with `--theme`.
+//!
+//! Components don't name colours themselves; they ask the session's [`Palette`] for the style of
+//! a role (an error, the selection, an accent) so a theme can change every screen at once.
+//!
+//! The `high-contrast` theme is bright text on black with thick borders. It drops the dim and
+//! light-blue accents of the default theme, and anything that matters (errors, warnings, the
+//! selection) is bold or reversed as well as coloured, so it still stands out without colour.
+
+use ratatui::{
+    style::{Color, Modifier, Style},
+    widgets::{Block, BorderType, Borders},
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Theme {
+    #[default]
+    Default,
+    HighContrast,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 2] = [Theme::Default, Theme::HighContrast];
+
+    /// Name used on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Theme::Default => "default",
+            Theme::HighContrast => "high-contrast",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|theme| theme.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn palette(self) -> Palette {
+        match self {
+            Theme::Default => Palette::DEFAULT,
+            Theme::HighContrast => Palette::HIGH_CONTRAST,
+        }
+    }
+}
+
+/// The style of each role a component can ask for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    /// Behind everything; the default theme leaves the terminal's own colours alone.
+    pub base: Style,
+    pub border: Style,
+    pub border_type: BorderType,
+    /// Prompts, the bridge status and other things worth finding at a glance.
+    pub accent: Style,
+    /// Top to bottom, the rows of the banner and the cat on the preconnect screen, and the parts
+    /// of the command syntax in help.
+    pub gradient: [Style; 4],
+    /// The highlighted row of a list.
+    pub selection: Style,
+    /// The byte the inspector's cursor is on.
+    pub cursor: Style,
+    /// The title of the screen shown while connecting.
+    pub heading: Style,
+    /// The title of the list the arrow keys move in.
+    pub focus: Style,
+    pub error: Style,
+    pub warning: Style,
+    pub success: Style,
+    /// Keystroke mode and auto-repeat, which change what the keyboard does.
+    pub mode: Style,
+    /// Hints and settled history entries, meant to recede.
+    pub muted: Style,
+    /// Laid over the text the message search found.
+    pub search_match: Style,
+}
+
+impl Palette {
+    pub const DEFAULT: Self = Self {
+        base: Style::new(),
+        border: Style::new(),
+        border_type: BorderType::Plain,
+        accent: Style::new().fg(Color::Cyan),
+        gradient: [
+            Style::new().fg(Color::Cyan),
+            Style::new().fg(Color::LightCyan),
+            Style::new().fg(Color::LightBlue),
+            Style::new().fg(Color::Blue),
+        ],
+        selection: Style::new()
+            .fg(Color::Black)
+            .bg(Color::LightBlue)
+            .add_modifier(Modifier::BOLD),
+        cursor: Style::new().fg(Color::Black).bg(Color::LightBlue),
+        heading: Style::new()
+            .fg(Color::LightCyan)
+            .add_modifier(Modifier::BOLD),
+        focus: Style::new().fg(Color::Blue),
+        error: Style::new().fg(Color::Red),
+        warning: Style::new().fg(Color::Yellow),
+        success: Style::new().fg(Color::Green),
+        mode: Style::new().fg(Color::Magenta),
+        muted: Style::new().fg(Color::DarkGray),
+        search_match: Style::new().add_modifier(Modifier::REVERSED),
+    };
+
+    pub const HIGH_CONTRAST: Self = Self {
+        base: Style::new().fg(Color::White).bg(Color::Black),
+        border: Style::new().fg(Color::White).add_modifier(Modifier::BOLD),
+        border_type: BorderType::Thick,
+        accent: Style::new()
+            .fg(Color::LightCyan)
+            .add_modifier(Modifier::BOLD),
+        gradient: [Style::new().fg(Color::White).add_modifier(Modifier::BOLD); 4],
+        selection: Style::new()
+            .fg(Color::Black)
+            .bg(Color::White)
+            .add_modifier(Modifier::BOLD),
+        cursor: Style::new()
+            .fg(Color::Black)
+            .bg(Color::White)
+            .add_modifier(Modifier::BOLD),
+        heading: Style::new()
+            .fg(Color::LightCyan)
+            .add_modifier(Modifier::BOLD),
+        focus: Style::new()
+            .fg(Color::White)
+            .add_modifier(Modifier::BOLD.union(Modifier::REVERSED)),
+        error: Style::new()
+            .fg(Color::LightRed)
+            .add_modifier(Modifier::BOLD.union(Modifier::UNDERLINED)),
+        warning: Style::new()
+            .fg(Color::LightYellow)
+            .add_modifier(Modifier::BOLD),
+        success: Style::new()
+            .fg(Color::LightGreen)
+            .add_modifier(Modifier::BOLD),
+        mode: Style::new()
+            .fg(Color::LightMagenta)
+            .add_modifier(Modifier::BOLD),
+        muted: Style::new().fg(Color::White),
+        search_match: Style::new().add_modifier(Modifier::BOLD.union(Modifier::REVERSED)),
+    };
+
+    /// A bordered block in the theme's border style.
+    pub fn block(&self) -> Block<'static> {
+        Block::default()
+            .borders(Borders::ALL)
+            .border_type(self.border_type)
+            .border_style(self.border)
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn themes_are_named_on_the_command_line() {
+        for theme in Theme::ALL {
+            assert_eq!(Theme::from_name(theme.name()), Some(theme));
+        }
+        assert_eq!(Theme::from_name("High-Contrast"), Some(Theme::HighContrast));
+        assert_eq!(Theme::from_name("solarized"), None);
+    }
+
+    #[test]
+    fn high_contrast_marks_state_with_more_than_colour() {
+        let palette = Theme::HighContrast.palette();
+        for style in [
+            palette.error,
+            palette.warning,
+            palette.selection,
+            palette.cursor,
+            palette.heading,
+            palette.focus,
+            palette.mode,
+            palette.search_match,
+        ] {
+            assert!(style.add_modifier.contains(Modifier::BOLD), "{style:?}");
+        }
+        let dim = [Color::DarkGray, Color::LightBlue, Color::Blue];
+        for style in palette.gradient.iter().chain([&palette.muted]) {
+            assert!(!dim.contains(&style.fg.unwrap()), "{style:?}");
+        }
+    }
+}
@@ -0,0 +1,408 @@
+//! Byte pipes a session can run over.
+//!
+//! The handshake and the session in `app` only need something to read from and write to, so each
+//! backend just has to open its stream. The serial port is the default; `--simulate` swaps in the
+//! in-process [`Simulator`](crate::simulator::Simulator), and `--tcp` lists a [`Tcp`] port whose
+//! name starts with [`TCP_PREFIX`].
+
+use std::{fmt, io};
+
+use protocol::{device_info::Parity, transport::Framing};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpStream,
+};
+use tokio_serial::{DataBits, SerialPort, SerialPortBuilderExt, SerialStream, StopBits};
+
+use crate::keymap::KeyBinding;
+
+/// A way of reaching a device.
+pub trait Transport {
+    /// The open connection the handshake and session run over.
+    type Stream: AsyncRead + AsyncWrite + Unpin + Send + 'static;
+
+    /// Open `port`. The error is shown on the error screen as is.
+    fn open(
+        &self,
+        port: &str,
+        baud_rate: u32,
+    ) -> impl Future<Output = Result<Self::Stream, String>> + Send;
+
+    /// Character format `stream` was opened with, for transports whose line has one.
+    fn line(_stream: &Self::Stream) -> Option<LineSettings> {
+        None
+    }
+}
+
+/// A local serial port, 8N1 at the chosen baud rate.
+pub struct Serial;
+
+impl Transport for Serial {
+    type Stream = SerialStream;
+
+    async fn open(&self, port: &str, baud_rate: u32) -> Result<SerialStream, String> {
+        let serial_port_builder = tokio_serial::new(port, baud_rate)
+            .data_bits(tokio_serial::DataBits::Eight)
+            .stop_bits(tokio_serial::StopBits::One)
+            .parity(tokio_serial::Parity::None)
+            .timeout(std::time::Duration::from_millis(1000));
+
+        let serial_port = serial_port_builder
+            .open_native_async()
+            .map_err(|e| format!("Failed to open serial port {port}.\nError: {e}"))?;
+
+        serial_port
+            .clear(tokio_serial::ClearBuffer::All)
+            .map_err(|e| format!("Failed to clear serial port buffer.\nError {e}"))?;
+
+        Ok(serial_port)
+    }
+
+    fn line(stream: &SerialStream) -> Option<LineSettings> {
+        LineSettings::of(stream)
+    }
+}
+
+/// Serde mirror of protocol's [`Parity`], which stays free of serde.
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Parity")]
+enum ParityDef {
+    None,
+    Even,
+    Odd,
+}
+
+/// Serde mirror of protocol's [`Framing`], which stays free of serde.
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Framing")]
+enum FramingDef {
+    Postcard,
+    LengthPrefixed,
+}
+
+/// How a serial line paces the sender.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FlowControl {
+    None,
+    /// XON/XOFF characters in the data.
+    Software,
+    /// The RTS and CTS lines.
+    Hardware,
+}
+
+impl FlowControl {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FlowControl::None => "no",
+            FlowControl::Software => "XON/XOFF",
+            FlowControl::Hardware => "RTS/CTS",
+        }
+    }
+}
+
+/// Character format and flow control of a serial line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LineSettings {
+    pub data_bits: u8,
+    #[serde(with = "ParityDef")]
+    pub parity: Parity,
+    pub stop_bits: u8,
+    pub flow_control: FlowControl,
+}
+
+impl LineSettings {
+    /// What `port` is set to, or `None` when it can't say.
+    fn of(port: &impl SerialPort) -> Option<Self> {
+        let data_bits = match port.data_bits().ok()? {
+            DataBits::Five => 5,
+            DataBits::Six => 6,
+            DataBits::Seven => 7,
+            DataBits::Eight => 8,
+        };
+        let parity = match port.parity().ok()? {
+            tokio_serial::Parity::None => Parity::None,
+            tokio_serial::Parity::Even => Parity::Even,
+            tokio_serial::Parity::Odd => Parity::Odd,
+        };
+        let stop_bits = match port.stop_bits().ok()? {
+            StopBits::One => 1,
+            StopBits::Two => 2,
+        };
+        let flow_control = match port.flow_control().ok()? {
+            tokio_serial::FlowControl::None => FlowControl::None,
+            tokio_serial::FlowControl::Software => FlowControl::Software,
+            tokio_serial::FlowControl::Hardware => FlowControl::Hardware,
+        };
+        Some(Self {
+            data_bits,
+            parity,
+            stop_bits,
+            flow_control,
+        })
+    }
+}
+
+/// The usual shorthand and the flow control, e.g. `8N1, no flow control`.
+impl fmt::Display for LineSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parity = match self.parity {
+            Parity::None => 'N',
+            Parity::Even => 'E',
+            Parity::Odd => 'O',
+        };
+        write!(
+            f,
+            "{}{parity}{}, {} flow control",
+            self.data_bits,
+            self.stop_bits,
+            self.flow_control.name()
+        )
+    }
+}
+
+/// What a session's traffic is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SessionMode {
+    /// Framed commands and their responses.
+    #[default]
+    Commands,
+    /// Raw bytes to and from a UART the device bridged at this baud rate.
+    Bridged(u32),
+}
+
+impl fmt::Display for SessionMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionMode::Commands => f.write_str("commands"),
+            SessionMode::Bridged(baud) => write!(f, "UART bridge @ {baud} baud"),
+        }
+    }
+}
+
+/// What an established session runs over.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConnectionInfo {
+    pub port: String,
+    pub baud_rate: u32,
+    /// `None` where the transport has no line settings of its own, such as over TCP.
+    pub line: Option<LineSettings>,
+    /// Frame layout agreed in the handshake.
+    #[serde(with = "FramingDef")]
+    pub framing: Framing,
+    /// Whether responses arrive tagged with the method that produced them.
+    pub tagged: bool,
+    pub mode: SessionMode,
+    /// Label the device reported, once it has.
+    pub label: Option<String>,
+}
+
+impl ConnectionInfo {
+    /// One line for the session header, e.g.
+    /// `/dev/ttyACM0 @ 115200 baud, 8N1, no flow control, postcard framing, commands`. A TCP port
+    /// leaves out the baud rate, which is the server's to set.
+    pub fn summary(&self) -> String {
+        let mut parts = vec![match tcp_address(&self.port) {
+            Some(_) => self.port.clone(),
+            None => format!("{} @ {} baud", self.port, self.baud_rate),
+        }];
+        if let Some(line) = self.line {
+            parts.push(line.to_string());
+        }
+        parts.push(format!("{} framing", self.framing.name()));
+        if self.tagged {
+            parts.push("tagged".into());
+        }
+        parts.push(self.mode.to_string());
+        parts.extend(self.label.clone());
+        parts.join(", ")
+    }
+}
+
+/// Marks a port list entry as a TCP address rather than a local serial port.
+pub const TCP_PREFIX: &str = "tcp://";
+
+/// Port list entry for the serial server at `address`.
+pub fn tcp_port_name(address: &str) -> String {
+    format!("{TCP_PREFIX}{address}")
+}
+
+/// The `HOST:PORT` address of a TCP port list entry, or `None` for a serial port.
+pub fn tcp_address(port: &str) -> Option<&str> {
+    port.strip_prefix(TCP_PREFIX)
+}
+
+/// A serial port shared over the network by a raw TCP server such as ser2net. The baud rate is
+/// whatever the server was configured with.
+pub struct Tcp;
+
+impl Transport for Tcp {
+    type Stream = TcpStream;
+
+    async fn open(&self, port: &str, _baud_rate: u32) -> Result<TcpStream, String> {
+        let address = tcp_address(port).unwrap_or(port);
+        let stream = TcpStream::connect(address)
+            .await
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::ConnectionRefused => format!(
+                    "Connection to {address} was refused.\nCheck that the serial server is running and listening on that port."
+                ),
+                io::ErrorKind::TimedOut => format!(
+                    "Timed out connecting to {address}.\nCheck the host name and that it is reachable."
+                ),
+                _ => format!("Failed to connect to {address}.\nError: {e}"),
+            })?;
+        // Commands are small and latency matters more than packing them together.
+        stream
+            .set_nodelay(true)
+            .map_err(|e| format!("Failed to configure the connection to {address}.\nError: {e}"))?;
+        Ok(stream)
+    }
+}
+
+/// Message for a failed `operation` ("read" or "write") on an open connection. A connection
+/// dropped by the far end, which only happens over the network, is told apart from a local
+/// serial error.
+pub fn link_error(operation: &str, err: &io::Error) -> String {
+    match err.kind() {
+        io::ErrorKind::ConnectionReset
+        | io::ErrorKind::ConnectionAborted
+        | io::ErrorKind::BrokenPipe => {
+            format!("Connection reset by the remote end during a {operation}.\nError: {err}")
+        }
+        _ => format!("Serial {operation} failed: {err}"),
+    }
+}
+
+/// Status line for a failure to list the serial ports, with a hint at the usual causes and at
+/// `retry_key`, the key that lists them again, when one is bound.
+pub fn port_list_error(err: &tokio_serial::Error, retry_key: Option<KeyBinding>) -> String {
+    let reason = match err.kind() {
+        tokio_serial::ErrorKind::Io(io::ErrorKind::PermissionDenied) => {
+            "permission denied. Check you are in the group that owns the serial devices \
+             (dialout or uucp on most Linux systems)."
+                .to_owned()
+        }
+        tokio_serial::ErrorKind::Io(io::ErrorKind::NotFound)
+        | tokio_serial::ErrorKind::NoDevice => {
+            "the system's serial device list isn't available.".to_owned()
+        }
+        _ => format!("{}.", err.description),
+    };
+    match retry_key {
+        Some(key) => format!("Failed to list ports: {reason} Press {key} to retry."),
+        None => format!("Failed to list ports: {reason}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn refused_tcp_connection_says_so() {
+        // Bind to find a free port, then close it so nothing is listening there.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        drop(listener);
+
+        let message = Tcp.open(&tcp_port_name(&address), 0).await.unwrap_err();
+        assert!(
+            message.starts_with(&format!("Connection to {address} was refused.")),
+            "{message}"
+        );
+    }
+
+    #[tokio::test]
+    async fn tcp_port_names_round_trip() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let port = tcp_port_name(&address);
+        assert_eq!(tcp_address(&port), Some(address.as_str()));
+        assert_eq!(tcp_address("/dev/ttyACM0"), None);
+        assert!(Tcp.open(&port, 0).await.is_ok());
+    }
+
+    #[test]
+    fn connection_summaries_stay_on_one_short_line() {
+        let serial = ConnectionInfo {
+            port: "/dev/ttyACM0".into(),
+            baud_rate: 115_200,
+            line: Some(LineSettings {
+                data_bits: 8,
+                parity: Parity::None,
+                stop_bits: 1,
+                flow_control: FlowControl::None,
+            }),
+            framing: Framing::Postcard,
+            tagged: false,
+            mode: SessionMode::Commands,
+            label: Some("SiTerm RP2040 v0.1.0".into()),
+        };
+        assert_eq!(
+            serial.summary(),
+            "/dev/ttyACM0 @ 115200 baud, 8N1, no flow control, postcard framing, commands, \
+             SiTerm RP2040 v0.1.0"
+        );
+
+        let tcp = ConnectionInfo {
+            port: tcp_port_name("lab:4001"),
+            line: None,
+            framing: Framing::LengthPrefixed,
+            tagged: true,
+            mode: SessionMode::Bridged(9_600),
+            label: None,
+            ..serial
+        };
+        assert_eq!(
+            tcp.summary(),
+            "tcp://lab:4001, length framing, tagged, UART bridge @ 9600 baud"
+        );
+
+        let seven_e_two = LineSettings {
+            data_bits: 7,
+            parity: Parity::Even,
+            stop_bits: 2,
+            flow_control: FlowControl::Hardware,
+        };
+        assert_eq!(seven_e_two.to_string(), "7E2, RTS/CTS flow control");
+    }
+
+    #[test]
+    fn remote_resets_are_distinct_from_serial_errors() {
+        let reset = io::Error::from(io::ErrorKind::ConnectionReset);
+        assert!(link_error("read", &reset).starts_with("Connection reset by the remote end"));
+        let other = io::Error::other("device unplugged");
+        assert_eq!(
+            link_error("write", &other),
+            "Serial write failed: device unplugged"
+        );
+    }
+
+    #[test]
+    fn port_list_failures_explain_the_likely_cause() {
+        let error = |kind, description| tokio_serial::Error::new(kind, description);
+        let denied = error(
+            tokio_serial::ErrorKind::Io(io::ErrorKind::PermissionDenied),
+            "Permission denied",
+        );
+        assert!(
+            port_list_error(&denied, None).starts_with("Failed to list ports: permission denied."),
+            "{}",
+            port_list_error(&denied, None)
+        );
+        let missing = error(tokio_serial::ErrorKind::NoDevice, "no udev");
+        assert_eq!(
+            port_list_error(&missing, None),
+            "Failed to list ports: the system's serial device list isn't available."
+        );
+        let other = error(tokio_serial::ErrorKind::Unknown, "enumeration failed");
+        let f5 = KeyBinding::parse("f5").unwrap();
+        assert_eq!(
+            port_list_error(&other, Some(f5)),
+            "Failed to list ports: enumeration failed. Press F5 to retry."
+        );
+    }
+}
@@ -1,7 +1,7 @@
 #![allow(dead_code)] // Remove this once you start using the code
 
 use std::{
-    io::{Stdout, stdout},
+    io::{IsTerminal, Stdout, stdin, stdout},
     ops::{Deref, DerefMut},
     time::Duration,
 };
@@ -42,6 +42,39 @@ pub enum Event {
     Resize(u16, u16),
 }
 
+/// Whether the standard streams are attached to a terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TerminalStreams {
+    pub stdin: bool,
+    pub stdout: bool,
+}
+
+impl TerminalStreams {
+    pub fn detect() -> Self {
+        Self {
+            stdin: stdin().is_terminal(),
+            stdout: stdout().is_terminal(),
+        }
+    }
+
+    /// Explain why the TUI can't run on these streams, or `None` if it can. Without a terminal on
+    /// both, entering raw mode fails with an error that doesn't say what actually went wrong.
+    pub fn unsupported_reason(&self) -> Option<&'static str> {
+        match (self.stdin, self.stdout) {
+            (true, true) => None,
+            (false, true) => {
+                Some("SiTerm requires an interactive terminal, but stdin is not a terminal.")
+            }
+            (true, false) => Some(
+                "SiTerm requires an interactive terminal, but stdout is redirected; run it without redirecting output.",
+            ),
+            (false, false) => {
+                Some("SiTerm requires an interactive terminal; stdin and stdout are not terminals.")
+            }
+        }
+    }
+}
+
 pub struct Tui {
     pub terminal: ratatui::Terminal<Backend<Stdout>>,
     pub task: JoinHandle<()>,
@@ -233,3 +266,25 @@ impl Drop for Tui {
         self.exit().unwrap();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn both_streams_must_be_terminals() {
+        let interactive = TerminalStreams {
+            stdin: true,
+            stdout: true,
+        };
+        assert_eq!(interactive.unsupported_reason(), None);
+
+        for (stdin, stdout) in [(false, true), (true, false), (false, false)] {
+            let reason = TerminalStreams { stdin, stdout }.unsupported_reason();
+            assert!(
+                reason.is_some_and(|reason| reason.contains("interactive terminal")),
+                "stdin={stdin} stdout={stdout}"
+            );
+        }
+    }
+}